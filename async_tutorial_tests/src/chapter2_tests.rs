@@ -1,6 +1,6 @@
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{Context, Poll, Waker};
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
@@ -96,6 +96,110 @@ async fn test_shared_state_future() {
     assert!(shared_state.lock().unwrap().completed);
 }
 
+// Predicate Future: generalizes MyFuture from "a completed flag" to
+// "any condition over shared state"
+/// Shared state watched by `WaitFor`s, stored next to the wakers of those
+/// still pending
+pub struct Watched<T> {
+    pub value: T,
+    wakers: Vec<Waker>,
+}
+
+impl<T> Watched<T> {
+    pub fn new(value: T) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            value,
+            wakers: Vec::new(),
+        }))
+    }
+}
+
+/// Wakes every `WaitFor` watching `state`. Call it after mutating the value
+/// (and after releasing its lock) so waiters re-check their predicates.
+pub fn wake_waiters<T>(state: &Arc<Mutex<Watched<T>>>) {
+    let wakers = std::mem::take(&mut state.lock().unwrap().wakers);
+    for waker in wakers {
+        waker.wake();
+    }
+}
+
+/// Resolves with a clone of the value once `predicate` holds for it
+pub struct WaitFor<T, P> {
+    state: Arc<Mutex<Watched<T>>>,
+    predicate: P,
+}
+
+impl<T, P> WaitFor<T, P>
+where
+    T: Clone,
+    P: Fn(&T) -> bool,
+{
+    pub fn new(state: Arc<Mutex<Watched<T>>>, predicate: P) -> Self {
+        Self { state, predicate }
+    }
+}
+
+impl<T, P> Future for WaitFor<T, P>
+where
+    T: Clone,
+    P: Fn(&T) -> bool + Unpin,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+
+        if (self.predicate)(&state.value) {
+            Poll::Ready(state.value.clone())
+        } else {
+            // Register while still holding the state lock: a notifier can't
+            // mutate the value in between and wake before we're registered.
+            // A task that is polled again is already in the list
+            if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_wait_for_predicate() {
+    let counter = Watched::new(0u32);
+
+    let notifier_counter = counter.clone();
+    tokio::spawn(async move {
+        for _ in 0..10 {
+            sleep(Duration::from_millis(20)).await;
+            notifier_counter.lock().unwrap().value += 1;
+            wake_waiters(&notifier_counter);
+        }
+    });
+
+    let start = Instant::now();
+    let seen = WaitFor::new(counter.clone(), |count: &u32| *count >= 5).await;
+
+    assert!(seen >= 5);
+    // Resolved around the fifth increment, not after all ten
+    assert!(start.elapsed() < Duration::from_millis(180));
+}
+
+#[test]
+fn test_wait_for_registers_each_task_once() {
+    let state = Watched::new(false);
+    let mut wait = WaitFor::new(state.clone(), |done: &bool| *done);
+    let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+    for _ in 0..3 {
+        assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+    }
+    assert_eq!(state.lock().unwrap().wakers.len(), 1);
+
+    // Waking hands the wakers out; the state keeps none of them
+    wake_waiters(&state);
+    assert!(state.lock().unwrap().wakers.is_empty());
+}
+
 // Async function test
 async fn my_async_function() -> String {
     "Hello, async world!".to_string()
@@ -1,7 +1,9 @@
 use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
 use tokio::time::{sleep, Duration};
 
 // Simple Executor
@@ -13,6 +15,18 @@ struct Task {
     future: Pin<Box<dyn Future<Output = ()> + 'static>>,
 }
 
+// What the executor did during one `run`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExecutorMetrics {
+    // Every call to `poll`, whatever it returned
+    pub polls: usize,
+    // Polls that returned `Pending` and sent the task back to the queue
+    pub pending: usize,
+    pub completed: usize,
+    // Most tasks waiting in the queue at once
+    pub max_queue_depth: usize,
+}
+
 impl Executor {
     fn new() -> Self {
         Executor {
@@ -29,20 +43,194 @@ impl Executor {
         });
     }
 
-    fn run(&mut self) {
+    fn run(&mut self) -> ExecutorMetrics {
         let waker = futures::task::noop_waker();
         let mut cx = Context::from_waker(&waker);
+        let mut metrics = ExecutorMetrics::default();
+
+        loop {
+            metrics.max_queue_depth = metrics.max_queue_depth.max(self.tasks.len());
+            let Some(mut task) = self.tasks.pop_front() else {
+                break;
+            };
 
-        while let Some(mut task) = self.tasks.pop_front() {
+            metrics.polls += 1;
             match task.future.as_mut().poll(&mut cx) {
                 Poll::Ready(()) => {
                     // Task completed
+                    metrics.completed += 1;
                 }
                 Poll::Pending => {
+                    metrics.pending += 1;
                     self.tasks.push_back(task);
                 }
             }
         }
+
+        metrics
+    }
+}
+
+// Yield Future: returns Pending `remaining` times before completing
+pub struct Yield {
+    remaining: usize,
+}
+
+impl Yield {
+    pub fn new(remaining: usize) -> Self {
+        Yield { remaining }
+    }
+}
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.remaining == 0 {
+            Poll::Ready(())
+        } else {
+            self.remaining -= 1;
+            // Ask to be polled again so the executor can run other tasks first
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+// Work-stealing Executor: each worker thread owns a local deque; idle
+// workers take a batch from the shared injector or steal from the back of
+// another worker's deque. Woken tasks are re-queued onto the injector.
+type BoxedTask = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+struct StealableTask {
+    // `None` once the task has completed
+    future: Mutex<Option<BoxedTask>>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    injector: Mutex<VecDeque<Arc<StealableTask>>>,
+    locals: Vec<Mutex<VecDeque<Arc<StealableTask>>>>,
+    // Tasks spawned but not yet completed
+    unfinished: AtomicUsize,
+    // Number of polls performed by each worker
+    polls: Vec<AtomicUsize>,
+}
+
+impl Wake for StealableTask {
+    fn wake(self: Arc<Self>) {
+        let shared = self.shared.clone();
+        shared.injector.lock().unwrap().push_back(self);
+    }
+}
+
+pub struct WorkStealingExecutor {
+    shared: Arc<Shared>,
+}
+
+impl WorkStealingExecutor {
+    // Upper bound on how many tasks a worker moves from the injector at once
+    const BATCH: usize = 32;
+
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "need at least one worker thread");
+        WorkStealingExecutor {
+            shared: Arc::new(Shared {
+                injector: Mutex::new(VecDeque::new()),
+                locals: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+                unfinished: AtomicUsize::new(0),
+                polls: (0..threads).map(|_| AtomicUsize::new(0)).collect(),
+            }),
+        }
+    }
+
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shared.unfinished.fetch_add(1, Ordering::SeqCst);
+        let task = Arc::new(StealableTask {
+            future: Mutex::new(Some(Box::pin(future))),
+            shared: self.shared.clone(),
+        });
+        self.shared.injector.lock().unwrap().push_back(task);
+    }
+
+    // Runs until every spawned task has completed and returns how many
+    // polls each worker performed
+    pub fn run(self) -> Vec<usize> {
+        // Release all workers at once so the first thread started doesn't
+        // drain the injector before the others exist
+        let threads = self.shared.locals.len();
+        let start = Arc::new(Barrier::new(threads));
+        let workers: Vec<_> = (0..threads)
+            .map(|id| {
+                let shared = self.shared.clone();
+                let start = start.clone();
+                std::thread::spawn(move || {
+                    start.wait();
+                    Self::worker(id, &shared)
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        self.shared
+            .polls
+            .iter()
+            .map(|polls| polls.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    fn worker(id: usize, shared: &Arc<Shared>) {
+        while shared.unfinished.load(Ordering::SeqCst) > 0 {
+            let Some(task) = Self::find_task(id, shared) else {
+                std::thread::yield_now();
+                continue;
+            };
+
+            let mut slot = task.future.lock().unwrap();
+            // A task woken twice may be queued twice; skip it once it's done
+            let Some(future) = slot.as_mut() else {
+                continue;
+            };
+
+            shared.polls[id].fetch_add(1, Ordering::SeqCst);
+            let waker = Waker::from(task.clone());
+            let mut cx = Context::from_waker(&waker);
+            if future.as_mut().poll(&mut cx).is_ready() {
+                *slot = None;
+                shared.unfinished.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn find_task(id: usize, shared: &Shared) -> Option<Arc<StealableTask>> {
+        // 1. Own deque, front first
+        if let Some(task) = shared.locals[id].lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        // 2. A batch from the injector: run one, keep the rest locally
+        {
+            let mut injector = shared.injector.lock().unwrap();
+            if let Some(task) = injector.pop_front() {
+                let take = (injector.len() / shared.locals.len()).min(Self::BATCH);
+                shared.locals[id]
+                    .lock()
+                    .unwrap()
+                    .extend(injector.drain(..take));
+                return Some(task);
+            }
+        }
+
+        // 3. Steal from the back of another worker's deque
+        let workers = shared.locals.len();
+        (1..workers)
+            .map(|offset| (id + offset) % workers)
+            .find_map(|victim| shared.locals[victim].lock().unwrap().pop_back())
     }
 }
 
@@ -51,13 +239,95 @@ async fn test_task() {
     sleep(Duration::from_millis(100)).await;
 }
 
-#[tokio::test]
+// The executor busy-polls on the test's thread, so the timer has to be
+// driven by the runtime's other threads
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 async fn test_executor() {
     let mut executor = Executor::new();
     executor.spawn(test_task());
     executor.run();
 }
 
+// Test that yielding tasks are interleaved fairly
+#[test]
+fn test_yield_fairness() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let polls = Rc::new(RefCell::new(Vec::new()));
+    let mut executor = Executor::new();
+
+    for id in 0..2 {
+        let polls = polls.clone();
+        let mut yielder = Yield::new(5);
+        executor.spawn(futures::future::poll_fn(move |cx| {
+            polls.borrow_mut().push(id);
+            Pin::new(&mut yielder).poll(cx)
+        }));
+    }
+
+    executor.run();
+
+    // 5 Pending polls plus the final Ready poll per task, alternating
+    let expected: Vec<i32> = (0..6).flat_map(|_| [0, 1]).collect();
+    assert_eq!(*polls.borrow(), expected);
+}
+
+// Test that the executor's metrics match tasks with known poll patterns
+#[test]
+fn test_executor_metrics() {
+    let mut executor = Executor::new();
+    // Ready at once, then after 2 and 3 Pending polls
+    for pending in [0, 2, 3] {
+        executor.spawn(Yield::new(pending));
+    }
+
+    let metrics = executor.run();
+
+    assert_eq!(
+        metrics,
+        ExecutorMetrics {
+            polls: 8, // 1 + 3 + 4
+            pending: 5,
+            completed: 3,
+            max_queue_depth: 3,
+        }
+    );
+
+    // Running an empty queue again reports nothing
+    assert_eq!(executor.run(), ExecutorMetrics::default());
+}
+
+// Test that the work-stealing executor completes every task and spreads
+// the work over more than one worker
+#[test]
+fn test_work_stealing_executor() {
+    let executor = WorkStealingExecutor::new(4);
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..1000 {
+        let completed = completed.clone();
+        executor.spawn(async move {
+            Yield::new(1).await;
+            // A brief pause deschedules the worker thread, so the others get
+            // to run (and steal) even on a single-core machine
+            std::thread::sleep(std::time::Duration::from_micros(50));
+            completed.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    let polls = executor.run();
+
+    assert_eq!(completed.load(Ordering::SeqCst), 1000);
+    // Each task is polled once to yield and once to finish
+    assert_eq!(polls.iter().sum::<usize>(), 2000);
+    assert!(
+        polls.iter().filter(|&&count| count > 0).count() > 1,
+        "only one worker ran tasks: {:?}",
+        polls
+    );
+}
+
 // Test task scheduling
 #[tokio::test]
 async fn test_task_scheduling() {
@@ -1 +1,77 @@
+use futures::sink::{Sink, SinkExt};
 use futures::stream::{Stream, StreamExt};
+
+// A source of items that pushes them into a `Sink`
+//
+// Implementors only say how to fetch the next batch; the default `produce`
+// feeds each batch into the sink and flushes it. `feed` waits whenever a
+// bounded sink is full, so every producer (iterators, files, channels)
+// gets the same backpressure handling for free
+trait AsyncProducer {
+    type Item;
+
+    // How many items `produce` requests per batch
+    const BATCH_SIZE: usize = 16;
+
+    // Returns up to `max` items; an empty batch means the producer is done
+    async fn next_batch(&mut self, max: usize) -> Vec<Self::Item>;
+
+    // Forwards every item into `sink`, then closes it. Stops early if the
+    // sink errors (for example because the receiver was dropped)
+    async fn produce(&mut self, sink: impl Sink<Self::Item>) {
+        let mut sink = std::pin::pin!(sink);
+
+        loop {
+            let batch = self.next_batch(Self::BATCH_SIZE).await;
+            if batch.is_empty() {
+                break;
+            }
+            for item in batch {
+                // Waits here while the sink is full: that's the backpressure
+                if sink.feed(item).await.is_err() {
+                    return;
+                }
+            }
+            if sink.flush().await.is_err() {
+                return;
+            }
+        }
+
+        let _ = sink.close().await;
+    }
+}
+
+// Produces the numbers of a range
+struct RangeProducer {
+    range: std::ops::Range<u32>,
+}
+
+impl AsyncProducer for RangeProducer {
+    type Item = u32;
+
+    const BATCH_SIZE: usize = 4;
+
+    async fn next_batch(&mut self, max: usize) -> Vec<u32> {
+        self.range.by_ref().take(max).collect()
+    }
+}
+
+// Test that a producer feeding a slow consumer through a small channel
+// delivers every item, in order
+#[tokio::test]
+async fn test_range_producer_with_slow_consumer() {
+    // Capacity 2: the producer must keep waiting for the consumer
+    let (tx, rx) = futures::channel::mpsc::channel(2);
+    let mut producer = RangeProducer { range: 0..50 };
+
+    let consumer = rx
+        .then(|item| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            item
+        })
+        .collect::<Vec<u32>>();
+
+    let ((), received) = tokio::join!(producer.produce(tx), consumer);
+
+    assert_eq!(received, (0..50).collect::<Vec<_>>());
+}
@@ -1 +1,255 @@
+use futures::stream::{self, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::fs::ReadDir;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+// Directory walker: directories still to visit go on an explicit queue
+// instead of the walker calling itself, so a deep tree needs neither boxed
+// recursive futures nor stack space proportional to its depth
+struct Walk {
+    pending: VecDeque<PathBuf>,
+    current: Option<ReadDir>,
+}
+
+// Yields every file under `root`. Symlinks are yielded as-is rather than
+// followed, so a link back up the tree can't make the walk loop forever
+pub fn walk_dir(root: PathBuf) -> impl Stream<Item = io::Result<PathBuf>> {
+    let walk = Walk {
+        pending: VecDeque::from([root]),
+        current: None,
+    };
+
+    stream::unfold(walk, |mut walk| async move {
+        loop {
+            let entries = match walk.current.as_mut() {
+                Some(entries) => entries,
+                None => {
+                    let dir = walk.pending.pop_front()?;
+                    match tokio::fs::read_dir(&dir).await {
+                        Ok(entries) => walk.current.insert(entries),
+                        Err(e) => return Some((Err(e), walk)),
+                    }
+                }
+            };
+
+            match entries.next_entry().await {
+                Ok(Some(entry)) => match entry.file_type().await {
+                    Ok(file_type) if file_type.is_dir() => walk.pending.push_back(entry.path()),
+                    Ok(_) => return Some((Ok(entry.path()), walk)),
+                    Err(e) => return Some((Err(e), walk)),
+                },
+                // This directory is done; move on to the next queued one
+                Ok(None) => walk.current = None,
+                Err(e) => {
+                    walk.current = None;
+                    return Some((Err(e), walk));
+                }
+            }
+        }
+    })
+}
+
+// Longest gap between two checks in `wait_for_file`, however long it waits
+const MAX_FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Resolves once `path` exists. Checks start `poll_interval` apart and back
+// off by doubling (up to a second), so a file that shows up quickly is
+// noticed quickly while a long wait costs few checks. Fails with
+// `TimedOut` if the file hasn't appeared after `timeout`
+pub async fn wait_for_file(
+    path: PathBuf,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> io::Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut delay = poll_interval;
+
+    loop {
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} did not appear within {:?}", path.display(), timeout),
+            ));
+        }
+
+        // Never sleep past the deadline; the final check happens right at it
+        tokio::time::sleep(delay.min(deadline - now)).await;
+        delay = (delay * 2).min(MAX_FILE_POLL_INTERVAL);
+    }
+}
+
+// Size of the buffer `copy_with_progress` reads into
+const COPY_CHUNK_SIZE: usize = 8 * 1024;
+
+// Copies everything from `reader` to `writer` a chunk at a time, calling
+// `on_progress` with the total bytes copied so far after each chunk, and
+// returns the total. Awaiting between chunks keeps a long transfer from
+// hogging the executor, and the callback is how a UI hears about it
+pub async fn copy_with_progress<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    mut reader: R,
+    mut writer: W,
+    on_progress: impl Fn(u64),
+) -> io::Result<u64> {
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    let mut copied = 0;
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).await?;
+        copied += read as u64;
+        on_progress(copied);
+    }
+
+    writer.flush().await?;
+    Ok(copied)
+}
+
+// Test that every file in a nested tree is yielded exactly once, however
+// deep it sits
+#[tokio::test]
+async fn test_walk_dir() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    let mut expected = HashSet::new();
+
+    // A deep chain of directories with a file at every level...
+    let mut dir = root.path().to_path_buf();
+    for depth in 0..200 {
+        dir.push(format!("d{}", depth));
+        tokio::fs::create_dir(&dir).await?;
+        let file = dir.join("file.txt");
+        tokio::fs::write(&file, depth.to_string()).await?;
+        expected.insert(file);
+    }
+
+    // ...plus some wide directories and an empty one
+    for branch in 0..5 {
+        let dir = root.path().join(format!("wide{}", branch));
+        tokio::fs::create_dir(&dir).await?;
+        for index in 0..10 {
+            let file = dir.join(format!("{}.txt", index));
+            tokio::fs::write(&file, b"").await?;
+            expected.insert(file);
+        }
+    }
+    tokio::fs::create_dir(root.path().join("empty")).await?;
+
+    let walked: Vec<PathBuf> = walk_dir(root.path().to_path_buf())
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<io::Result<_>>()?;
+
+    let unique: HashSet<PathBuf> = walked.iter().cloned().collect();
+    assert_eq!(walked.len(), unique.len(), "a file was yielded twice");
+    assert_eq!(unique, expected);
+    Ok(())
+}
+
+// Test that a missing root surfaces as an error item
+#[tokio::test]
+async fn test_walk_dir_missing_root() {
+    let items: Vec<_> = walk_dir(PathBuf::from("does/not/exist")).collect().await;
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0].as_ref().unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+}
+
+// Test that wait_for_file notices a file soon after another task creates it
+#[tokio::test]
+async fn test_wait_for_file_appears() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("ready.flag");
+
+    let writer_path = path.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tokio::fs::write(writer_path, b"done").await
+    });
+
+    let start = std::time::Instant::now();
+    wait_for_file(path, Duration::from_millis(10), Duration::from_secs(5)).await?;
+    let waited = start.elapsed();
+
+    // Checks at 0, 10, 30, 70 and 150ms: found at the first one after 100ms
+    assert!(waited >= Duration::from_millis(100), "{:?}", waited);
+    assert!(waited < Duration::from_millis(300), "{:?}", waited);
+    Ok(())
+}
+
+// Test that waiting for a file that never appears times out on schedule
+#[tokio::test]
+async fn test_wait_for_file_times_out() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    let start = std::time::Instant::now();
+    let result = wait_for_file(
+        dir.path().join("never.flag"),
+        Duration::from_millis(10),
+        Duration::from_millis(200),
+    )
+    .await;
+
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_millis(400));
+    Ok(())
+}
+
+// Test that a copy reports steadily growing progress that ends at the total,
+// and that every byte arrives intact
+#[tokio::test]
+async fn test_copy_with_progress() -> io::Result<()> {
+    let source: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let mut destination = Vec::new();
+    let reports = std::sync::Mutex::new(Vec::new());
+
+    let total = copy_with_progress(source.as_slice(), &mut destination, |copied| {
+        reports.lock().unwrap().push(copied)
+    })
+    .await?;
+
+    assert_eq!(total, source.len() as u64);
+    assert_eq!(destination, source);
+
+    let reports = reports.into_inner().unwrap();
+    // 100,000 bytes in 8 KiB chunks
+    assert_eq!(reports.len(), 13);
+    assert!(
+        reports.windows(2).all(|pair| pair[0] < pair[1]),
+        "{:?}",
+        reports
+    );
+    assert_eq!(reports.last(), Some(&total));
+    Ok(())
+}
+
+// Test that copying nothing succeeds without reporting progress
+#[tokio::test]
+async fn test_copy_with_progress_empty() -> io::Result<()> {
+    let calls = std::sync::atomic::AtomicUsize::new(0);
+
+    let total = copy_with_progress(&b""[..], tokio::io::sink(), |_| {
+        calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    })
+    .await?;
+
+    assert_eq!(total, 0);
+    assert_eq!(calls.into_inner(), 0);
+    Ok(())
+}
@@ -1,3 +1,5 @@
+mod chapter2_tests;
+mod chapter3_tests;
 mod chapter4_tests;
 mod chapter5_tests;
 mod chapter6_tests;
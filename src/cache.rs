@@ -0,0 +1,394 @@
+//! # TTL Cache
+//!
+//! A small LRU cache whose entries also expire after a time-to-live.
+//! It was extracted from the `real_world` example's API client so the
+//! caching logic can be reused and tested on its own.
+//!
+//! ## Async Awareness:
+//! All methods take `&self` and lock an internal `std::sync::Mutex` only for
+//! the duration of the call. The lock is never held across an `.await`, so a
+//! cache can be shared between tasks behind an `Arc` without blocking the
+//! runtime or tripping `clippy::await_holding_lock`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// # Struct: TtlCache
+///
+/// A bounded cache that evicts the least recently used entry when full and
+/// treats entries older than their time-to-live as absent.
+///
+/// ## Fields:
+/// - `capacity`: Maximum number of entries kept at once
+/// - `default_ttl`: Time-to-live used by `insert`
+/// - `inner`: Entries and recency bookkeeping, guarded by a mutex
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::cache::TtlCache;
+/// use std::time::Duration;
+///
+/// let cache = TtlCache::new(2, Duration::from_secs(60));
+/// cache.insert("a", 1);
+/// cache.insert("b", 2);
+/// cache.get(&"a"); // "a" is now the most recently used
+/// cache.insert("c", 3); // evicts "b"
+///
+/// assert_eq!(cache.get(&"b"), None);
+/// assert_eq!(cache.get(&"a"), Some(1));
+/// ```
+pub struct TtlCache<K, V> {
+    capacity: usize,
+    default_ttl: Duration,
+    inner: Mutex<Inner<K, V>>,
+}
+
+/// Entries plus an index from "last used" tick to key. The smallest tick
+/// in `recency` is always the least recently used entry.
+struct Inner<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+impl<K, V> Inner<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &K) -> Option<Entry<V>> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        Some(entry)
+    }
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    /// # Function: new
+    ///
+    /// Creates an empty cache.
+    ///
+    /// ## Arguments:
+    /// - `capacity`: Maximum number of entries; must be greater than zero
+    /// - `default_ttl`: Time-to-live for entries added with `insert`
+    ///
+    /// ## Panics:
+    /// - If `capacity` is zero
+    pub fn new(capacity: usize, default_ttl: Duration) -> Self {
+        assert!(capacity > 0, "TtlCache capacity must be greater than zero");
+
+        Self {
+            capacity,
+            default_ttl,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    /// # Function: get
+    ///
+    /// Looks up a key. A hit marks the entry as most recently used; an
+    /// expired entry is removed and reported as a miss.
+    ///
+    /// ## Arguments:
+    /// - `key`: The key to look up
+    ///
+    /// ## Returns:
+    /// - `Option<V>`: A clone of the cached value, if present and unexpired
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let expired = inner.entries.get(key)?.expires_at <= Instant::now();
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+
+        let tick = inner.next_tick();
+        let entry = inner.entries.get_mut(key)?;
+        let previous = std::mem::replace(&mut entry.last_used, tick);
+        let value = entry.value.clone();
+
+        inner.recency.remove(&previous);
+        inner.recency.insert(tick, key.clone());
+        Some(value)
+    }
+
+    /// # Function: insert
+    ///
+    /// Stores a value using the cache's default time-to-live.
+    ///
+    /// ## Arguments:
+    /// - `key`: The key to store the value under
+    /// - `value`: The value to cache
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_with_ttl(key, value, self.default_ttl);
+    }
+
+    /// # Function: insert_with_ttl
+    ///
+    /// Stores a value that expires after `ttl`. Replacing an existing key
+    /// resets its expiry and recency. If the cache is full, expired entries
+    /// are dropped first and then the least recently used one is evicted.
+    ///
+    /// ## Arguments:
+    /// - `key`: The key to store the value under
+    /// - `value`: The value to cache
+    /// - `ttl`: How long the entry stays valid
+    pub fn insert_with_ttl(&self, key: K, value: V, ttl: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        inner.remove(&key);
+
+        if inner.entries.len() >= self.capacity {
+            inner.entries.retain(|_, entry| entry.expires_at > now);
+            let Inner {
+                entries, recency, ..
+            } = &mut *inner;
+            recency.retain(|_, key| entries.contains_key(key));
+        }
+
+        while inner.entries.len() >= self.capacity {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            inner.entries.remove(&oldest);
+        }
+
+        let tick = inner.next_tick();
+        inner.recency.insert(tick, key.clone());
+        inner.entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: now + ttl,
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Returns the number of stored entries, including expired ones that
+    /// have not been cleaned up yet.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Returns `true` if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// # Function: cache_aside
+///
+/// The cache-aside pattern: return the cached value for `key` if there is
+/// one, otherwise compute it, store it and return it.
+///
+/// ## Arguments:
+/// - `cache`: The cache to read and fill
+/// - `key`: What to look up
+/// - `ttl`: How long a computed value stays cached
+/// - `compute`: Produces the value on a miss; not called on a hit
+///
+/// ## Returns:
+/// - The cached or freshly computed value
+///
+/// ## Note:
+/// - The cache is not locked while `compute` runs, so concurrent misses
+///   for the same key each compute the value (the last one stored wins)
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::cache::{cache_aside, TtlCache};
+/// use std::time::Duration;
+///
+/// # futures::executor::block_on(async {
+/// let cache = TtlCache::new(16, Duration::from_secs(60));
+/// let ttl = Duration::from_secs(10);
+/// let first = cache_aside(&cache, "answer", ttl, || async { 42 }).await;
+/// let second = cache_aside(&cache, "answer", ttl, || async { unreachable!() }).await;
+/// assert_eq!((first, second), (42, 42));
+/// # });
+/// ```
+pub async fn cache_aside<K, V, F, Fut>(
+    cache: &TtlCache<K, V>,
+    key: K,
+    ttl: Duration,
+    compute: F,
+) -> V
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = V>,
+{
+    if let Some(value) = cache.get(&key) {
+        return value;
+    }
+
+    let value = compute().await;
+    cache.insert_with_ttl(key, value.clone(), ttl);
+    value
+}
+
+/// # Function: try_cache_aside
+///
+/// Like [`cache_aside`], for computations that can fail. Errors are
+/// returned to the caller and never cached, so the next call tries again.
+///
+/// ## Arguments:
+/// - `cache`: The cache to read and fill
+/// - `key`: What to look up
+/// - `ttl`: How long a computed value stays cached
+/// - `compute`: Produces the value on a miss; not called on a hit
+///
+/// ## Returns:
+/// - The cached or freshly computed value, or the computation's error
+pub async fn try_cache_aside<K, V, E, F, Fut>(
+    cache: &TtlCache<K, V>,
+    key: K,
+    ttl: Duration,
+    compute: F,
+) -> Result<V, E>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<V, E>>,
+{
+    if let Some(value) = cache.get(&key) {
+        return Ok(value);
+    }
+
+    let value = compute().await?;
+    cache.insert_with_ttl(key, value.clone(), ttl);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    /// Test that the least recently inserted entry is evicted first
+    #[test]
+    fn test_eviction_order() {
+        let cache = TtlCache::new(3, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+
+        cache.insert("d", 4);
+        assert_eq!(cache.get(&"a"), None);
+
+        cache.insert("e", 5);
+        assert_eq!(cache.get(&"b"), None);
+
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(&"c"), Some(3));
+        assert_eq!(cache.get(&"d"), Some(4));
+        assert_eq!(cache.get(&"e"), Some(5));
+    }
+
+    /// Test that entries disappear once their time-to-live has passed
+    #[test]
+    fn test_expiry() {
+        let cache = TtlCache::new(4, Duration::from_millis(30));
+        cache.insert("short", 1);
+        cache.insert_with_ttl("long", 2, Duration::from_secs(60));
+
+        assert_eq!(cache.get(&"short"), Some(1));
+        sleep(Duration::from_millis(50));
+
+        assert_eq!(cache.get(&"short"), None);
+        assert_eq!(cache.get(&"long"), Some(2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    /// Test that reading an entry protects it from eviction
+    #[test]
+    fn test_get_updates_recency() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    /// Test that cache_aside computes once per TTL period
+    #[tokio::test]
+    async fn test_cache_aside_computes_on_miss_only() {
+        let cache = TtlCache::new(4, Duration::from_secs(60));
+        let ttl = Duration::from_millis(30);
+        let computed = std::cell::Cell::new(0);
+        let compute = || async {
+            computed.set(computed.get() + 1);
+            "value"
+        };
+
+        assert_eq!(cache_aside(&cache, "key", ttl, compute).await, "value");
+        assert_eq!(cache_aside(&cache, "key", ttl, compute).await, "value");
+        assert_eq!(computed.get(), 1);
+
+        sleep(Duration::from_millis(50));
+        assert_eq!(cache_aside(&cache, "key", ttl, compute).await, "value");
+        assert_eq!(computed.get(), 2);
+    }
+
+    /// Test that try_cache_aside does not cache errors
+    #[tokio::test]
+    async fn test_try_cache_aside_skips_errors() {
+        let cache = TtlCache::new(4, Duration::from_secs(60));
+        let ttl = Duration::from_secs(60);
+
+        let failed: Result<u32, &str> =
+            try_cache_aside(&cache, "key", ttl, || async { Err("unavailable") }).await;
+        assert_eq!(failed, Err("unavailable"));
+        assert!(cache.is_empty());
+
+        let loaded: Result<u32, &str> =
+            try_cache_aside(&cache, "key", ttl, || async { Ok(7) }).await;
+        assert_eq!(loaded, Ok(7));
+        assert_eq!(cache.get(&"key"), Some(7));
+    }
+
+    /// Test that expired entries are reclaimed before live ones are evicted
+    #[test]
+    fn test_expired_entries_make_room_first() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("live", 1);
+        cache.insert_with_ttl("stale", 2, Duration::from_millis(10));
+        sleep(Duration::from_millis(30));
+
+        cache.insert("new", 3);
+
+        assert_eq!(cache.get(&"live"), Some(1));
+        assert_eq!(cache.get(&"new"), Some(3));
+    }
+}
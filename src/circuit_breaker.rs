@@ -0,0 +1,257 @@
+//! # Circuit Breaker
+//!
+//! A circuit breaker stops calling a dependency that keeps failing, so the
+//! caller fails fast instead of piling more work onto a struggling service.
+//! It was extracted from the `real_world` example's API client so the
+//! `error_handling` example's database pool can use the same logic.
+//!
+//! ## Async Awareness:
+//! The breaker only records outcomes; it never awaits anything itself.
+//! Callers check `allow_request` before starting an operation and report
+//! the result afterwards, so no lock is held across an `.await`. State
+//! changes are published on a `tokio::sync::broadcast` channel, which never
+//! blocks the sender, so alerting can be attached with `subscribe`.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Buffered state changes per subscriber before the oldest are dropped
+const STATE_CHANGE_CAPACITY: usize = 32;
+
+/// # Struct: CircuitBreakerConfig
+///
+/// Settings for the circuit breaker that protects a failing dependency.
+///
+/// ## Fields:
+/// - `failure_threshold`: Consecutive failures that open the circuit
+/// - `reset_timeout`: How long the circuit stays open before a trial request
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub reset_timeout: Duration,
+}
+
+/// # Enum: CircuitState
+///
+/// The state a `CircuitBreaker` is in.
+///
+/// ## Variants:
+/// - `Closed`: Requests pass through
+/// - `Open`: Requests are rejected until the reset timeout has passed
+/// - `HalfOpen`: A trial request is running; its outcome decides the next state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        };
+        f.write_str(name)
+    }
+}
+
+/// # Struct: StateChange
+///
+/// A transition of a `CircuitBreaker`, as received from `subscribe`.
+///
+/// ## Fields:
+/// - `from`: The state before the transition
+/// - `to`: The state after it
+/// - `at`: When it happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateChange {
+    pub from: CircuitState,
+    pub to: CircuitState,
+    pub at: Instant,
+}
+
+/// # Struct: CircuitBreaker
+///
+/// Tracks consecutive failures and rejects requests while the circuit is
+/// open, so a struggling dependency isn't hammered with retries.
+///
+/// ## States:
+/// - **Closed**: all requests pass through
+/// - **Open**: requests are rejected until `reset_timeout` has passed
+/// - **Half-open**: one trial request is let through; success closes the
+///   circuit, failure opens it again for another `reset_timeout`
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+/// use std::time::Duration;
+///
+/// let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+///     failure_threshold: 2,
+///     reset_timeout: Duration::from_secs(30),
+/// });
+/// breaker.record_failure();
+/// breaker.record_failure();
+///
+/// assert!(breaker.is_open());
+/// assert!(!breaker.allow_request());
+/// ```
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<Inner>,
+    changes: broadcast::Sender<StateChange>,
+}
+
+/// Everything that changes as outcomes are recorded. One lock covers all
+/// of it, so a transition is decided and published in a single step and
+/// subscribers see changes in the order they happened.
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the circuit last opened or let a trial request through
+    since: Instant,
+}
+
+impl CircuitBreaker {
+    /// # Function: new
+    ///
+    /// Creates a breaker in the closed state.
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        let (changes, _) = broadcast::channel(STATE_CHANGE_CAPACITY);
+        Self {
+            config,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                since: Instant::now(),
+            }),
+            changes,
+        }
+    }
+
+    /// # Function: subscribe
+    ///
+    /// Returns a receiver for every state change from now on, e.g. to raise
+    /// an alert when the circuit opens.
+    ///
+    /// ## Note:
+    /// - A subscriber that falls more than 32 changes behind loses the
+    ///   oldest ones and gets `RecvError::Lagged` once
+    pub fn subscribe(&self) -> broadcast::Receiver<StateChange> {
+        self.changes.subscribe()
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Moves to `to`, publishing a `StateChange` if that is a different
+    /// state. Called with the lock held, so the event can't be overtaken
+    /// by a later transition.
+    fn transition(&self, inner: &mut Inner, to: CircuitState) {
+        let now = Instant::now();
+        if inner.state != to {
+            // Having no subscribers is fine
+            let _ = self.changes.send(StateChange {
+                from: inner.state,
+                to,
+                at: now,
+            });
+            inner.state = to;
+        }
+        inner.since = now;
+    }
+
+    /// Returns whether a request may be sent right now.
+    pub fn allow_request(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed => true,
+            _ if inner.since.elapsed() >= self.config.reset_timeout => {
+                // Half-open: let this request through as a trial and keep
+                // rejecting others until its outcome is recorded
+                self.transition(&mut inner, CircuitState::HalfOpen);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` while the circuit is open or half-open.
+    pub fn is_open(&self) -> bool {
+        self.state() != CircuitState::Closed
+    }
+
+    /// Closes the circuit and resets the failure count.
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        self.transition(&mut inner, CircuitState::Closed);
+    }
+
+    /// Counts a failure, opening the circuit once the threshold is reached.
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        if inner.consecutive_failures >= self.config.failure_threshold {
+            self.transition(&mut inner, CircuitState::Open);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    /// Test that a subscriber sees every transition, in order
+    #[test]
+    fn test_state_changes_are_broadcast() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            reset_timeout: Duration::from_millis(20),
+        });
+        let mut changes = breaker.subscribe();
+
+        breaker.record_failure();
+        breaker.record_failure(); // closed -> open
+        assert!(!breaker.allow_request());
+
+        sleep(Duration::from_millis(30));
+        assert!(breaker.allow_request()); // open -> half-open
+        breaker.record_failure(); // half-open -> open
+
+        sleep(Duration::from_millis(30));
+        assert!(breaker.allow_request()); // open -> half-open
+        breaker.record_success(); // half-open -> closed
+        breaker.record_success(); // already closed: no event
+
+        let mut transitions = Vec::new();
+        let mut last_at = None;
+        while let Ok(change) = changes.try_recv() {
+            assert!(last_at.is_none_or(|last| change.at >= last));
+            last_at = Some(change.at);
+            transitions.push((change.from, change.to));
+        }
+
+        use CircuitState::*;
+        assert_eq!(
+            transitions,
+            vec![
+                (Closed, Open),
+                (Open, HalfOpen),
+                (HalfOpen, Open),
+                (Open, HalfOpen),
+                (HalfOpen, Closed),
+            ]
+        );
+        assert_eq!(breaker.state(), Closed);
+    }
+}
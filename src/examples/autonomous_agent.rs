@@ -10,17 +10,40 @@
 //! 4. Error handling in stateful async operations
 //! 5. Waker management for complex polling scenarios
 //! 6. Real-world patterns for autonomous systems
+//! 7. Request/response messaging with actors
+//! 8. Recording and replaying agent runs for deterministic tests
+
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
 
 use anyhow::Result as AnyhowResult;
+use future_trait_tutorial::outln;
+use future_trait_tutorial::output::{self, BufferedOutput};
+use future_trait_tutorial::seed::{self, TestRng};
+use future_trait_tutorial::state_machine::Step;
+#[cfg(feature = "lock-tracking")]
+use future_trait_tutorial::tracked_mutex::TrackedMutex as Mutex;
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "lock-tracking"))]
+use std::sync::Mutex;
 use std::{
+    collections::VecDeque,
     future::Future,
+    panic::AssertUnwindSafe,
+    path::Path,
     pin::Pin,
     sync::Arc,
-    task::{Context, Poll},
+    task::Poll,
     time::{Duration, Instant},
 };
-use tokio::{sync::oneshot, time::sleep};
+use thiserror::Error;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, oneshot},
+    time::sleep,
+};
 
 /// # Struct: AgentResponse
 ///
@@ -43,6 +66,101 @@ pub struct AgentResponse {
     pub goal: u32,
 }
 
+/// # Enum: AgentEvent
+///
+/// Noteworthy things that happen while an agent runs, reported to its
+/// `AgentObserver`.
+///
+/// ## Variants:
+/// - `GoalDrift`: A later response tried to change the already established goal
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentEvent {
+    /// A response after the first carried a different, nonzero goal
+    GoalDrift {
+        /// The goal set by the first response, which stays in effect
+        established: u32,
+        /// The goal the later response tried to set (ignored)
+        attempted: u32,
+    },
+}
+
+/// # Trait: AgentObserver
+///
+/// Receives `AgentEvent`s from a running agent. Observers are called from
+/// inside `poll`, so they must return quickly and never block.
+pub trait AgentObserver: Send + Sync {
+    fn on_event(&self, event: &AgentEvent);
+
+    /// Called after every response with the updated progress and the goal.
+    /// Progress may overshoot the goal on the final step.
+    fn on_progress(&self, _progress: u32, _goal: u32) {}
+}
+
+/// # Struct: EventLog
+///
+/// An observer that records every event it receives, for inspection after
+/// (or while) the agent runs.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    events: Mutex<Vec<AgentEvent>>,
+}
+
+impl EventLog {
+    /// Returns a copy of the events recorded so far.
+    pub fn events(&self) -> Vec<AgentEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl AgentObserver for EventLog {
+    fn on_event(&self, event: &AgentEvent) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
+
+/// # Struct: ProgressBarObserver
+///
+/// An observer that renders the agent's progress toward its goal as a
+/// terminal progress bar (feature `progress-bar`).
+///
+/// ## Note:
+/// The bar saturates at the goal: the final step usually overshoots it,
+/// but a bar can't be more than full.
+#[cfg(feature = "progress-bar")]
+#[derive(Debug, Clone)]
+pub struct ProgressBarObserver {
+    bar: indicatif::ProgressBar,
+}
+
+#[cfg(feature = "progress-bar")]
+impl ProgressBarObserver {
+    /// Creates an observer that draws on `bar`; its length becomes the goal.
+    pub fn new(bar: indicatif::ProgressBar) -> Self {
+        Self { bar }
+    }
+
+    /// Returns the bar being drawn, e.g. to inspect its position.
+    pub fn bar(&self) -> &indicatif::ProgressBar {
+        &self.bar
+    }
+}
+
+#[cfg(feature = "progress-bar")]
+impl AgentObserver for ProgressBarObserver {
+    fn on_event(&self, event: &AgentEvent) {
+        // Print above the bar instead of tearing through it
+        self.bar.println(format!("⚠️  {:?}", event));
+    }
+
+    fn on_progress(&self, progress: u32, goal: u32) {
+        self.bar.set_length(goal as u64);
+        self.bar.set_position(progress.min(goal) as u64);
+        if progress >= goal {
+            self.bar.finish();
+        }
+    }
+}
+
 /// # Enum: AgentState
 ///
 /// Represents the different states of the autonomous agent.
@@ -54,6 +172,7 @@ pub struct AgentResponse {
 /// - `Acting`: Agent has received a response and is processing it
 /// - `Completed`: Agent has reached its goal
 /// - `Failed`: Agent encountered an unrecoverable error
+/// - `Poisoned`: A transition was interrupted by a panic
 #[derive(Debug)]
 enum AgentState {
     /// Agent is starting up, no goal set yet
@@ -61,7 +180,7 @@ enum AgentState {
     /// Agent is making a decision via API call
     Planning {
         /// Channel to receive the API response
-        receiver: oneshot::Receiver<Result<AgentResponse, String>>,
+        receiver: oneshot::Receiver<LlmReply>,
     },
     /// Agent has received a response and is processing it
     Acting {
@@ -78,6 +197,204 @@ enum AgentState {
         /// Error message describing the failure
         error: String,
     },
+    /// A panic interrupted a state transition, so the real state is unknown.
+    /// `poll` parks the agent here while a transition is in progress.
+    Poisoned,
+}
+
+impl AgentState {
+    /// The state without its data, for the audit log
+    fn kind(&self) -> StateKind {
+        match self {
+            AgentState::Initializing => StateKind::Initializing,
+            AgentState::Planning { .. } => StateKind::Planning,
+            AgentState::Acting { .. } => StateKind::Acting,
+            AgentState::Completed { .. } => StateKind::Completed,
+            AgentState::Failed { .. } => StateKind::Failed,
+            AgentState::Poisoned => StateKind::Poisoned,
+        }
+    }
+}
+
+/// # Enum: StateKind
+///
+/// Names the states of an agent's state machine, as recorded in its
+/// `AuditLog`. Mirrors the private `AgentState` without the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateKind {
+    Initializing,
+    Planning,
+    Acting,
+    Completed,
+    Failed,
+    Poisoned,
+}
+
+/// # Struct: AuditEntry
+///
+/// One state transition of an agent.
+///
+/// ## Fields:
+/// - `timestamp`: When the transition happened
+/// - `from_state`: The state that was left
+/// - `to_state`: The state that was entered
+/// - `progress`: The agent's progress after the transition
+/// - `llm_duration`: How long the LLM call took, for transitions out of
+///   `Planning`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub timestamp: Instant,
+    pub from_state: StateKind,
+    pub to_state: StateKind,
+    pub progress: u32,
+    pub llm_duration: Option<Duration>,
+}
+
+/// # Struct: AuditLog
+///
+/// Every state transition of an agent, in order, to explain afterwards
+/// how it reached its result and where the time went.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Returns the recorded transitions, oldest first.
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Returns the total time spent waiting for the LLM.
+    pub fn llm_time(&self) -> Duration {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.llm_duration)
+            .sum()
+    }
+}
+
+/// What a background LLM call sends back: the call's result, or the
+/// payload of a panic so `poll` can re-raise it
+type LlmReply = std::thread::Result<Result<AgentResponse, String>>;
+
+/// # Enum: AgentError
+///
+/// Why an agent finished without reaching its goal.
+///
+/// ## Variants:
+/// - `Failed`: The LLM call failed; carries the progress made until then
+/// - `Poisoned`: An earlier poll panicked mid-transition, so the agent
+///   refuses to continue rather than silently starting over
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AgentError {
+    #[error("agent failed at progress {progress}: {error}")]
+    Failed { error: String, progress: u32 },
+
+    #[error("agent was interrupted by a panic and cannot continue")]
+    Poisoned,
+}
+
+/// # Enum: StepResult
+///
+/// Where an agent stands after `AutonomousAgent::step`.
+///
+/// ## Variants:
+/// - `InProgress`: Another round is needed; carries the progress so far
+/// - `Completed`: The goal is reached; carries the final progress
+/// - `Failed`: The agent stopped without reaching its goal
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    InProgress(u32),
+    Completed(u32),
+    Failed(AgentError),
+}
+
+/// # Enum: ContextParseError
+///
+/// Why a context string could not be read as the agent's progress.
+///
+/// ## Variants:
+/// - `Empty`: The context is empty or only whitespace
+/// - `NotNumeric`: The context contains something other than decimal digits
+/// - `Overflow`: The context is a number too large for a `u32`
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ContextParseError {
+    #[error("context is empty")]
+    Empty,
+
+    #[error("context is not a number: {0:?}")]
+    NotNumeric(String),
+
+    #[error("context {0} does not fit in a u32")]
+    Overflow(String),
+}
+
+/// # Function: parse_context
+///
+/// Reads the progress value sent to the LLM as context. Unlike
+/// `str::parse`, it accepts only plain decimal digits (surrounding
+/// whitespace is ignored), and it reports *why* malformed input was
+/// rejected instead of letting callers fall back to a default.
+///
+/// ## Arguments:
+/// - `context`: The context string, e.g. `"150"`
+///
+/// ## Returns:
+/// - `Result<u32, ContextParseError>`: The progress value, or the reason it
+///   could not be read
+///
+/// ## Note:
+/// - Total over all inputs: it never panics, whatever the string contains
+pub fn parse_context(context: &str) -> Result<u32, ContextParseError> {
+    let digits = context.trim();
+
+    if digits.is_empty() {
+        return Err(ContextParseError::Empty);
+    }
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(ContextParseError::NotNumeric(context.to_string()));
+    }
+
+    // Only digits are left, so the only way parsing can fail is overflow
+    digits
+        .parse()
+        .map_err(|_| ContextParseError::Overflow(digits.to_string()))
+}
+
+/// # Trait: LlmClient
+///
+/// Anything an `AutonomousAgent` can ask for its next decision.
+///
+/// ## Note:
+/// `extract` is the object-safe spelling of `async fn extract(&self, ...)`
+/// (the agent holds its client as `Arc<dyn LlmClient>`), so the future is
+/// boxed. Implementations just wrap an async block in `Box::pin`.
+pub trait LlmClient: Send + Sync {
+    /// Asks for the next decision given the agent's current progress
+    fn extract<'a>(&'a self, context: &'a str) -> BoxFuture<'a, AnyhowResult<AgentResponse>>;
+
+    /// Whether `extract` is ready on its first poll. The agent then calls
+    /// it inline instead of spawning a task for it.
+    fn answers_instantly(&self) -> bool {
+        false
+    }
+}
+
+/// # Enum: HealthError
+///
+/// Why a `MockLlmClient` health check failed.
+///
+/// ## Variants:
+/// - `Unavailable`: The client is configured to fail its calls
+/// - `Faulty`: The client is configured to panic on its calls
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum HealthError {
+    #[error("LLM service is unavailable")]
+    Unavailable,
+
+    #[error("LLM client is faulty")]
+    Faulty,
 }
 
 /// # Struct: MockLlmClient
@@ -90,13 +407,28 @@ enum AgentState {
 /// - Simulates realistic API response times
 /// - Generates random but consistent responses
 /// - Can simulate failures for error handling demonstration
+/// - Can replay a fixed script of responses
+/// - Can draw actions and response times from a seeded `TestRng`
 /// - Thread-safe for use in async contexts
 #[derive(Debug, Clone)]
 pub struct MockLlmClient {
     /// Whether this client should simulate failures
     should_fail: bool,
+    /// Whether this client should panic instead of answering
+    should_panic: bool,
     /// Base delay for simulating API response time
     response_delay: Duration,
+    /// Responses to return in order instead of the simulated ones
+    script: Option<Arc<Mutex<VecDeque<AgentResponse>>>>,
+    /// Random source for actions and delays, plus the delays it chose
+    randomness: Option<Arc<Mutex<Randomness>>>,
+}
+
+/// The random state of a randomized `MockLlmClient`
+#[derive(Debug)]
+struct Randomness {
+    rng: TestRng,
+    delays: Vec<Duration>,
 }
 
 impl MockLlmClient {
@@ -115,7 +447,10 @@ impl MockLlmClient {
     pub fn new() -> Self {
         Self {
             should_fail: false,
+            should_panic: false,
             response_delay: Duration::from_millis(200), // Simulate 200ms API response time
+            script: None,
+            randomness: None,
         }
     }
 
@@ -131,7 +466,125 @@ impl MockLlmClient {
     pub fn with_failure_rate(should_fail: bool) -> Self {
         Self {
             should_fail,
+            should_panic: false,
             response_delay: Duration::from_millis(200),
+            script: None,
+            randomness: None,
+        }
+    }
+
+    /// # Function: panicking
+    ///
+    /// Creates a mock client whose calls panic, simulating a bug in the
+    /// client rather than a reported API failure.
+    ///
+    /// ## Returns:
+    /// - A MockLlmClient that panics on its first (and every) call
+    pub fn panicking() -> Self {
+        Self {
+            should_panic: true,
+            response_delay: Duration::from_millis(10),
+            ..Self::new()
+        }
+    }
+
+    /// # Function: scripted
+    ///
+    /// Creates a mock client that replays the given responses in order,
+    /// repeating the last one once the script runs out. Useful for steering
+    /// an agent through a specific scenario.
+    ///
+    /// ## Arguments:
+    /// - `responses`: The responses to return, one per call
+    ///
+    /// ## Returns:
+    /// - A MockLlmClient with a short simulated response time
+    pub fn scripted(responses: Vec<AgentResponse>) -> Self {
+        Self {
+            should_fail: false,
+            should_panic: false,
+            response_delay: Duration::from_millis(10),
+            script: Some(Arc::new(Mutex::new(responses.into()))),
+            randomness: None,
+        }
+    }
+
+    /// # Function: randomized
+    ///
+    /// Creates a mock client whose actions and response times are random,
+    /// like a real model's, yet fully determined by `rng`: the same seed
+    /// gives the same run.
+    ///
+    /// ## Arguments:
+    /// - `rng`: Source of the random choices
+    ///
+    /// ## Returns:
+    /// - A MockLlmClient answering with actions of 50-149 after 5-24ms.
+    ///   Clones share the generator.
+    pub fn randomized(rng: TestRng) -> Self {
+        Self {
+            response_delay: Duration::from_millis(5),
+            randomness: Some(Arc::new(Mutex::new(Randomness {
+                rng,
+                delays: Vec::new(),
+            }))),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the response times a randomized client has chosen so far.
+    pub fn chosen_delays(&self) -> Vec<Duration> {
+        self.randomness
+            .as_ref()
+            .map(|randomness| randomness.lock().unwrap().delays.clone())
+            .unwrap_or_default()
+    }
+
+    /// # Function: with_response_delay
+    ///
+    /// Changes the simulated response time. With `Duration::ZERO` the client
+    /// answers instantly, without ever returning `Pending`.
+    ///
+    /// ## Arguments:
+    /// - `delay`: How long each call takes
+    ///
+    /// ## Returns:
+    /// - The client with the new response time
+    pub fn with_response_delay(mut self, delay: Duration) -> Self {
+        self.response_delay = delay;
+        self
+    }
+
+    /// # Function: health_check
+    ///
+    /// Probes the simulated service the way a readiness check would, so a
+    /// coordinator can refuse to start agents against a broken client.
+    ///
+    /// ## Returns:
+    /// - A future resolving to `Ok(())` if calls would succeed, or the
+    ///   `HealthError` they would run into. It owns everything it needs, so
+    ///   it can be spawned or raced against a timeout.
+    ///
+    /// ## Note:
+    /// - A probe is cheap: it takes a tenth of the configured response delay
+    /// - Unlike `extract`, it reports a panicking client instead of panicking
+    pub fn health_check(&self) -> impl Future<Output = Result<(), HealthError>> + Send + 'static {
+        let probe_delay = self.response_delay / 10;
+        let should_fail = self.should_fail;
+        let should_panic = self.should_panic;
+
+        async move {
+            if !probe_delay.is_zero() {
+                sleep(probe_delay).await;
+            }
+
+            if should_panic {
+                Err(HealthError::Faulty)
+            } else if should_fail {
+                Err(HealthError::Unavailable)
+            } else {
+                Ok(())
+            }
         }
     }
 
@@ -151,26 +604,58 @@ impl MockLlmClient {
     /// - Otherwise, only provides an action value
     /// - Simulates realistic response times
     /// - Can simulate failures for error handling testing
+    /// - Fails with a `ContextParseError` if the context is not a progress value
     pub async fn extract(&self, context: &str) -> AnyhowResult<AgentResponse> {
-        println!("🤖 LLM API call with context: '{}'", context);
+        outln!("🤖 LLM API call with context: '{}'", context);
+
+        // A randomized client draws its action up front, so the sequence of
+        // draws doesn't depend on how the calls interleave
+        let mut response_delay = self.response_delay;
+        let mut random_action = None;
+        if let Some(randomness) = &self.randomness {
+            let mut randomness = randomness.lock().unwrap();
+            response_delay += Duration::from_millis(randomness.rng.below(20));
+            random_action = Some(50 + randomness.rng.below(100) as u32);
+            randomness.delays.push(response_delay);
+        }
 
         // Simulate API response time
-        sleep(self.response_delay).await;
+        if !response_delay.is_zero() {
+            sleep(response_delay).await;
+        }
+
+        if self.should_panic {
+            panic!("Simulated LLM client panic");
+        }
 
         // Simulate failures if configured
         if self.should_fail {
             return Err(anyhow::anyhow!("Simulated LLM API failure"));
         }
 
-        // Parse current progress from context
-        let current_progress: u32 = context.parse().unwrap_or(0);
+        // Parse current progress from context; malformed context is an error
+        // (and fails the agent) rather than being mistaken for progress 0
+        let current_progress = parse_context(context)?;
+
+        let scripted = self.script.as_ref().and_then(|script| {
+            let mut script = script.lock().unwrap();
+            if script.len() > 1 {
+                script.pop_front()
+            } else {
+                script.front().cloned()
+            }
+        });
 
-        let response = if current_progress == 0 {
+        let response = if let Some(response) = scripted {
+            response
+        } else if current_progress == 0 {
             // First call: set both action and goal
             AgentResponse {
-                action: 150, // Fixed for predictable testing
-                goal: 1000,  // Fixed for predictable testing
+                action: random_action.unwrap_or(150), // Fixed unless randomized
+                goal: 1000,                           // Fixed for predictable testing
             }
+        } else if let Some(action) = random_action {
+            AgentResponse { action, goal: 0 }
         } else {
             // Subsequent calls: only provide action
             // Use a deterministic but varied action based on current progress
@@ -181,14 +666,163 @@ impl MockLlmClient {
             }
         };
 
-        println!(
+        outln!(
             "✅ LLM response: action={}, goal={}",
-            response.action, response.goal
+            response.action,
+            response.goal
         );
         Ok(response)
     }
 }
 
+impl Default for MockLlmClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LlmClient for MockLlmClient {
+    fn extract<'a>(&'a self, context: &'a str) -> BoxFuture<'a, AnyhowResult<AgentResponse>> {
+        Box::pin(MockLlmClient::extract(self, context))
+    }
+
+    fn answers_instantly(&self) -> bool {
+        self.response_delay.is_zero()
+    }
+}
+
+/// # Struct: RecordedCall
+///
+/// One LLM call of a recorded run: a line of the trajectory file written
+/// by `RecordingLlmClient` and read by `ReplayLlmClient`.
+///
+/// ## Fields:
+/// - `context`: The context the agent sent
+/// - `response`: What the client answered, or its error message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordedCall {
+    pub context: String,
+    pub response: Result<AgentResponse, String>,
+}
+
+/// # Struct: RecordingLlmClient
+///
+/// Wraps another client and appends every call it answers to a
+/// trajectory file (one JSON `RecordedCall` per line), so the run can be
+/// replayed later with `ReplayLlmClient`.
+///
+/// ## Fields:
+/// - `inner`: The client that actually answers
+/// - `file`: The trajectory file; an async mutex because it is held while
+///   writing
+pub struct RecordingLlmClient<C> {
+    inner: C,
+    file: tokio::sync::Mutex<tokio::fs::File>,
+}
+
+impl<C: LlmClient> RecordingLlmClient<C> {
+    /// # Function: create
+    ///
+    /// Starts recording `inner`'s calls to `path`.
+    ///
+    /// ## Arguments:
+    /// - `inner`: The client to record
+    /// - `path`: The trajectory file; an existing file is overwritten
+    ///
+    /// ## Returns:
+    /// - The recording client, or the error creating the file
+    pub async fn create(inner: C, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = tokio::fs::File::create(path).await?;
+        Ok(Self {
+            inner,
+            file: tokio::sync::Mutex::new(file),
+        })
+    }
+}
+
+impl<C: LlmClient> LlmClient for RecordingLlmClient<C> {
+    fn extract<'a>(&'a self, context: &'a str) -> BoxFuture<'a, AnyhowResult<AgentResponse>> {
+        Box::pin(async move {
+            let result = self.inner.extract(context).await;
+
+            let call = RecordedCall {
+                context: context.to_string(),
+                response: result.as_ref().map(Clone::clone).map_err(|e| e.to_string()),
+            };
+            let mut line = serde_json::to_string(&call)?;
+            line.push('\n');
+            let mut file = self.file.lock().await;
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await?;
+
+            result
+        })
+    }
+}
+
+/// # Struct: ReplayLlmClient
+///
+/// Answers with the calls of a recorded trajectory, in order and without
+/// any latency, so an agent run can be reproduced exactly.
+///
+/// ## Fields:
+/// - `calls`: The recorded calls not replayed yet
+///
+/// ## Note:
+/// - A call whose context differs from the recorded one fails: the run
+///   has diverged from the recording, so the rest of it would not match
+pub struct ReplayLlmClient {
+    calls: Mutex<VecDeque<RecordedCall>>,
+}
+
+impl ReplayLlmClient {
+    /// # Function: load
+    ///
+    /// Reads a trajectory file written by `RecordingLlmClient`.
+    ///
+    /// ## Arguments:
+    /// - `path`: The trajectory file
+    ///
+    /// ## Returns:
+    /// - The replaying client, or an error if the file can't be read or
+    ///   a line is not a `RecordedCall`
+    pub async fn load(path: impl AsRef<Path>) -> AnyhowResult<Self> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        let calls = contents
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            calls: Mutex::new(calls),
+        })
+    }
+
+    /// Returns how many recorded calls have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+}
+
+impl LlmClient for ReplayLlmClient {
+    fn extract<'a>(&'a self, context: &'a str) -> BoxFuture<'a, AnyhowResult<AgentResponse>> {
+        let call = self.calls.lock().unwrap().pop_front();
+        Box::pin(async move {
+            let call = call.ok_or_else(|| anyhow::anyhow!("Recording has no more calls"))?;
+            anyhow::ensure!(
+                call.context == context,
+                "Replay diverged: recorded context {:?}, got {:?}",
+                call.context,
+                context
+            );
+            call.response.map_err(anyhow::Error::msg)
+        })
+    }
+
+    fn answers_instantly(&self) -> bool {
+        true
+    }
+}
+
 /// # Struct: AutonomousAgent
 ///
 /// An autonomous agent that implements the Future trait to demonstrate
@@ -208,9 +842,13 @@ impl MockLlmClient {
 /// - `goal`: Target goal to reach (set by first LLM response)
 /// - `state`: Current state of the agent state machine
 /// - `start_time`: When the agent started (for performance tracking)
+/// - `observer`: Optional observer notified of noteworthy events
+/// - `audit_log`: Every state transition so far
+/// - `llm_started`: When the current LLM call was sent
+/// - `audited`: The state the audit log last recorded a move to
 pub struct AutonomousAgent {
     /// The LLM client for making decisions
-    llm: Arc<MockLlmClient>,
+    llm: Arc<dyn LlmClient>,
     /// Current progress toward the goal
     progress: u32,
     /// Target goal to reach (set by first LLM response)
@@ -219,6 +857,13 @@ pub struct AutonomousAgent {
     state: AgentState,
     /// When the agent started (for performance tracking)
     start_time: Instant,
+    /// Optional observer notified of noteworthy events
+    observer: Option<Arc<dyn AgentObserver>>,
+    /// Every state transition so far
+    audit_log: AuditLog,
+    /// When the current LLM call was sent
+    llm_started: Option<Instant>,
+    audited: StateKind,
 }
 
 impl AutonomousAgent {
@@ -241,16 +886,20 @@ impl AutonomousAgent {
     /// ```rust
     /// let client = MockLlmClient::new();
     /// let agent = AutonomousAgent::new(client);
-    /// let final_progress = agent.await;
+    /// let final_progress = agent.await?;
     /// ```
-    pub fn new(llm: MockLlmClient) -> Self {
-        println!("🚀 Creating new autonomous agent");
+    pub fn new(llm: impl LlmClient + 'static) -> Self {
+        outln!("🚀 Creating new autonomous agent");
         Self {
             llm: Arc::new(llm),
             progress: 0,
             goal: 0,
             state: AgentState::Initializing,
             start_time: Instant::now(),
+            observer: None,
+            audit_log: AuditLog::default(),
+            llm_started: None,
+            audited: StateKind::Initializing,
         }
     }
 
@@ -264,8 +913,8 @@ impl AutonomousAgent {
     ///
     /// ## Returns:
     /// - A new AutonomousAgent with the specified initial progress
-    pub fn with_initial_progress(llm: MockLlmClient, initial_progress: u32) -> Self {
-        println!(
+    pub fn with_initial_progress(llm: impl LlmClient + 'static, initial_progress: u32) -> Self {
+        outln!(
             "🚀 Creating agent with initial progress: {}",
             initial_progress
         );
@@ -275,6 +924,165 @@ impl AutonomousAgent {
             goal: 0,
             state: AgentState::Initializing,
             start_time: Instant::now(),
+            observer: None,
+            audit_log: AuditLog::default(),
+            llm_started: None,
+            audited: StateKind::Initializing,
+        }
+    }
+
+    /// # Function: with_observer
+    ///
+    /// Attaches an observer that is notified of events such as goal drift.
+    ///
+    /// ## Arguments:
+    /// - `observer`: The observer to notify
+    ///
+    /// ## Returns:
+    /// - The agent with the observer attached
+    pub fn with_observer(mut self, observer: Arc<dyn AgentObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// # Function: with_progress_bar
+    ///
+    /// Shows the agent's progress as a terminal progress bar
+    /// (feature `progress-bar`). Replaces any attached observer.
+    ///
+    /// ## Returns:
+    /// - The agent with a `ProgressBarObserver` attached
+    #[cfg(feature = "progress-bar")]
+    pub fn with_progress_bar(self) -> Self {
+        let bar = indicatif::ProgressBar::new(0).with_style(
+            indicatif::ProgressStyle::with_template("{bar:40} {pos}/{len} ({elapsed})")
+                .expect("progress bar template is valid"),
+        );
+        self.with_observer(Arc::new(ProgressBarObserver::new(bar)))
+    }
+
+    /// # Function: step
+    ///
+    /// Advances the agent by exactly one LLM round: sends the context,
+    /// awaits the reply and applies it. Lets tests and debuggers drive the
+    /// agent one decision at a time instead of awaiting it as a whole.
+    ///
+    /// ## Returns:
+    /// - `StepResult`: The progress after the round, or how the agent ended.
+    ///   Stepping a finished agent returns the same result again.
+    ///
+    /// ## Note:
+    /// - The call is awaited in place rather than spawned, so it only makes
+    ///   progress while `step` is being awaited
+    /// - Dropping `step` mid-call leaves the agent as it was; the round is
+    ///   simply repeated by the next `step` or `poll`
+    /// - Can be mixed with polling: a call already started by `poll` is
+    ///   finished rather than sent again
+    pub async fn step(&mut self) -> StepResult {
+        // The state only moves once the call is done, so the transitions
+        // through Planning and Acting are recorded explicitly
+        match &mut self.state {
+            AgentState::Initializing => {
+                let llm = Arc::clone(&self.llm);
+                let context = self.next_context();
+                let started = Instant::now();
+                let reply = llm.extract(&context).await;
+                self.llm_started = Some(started);
+                self.audit_at(StateKind::Initializing, StateKind::Planning, started);
+                match reply {
+                    Ok(response) => self.act_on(response),
+                    Err(error) => self.state = self.handle_error(error.to_string()),
+                }
+            }
+            AgentState::Planning { receiver } => match receiver.await {
+                Ok(Ok(Ok(response))) => self.act_on(response),
+                Ok(Ok(Err(error))) => self.state = self.handle_error(error),
+                Ok(Err(panic)) => std::panic::resume_unwind(panic),
+                Err(_) => {
+                    self.state =
+                        self.handle_error("Communication channel closed unexpectedly".to_string())
+                }
+            },
+            AgentState::Acting { .. } => {
+                if let AgentState::Acting { response } =
+                    std::mem::replace(&mut self.state, AgentState::Poisoned)
+                {
+                    self.state = self.process_response(response);
+                    self.audit(StateKind::Acting, self.state.kind());
+                }
+            }
+            AgentState::Completed { .. } | AgentState::Failed { .. } | AgentState::Poisoned => {}
+        }
+
+        match &self.state {
+            AgentState::Completed { final_progress } => StepResult::Completed(*final_progress),
+            AgentState::Failed { error } => StepResult::Failed(AgentError::Failed {
+                error: error.clone(),
+                progress: self.progress,
+            }),
+            AgentState::Poisoned => StepResult::Failed(AgentError::Poisoned),
+            _ => StepResult::InProgress(self.progress),
+        }
+    }
+
+    /// Records Planning -> Acting -> next state for a reply `step` received
+    fn act_on(&mut self, response: AgentResponse) {
+        self.audit(StateKind::Planning, StateKind::Acting);
+        self.state = self.process_response(response);
+        self.audit(StateKind::Acting, self.state.kind());
+    }
+
+    /// # Function: take_audit_log
+    ///
+    /// Hands over the transitions recorded so far and starts a new log.
+    /// To read the log after completion, await the agent by reference:
+    /// `(&mut agent).await`.
+    ///
+    /// ## Returns:
+    /// - `AuditLog`: The transitions since the agent was created, or since
+    ///   the previous call
+    pub fn take_audit_log(&mut self) -> AuditLog {
+        std::mem::take(&mut self.audit_log)
+    }
+
+    /// Appends a transition to the audit log. Leaving `Planning` means the
+    /// LLM call has finished, so that entry carries the call's duration.
+    fn audit(&mut self, from_state: StateKind, to_state: StateKind) {
+        self.audit_at(from_state, to_state, Instant::now());
+    }
+
+    /// Like `audit`, for a transition that happened at `timestamp`
+    fn audit_at(&mut self, from_state: StateKind, to_state: StateKind, timestamp: Instant) {
+        let llm_duration = if from_state == StateKind::Planning {
+            self.llm_started.take().map(|started| started.elapsed())
+        } else {
+            None
+        };
+        self.audit_log.entries.push(AuditEntry {
+            timestamp,
+            from_state,
+            to_state,
+            progress: self.progress,
+            llm_duration,
+        });
+        self.audited = to_state;
+    }
+
+    /// Records the move `poll` just made, if it changed the kind of state
+    fn note_transition(&mut self) {
+        let current = self.state.kind();
+        if current != self.audited {
+            self.audit(self.audited, current);
+        }
+    }
+
+    /// The context for the next LLM call. Until a goal is known we send
+    /// "0", which asks the LLM to set one (even with initial progress)
+    fn next_context(&self) -> String {
+        if self.goal == 0 {
+            "0".to_string()
+        } else {
+            self.progress.to_string()
         }
     }
 
@@ -289,34 +1097,45 @@ impl AutonomousAgent {
     /// - `waker`: The waker to notify when the call completes
     ///
     /// ## Returns:
-    /// - `oneshot::Receiver<Result<AgentResponse, String>>`: Channel to receive the response
+    /// - `oneshot::Receiver<LlmReply>`: Channel to receive the response
     ///
     /// ## Key Patterns:
     /// - Spawns work on the tokio runtime to avoid blocking poll()
     /// - Uses oneshot channel for single-response communication
     /// - Clones waker to notify when background work completes
     /// - Converts errors to strings for channel transmission
+    /// - Catches a panic so it is re-raised in poll() instead of being lost
     fn start_llm_call(
-        llm: Arc<MockLlmClient>,
+        llm: Arc<dyn LlmClient>,
         context: String,
         waker: std::task::Waker,
-    ) -> oneshot::Receiver<Result<AgentResponse, String>> {
+    ) -> oneshot::Receiver<LlmReply> {
         let (tx, rx) = oneshot::channel();
 
+        // An instant client answers on its first poll, so there is no need
+        // for a background task; the reply is ready when Planning polls it
+        if llm.answers_instantly() {
+            let call = AssertUnwindSafe(llm.extract(&context)).catch_unwind();
+            if let Some(result) = call.now_or_never() {
+                let _ = tx.send(result.map(|result| result.map_err(|e| e.to_string())));
+                return rx;
+            }
+        }
+
         // Spawn the LLM call in a background task
         // This ensures we don't block the executor thread
         tokio::spawn(async move {
-            println!("🔄 Starting background LLM call");
-            let result = llm.extract(&context).await;
+            outln!("🔄 Starting background LLM call");
+            let result = AssertUnwindSafe(llm.extract(&context)).catch_unwind().await;
 
             // Convert the result to a string-based error for channel transmission
-            let channel_result = result.map_err(|e| e.to_string());
+            let channel_result = result.map(|result| result.map_err(|e| e.to_string()));
 
             // Send the result through the channel
-            if let Err(_) = tx.send(channel_result) {
-                println!("⚠️  Failed to send LLM response - receiver dropped");
+            if tx.send(channel_result).is_err() {
+                outln!("⚠️  Failed to send LLM response - receiver dropped");
             } else {
-                println!("📤 LLM response sent through channel");
+                outln!("📤 LLM response sent through channel");
             }
 
             // Wake the future to continue polling
@@ -328,51 +1147,73 @@ impl AutonomousAgent {
 
     /// # Function: process_response
     ///
-    /// Processes an LLM response and updates the agent's state.
+    /// Processes an LLM response and decides the agent's next state.
     /// This demonstrates state transition logic in async state machines.
     ///
     /// ## Arguments:
     /// - `response`: The response received from the LLM
     ///
+    /// ## Returns:
+    /// - `AgentState`: The state to move to
+    ///
     /// ## State Transitions:
     /// - Updates progress with the action value
     /// - Sets goal if this is the first response
+    /// - Ignores a later attempt to change the goal, reporting `GoalDrift`
     /// - Transitions to Completed if goal is reached
     /// - Transitions back to Initializing for next iteration
-    fn process_response(&mut self, response: AgentResponse) {
-        println!(
+    fn process_response(&mut self, response: AgentResponse) -> AgentState {
+        outln!(
             "📊 Processing response: action={}, goal={}",
-            response.action, response.goal
+            response.action,
+            response.goal
         );
 
         // Set goal if this is the first response (goal > 0)
         if self.goal == 0 && response.goal > 0 {
             self.goal = response.goal;
-            println!("🎯 Goal set by agent: {}", self.goal);
+            outln!("🎯 Goal set by agent: {}", self.goal);
+        } else if response.goal > 0 && response.goal != self.goal {
+            // Only the first response may set the goal; keep it fixed
+            outln!(
+                "⚠️  Goal drift: response tried to change goal {} -> {}, ignoring",
+                self.goal,
+                response.goal
+            );
+            if let Some(observer) = &self.observer {
+                observer.on_event(&AgentEvent::GoalDrift {
+                    established: self.goal,
+                    attempted: response.goal,
+                });
+            }
         }
 
         // Update progress with the action
         self.progress += response.action;
-        println!(
+        outln!(
             "📈 Progress updated: {} / {} ({:.1}%)",
             self.progress,
             self.goal,
             (self.progress as f64 / self.goal as f64) * 100.0
         );
+        if let Some(observer) = &self.observer {
+            observer.on_progress(self.progress, self.goal);
+        }
 
         // Check if goal is reached
         if self.progress >= self.goal && self.goal > 0 {
             let elapsed = self.start_time.elapsed();
-            println!(
+            outln!(
                 "🏆 Goal achieved! Final progress: {} (took {:?})",
-                self.progress, elapsed
+                self.progress,
+                elapsed
             );
-            self.state = AgentState::Completed {
+            AgentState::Completed {
                 final_progress: self.progress,
-            };
+            }
         } else {
             // Continue with next iteration
-            self.state = AgentState::Initializing;
+            AgentState::Initializing
         }
     }
 
@@ -384,119 +1225,324 @@ impl AutonomousAgent {
     /// ## Arguments:
     /// - `error`: The error message from the failed API call
     ///
+    /// ## Returns:
+    /// - `AgentState`: The state to move to
+    ///
     /// ## Error Handling Strategy:
     /// - Logs the error for debugging
     /// - Transitions to Failed state for unrecoverable errors
     /// - Could be extended to implement retry logic
-    fn handle_error(&mut self, error: String) {
-        println!("❌ Agent error: {}", error);
-        self.state = AgentState::Failed { error };
+    fn handle_error(&self, error: String) -> AgentState {
+        outln!("❌ Agent error: {}", error);
+        AgentState::Failed { error }
+    }
+}
+
+/// How many state transitions a single `poll` may make before yielding
+const TRANSITION_BUDGET: u32 = 16;
+
+future_trait_tutorial::state_machine! {
+    /// # Implementation: Future for AutonomousAgent
+    ///
+    /// This is the core implementation that makes AutonomousAgent a Future.
+    /// It demonstrates advanced polling patterns for complex state machines.
+    /// The loop around the states is generated by `state_machine!`; each arm
+    /// below only decides what comes next.
+    ///
+    /// ## Key Patterns Demonstrated:
+    /// 1. **State Machine Polling**: Different logic for each state
+    /// 2. **Channel Integration**: Polling oneshot receivers
+    /// 3. **Background Task Coordination**: Spawning work and waiting for results
+    /// 4. **Efficient Waker Usage**: Only waking when state changes
+    /// 5. **Error Propagation**: Handling errors at each state transition
+    impl Future for AutonomousAgent {
+        /// The agent completes with its final progress value, or why it stopped
+        type Output = Result<u32, AgentError>;
+        state = state, poisoned = AgentState::Poisoned, budget = TRANSITION_BUDGET;
+        on_transition = note_transition;
+
+        /// # Function: poll
+        ///
+        /// The heart of the Future implementation. This method is called by the
+        /// executor to advance the agent's state machine.
+        ///
+        /// ## Polling Strategy:
+        /// - Uses pattern matching on the current state
+        /// - Each state has specific polling logic
+        /// - Transitions between states based on results
+        /// - Returns Poll::Pending when waiting for async operations
+        /// - Returns Poll::Ready when the goal is achieved or an error occurs
+        /// - Parks the state in `Poisoned` during each transition, so a panic
+        ///   mid-transition can't leave the agent looking like it should restart
+        /// - Yields after `TRANSITION_BUDGET` transitions: with an instant LLM
+        ///   the loop never hits `Pending`, and would otherwise keep the executor
+        ///   thread busy until the goal is reached
+        /// - Records every change of state in the audit log
+        ///
+        /// ## Arguments:
+        /// - `self`: Pin<&mut Self> - ensures the future won't move in memory
+        /// - `cx`: &mut Context - provides access to the waker
+        ///
+        /// ## Returns:
+        /// - `Poll<Self::Output>`: Ready with final progress (or an error) or Pending
+        poll(agent, cx) {
+            AgentState::Initializing => {
+                outln!("🔄 Agent state: Initializing");
+
+                // Start a new LLM call
+                let context = agent.next_context();
+                agent.llm_started = Some(Instant::now());
+                let receiver =
+                    Self::start_llm_call(Arc::clone(&agent.llm), context, cx.waker().clone());
+
+                // Transition to Planning state and immediately poll it
+                Step::Next(AgentState::Planning { receiver })
+            }
+
+            AgentState::Planning { mut receiver } => {
+                outln!("🤔 Agent state: Planning (polling LLM response)");
+
+                // Poll the oneshot receiver for the LLM response
+                match Pin::new(&mut receiver).poll(cx) {
+                    Poll::Pending => {
+                        // LLM call is still in progress
+                        outln!("⏳ LLM call still in progress");
+                        Step::Pending(AgentState::Planning { receiver })
+                    }
+                    Poll::Ready(Ok(Ok(Ok(response)))) => {
+                        // LLM call succeeded; process the response next
+                        outln!("✅ LLM call succeeded");
+                        Step::Next(AgentState::Acting { response })
+                    }
+                    Poll::Ready(Ok(Ok(Err(error)))) => {
+                        // LLM call failed
+                        outln!("❌ LLM call failed: {}", error);
+                        Step::Next(agent.handle_error(error))
+                    }
+                    Poll::Ready(Ok(Err(panic))) => {
+                        // The LLM client panicked; surface it to our caller
+                        outln!("❌ LLM call panicked");
+                        std::panic::resume_unwind(panic);
+                    }
+                    Poll::Ready(Err(_)) => {
+                        // Channel was closed unexpectedly
+                        let error = "Communication channel closed unexpectedly".to_string();
+                        outln!("❌ {}", error);
+                        Step::Next(agent.handle_error(error))
+                    }
+                }
+            }
+
+            AgentState::Acting { response } => {
+                outln!("⚡ Agent state: Acting (processing response)");
+
+                // Process the LLM response and move to whatever it decides
+                Step::Next(agent.process_response(response))
+            }
+
+            AgentState::Completed { final_progress } => {
+                outln!("🏁 Agent state: Completed");
+                Step::Ready(AgentState::Completed { final_progress }, Ok(final_progress))
+            }
+
+            AgentState::Failed { error } => {
+                outln!("💥 Agent state: Failed - {}", error);
+                let progress = agent.progress;
+                Step::Ready(
+                    AgentState::Failed { error: error.clone() },
+                    Err(AgentError::Failed { error, progress }),
+                )
+            }
+
+            AgentState::Poisoned => {
+                outln!("☠️  Agent state: Poisoned");
+                Step::Ready(AgentState::Poisoned, Err(AgentError::Poisoned))
+            }
+        }
     }
 }
 
-/// # Implementation: Future for AutonomousAgent
+/// # Enum: AskError
 ///
-/// This is the core implementation that makes AutonomousAgent a Future.
-/// It demonstrates advanced polling patterns for complex state machines.
+/// Ways a request/response exchange with an actor can fail.
 ///
-/// ## Key Patterns Demonstrated:
-/// 1. **State Machine Polling**: Different logic for each state
-/// 2. **Channel Integration**: Polling oneshot receivers
-/// 3. **Background Task Coordination**: Spawning work and waiting for results
-/// 4. **Efficient Waker Usage**: Only waking when state changes
-/// 5. **Error Propagation**: Handling errors at each state transition
-impl Future for AutonomousAgent {
-    /// The agent completes with its final progress value
-    type Output = u32;
+/// ## Variants:
+/// - `Closed`: The actor's mailbox is closed, so the request was never delivered
+/// - `NoResponse`: The actor received the request but dropped the responder
+#[derive(Error, Debug, PartialEq)]
+pub enum AskError {
+    #[error("actor mailbox is closed")]
+    Closed,
+
+    #[error("actor dropped the request without responding")]
+    NoResponse,
+}
 
-    /// # Function: poll
-    ///
-    /// The heart of the Future implementation. This method is called by the
-    /// executor to advance the agent's state machine.
+/// # Function: ask
+///
+/// Sends a request to an actor together with a fresh oneshot responder and
+/// waits for the reply. This generalizes the agent's channel pattern: the
+/// caller owns the receiving half, the actor owns the sending half.
+///
+/// ## Arguments:
+/// - `tx`: The actor's mailbox
+/// - `req`: The request to send
+///
+/// ## Returns:
+/// - `Result<Resp, AskError>`: The actor's reply, or why none arrived
+///
+/// ## Cancellation:
+/// Dropping the returned future drops the oneshot receiver, so an actor that
+/// checks `responder.is_closed()` can skip work nobody is waiting for.
+pub async fn ask<Req, Resp>(
+    tx: &mpsc::Sender<(Req, oneshot::Sender<Resp>)>,
+    req: Req,
+) -> Result<Resp, AskError> {
+    let (responder, reply) = oneshot::channel();
+    tx.send((req, responder))
+        .await
+        .map_err(|_| AskError::Closed)?;
+    reply.await.map_err(|_| AskError::NoResponse)
+}
+
+/// # Function: spawn_counter_actor
+///
+/// Starts an actor that owns a running total. Each request adds its value
+/// to the total and is answered with the new total.
+///
+/// ## Returns:
+/// - The actor's mailbox; the actor stops once every sender is dropped
+fn spawn_counter_actor() -> mpsc::Sender<(u32, oneshot::Sender<u32>)> {
+    let (tx, mut rx) = mpsc::channel::<(u32, oneshot::Sender<u32>)>(16);
+
+    tokio::spawn(async move {
+        let mut total = 0;
+        while let Some((amount, responder)) = rx.recv().await {
+            total += amount;
+            // The asker may have given up; that's not the actor's problem
+            let _ = responder.send(total);
+        }
+    });
+
+    tx
+}
+
+/// # Enum: CounterCommand
+///
+/// Messages understood by the batching counter actor.
+///
+/// ## Variants:
+/// - `Increment`: Adds to the total; applied with the rest of its batch
+/// - `Flush`: Applies the pending batch now and replies with the total
+#[derive(Debug)]
+pub enum CounterCommand {
+    Increment(u32),
+    Flush(oneshot::Sender<u32>),
+}
+
+/// # Struct: BatchingCounter
+///
+/// Handle to a counter actor that batches writes: increments arriving
+/// within `window` of the first pending one are coalesced and applied
+/// together when that deadline passes, or earlier on `flush`. Batching
+/// like this turns many small writes (to a database, say) into few large
+/// ones.
+///
+/// ## Fields:
+/// - `mailbox`: The actor's command channel
+/// - `batches`: How many batches the actor has applied (instrumentation)
+#[derive(Debug, Clone)]
+pub struct BatchingCounter {
+    mailbox: mpsc::Sender<CounterCommand>,
+    batches: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl BatchingCounter {
+    /// # Function: spawn
     ///
-    /// ## Polling Strategy:
-    /// - Uses pattern matching on the current state
-    /// - Each state has specific polling logic
-    /// - Transitions between states based on results
-    /// - Returns Poll::Pending when waiting for async operations
-    /// - Returns Poll::Ready when the goal is achieved or an error occurs
+    /// Starts the actor.
     ///
     /// ## Arguments:
-    /// - `self`: Pin<&mut Self> - ensures the future won't move in memory
-    /// - `cx`: &mut Context - provides access to the waker
+    /// - `window`: How long the first pending increment may wait for
+    ///   others to join its batch
     ///
     /// ## Returns:
-    /// - `Poll<Self::Output>`: Ready with final progress or Pending
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        loop {
-            match std::mem::replace(&mut self.state, AgentState::Initializing) {
-                AgentState::Initializing => {
-                    println!("🔄 Agent state: Initializing");
-
-                    // Start a new LLM call
-                    let context = self.progress.to_string();
-                    let receiver =
-                        Self::start_llm_call(Arc::clone(&self.llm), context, cx.waker().clone());
-
-                    // Transition to Planning state
-                    self.state = AgentState::Planning { receiver };
-                    // Continue the loop to immediately poll the new state
+    /// - A handle; the actor applies what's pending and stops once every
+    ///   handle is dropped
+    pub fn spawn(window: Duration) -> Self {
+        use std::sync::atomic::Ordering;
+
+        let (mailbox, mut rx) = mpsc::channel(64);
+        let batches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let applied = batches.clone();
+
+        tokio::spawn(async move {
+            let mut total = 0;
+            let mut pending: Option<u32> = None;
+            let mut deadline: Option<tokio::time::Instant> = None;
+
+            let apply = |total: &mut u32, pending: &mut Option<u32>| {
+                if let Some(batch) = pending.take() {
+                    *total += batch;
+                    applied.fetch_add(1, Ordering::SeqCst);
                 }
+            };
+
+            loop {
+                let command = tokio::select! {
+                    command = rx.recv() => command,
+                    _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)),
+                        if deadline.is_some() =>
+                    {
+                        apply(&mut total, &mut pending);
+                        deadline = None;
+                        continue;
+                    }
+                };
 
-                AgentState::Planning { mut receiver } => {
-                    println!("🤔 Agent state: Planning (polling LLM response)");
-
-                    // Poll the oneshot receiver for the LLM response
-                    match Pin::new(&mut receiver).poll(cx) {
-                        Poll::Pending => {
-                            // LLM call is still in progress
-                            println!("⏳ LLM call still in progress");
-                            self.state = AgentState::Planning { receiver };
-                            return Poll::Pending;
-                        }
-                        Poll::Ready(Ok(Ok(response))) => {
-                            // LLM call succeeded
-                            println!("✅ LLM call succeeded");
-                            self.state = AgentState::Acting { response };
-                            // Continue the loop to process the response
-                        }
-                        Poll::Ready(Ok(Err(error))) => {
-                            // LLM call failed
-                            println!("❌ LLM call failed: {}", error);
-                            self.handle_error(error);
-                            // Continue the loop to handle the error state
-                        }
-                        Poll::Ready(Err(_)) => {
-                            // Channel was closed unexpectedly
-                            let error = "Communication channel closed unexpectedly".to_string();
-                            println!("❌ {}", error);
-                            self.handle_error(error);
-                            // Continue the loop to handle the error state
-                        }
+                match command {
+                    Some(CounterCommand::Increment(amount)) => {
+                        *pending.get_or_insert(0) += amount;
+                        // The window starts with the first increment of a batch
+                        deadline.get_or_insert_with(|| tokio::time::Instant::now() + window);
+                    }
+                    Some(CounterCommand::Flush(responder)) => {
+                        apply(&mut total, &mut pending);
+                        deadline = None;
+                        let _ = responder.send(total);
+                    }
+                    None => {
+                        apply(&mut total, &mut pending);
+                        break;
                     }
                 }
+            }
+        });
 
-                AgentState::Acting { response } => {
-                    println!("⚡ Agent state: Acting (processing response)");
+        Self { mailbox, batches }
+    }
 
-                    // Process the LLM response and update state
-                    self.process_response(response);
-                    // Continue the loop to handle the new state
-                }
+    /// Queues an increment; it is applied with the rest of its batch.
+    pub async fn increment(&self, amount: u32) -> Result<(), AskError> {
+        self.mailbox
+            .send(CounterCommand::Increment(amount))
+            .await
+            .map_err(|_| AskError::Closed)
+    }
 
-                AgentState::Completed { final_progress } => {
-                    println!("🏁 Agent state: Completed");
-                    return Poll::Ready(final_progress);
-                }
+    /// Applies pending increments immediately and returns the new total.
+    pub async fn flush(&self) -> Result<u32, AskError> {
+        let (responder, reply) = oneshot::channel();
+        self.mailbox
+            .send(CounterCommand::Flush(responder))
+            .await
+            .map_err(|_| AskError::Closed)?;
+        reply.await.map_err(|_| AskError::NoResponse)
+    }
 
-                AgentState::Failed { error } => {
-                    println!("💥 Agent state: Failed - {}", error);
-                    // For this example, we'll return the current progress even on failure
-                    // In a real application, you might want to return an error type
-                    return Poll::Ready(self.progress);
-                }
-            }
-        }
+    /// Returns how many batches have been applied so far.
+    pub fn batches_applied(&self) -> usize {
+        self.batches.load(std::sync::atomic::Ordering::SeqCst)
     }
 }
 
@@ -511,22 +1557,23 @@ impl Future for AutonomousAgent {
 /// - Observing state transitions
 /// - Performance tracking
 async fn demonstrate_basic_agent() {
-    println!("\n=== Basic Autonomous Agent ===");
+    outln!("\n=== Basic Autonomous Agent ===");
 
     // Create a mock LLM client
     let llm_client = MockLlmClient::new();
 
     // Create and run the agent
-    println!("1. Creating and running autonomous agent:");
+    outln!("1. Creating and running autonomous agent:");
     let start_time = Instant::now();
 
     let agent = AutonomousAgent::new(llm_client);
-    let final_progress = agent.await;
+    let result = agent.await;
 
     let total_time = start_time.elapsed();
-    println!(
-        "   Agent completed with final progress: {} (took {:?})",
-        final_progress, total_time
+    outln!(
+        "   Agent finished with {:?} (took {:?})",
+        result,
+        total_time
     );
 }
 
@@ -540,20 +1587,21 @@ async fn demonstrate_basic_agent() {
 /// - Decision-making adapts to current state
 /// - Goal achievement is relative to starting point
 async fn demonstrate_agent_with_initial_progress() {
-    println!("\n=== Agent with Initial Progress ===");
+    outln!("\n=== Agent with Initial Progress ===");
 
     let llm_client = MockLlmClient::new();
 
-    println!("1. Agent starting with progress 800:");
+    outln!("1. Agent starting with progress 800:");
     let start_time = Instant::now();
 
     let agent = AutonomousAgent::with_initial_progress(llm_client, 800);
-    let final_progress = agent.await;
+    let result = agent.await;
 
     let total_time = start_time.elapsed();
-    println!(
-        "   Agent completed with final progress: {} (took {:?})",
-        final_progress, total_time
+    outln!(
+        "   Agent finished with {:?} (took {:?})",
+        result,
+        total_time
     );
 }
 
@@ -568,22 +1616,100 @@ async fn demonstrate_agent_with_initial_progress() {
 /// - Graceful degradation strategies
 /// - Error logging and debugging
 async fn demonstrate_error_handling() {
-    println!("\n=== Error Handling ===");
+    outln!("\n=== Error Handling ===");
 
     // Create a client that simulates failures
     let failing_client = MockLlmClient::with_failure_rate(true);
 
-    println!("1. Agent with failing LLM client:");
+    outln!("1. Agent with failing LLM client:");
     let start_time = Instant::now();
 
     let agent = AutonomousAgent::new(failing_client);
-    let final_progress = agent.await;
+    let result = agent.await;
 
     let total_time = start_time.elapsed();
-    println!(
-        "   Agent handled failure, final progress: {} (took {:?})",
-        final_progress, total_time
-    );
+    match result {
+        Ok(final_progress) => outln!("   Unexpected success: {}", final_progress),
+        Err(error) => outln!(
+            "   Agent handled failure: {} (took {:?})",
+            error,
+            total_time
+        ),
+    }
+
+    outln!("\n2. Agent whose LLM tries to move the goal:");
+    let drifting_client = MockLlmClient::scripted(vec![
+        AgentResponse {
+            action: 400,
+            goal: 1000,
+        },
+        AgentResponse {
+            action: 300,
+            goal: 3000,
+        },
+        AgentResponse {
+            action: 300,
+            goal: 0,
+        },
+    ]);
+    let log = Arc::new(EventLog::default());
+
+    let result = AutonomousAgent::new(drifting_client)
+        .with_observer(log.clone())
+        .await;
+
+    outln!("   Final progress: {:?} (goal stayed at 1000)", result);
+    for event in log.events() {
+        outln!("   Observed: {:?}", event);
+    }
+
+    outln!("\n3. Rejecting malformed context:");
+    for context in ["150", "", "12abc", "99999999999"] {
+        match parse_context(context) {
+            Ok(progress) => outln!("   {:?} -> progress {}", context, progress),
+            Err(error) => outln!("   {:?} -> {}", context, error),
+        }
+    }
+
+    outln!("\n4. Agent whose LLM client panics:");
+    let mut agent = AutonomousAgent::new(MockLlmClient::panicking());
+    let first = AssertUnwindSafe(&mut agent).catch_unwind().await;
+    outln!("   First run panicked: {}", first.is_err());
+    // Polling again must not quietly start over with a fresh LLM call
+    match agent.await {
+        Err(AgentError::Poisoned) => outln!("   Second run: agent reports it is poisoned"),
+        other => outln!("   Second run: unexpected {:?}", other),
+    }
+
+    outln!("\n5. Gating agent starts on a health check:");
+    for client in [MockLlmClient::new(), MockLlmClient::with_failure_rate(true)] {
+        match client.health_check().await {
+            Ok(()) => {
+                let result = AutonomousAgent::with_initial_progress(client, 900).await;
+                outln!("   Healthy client, agent finished with: {:?}", result);
+            }
+            Err(error) => outln!("   Not starting agent: {}", error),
+        }
+    }
+}
+
+/// # Function: demonstrate_progress_bar
+///
+/// Demonstrates watching an agent through an observer that draws a
+/// terminal progress bar (run with `--features progress-bar`).
+///
+/// ## Key Learning Points:
+/// - Observers turn the agent's internal progress into visible output
+/// - The bar saturates at the goal even when the last step overshoots it
+#[cfg(feature = "progress-bar")]
+async fn demonstrate_progress_bar() {
+    outln!("\n=== Progress Bar ===");
+
+    outln!("1. Agent with a progress bar:");
+    let result = AutonomousAgent::new(MockLlmClient::new())
+        .with_progress_bar()
+        .await;
+    outln!("   Agent finished with {:?}", result);
 }
 
 /// # Function: demonstrate_concurrent_agents
@@ -597,9 +1723,9 @@ async fn demonstrate_error_handling() {
 /// - Performance benefits of concurrent execution
 /// - Handling mixed success/failure scenarios
 async fn demonstrate_concurrent_agents() {
-    println!("\n=== Concurrent Agents ===");
+    outln!("\n=== Concurrent Agents ===");
 
-    println!("1. Running 3 agents concurrently:");
+    outln!("1. Running 3 agents concurrently:");
     let start_time = Instant::now();
 
     // Create multiple agents with different configurations
@@ -611,14 +1737,111 @@ async fn demonstrate_concurrent_agents() {
     let (progress1, progress2, progress3) = tokio::join!(agent1, agent2, agent3);
 
     let total_time = start_time.elapsed();
-    println!("   Agent 1 final progress: {}", progress1);
-    println!("   Agent 2 final progress: {}", progress2);
-    println!("   Agent 3 final progress: {}", progress3);
-    println!("   All agents completed in: {:?}", total_time);
+    outln!("   Agent 1 finished with: {:?}", progress1);
+    outln!("   Agent 2 finished with: {:?}", progress2);
+    outln!("   Agent 3 finished with: {:?}", progress3);
+    outln!("   All agents completed in: {:?}", total_time);
+
+    // Calculate total progress across the agents that reached their goal
+    let total_progress: u32 = [progress1, progress2, progress3]
+        .into_iter()
+        .flatten()
+        .sum();
+    outln!("   Combined progress: {}", total_progress);
+}
+
+/// # Function: demonstrate_record_replay
+///
+/// Demonstrates recording an agent's LLM calls and replaying them.
+///
+/// ## Key Learning Points:
+/// - A trait object lets the agent work with any LLM client
+/// - Wrapping a client adds behavior (recording) without changing it
+/// - A replayed run reproduces the original without its latency
+async fn demonstrate_record_replay() -> AnyhowResult<()> {
+    outln!("\n=== Recording and Replaying a Run ===");
+    let path = std::env::temp_dir().join(format!("agent-trajectory-{}.jsonl", std::process::id()));
+
+    outln!("1. Recording a run against the mock LLM:");
+    let start_time = Instant::now();
+    let llm = MockLlmClient::randomized(TestRng::from_run_seed());
+    let recorder = RecordingLlmClient::create(llm, &path).await?;
+    let recorded = AutonomousAgent::with_initial_progress(recorder, 600).await;
+    outln!(
+        "   Finished with {:?} in {:?}",
+        recorded,
+        start_time.elapsed()
+    );
+
+    outln!("\n2. Replaying the recording:");
+    let start_time = Instant::now();
+    let replayed =
+        AutonomousAgent::with_initial_progress(ReplayLlmClient::load(&path).await?, 600).await;
+    outln!(
+        "   Finished with {:?} in {:?}",
+        replayed,
+        start_time.elapsed()
+    );
+
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}
+
+/// # Function: demonstrate_stepping
+///
+/// Drives an agent one LLM round at a time with `step` instead of awaiting
+/// it, printing the progress between rounds.
+///
+/// ## Key Learning Points:
+/// - A state machine can expose its transitions as well as a `Future`
+/// - Stepping makes every intermediate state observable in tests
+async fn demonstrate_stepping() {
+    outln!("\n=== Stepping an Agent Round by Round ===");
+
+    let client = MockLlmClient::scripted(vec![
+        AgentResponse {
+            action: 400,
+            goal: 1000,
+        },
+        AgentResponse {
+            action: 350,
+            goal: 0,
+        },
+    ]);
+    let mut agent = AutonomousAgent::new(client);
+
+    let mut round = 1;
+    loop {
+        match agent.step().await {
+            StepResult::InProgress(progress) => {
+                outln!("   Round {}: progress {}", round, progress)
+            }
+            StepResult::Completed(progress) => {
+                outln!("   Round {}: goal reached at {}", round, progress);
+                break;
+            }
+            StepResult::Failed(error) => {
+                outln!("   Round {}: {}", round, error);
+                break;
+            }
+        }
+        round += 1;
+    }
 
-    // Calculate total progress across all agents
-    let total_progress = progress1 + progress2 + progress3;
-    println!("   Combined progress: {}", total_progress);
+    let log = agent.take_audit_log();
+    outln!(
+        "   Audit log: {} transitions, {:?} waiting for the LLM",
+        log.entries().len(),
+        log.llm_time()
+    );
+    for entry in log.entries() {
+        outln!(
+            "     {:?} -> {:?} at progress {}",
+            entry.from_state,
+            entry.to_state,
+            entry.progress
+        );
+    }
 }
 
 /// # Function: demonstrate_agent_cancellation
@@ -632,25 +1855,64 @@ async fn demonstrate_concurrent_agents() {
 /// - Proper resource cleanup on cancellation
 /// - Using select! for timeout-based cancellation
 async fn demonstrate_agent_cancellation() {
-    println!("\n=== Agent Cancellation ===");
+    outln!("\n=== Agent Cancellation ===");
 
-    println!("1. Agent with timeout (will be cancelled):");
+    outln!("1. Agent with timeout (will be cancelled):");
     let start_time = Instant::now();
 
     let agent = AutonomousAgent::new(MockLlmClient::new());
 
     // Use select! to implement a timeout
     tokio::select! {
-        final_progress = agent => {
-            println!("   Agent completed with progress: {}", final_progress);
+        result = agent => {
+            outln!("   Agent finished with: {:?}", result);
         }
         _ = sleep(Duration::from_millis(300)) => {
-            println!("   Agent was cancelled due to timeout");
+            outln!("   Agent was cancelled due to timeout");
         }
     }
 
     let elapsed = start_time.elapsed();
-    println!("   Cancellation demo completed in: {:?}", elapsed);
+    outln!("   Cancellation demo completed in: {:?}", elapsed);
+}
+
+/// # Function: demonstrate_request_response
+///
+/// Demonstrates talking to an actor with `ask`: several agents report their
+/// progress to a shared counter actor and get the combined total back.
+///
+/// ## Key Learning Points:
+/// - Pairing each request with its own oneshot responder
+/// - Actors own their state, so no locks are needed
+/// - A dropped responder surfaces as `AskError::NoResponse`
+async fn demonstrate_request_response() {
+    outln!("\n=== Request/Response with an Actor ===");
+
+    outln!("1. Reporting progress to a counter actor:");
+    let counter = spawn_counter_actor();
+
+    for (agent, progress) in [("agent-1", 120), ("agent-2", 340), ("agent-3", 75)] {
+        match ask(&counter, progress).await {
+            Ok(total) => outln!("   {} reported {}, combined: {}", agent, progress, total),
+            Err(error) => outln!("   {} could not report: {}", agent, error),
+        }
+    }
+
+    outln!("\n2. Batching writes in a counter actor:");
+    let counter = BatchingCounter::spawn(Duration::from_millis(50));
+    for step in 1..=10 {
+        let _ = counter.increment(step).await;
+    }
+    sleep(Duration::from_millis(80)).await; // Let the batch deadline pass
+    let _ = counter.increment(100).await;
+    match counter.flush().await {
+        Ok(total) => outln!(
+            "   Total {} after 11 increments in {} batches",
+            total,
+            counter.batches_applied()
+        ),
+        Err(error) => outln!("   Flush failed: {}", error),
+    }
 }
 
 /// # Function: main
@@ -663,34 +1925,66 @@ async fn demonstrate_agent_cancellation() {
 /// 3. Error handling and resilience
 /// 4. Concurrent agent execution
 /// 5. Cancellation and timeout handling
+/// 6. Request/response messaging with actors
 #[tokio::main]
 async fn main() -> AnyhowResult<()> {
-    println!("🤖 Autonomous Agent Future Implementation Tutorial");
-    println!("=================================================");
-    println!(
+    let _flush = output::install_global(BufferedOutput::new(
+        tokio::io::stdout(),
+        8 * 1024,
+        Duration::from_millis(50),
+    ));
+    outln!("🤖 Autonomous Agent Future Implementation Tutorial");
+    outln!("=================================================");
+    outln!(
         "This example demonstrates building complex async state machines using the Future trait."
     );
+    outln!(
+        "🎲 Seed {} (set {} to repeat this run)",
+        seed::run_seed(),
+        seed::SEED_ENV_VAR
+    );
+
+    demonstrate_basic_agent().await;
+
+    demonstrate_agent_with_initial_progress().await;
+
+    demonstrate_concurrent_agents().await;
 
-    // For demonstration purposes, let's just run the cancellation example
-    // to show the concept without infinite loops
     demonstrate_agent_cancellation().await;
 
     // Error handling (this will complete quickly)
     demonstrate_error_handling().await;
 
-    println!("\n✅ Autonomous Agent Tutorial completed!");
-    println!("Key takeaways:");
-    println!("  - Complex state machines can be implemented using the Future trait");
-    println!("  - Enum-based states provide clear state transition logic");
-    println!("  - Background tasks can be coordinated using channels and wakers");
-    println!("  - Error handling is crucial for robust autonomous systems");
-    println!("  - Multiple agents can run concurrently for improved performance");
-    println!("  - Cancellation safety ensures clean resource management");
-    println!("  - Real-world async patterns can be built on Future fundamentals");
-
-    println!("\nNote: The basic agent demo is commented out to prevent infinite loops.");
-    println!("In a real implementation, you would add proper termination conditions.");
-    println!("\nNext: Experiment with different agent configurations and error scenarios");
+    demonstrate_request_response().await;
+
+    demonstrate_record_replay().await?;
+
+    demonstrate_stepping().await;
+
+    #[cfg(feature = "progress-bar")]
+    demonstrate_progress_bar().await;
+
+    outln!("\n✅ Autonomous Agent Tutorial completed!");
+    outln!("Key takeaways:");
+    outln!("  - Complex state machines can be implemented using the Future trait");
+    outln!("  - Enum-based states provide clear state transition logic");
+    outln!("  - Background tasks can be coordinated using channels and wakers");
+    outln!("  - Error handling is crucial for robust autonomous systems");
+    outln!("  - Validating responses keeps a misbehaving LLM from moving the goal");
+    outln!("  - A panic mid-transition poisons the agent instead of restarting it");
+    outln!("  - A cheap health check keeps agents from starting on a broken client");
+    outln!("  - Multiple agents can run concurrently for improved performance");
+    outln!("  - Cancellation safety ensures clean resource management");
+    outln!("  - Actors answer requests through per-request oneshot channels");
+    outln!("  - Batching coalesces many small writes into a few larger ones");
+    outln!("  - Recorded LLM calls can be replayed to reproduce a run exactly");
+    outln!("  - Drawing every random choice from one seed makes whole runs repeatable");
+    outln!("  - step() drives the state machine one round at a time for tests");
+    outln!("  - An audit log of transitions explains how the agent got its result");
+    outln!("  - Real-world async patterns can be built on Future fundamentals");
+    outln!("  - Buffered output batches many small writes into a few large ones");
+
+    outln!("\nNext: Experiment with different agent configurations and error scenarios");
 
     Ok(())
 }
@@ -698,14 +1992,14 @@ async fn main() -> AnyhowResult<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_test;
+    use std::task::Context;
 
     /// Test basic agent functionality
     #[tokio::test]
     async fn test_basic_agent() {
         let client = MockLlmClient::new();
         let agent = AutonomousAgent::new(client);
-        let final_progress = agent.await;
+        let final_progress = agent.await.unwrap();
 
         // Agent should reach its goal
         assert!(final_progress >= 1000);
@@ -717,7 +2011,7 @@ mod tests {
         let client = MockLlmClient::new();
         let initial_progress = 800;
         let agent = AutonomousAgent::with_initial_progress(client, initial_progress);
-        let final_progress = agent.await;
+        let final_progress = agent.await.unwrap();
 
         // Final progress should be at least the initial progress
         assert!(final_progress >= initial_progress);
@@ -725,16 +2019,34 @@ mod tests {
         assert!(final_progress >= 1000);
     }
 
+    /// Test that an agent asks for a goal before reporting its progress:
+    /// the LLM only sets a goal for context "0", so an agent with initial
+    /// progress that sent its progress first would never get one
+    #[tokio::test]
+    async fn test_initial_progress_asks_for_goal() {
+        let mut agent = AutonomousAgent::with_initial_progress(MockLlmClient::new(), 800);
+        assert_eq!(agent.next_context(), "0");
+
+        agent.goal = 1000;
+        assert_eq!(agent.next_context(), "800");
+    }
+
     /// Test error handling
     #[tokio::test]
     async fn test_error_handling() {
         let failing_client = MockLlmClient::with_failure_rate(true);
         let agent = AutonomousAgent::new(failing_client);
-        let final_progress = agent.await;
+        let result = agent.await;
 
         // Agent should handle the error gracefully
         // Final progress should be 0 since no successful calls were made
-        assert_eq!(final_progress, 0);
+        assert_eq!(
+            result,
+            Err(AgentError::Failed {
+                error: "Simulated LLM API failure".to_string(),
+                progress: 0,
+            })
+        );
     }
 
     /// Test concurrent agents
@@ -742,11 +2054,13 @@ mod tests {
     async fn test_concurrent_agents() {
         let start = Instant::now();
 
-        let agent1 = AutonomousAgent::new(MockLlmClient::new());
-        let agent2 = AutonomousAgent::new(MockLlmClient::new());
-        let agent3 = AutonomousAgent::new(MockLlmClient::new());
+        let client = || MockLlmClient::new().with_response_delay(Duration::from_millis(50));
+        let agent1 = AutonomousAgent::new(client());
+        let agent2 = AutonomousAgent::new(client());
+        let agent3 = AutonomousAgent::new(client());
 
         let (p1, p2, p3) = tokio::join!(agent1, agent2, agent3);
+        let (p1, p2, p3) = (p1.unwrap(), p2.unwrap(), p3.unwrap());
 
         let elapsed = start.elapsed();
 
@@ -756,7 +2070,7 @@ mod tests {
         assert!(p3 >= 1000);
 
         // Concurrent execution should be faster than sequential
-        // (Each agent takes ~400ms, so concurrent should be much less than 1200ms)
+        // (Each agent makes 10 calls of 50ms, so ~500ms each and ~1500ms sequentially)
         assert!(elapsed < Duration::from_millis(800));
     }
 
@@ -807,4 +2121,516 @@ mod tests {
             .to_string()
             .contains("Simulated LLM API failure"));
     }
+
+    /// Test that health checks pass for a working client and fail otherwise
+    #[tokio::test(start_paused = true)]
+    async fn test_health_check() {
+        let start = tokio::time::Instant::now();
+        assert_eq!(MockLlmClient::new().health_check().await, Ok(()));
+        // A probe is much quicker than a real call
+        assert!(start.elapsed() < Duration::from_millis(200));
+
+        let failing = MockLlmClient::with_failure_rate(true);
+        assert_eq!(failing.health_check().await, Err(HealthError::Unavailable));
+        assert_eq!(
+            MockLlmClient::panicking().health_check().await,
+            Err(HealthError::Faulty)
+        );
+    }
+
+    /// Test that a replayed recording reaches the same final progress as
+    /// the recorded run, without any of its latency
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "test-record-then-replay-{}.jsonl",
+            std::process::id()
+        ));
+
+        let recorder = RecordingLlmClient::create(
+            MockLlmClient::new().with_response_delay(Duration::from_millis(20)),
+            &path,
+        )
+        .await
+        .unwrap();
+        let recorded = AutonomousAgent::new(recorder).await.unwrap();
+
+        let replay = ReplayLlmClient::load(&path).await.unwrap();
+        assert!(replay.remaining() > 1);
+
+        let start = Instant::now();
+        let replayed = AutonomousAgent::new(replay).await.unwrap();
+        assert_eq!(replayed, recorded);
+        // Every call answers inline: no simulated or spawned-task latency
+        assert!(start.elapsed() < Duration::from_millis(20));
+
+        // A run that asks something else is reported as diverged
+        let replay = ReplayLlmClient::load(&path).await.unwrap();
+        let diverged = AutonomousAgent::with_initial_progress(replay, 5).await;
+        assert!(diverged.is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Test that parse_context classifies each kind of input
+    #[test]
+    fn test_parse_context_cases() {
+        assert_eq!(parse_context("150"), Ok(150));
+        assert_eq!(parse_context(" 42\n"), Ok(42));
+        assert_eq!(parse_context("4294967295"), Ok(u32::MAX));
+        assert_eq!(parse_context(""), Err(ContextParseError::Empty));
+        assert_eq!(parse_context(" \t"), Err(ContextParseError::Empty));
+        assert_eq!(
+            parse_context("+5"),
+            Err(ContextParseError::NotNumeric("+5".to_string()))
+        );
+        assert_eq!(
+            parse_context("-1"),
+            Err(ContextParseError::NotNumeric("-1".to_string()))
+        );
+        assert_eq!(
+            parse_context("4294967296"),
+            Err(ContextParseError::Overflow("4294967296".to_string()))
+        );
+    }
+
+    /// Test parse_context against many random strings: it must never panic
+    /// and must agree with a straightforward reference classification
+    #[test]
+    fn test_parse_context_random_inputs() {
+        const ALPHABET: &[char] = &[
+            '0', '1', '5', '9', '0', '7', ' ', '\t', '\n', '-', '+', '.', 'a', 'Z', 'é', '٣', '🦀',
+        ];
+
+        // xorshift64: a fixed seed keeps failures reproducible
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let len = (next() % 16) as usize;
+            let input: String = (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect();
+
+            let trimmed = input.trim();
+            let expected = if trimmed.is_empty() {
+                Err(ContextParseError::Empty)
+            } else if !trimmed.chars().all(|c| c.is_ascii_digit()) {
+                Err(ContextParseError::NotNumeric(input.clone()))
+            } else {
+                let significant = trimmed.trim_start_matches('0');
+                if significant.len() > 10
+                    || significant.parse::<u64>().unwrap_or(0) > u32::MAX as u64
+                {
+                    Err(ContextParseError::Overflow(trimmed.to_string()))
+                } else {
+                    Ok(significant.parse::<u32>().unwrap_or(0))
+                }
+            };
+
+            assert_eq!(parse_context(&input), expected, "input {:?}", input);
+        }
+    }
+
+    /// Test that malformed context fails the LLM call instead of reading as 0
+    #[tokio::test]
+    async fn test_extract_rejects_malformed_context() {
+        let client = MockLlmClient::scripted(vec![AgentResponse {
+            action: 10,
+            goal: 100,
+        }]);
+
+        let error = client.extract("12abc").await.unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<ContextParseError>(),
+            Some(&ContextParseError::NotNumeric("12abc".to_string()))
+        );
+    }
+
+    /// Test ask against an echo actor
+    #[tokio::test]
+    async fn test_ask_echo_actor() {
+        let (tx, mut rx) = mpsc::channel::<(String, oneshot::Sender<String>)>(4);
+        tokio::spawn(async move {
+            while let Some((message, responder)) = rx.recv().await {
+                let _ = responder.send(message);
+            }
+        });
+
+        assert_eq!(ask(&tx, "hello".to_string()).await.unwrap(), "hello");
+        assert_eq!(ask(&tx, "again".to_string()).await.unwrap(), "again");
+    }
+
+    /// Test that a dropped responder is reported as NoResponse
+    #[tokio::test]
+    async fn test_ask_dropped_responder() {
+        let (tx, mut rx) = mpsc::channel::<(u32, oneshot::Sender<u32>)>(4);
+        tokio::spawn(async move {
+            while let Some((_request, responder)) = rx.recv().await {
+                drop(responder);
+            }
+        });
+
+        assert_eq!(ask(&tx, 1).await, Err(AskError::NoResponse));
+    }
+
+    /// Test the counter actor's running total
+    #[tokio::test]
+    async fn test_counter_actor() {
+        let counter = spawn_counter_actor();
+
+        assert_eq!(ask(&counter, 5).await, Ok(5));
+        assert_eq!(ask(&counter, 7).await, Ok(12));
+    }
+
+    /// Test that a later response cannot change the established goal
+    #[tokio::test]
+    async fn test_goal_drift_is_ignored_and_recorded() {
+        let client = MockLlmClient::scripted(vec![
+            AgentResponse {
+                action: 150,
+                goal: 1000,
+            },
+            AgentResponse {
+                action: 100,
+                goal: 5000,
+            },
+            AgentResponse {
+                action: 800,
+                goal: 0,
+            },
+        ]);
+        let log = Arc::new(EventLog::default());
+
+        let final_progress = AutonomousAgent::new(client)
+            .with_observer(log.clone())
+            .await
+            .unwrap();
+
+        // Had the goal moved to 5000, the agent would still be running
+        assert_eq!(final_progress, 1050);
+        assert_eq!(
+            log.events(),
+            vec![AgentEvent::GoalDrift {
+                established: 1000,
+                attempted: 5000,
+            }]
+        );
+    }
+
+    /// Test driving the agent one LLM round at a time
+    #[tokio::test]
+    async fn test_step_by_step() {
+        let client = MockLlmClient::scripted(vec![
+            AgentResponse {
+                action: 300,
+                goal: 1000,
+            },
+            AgentResponse {
+                action: 300,
+                goal: 0,
+            },
+        ]);
+        let mut agent = AutonomousAgent::new(client);
+
+        assert_eq!(agent.step().await, StepResult::InProgress(300));
+        assert_eq!(agent.step().await, StepResult::InProgress(600));
+        assert_eq!(agent.step().await, StepResult::InProgress(900));
+        assert_eq!(agent.step().await, StepResult::Completed(1200));
+
+        // A finished agent stays finished, stepped or awaited
+        assert_eq!(agent.step().await, StepResult::Completed(1200));
+        assert_eq!(agent.await, Ok(1200));
+    }
+
+    /// Test that the audit log records a connected sequence of transitions
+    /// ending in Completed, with a duration for every LLM call
+    #[tokio::test]
+    async fn test_audit_log() {
+        let client = MockLlmClient::scripted(vec![
+            AgentResponse {
+                action: 400,
+                goal: 1000,
+            },
+            AgentResponse {
+                action: 300,
+                goal: 0,
+            },
+        ]);
+        let mut agent = AutonomousAgent::new(client);
+        assert_eq!((&mut agent).await, Ok(1000));
+
+        let log = agent.take_audit_log();
+        let entries = log.entries();
+        assert_eq!(entries.first().unwrap().from_state, StateKind::Initializing);
+        assert_eq!(entries.last().unwrap().to_state, StateKind::Completed);
+        assert_eq!(entries.last().unwrap().progress, 1000);
+        for pair in entries.windows(2) {
+            assert_eq!(pair[0].to_state, pair[1].from_state);
+            assert!(pair[0].timestamp <= pair[1].timestamp);
+        }
+
+        // Three LLM rounds: 400, 700, 1000
+        let calls: Vec<&AuditEntry> = entries
+            .iter()
+            .filter(|entry| entry.from_state == StateKind::Planning)
+            .collect();
+        assert_eq!(calls.len(), 3);
+        for call in &calls {
+            assert_eq!(call.to_state, StateKind::Acting);
+            // The scripted client takes 10ms per call
+            assert!(call.llm_duration.unwrap() >= Duration::from_millis(10));
+        }
+        assert!(entries
+            .iter()
+            .filter(|entry| entry.from_state != StateKind::Planning)
+            .all(|entry| entry.llm_duration.is_none()));
+        assert!(log.llm_time() >= Duration::from_millis(30));
+
+        // Taking the log empties it
+        assert!(agent.take_audit_log().entries().is_empty());
+    }
+
+    /// Test that a randomized run is identical for the same seed: final
+    /// progress, number of LLM calls, responses and chosen delays
+    #[tokio::test(start_paused = true)]
+    async fn test_same_seed_same_run() {
+        async fn run(seed: u64) -> (Result<u32, AgentError>, usize, Vec<Duration>, String) {
+            let llm = MockLlmClient::randomized(TestRng::new(seed));
+            let mut agent = AutonomousAgent::new(llm.clone());
+            let result = (&mut agent).await;
+
+            let log = agent.take_audit_log();
+            let calls = log
+                .entries()
+                .iter()
+                .filter(|entry| entry.from_state == StateKind::Planning)
+                .count();
+            let progress: Vec<u32> = log.entries().iter().map(|entry| entry.progress).collect();
+            (
+                result,
+                calls,
+                llm.chosen_delays(),
+                format!("{:?}", progress),
+            )
+        }
+
+        let first = run(2024).await;
+        let second = run(2024).await;
+        assert_eq!(first, second);
+
+        let (result, calls, delays, _) = &first;
+        assert!(result.as_ref().unwrap() >= &1000);
+        assert_eq!(delays.len(), *calls);
+        assert!(delays
+            .iter()
+            .all(|delay| (5..25).contains(&delay.as_millis())));
+
+        // Sanity check: the seed does matter
+        assert_ne!(run(7).await, first);
+    }
+
+    /// Test that a failing call ends stepping with the progress made so far
+    #[tokio::test]
+    async fn test_step_reports_failure() {
+        let mut agent = AutonomousAgent::with_initial_progress(
+            MockLlmClient::with_failure_rate(true).with_response_delay(Duration::ZERO),
+            200,
+        );
+
+        let StepResult::Failed(AgentError::Failed { progress, .. }) = agent.step().await else {
+            panic!("expected the first step to fail");
+        };
+        assert_eq!(progress, 200);
+    }
+
+    /// Test that the `state_machine!`-generated poll reaches the same results
+    /// as driving the agent by hand with `step`: same outcome, and for a
+    /// successful run the same transitions at the same progress
+    #[tokio::test]
+    async fn test_generated_poll_matches_stepping() {
+        fn script() -> MockLlmClient {
+            MockLlmClient::scripted(vec![
+                AgentResponse {
+                    action: 250,
+                    goal: 1000,
+                },
+                AgentResponse {
+                    action: 350,
+                    goal: 1500,
+                },
+                AgentResponse {
+                    action: 200,
+                    goal: 0,
+                },
+            ])
+            .with_response_delay(Duration::ZERO)
+        }
+        async fn stepped(mut agent: AutonomousAgent) -> (Result<u32, AgentError>, AuditLog) {
+            let outcome = loop {
+                match agent.step().await {
+                    StepResult::InProgress(_) => continue,
+                    StepResult::Completed(progress) => break Ok(progress),
+                    StepResult::Failed(error) => break Err(error),
+                }
+            };
+            (outcome, agent.take_audit_log())
+        }
+        async fn polled(mut agent: AutonomousAgent) -> (Result<u32, AgentError>, AuditLog) {
+            let outcome = (&mut agent).await;
+            (outcome, agent.take_audit_log())
+        }
+        fn transitions(log: &AuditLog) -> Vec<(StateKind, StateKind, u32)> {
+            log.entries()
+                .iter()
+                .map(|entry| (entry.from_state, entry.to_state, entry.progress))
+                .collect()
+        }
+
+        let (by_step, step_log) = stepped(AutonomousAgent::new(script())).await;
+        let (by_poll, poll_log) = polled(AutonomousAgent::new(script())).await;
+        assert_eq!(by_poll, Ok(1000));
+        assert_eq!(by_poll, by_step);
+        assert_eq!(transitions(&poll_log), transitions(&step_log));
+
+        let failing = || {
+            AutonomousAgent::with_initial_progress(
+                MockLlmClient::with_failure_rate(true).with_response_delay(Duration::ZERO),
+                300,
+            )
+        };
+        let (by_step, _) = stepped(failing()).await;
+        let (by_poll, _) = polled(failing()).await;
+        assert!(matches!(
+            by_poll,
+            Err(AgentError::Failed { progress: 300, .. })
+        ));
+        assert_eq!(by_poll, by_step);
+    }
+
+    /// Test that a panic mid-transition poisons the agent instead of letting
+    /// a later poll silently start a new LLM call
+    #[tokio::test]
+    async fn test_panic_mid_transition_poisons_agent() {
+        let mut agent = AutonomousAgent::new(MockLlmClient::panicking());
+
+        // The client's panic resurfaces in poll
+        let first = AssertUnwindSafe(&mut agent).catch_unwind().await;
+        let panic = first.unwrap_err();
+        assert_eq!(
+            panic.downcast_ref::<&str>(),
+            Some(&"Simulated LLM client panic")
+        );
+
+        // Polling again reports the poisoning right away (no restart, no loop)
+        let second = tokio::time::timeout(Duration::from_secs(1), &mut agent).await;
+        assert_eq!(second.unwrap(), Err(AgentError::Poisoned));
+    }
+
+    /// Test that an agent whose LLM answers instantly still yields to the
+    /// executor instead of running to its goal inside a single poll
+    #[tokio::test]
+    async fn test_agent_yields_after_transition_budget() {
+        // A future wrapper that counts how often it is polled
+        struct CountPolls<F> {
+            inner: F,
+            polls: u32,
+        }
+
+        impl<F: Future + Unpin> Future for CountPolls<F> {
+            type Output = F::Output;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+                self.polls += 1;
+                Pin::new(&mut self.inner).poll(cx)
+            }
+        }
+
+        // 100 steps of 1: about 300 transitions, all without waiting
+        let client = MockLlmClient::scripted(vec![
+            AgentResponse {
+                action: 1,
+                goal: 100,
+            },
+            AgentResponse { action: 1, goal: 0 },
+        ])
+        .with_response_delay(Duration::ZERO);
+
+        let mut counted = CountPolls {
+            inner: AutonomousAgent::new(client),
+            polls: 0,
+        };
+        let result = (&mut counted).await;
+
+        assert_eq!(result, Ok(100));
+        // Without the budget a single poll would run all the way to the goal
+        let min_polls = (300 / TRANSITION_BUDGET).max(2);
+        assert!(
+            counted.polls >= min_polls,
+            "finished in {} polls",
+            counted.polls
+        );
+    }
+
+    /// Test that the progress bar tracks progress and saturates at the goal
+    #[cfg(feature = "progress-bar")]
+    #[tokio::test]
+    async fn test_progress_bar_tracks_agent() {
+        let observer = ProgressBarObserver::new(indicatif::ProgressBar::hidden());
+        observer.on_progress(150, 1000);
+        assert_eq!(observer.bar().position(), 150);
+        assert_eq!(observer.bar().length(), Some(1000));
+        assert!(!observer.bar().is_finished());
+
+        let observer = Arc::new(ProgressBarObserver::new(indicatif::ProgressBar::hidden()));
+        let client = MockLlmClient::scripted(vec![
+            AgentResponse {
+                action: 400,
+                goal: 1000,
+            },
+            AgentResponse {
+                action: 400,
+                goal: 0,
+            },
+        ]);
+
+        let final_progress = AutonomousAgent::new(client)
+            .with_observer(observer.clone())
+            .await
+            .unwrap();
+
+        // 1200 overshoots the goal; the bar stops at full
+        assert_eq!(final_progress, 1200);
+        assert_eq!(observer.bar().position(), 1000);
+        assert_eq!(observer.bar().length(), Some(1000));
+        assert!(observer.bar().is_finished());
+    }
+
+    /// Test that rapid increments are applied as one batch with the right total
+    #[tokio::test(start_paused = true)]
+    async fn test_batching_counter_coalesces_increments() {
+        let counter = BatchingCounter::spawn(Duration::from_millis(50));
+
+        for _ in 0..10 {
+            counter.increment(1).await.unwrap();
+        }
+        assert_eq!(counter.batches_applied(), 0); // Still waiting for the window
+
+        sleep(Duration::from_millis(60)).await;
+        assert_eq!(counter.batches_applied(), 1);
+        assert_eq!(counter.flush().await, Ok(10));
+        // Nothing was pending, so the flush applied no extra batch
+        assert_eq!(counter.batches_applied(), 1);
+
+        // flush applies a pending batch without waiting for its deadline
+        counter.increment(5).await.unwrap();
+        assert_eq!(counter.flush().await, Ok(15));
+        assert_eq!(counter.batches_applied(), 2);
+    }
 }
@@ -8,6 +8,9 @@
 //! 3. Simple async operations with timing
 //! 4. The difference between sync and async execution
 
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
@@ -212,7 +215,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_test;
 
     /// Test that demonstrates how to test async functions
     #[tokio::test]
@@ -9,76 +9,1622 @@
 //! 4. Dynamic collections: FuturesUnordered, FuturesOrdered
 //! 5. Custom combinator implementations
 //! 6. Real-world composition patterns
+//! 7. Staged pipelines connected by bounded channels
 
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use future_trait_tutorial::outln;
+use future_trait_tutorial::output::{self, BufferedOutput};
 use futures::{
-    future::{join_all, try_join_all, FutureExt, TryFutureExt},
-    stream::{FuturesUnordered, StreamExt},
+    future::{join_all, try_join_all, BoxFuture, FutureExt, TryFuture, TryFutureExt},
+    stream::{FusedStream, FuturesOrdered, FuturesUnordered, Stream, StreamExt},
     Future,
 };
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
-use tokio::time::sleep;
+use thiserror::Error;
+use tokio::{sync::mpsc, task::JoinSet, time::sleep};
+use tokio_util::sync::CancellationToken;
+
+/// # Macro: join_named
+///
+/// Like `tokio::join!`, but the results come back in a struct with named
+/// fields instead of a tuple, so they can't be mixed up by position:
+/// `join_named!(users: fetch_users(), posts: fetch_posts())` resolves to a
+/// value with `.users` and `.posts`.
+///
+/// ## Note:
+/// - The struct is generated per call site and has no nameable type; use
+///   its fields directly or destructure it with `let`
+/// - Outputs are kept as they are; a `Result` field still has to be
+///   checked (or use `?` on each field)
+macro_rules! join_named {
+    ($($name:ident : $future:expr),+ $(,)?) => {{
+        #[allow(non_camel_case_types)]
+        #[derive(Debug)]
+        struct Joined<$($name),+> {
+            $($name: $name),+
+        }
+
+        let ($($name,)+) = tokio::join!($($future),+);
+        Joined { $($name),+ }
+    }};
+}
+
+/// # Function: simulate_api_call
+///
+/// Simulates an API call that might succeed or fail.
+/// This function demonstrates how to create futures that return Results,
+/// which is essential for error handling in async code.
+///
+/// ## Arguments:
+/// - `name`: The name of the API endpoint being called
+/// - `delay`: How long the API call takes to complete
+/// - `should_succeed`: Whether this call should succeed or fail
+///
+/// ## Returns:
+/// - `Result<String, String>`: Success message or error message
+///
+/// ## Example:
+/// ```rust
+/// let result = simulate_api_call("users", Duration::from_millis(100), true).await;
+/// match result {
+///     Ok(data) => println!("API call succeeded: {}", data),
+///     Err(error) => println!("API call failed: {}", error),
+/// }
+/// ```
+async fn simulate_api_call(
+    name: &str,
+    delay: Duration,
+    should_succeed: bool,
+) -> Result<String, String> {
+    outln!("🌐 Starting API call to '{}'", name);
+
+    // Simulate network delay
+    sleep(delay).await;
+
+    if should_succeed {
+        let response = format!("Data from '{}' API (took {:?})", name, delay);
+        outln!("✅ API call to '{}' succeeded", name);
+        Ok(response)
+    } else {
+        let error = format!("Failed to call '{}' API", name);
+        outln!("❌ API call to '{}' failed", name);
+        Err(error)
+    }
+}
+
+/// # Function: simulate_database_query
+///
+/// Simulates a database query operation.
+/// This demonstrates another type of async operation that we can compose
+/// with other futures using combinators.
+///
+/// ## Arguments:
+/// - `table`: The database table being queried
+/// - `delay`: Query execution time
+///
+/// ## Returns:
+/// - A string containing the query result
+async fn simulate_database_query(table: &str, delay: Duration) -> String {
+    outln!("🗄️  Executing database query on table '{}'", table);
+    sleep(delay).await;
+    let result = format!("Query result from table '{}' (took {:?})", table, delay);
+    outln!("✅ Database query on '{}' completed", table);
+    result
+}
+
+/// # Struct: Scope
+///
+/// A handle for spawning child tasks whose lifetime is bounded by a call to
+/// [`scope`]. This demonstrates structured concurrency: no child task can
+/// outlive the block that created it.
+///
+/// ## Fields:
+/// - `tasks`: The set of spawned children, aborted if the set is dropped
+pub struct Scope {
+    tasks: Mutex<JoinSet<()>>,
+}
+
+impl Scope {
+    /// # Function: spawn
+    ///
+    /// Spawns a child task that the enclosing [`scope`] will wait for.
+    ///
+    /// ## Arguments:
+    /// - `future`: The child task to run on the tokio runtime
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.lock().unwrap().spawn(future);
+    }
+}
+
+/// # Function: scope
+///
+/// Runs `f` with a [`Scope`] and waits for every child it spawned before
+/// returning. This prevents detached tasks from leaking past their owner.
+///
+/// ## Arguments:
+/// - `f`: Receives the scope, spawns children, and returns the body future
+///
+/// ## Returns:
+/// - The output of the body future, once all children have finished
+///
+/// ## Cancellation Semantics:
+/// - If a child panics, the remaining children are aborted and the panic is
+///   resumed in the caller
+/// - If the body panics or the `scope` future is dropped early, the
+///   `JoinSet` is dropped and every child is aborted
+pub async fn scope<F, R>(f: impl FnOnce(&Scope) -> F) -> R
+where
+    F: Future<Output = R>,
+{
+    let scope = Scope {
+        tasks: Mutex::new(JoinSet::new()),
+    };
+
+    let result = f(&scope).await;
+
+    // The body is done, so no more children can be spawned
+    let mut tasks = scope.tasks.into_inner().unwrap();
+    while let Some(joined) = tasks.join_next().await {
+        if let Err(error) = joined {
+            if error.is_panic() {
+                // Cancel the siblings and wait until they are really gone
+                tasks.abort_all();
+                while tasks.join_next().await.is_some() {}
+                std::panic::resume_unwind(error.into_panic());
+            }
+        }
+    }
+
+    result
+}
+
+/// # Struct: ResourceTracker
+///
+/// Counts the sub-tasks of a computation that register with it, and the
+/// most that were running at the same time. Peak concurrency is the number
+/// that decides how many connections, buffers or threads a computation
+/// really needs.
+///
+/// ## Fields:
+/// - `in_flight`: Registered sub-tasks currently running
+/// - `peak`: Highest `in_flight` seen since tracking started
+/// - `started`: Sub-tasks registered since tracking started
+#[derive(Debug, Default)]
+pub struct ResourceTracker {
+    in_flight: AtomicUsize,
+    peak: AtomicUsize,
+    started: AtomicUsize,
+}
+
+/// # Struct: ResourceSnapshot
+///
+/// What a [`ResourceTracker`] observed during [`with_resource_tracking`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceSnapshot {
+    pub peak_in_flight: usize,
+    pub tasks_started: usize,
+    pub elapsed: Duration,
+}
+
+impl ResourceTracker {
+    /// # Function: track
+    ///
+    /// Wraps a sub-task so it counts as in flight from its first poll until
+    /// it finishes or is dropped.
+    ///
+    /// ## Arguments:
+    /// - `f`: The sub-task to count
+    ///
+    /// ## Returns:
+    /// - A future with the same output as `f`
+    pub fn track<F: Future>(self: &Arc<Self>, f: F) -> impl Future<Output = F::Output> {
+        let tracker = self.clone();
+        async move {
+            let _in_flight = InFlight::enter(tracker);
+            f.await
+        }
+    }
+}
+
+/// Counts one sub-task as in flight for as long as it is alive
+struct InFlight(Arc<ResourceTracker>);
+
+impl InFlight {
+    fn enter(tracker: Arc<ResourceTracker>) -> Self {
+        tracker.started.fetch_add(1, Ordering::SeqCst);
+        let now = tracker.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        tracker.peak.fetch_max(now, Ordering::SeqCst);
+        Self(tracker)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// # Function: with_resource_tracking
+///
+/// Runs a computation and reports the sub-tasks it registered with
+/// `tracker` (see [`ResourceTracker::track`]).
+///
+/// ## Arguments:
+/// - `f`: The computation to measure
+/// - `tracker`: Shared with the computation so it can register sub-tasks;
+///   its peak and start counts are reset when `f` starts
+///
+/// ## Returns:
+/// - `(F::Output, ResourceSnapshot)`: The output of `f` and what was observed
+pub async fn with_resource_tracking<F: Future>(
+    f: F,
+    tracker: Arc<ResourceTracker>,
+) -> (F::Output, ResourceSnapshot) {
+    tracker
+        .peak
+        .store(tracker.in_flight.load(Ordering::SeqCst), Ordering::SeqCst);
+    tracker.started.store(0, Ordering::SeqCst);
+    let start = tokio::time::Instant::now();
+
+    let output = f.await;
+
+    let snapshot = ResourceSnapshot {
+        peak_in_flight: tracker.peak.load(Ordering::SeqCst),
+        tasks_started: tracker.started.load(Ordering::SeqCst),
+        elapsed: start.elapsed(),
+    };
+    (output, snapshot)
+}
+
+/// # Struct: KeyedMutex
+///
+/// Mutual exclusion per key: futures run through [`KeyedMutex::with_key`]
+/// with equal keys run one at a time, in the order they asked, while
+/// futures with different keys run concurrently. Useful to serialize
+/// operations on the same account, file or row without a global lock.
+///
+/// ## Fields:
+/// - `locks`: One async mutex per key that is currently locked or awaited
+///
+/// ## Note:
+/// - Entries are removed once no one holds or waits for their key, so the
+///   map only grows with the number of keys in use at the same time
+pub struct KeyedMutex<K> {
+    locks: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl<K: Eq + Hash + Clone> KeyedMutex<K> {
+    /// Creates a keyed mutex with no keys locked.
+    pub fn new() -> Self {
+        Self {
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// # Function: with_key
+    ///
+    /// Runs `f` while holding the lock for `key`.
+    ///
+    /// ## Arguments:
+    /// - `key`: The resource `f` operates on
+    /// - `f`: The operation; it is not polled before the lock is held
+    ///
+    /// ## Returns:
+    /// - The output of `f`
+    ///
+    /// ## Note:
+    /// - Dropping the returned future gives up the lock (or the place in
+    ///   the queue for it)
+    pub async fn with_key<T>(&self, key: K, f: impl Future<Output = T>) -> T {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let _release = KeyRelease { mutex: self, key };
+
+        let _held = lock.lock_owned().await;
+        f.await
+    }
+
+    /// Returns how many keys are locked or waited for right now.
+    pub fn active_keys(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for KeyedMutex<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Removes a key's entry from its `KeyedMutex` once its last user is done.
+/// Declared before the lock guard, so it is dropped after it.
+struct KeyRelease<'a, K: Eq + Hash> {
+    mutex: &'a KeyedMutex<K>,
+    key: K,
+}
+
+impl<K: Eq + Hash> Drop for KeyRelease<'_, K> {
+    fn drop(&mut self) {
+        let mut locks = self.mutex.locks.lock().unwrap();
+        // Everyone else using the key took a clone while holding `locks`,
+        // so a count of one means only the map refers to it
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+/// # Struct: ConcurrencyLimiter
+///
+/// One concurrency cap shared by futures of any type. `buffer_unordered`
+/// limits a stream of one kind of future; a limiter can gate API calls,
+/// queries and timers together, e.g. to stay under a process-wide budget
+/// of open connections.
+///
+/// ## Fields:
+/// - `semaphore`: One permit per future allowed to run at once
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimiter {
+    semaphore: Arc<tokio::sync::Semaphore>,
+}
+
+impl ConcurrencyLimiter {
+    /// # Function: new
+    ///
+    /// Creates a limiter that lets `max` futures run at once.
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: Arc::new(tokio::sync::Semaphore::new(max)),
+        }
+    }
+
+    /// # Function: run
+    ///
+    /// Waits for a free slot, then runs `f` to completion. `f` is not
+    /// polled before it has a slot, so it doesn't start work early.
+    ///
+    /// ## Arguments:
+    /// - `f`: Any future
+    ///
+    /// ## Returns:
+    /// - The output of `f`
+    pub async fn run<F: Future>(&self, f: F) -> F::Output {
+        let _permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("the limiter's semaphore is never closed");
+        f.await
+    }
+
+    /// Returns how many more futures could start right now.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+/// # Struct: MergeFair
+///
+/// A stream that merges several input streams by polling them in
+/// round-robin order. Returned by [`merge_fair`].
+///
+/// ## Fields:
+/// - `streams`: The input streams that have not ended yet
+/// - `next`: Index of the stream to poll first on the next call
+pub struct MergeFair<S> {
+    streams: Vec<Pin<Box<S>>>,
+    next: usize,
+}
+
+impl<S: Stream> Stream for MergeFair<S> {
+    type Item = S::Item;
+
+    /// Polls each remaining stream at most once, starting after the stream
+    /// that produced the previous item, so a chatty source can't starve
+    /// the others.
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = &mut *self;
+        let mut pending = 0;
+
+        while pending < this.streams.len() {
+            let index = this.next % this.streams.len();
+
+            match this.streams[index].as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    this.next = index + 1;
+                    return Poll::Ready(Some(item));
+                }
+                Poll::Ready(None) => {
+                    // Drop the finished stream; its successor moves into `index`
+                    this.streams.remove(index);
+                    this.next = index;
+                }
+                Poll::Pending => {
+                    this.next = index + 1;
+                    pending += 1;
+                }
+            }
+        }
+
+        if this.streams.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// # Function: merge_fair
+///
+/// Merges multiple streams into one, round-robining between them so that
+/// every source gets a turn. The merged stream ends when all inputs end.
+///
+/// ## Arguments:
+/// - `streams`: The streams to merge
+///
+/// ## Returns:
+/// - A stream yielding items from all inputs in fair, interleaved order
+pub fn merge_fair<S: Stream>(streams: Vec<S>) -> impl Stream<Item = S::Item> {
+    MergeFair {
+        streams: streams.into_iter().map(Box::pin).collect(),
+        next: 0,
+    }
+}
+
+/// # Function: measure_throughput
+///
+/// Pairs every item of a stream with the stream's current rate in items
+/// per second, measured over a sliding time window.
+///
+/// ## Arguments:
+/// - `stream`: The stream to observe
+/// - `window`: How far back to look when computing the rate
+///
+/// ## Returns:
+/// - A stream of `(item, items_per_second)` tuples
+///
+/// ## Note:
+/// - The rate counts the items that arrived in the last `window`, including
+///   the current one, so it under-reports until a full window has passed
+/// - Uses `tokio::time::Instant`, so tests can drive it with a paused clock
+pub fn measure_throughput<S: Stream>(
+    stream: S,
+    window: Duration,
+) -> impl Stream<Item = (S::Item, f64)> {
+    let mut arrivals: VecDeque<tokio::time::Instant> = VecDeque::new();
+
+    stream.map(move |item| {
+        let now = tokio::time::Instant::now();
+        arrivals.push_back(now);
+
+        // Forget arrivals that have slid out of the window
+        while let Some(&oldest) = arrivals.front() {
+            if now.duration_since(oldest) >= window {
+                arrivals.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let rate = arrivals.len() as f64 / window.as_secs_f64();
+        (item, rate)
+    })
+}
+
+/// # Struct: RateForecaster
+///
+/// Estimates a request rate with exponential smoothing, the kind of signal
+/// an autoscaler acts on. Every new interval between requests moves the
+/// estimate a fraction `alpha` of the way towards that interval's rate, so
+/// a single outlier barely registers but a lasting change is followed.
+///
+/// ## Fields:
+/// - `alpha`: Smoothing factor in `(0, 1]`; higher reacts faster
+/// - `state`: The last request time and the current estimate
+///
+/// ## Note:
+/// - Methods take `&self`, so one task can `track` a stream while others
+///   read `current`
+pub struct RateForecaster {
+    alpha: f64,
+    state: Mutex<ForecastState>,
+}
+
+#[derive(Default)]
+struct ForecastState {
+    last: Option<Instant>,
+    rate: Option<f64>,
+}
+
+impl RateForecaster {
+    /// # Function: new
+    ///
+    /// Creates a forecaster with no estimate yet.
+    ///
+    /// ## Panics:
+    /// - If `alpha` is not in `(0, 1]`
+    pub fn new(alpha: f64) -> Self {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "alpha must be in (0, 1], got {}",
+            alpha
+        );
+        Self {
+            alpha,
+            state: Mutex::new(ForecastState::default()),
+        }
+    }
+
+    /// Folds one request time into the estimate. Times that are not after
+    /// the previous one are ignored.
+    pub fn record(&self, at: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(last) = state.last {
+            let Some(gap) = at.checked_duration_since(last).filter(|gap| !gap.is_zero()) else {
+                return;
+            };
+            let observed = 1.0 / gap.as_secs_f64();
+            state.rate = Some(match state.rate {
+                Some(rate) => self.alpha * observed + (1.0 - self.alpha) * rate,
+                None => observed,
+            });
+        }
+        state.last = Some(at);
+    }
+
+    /// # Function: track
+    ///
+    /// Records every request time from `times` until the stream ends.
+    pub async fn track<S: Stream<Item = Instant>>(&self, times: S) {
+        let mut times = std::pin::pin!(times);
+        while let Some(at) = times.next().await {
+            self.record(at);
+        }
+    }
+
+    /// Returns the smoothed requests-per-second estimate, or `0.0` until
+    /// two requests have been seen.
+    pub fn current(&self) -> f64 {
+        self.state.lock().unwrap().rate.unwrap_or(0.0)
+    }
+}
+
+/// # Function: scan_async
+///
+/// Threads state through a stream with an async step function, like
+/// `Iterator::scan`. The step can also end the stream early, which makes it
+/// a good fit for "process until a limit is reached" style processing.
+///
+/// ## Arguments:
+/// - `stream`: The input stream
+/// - `init`: The initial state
+/// - `f`: Step function taking the current state and the next item, and
+///   resolving to the new state plus an optional output item
+///
+/// ## Returns:
+/// - A stream of the step outputs; it ends when the input ends or as soon
+///   as a step returns `None`, without pulling further input
+pub fn scan_async<S, St, F, Fut, T>(stream: S, init: St, f: F) -> impl Stream<Item = T>
+where
+    S: Stream,
+    F: FnMut(St, S::Item) -> Fut,
+    Fut: Future<Output = (St, Option<T>)>,
+{
+    futures::stream::unfold(
+        (Box::pin(stream), init, f),
+        |(mut stream, state, mut f)| async move {
+            let item = stream.next().await?;
+            let (state, output) = f(state, item).await;
+            output.map(|output| (output, (stream, state, f)))
+        },
+    )
+}
+
+/// # Function: sliding_windows
+///
+/// Emits the last `size` items as a `Vec` for every new item, once that
+/// many have arrived: `[1, 2, 3, 4]` with size 2 becomes `[1, 2]`,
+/// `[2, 3]`, `[3, 4]`. Handy for moving averages and other rolling
+/// computations over a live feed.
+///
+/// ## Arguments:
+/// - `stream`: The input stream
+/// - `size`: Number of items per window
+///
+/// ## Returns:
+/// - A stream of windows, oldest item first; empty if the input has fewer
+///   than `size` items
+///
+/// ## Panics:
+/// - If `size` is zero
+pub fn sliding_windows<S: Stream>(stream: S, size: usize) -> impl Stream<Item = Vec<S::Item>>
+where
+    S::Item: Clone,
+{
+    assert!(size > 0, "a sliding window needs at least one item");
+
+    stream
+        .scan(VecDeque::with_capacity(size), move |window, item| {
+            if window.len() == size {
+                window.pop_front();
+            }
+            window.push_back(item);
+            let full = (window.len() == size).then(|| window.iter().cloned().collect());
+            futures::future::ready(Some(full))
+        })
+        .filter_map(futures::future::ready)
+}
+
+/// # Function: iter_to_stream
+///
+/// Turns a blocking iterator (a file reader, a database cursor, ...) into
+/// a stream. The iterator runs on tokio's blocking thread pool and hands
+/// items over through a bounded channel, so a slow consumer pauses the
+/// producer instead of letting items pile up in memory.
+///
+/// ## Arguments:
+/// - `iter`: The iterator to drain; it may block between items
+/// - `buffer`: How many items may wait for the consumer; at least 1
+///
+/// ## Returns:
+/// - A stream of the iterator's items, in order
+///
+/// ## Note:
+/// - Dropping the stream stops the iterator after its current item
+pub fn iter_to_stream<I>(iter: I, buffer: usize) -> impl Stream<Item = I::Item>
+where
+    I: Iterator + Send + 'static,
+    I::Item: Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(buffer.max(1));
+    tokio::task::spawn_blocking(move || {
+        for item in iter {
+            // Blocks this pool thread (not a runtime worker) while full
+            if sender.blocking_send(item).is_err() {
+                break; // The stream was dropped
+            }
+        }
+    });
+
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        let item = receiver.recv().await?;
+        Some((item, receiver))
+    })
+}
+
+/// # Function: with_heartbeat
+///
+/// Keeps a quiet stream visibly alive by injecting a heartbeat item
+/// whenever no real item has arrived for `interval`. Keep-alive protocols
+/// (server-sent events, websockets behind idle-timeout proxies) rely on
+/// exactly this.
+///
+/// ## Arguments:
+/// - `stream`: The data stream
+/// - `interval`: Longest allowed silence before a heartbeat is sent
+/// - `heartbeat`: The item to send; cloned for every heartbeat
+///
+/// ## Returns:
+/// - The data items interleaved with heartbeats; ends when `stream` ends
+///
+/// ## Note:
+/// - The silence timer restarts after every item, heartbeats included, so
+///   heartbeats repeat every `interval` during a long gap
+pub fn with_heartbeat<S>(
+    stream: S,
+    interval: Duration,
+    heartbeat: S::Item,
+) -> impl Stream<Item = S::Item>
+where
+    S: Stream,
+    S::Item: Clone,
+{
+    futures::stream::unfold(Box::pin(stream), move |mut stream| {
+        let heartbeat = heartbeat.clone();
+        async move {
+            // Dropping `next()` on timeout is fine: the stream keeps its own
+            // progress, so no data item is lost
+            match tokio::time::timeout(interval, stream.next()).await {
+                Ok(Some(item)) => Some((item, stream)),
+                Ok(None) => None,
+                Err(_) => Some((heartbeat, stream)),
+            }
+        }
+    })
+}
+
+/// # Function: map_concurrent
+///
+/// Maps every stream item to a future and runs up to `concurrency` of
+/// those futures at once. This is `buffered` / `buffer_unordered` with the
+/// ordering picked at runtime: the workhorse for "process this stream of
+/// jobs, N at a time".
+///
+/// ## Arguments:
+/// - `stream`: The items to process
+/// - `concurrency`: Maximum number of futures in flight
+/// - `ordered`: `true` emits results in submission order (a slow item holds
+///   back the ones behind it); `false` emits them as they complete
+/// - `f`: Turns an item into the future that processes it
+///
+/// ## Returns:
+/// - A stream of the futures' outputs
+///
+/// ## Panics:
+/// - If `concurrency` is zero, since nothing could ever run
+pub fn map_concurrent<S, F, Fut, T>(
+    stream: S,
+    concurrency: usize,
+    ordered: bool,
+    f: F,
+) -> impl Stream<Item = T>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future<Output = T>,
+{
+    assert!(
+        concurrency > 0,
+        "map_concurrent requires a concurrency of at least 1"
+    );
+
+    let futures = stream.map(f);
+    if ordered {
+        futures.buffered(concurrency).left_stream()
+    } else {
+        futures.buffer_unordered(concurrency).right_stream()
+    }
+}
+
+/// # Function: throttle_by_key
+///
+/// Rate-limits a stream separately for each key, e.g. per user or per
+/// host: at most `per_key` items with the same key are emitted in any
+/// `interval`. A key that is over its limit doesn't hold up the others.
+///
+/// ## Arguments:
+/// - `stream`: The input stream
+/// - `key_of`: Computes the rate-limiting key of an item
+/// - `per_key`: How many items per key may pass in any window of `interval`
+/// - `interval`: Length of the sliding window
+///
+/// ## Returns:
+/// - The same items, in input order per key; items of different keys may be
+///   reordered, since an unthrottled key overtakes a throttled one
+///
+/// ## Note:
+/// - Upstream is read eagerly so other keys can flow, which means items of a
+///   throttled key are buffered; a key that is always over its limit makes
+///   the buffer grow
+/// - Panics if `per_key` is zero
+pub fn throttle_by_key<S, K>(
+    stream: S,
+    key_of: impl Fn(&S::Item) -> K,
+    per_key: usize,
+    interval: Duration,
+) -> impl Stream<Item = S::Item>
+where
+    S: Stream,
+    K: Eq + Hash,
+{
+    use tokio::time::Instant;
+
+    assert!(per_key > 0, "throttle_by_key needs per_key > 0");
+
+    /// Recent emissions and waiting items of one key
+    struct KeyState<T> {
+        sent: VecDeque<Instant>,
+        waiting: VecDeque<(u64, T)>,
+    }
+
+    let keys: HashMap<K, usize> = HashMap::new();
+    let states: Vec<KeyState<S::Item>> = Vec::new();
+    let arrivals = 0u64;
+
+    futures::stream::unfold(
+        (Box::pin(stream.fuse()), key_of, keys, states, arrivals),
+        move |(mut stream, key_of, mut keys, mut states, mut arrivals)| async move {
+            loop {
+                // Among keys under their limit, emit the item that arrived first;
+                // otherwise note when the next key frees up
+                let now = Instant::now();
+                let mut ready: Option<(u64, usize)> = None;
+                let mut next_free: Option<Instant> = None;
+                for (index, state) in states.iter_mut().enumerate() {
+                    while state
+                        .sent
+                        .front()
+                        .is_some_and(|&sent| now.duration_since(sent) >= interval)
+                    {
+                        state.sent.pop_front();
+                    }
+                    let Some(&(arrival, _)) = state.waiting.front() else {
+                        continue;
+                    };
+                    if state.sent.len() < per_key {
+                        if ready.is_none_or(|(first, _)| arrival < first) {
+                            ready = Some((arrival, index));
+                        }
+                    } else {
+                        let free_at = state.sent[0] + interval;
+                        next_free = Some(next_free.map_or(free_at, |next| next.min(free_at)));
+                    }
+                }
+
+                if let Some((_, index)) = ready {
+                    let state = &mut states[index];
+                    let (_, item) = state.waiting.pop_front()?;
+                    state.sent.push_back(now);
+                    return Some((item, (stream, key_of, keys, states, arrivals)));
+                }
+                if stream.is_terminated() && next_free.is_none() {
+                    return None; // Nothing left upstream or waiting
+                }
+
+                let wait_for_key = async {
+                    match next_free {
+                        Some(free_at) => tokio::time::sleep_until(free_at).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    item = stream.next(), if !stream.is_terminated() => {
+                        if let Some(item) = item {
+                            let next_index = states.len();
+                            let index = *keys.entry(key_of(&item)).or_insert(next_index);
+                            if index == next_index {
+                                states.push(KeyState {
+                                    sent: VecDeque::new(),
+                                    waiting: VecDeque::new(),
+                                });
+                            }
+                            states[index].waiting.push_back((arrivals, item));
+                            arrivals += 1;
+                        }
+                    }
+                    _ = wait_for_key => {}
+                }
+            }
+        },
+    )
+}
+
+/// # Function: at_least
+///
+/// Awaits a future but never finishes sooner than `min`. If the future is
+/// faster, the remainder is slept off before returning its output. This
+/// keeps loading spinners from flickering on and off for quick operations.
+///
+/// ## Arguments:
+/// - `f`: The future to await
+/// - `min`: The minimum total time, measured from the first poll
+///
+/// ## Returns:
+/// - The unchanged output of `f`
+pub async fn at_least<F: Future>(f: F, min: Duration) -> F::Output {
+    let deadline = tokio::time::Instant::now() + min;
+    let output = f.await;
+    tokio::time::sleep_until(deadline).await;
+    output
+}
+
+/// # Struct: SharedFuture
+///
+/// A cloneable handle to a single computation, returned by [`shared`].
+/// The computation runs once, driven by whichever handle is polled; every
+/// clone then resolves to a copy of the same output.
+///
+/// ## Fields:
+/// - `inner`: The boxed computation behind `futures`' `Shared`
+pub struct SharedFuture<T> {
+    inner: futures::future::Shared<BoxFuture<'static, T>>,
+}
+
+impl<T: Clone> SharedFuture<T> {
+    /// Returns the output if the computation has already finished, without
+    /// driving it.
+    pub fn peek(&self) -> Option<T> {
+        self.inner.peek().cloned()
+    }
+}
+
+impl<T> Clone for SharedFuture<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Future for SharedFuture<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.inner.poll_unpin(cx)
+    }
+}
+
+/// # Function: shared
+///
+/// Turns a future into a [`SharedFuture`] that many tasks can await, for
+/// example one expensive lookup that several requests are waiting for.
+///
+/// ## Arguments:
+/// - `f`: The computation to share
+///
+/// ## Returns:
+/// - A handle; clone it once per awaiter
+///
+/// ## Note:
+/// - The output is cloned for every awaiter, so wrap large outputs in an
+///   `Arc`
+/// - Dropping every handle before the computation finishes cancels it
+pub fn shared<F>(f: F) -> SharedFuture<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Clone,
+{
+    SharedFuture {
+        inner: f.boxed().shared(),
+    }
+}
+
+/// # Function: run_cpu
+///
+/// Runs CPU-bound work on tokio's blocking thread pool and awaits its
+/// result. Computing directly inside a future would hold up every other
+/// task on the same worker thread until the computation is done.
+///
+/// ## Arguments:
+/// - `f`: The computation; it runs on another thread, never on the runtime
+///
+/// ## Returns:
+/// - The computation's result, or a `JoinError` if it panicked
+///
+/// ## Note:
+/// - Dropping the returned future does not stop `f`, which runs to
+///   completion in the background
+pub async fn run_cpu<F, T>(f: F) -> Result<T, tokio::task::JoinError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await
+}
+
+/// # Function: run_cpu_on
+///
+/// Like [`run_cpu`], but runs the work on a dedicated rayon thread pool
+/// (feature `cpu-pool`). A pool sized to the number of cores keeps heavy
+/// computations from competing with tokio's blocking pool, which is also
+/// used for file I/O.
+///
+/// ## Arguments:
+/// - `pool`: The pool to run on
+/// - `f`: The computation
+///
+/// ## Returns:
+/// - The computation's result, or the panic payload if it panicked
+#[cfg(feature = "cpu-pool")]
+pub async fn run_cpu_on<F, T>(pool: &rayon::ThreadPool, f: F) -> std::thread::Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (done, result) = tokio::sync::oneshot::channel();
+    pool.spawn(move || {
+        // An uncaught panic would abort the process on a rayon thread
+        let _ = done.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)));
+    });
+    result.await.expect("rayon pool dropped the computation")
+}
+
+/// # Function: or_default_after
+///
+/// Gives a future a grace period and falls back to `default` if it hasn't
+/// finished by then. Unlike `timeout` there is no error to handle: the
+/// caller always gets a usable value, which suits optional enrichments
+/// like recommendations or avatars.
+///
+/// ## Arguments:
+/// - `f`: The future to await
+/// - `after`: The grace period
+/// - `default`: Returned if `f` is still pending when the period ends
+///
+/// ## Returns:
+/// - The output of `f`, or `default`; in the latter case `f` is dropped
+pub async fn or_default_after<F: Future>(f: F, after: Duration, default: F::Output) -> F::Output {
+    tokio::time::timeout(after, f).await.unwrap_or(default)
+}
+
+/// # Function: timeout_resumable
+///
+/// Waits for `f` for at most `dur`, but unlike `tokio::time::timeout`
+/// does not drop it on expiry: the still-pending future is handed back,
+/// with whatever progress it made, so the caller can do something else
+/// and await it later.
+///
+/// ## Arguments:
+/// - `f`: The operation; `Unpin` so it can be polled by reference and
+///   then returned (use `Box::pin` for futures that are not)
+/// - `dur`: How long to wait this time
+///
+/// ## Returns:
+/// - `Ok(output)` if `f` finished in time, otherwise `Err(f)`
+pub async fn timeout_resumable<F: Future + Unpin>(mut f: F, dur: Duration) -> Result<F::Output, F> {
+    match tokio::time::timeout(dur, &mut f).await {
+        Ok(output) => Ok(output),
+        Err(_) => Err(f),
+    }
+}
+
+/// # Function: warn_if_slow
+///
+/// Awaits a future and prints a warning to stderr if it took longer than
+/// `threshold`. The output is passed through untouched, so this can wrap
+/// any call while hunting for the slow one.
+///
+/// ## Arguments:
+/// - `f`: The future to await
+/// - `threshold`: How long `f` may take before it is reported
+/// - `label`: Names the operation in the warning
+///
+/// ## Returns:
+/// - The unchanged output of `f`
+pub async fn warn_if_slow<F: Future>(f: F, threshold: Duration, label: &str) -> F::Output {
+    warn_if_slow_with(f, threshold, label, |label, elapsed| {
+        eprintln!(
+            "   ⚠️  {} took {:?} (threshold {:?})",
+            label, elapsed, threshold
+        )
+    })
+    .await
+}
+
+/// # Function: warn_if_slow_with
+///
+/// Like [`warn_if_slow`], but hands slow operations to `on_slow` instead of
+/// printing, so they can be sent to a logger or collected in a test.
+///
+/// ## Arguments:
+/// - `f`: The future to await
+/// - `threshold`: How long `f` may take before it is reported
+/// - `label`: Passed to `on_slow` to name the operation
+/// - `on_slow`: Called with the label and elapsed time, only if too slow
+///
+/// ## Returns:
+/// - The unchanged output of `f`
+pub async fn warn_if_slow_with<F: Future>(
+    f: F,
+    threshold: Duration,
+    label: &str,
+    on_slow: impl FnOnce(&str, Duration),
+) -> F::Output {
+    let start = tokio::time::Instant::now();
+    let output = f.await;
+    let elapsed = start.elapsed();
+    if elapsed > threshold {
+        on_slow(label, elapsed);
+    }
+    output
+}
+
+/// # Function: tap
+///
+/// Runs a side-effect closure on a future's output without changing it.
+/// This mirrors `Iterator::inspect` and is handy for logging or debugging
+/// values as they flow through an async pipeline.
+///
+/// ## Arguments:
+/// - `future`: The future whose output to observe
+/// - `side`: Closure that receives a reference to the output
+///
+/// ## Returns:
+/// - The unchanged output of `future`
+pub async fn tap<F: Future>(future: F, side: impl FnOnce(&F::Output)) -> F::Output {
+    let output = future.await;
+    side(&output);
+    output
+}
+
+/// # Function: tap_err
+///
+/// The fallible counterpart of [`tap`]: runs a closure only when the
+/// future fails, passing it the error, and returns the result unchanged.
+///
+/// ## Arguments:
+/// - `future`: The fallible future whose error to observe
+/// - `side`: Closure that receives a reference to the error
+///
+/// ## Returns:
+/// - The unchanged `Result` of `future`
+pub async fn tap_err<F: TryFuture>(
+    future: F,
+    side: impl FnOnce(&F::Error),
+) -> Result<F::Ok, F::Error> {
+    let result = future.into_future().await;
+    if let Err(error) = &result {
+        side(error);
+    }
+    result
+}
+
+/// # Function: select_priority
+///
+/// Races several futures like `select!`, but breaks ties by priority:
+/// if more than one future is ready in the same poll, the one with the
+/// highest priority wins. `tokio::select!` picks randomly among ready
+/// branches, which makes "prefer the cache if it's ready too" impossible
+/// to express.
+///
+/// ## Arguments:
+/// - `branches`: `(priority, future)` pairs; equal priorities keep their order
+///
+/// ## Returns:
+/// - The output of the winning branch; the others are dropped (cancelled)
+///
+/// ## Panics:
+/// - If `branches` is empty, since there would be nothing to wait for
+pub async fn select_priority<T>(mut branches: Vec<(u8, BoxFuture<'static, T>)>) -> T {
+    assert!(
+        !branches.is_empty(),
+        "select_priority requires at least one branch"
+    );
+
+    // Poll in priority order so the first ready branch is the preferred one
+    branches.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+
+    std::future::poll_fn(|cx| {
+        for (_, future) in branches.iter_mut() {
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready(output);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// # Enum: Selected
+///
+/// Which of three raced futures won, together with its value. Unlike the
+/// branches of a `select!` block, this is an ordinary value that can be
+/// returned, matched on later, or logged.
+///
+/// ## Variants:
+/// - `First`, `Second`, `Third`: The winner's position and output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selected<A, B, C> {
+    First(A),
+    Second(B),
+    Third(C),
+}
+
+/// # Function: select3
+///
+/// Races three futures of possibly different output types and reports
+/// which one finished first. The losers are dropped (cancelled).
+///
+/// ## Arguments:
+/// - `a`, `b`, `c`: The futures to race
+///
+/// ## Returns:
+/// - The winner tagged by position; if several are ready in the same poll,
+///   the earliest argument wins
+pub async fn select3<A, B, C>(a: A, b: B, c: C) -> Selected<A::Output, B::Output, C::Output>
+where
+    A: Future,
+    B: Future,
+    C: Future,
+{
+    tokio::select! {
+        biased;
+        value = a => Selected::First(value),
+        value = b => Selected::Second(value),
+        value = c => Selected::Third(value),
+    }
+}
+
+/// # Function: join_all_abortable
+///
+/// Like `join_all`, but an external signal can stop the whole batch. When
+/// `abort` fires, every future that hasn't finished yet is dropped
+/// (cancelling it) and the results gathered so far are returned.
+///
+/// ## Arguments:
+/// - `futures`: The futures to run concurrently
+/// - `abort`: Token that ends the batch early when cancelled
+///
+/// ## Returns:
+/// - `Vec<Option<F::Output>>`: In input order; `Some` for futures that
+///   completed, `None` for those cancelled by the abort
+pub async fn join_all_abortable<F: Future>(
+    futures: Vec<F>,
+    abort: CancellationToken,
+) -> Vec<Option<F::Output>> {
+    let mut results: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    let mut pending: FuturesUnordered<_> = futures
+        .into_iter()
+        .enumerate()
+        .map(|(index, future)| async move { (index, future.await) })
+        .collect();
+
+    loop {
+        tokio::select! {
+            // Check the token first so nothing completes after an abort
+            biased;
+            _ = abort.cancelled() => break,
+            next = pending.next() => match next {
+                Some((index, output)) => results[index] = Some(output),
+                None => break,
+            },
+        }
+    }
+
+    results
+}
+
+/// Pause between two attempts of `with_retry`
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// # Function: with_retry
+///
+/// A custom combinator that retries a future operation up to a specified
+/// number of times if it fails, but never past the caller's deadline. This
+/// demonstrates how to create reusable async patterns.
+///
+/// ## Arguments:
+/// - `future_fn`: A function that creates the future to retry
+/// - `max_retries`: Maximum number of retry attempts
+/// - `deadline`: When the caller stops caring about the result
+///
+/// ## Returns:
+/// - The result of the future, or the last error if all retries fail or
+///   the next retry could not start before `deadline`
+///
+/// ## Note:
+/// - A retry whose delay would end past the deadline is not waited for:
+///   the last error is returned at once instead of after a pointless sleep
+/// - An attempt that is already running is not interrupted; wrap it in
+///   `with_timeout` if it may hang
+async fn with_retry<F, Fut, T, E>(
+    mut future_fn: F,
+    max_retries: usize,
+    deadline: tokio::time::Instant,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        outln!("     Attempt {} of {}", attempts, max_retries + 1);
+
+        match future_fn().await {
+            Ok(result) => return Ok(result),
+            Err(error) => {
+                if attempts > max_retries {
+                    outln!("     All retries exhausted");
+                    return Err(error);
+                }
+                if tokio::time::Instant::now() + RETRY_DELAY > deadline {
+                    outln!("     Deadline too close to retry, giving up");
+                    return Err(error);
+                }
+                outln!("     Attempt failed: {}, retrying...", error);
+                sleep(RETRY_DELAY).await; // Brief delay between retries
+            }
+        }
+    }
+}
 
-/// # Function: simulate_api_call
+/// # Function: drain_until
 ///
-/// Simulates an API call that might succeed or fail.
-/// This function demonstrates how to create futures that return Results,
-/// which is essential for error handling in async code.
+/// Collects whatever finishes in a `FuturesUnordered` before `deadline`
+/// and hands back the rest, e.g. to render a page with the results that
+/// are ready and fill in the others later.
 ///
 /// ## Arguments:
-/// - `name`: The name of the API endpoint being called
-/// - `delay`: How long the API call takes to complete
-/// - `should_succeed`: Whether this call should succeed or fail
+/// - `set`: The futures to drain
+/// - `deadline`: When to stop waiting
 ///
 /// ## Returns:
-/// - `Result<String, String>`: Success message or error message
+/// - The outputs that completed in time, in completion order, and the set
+///   of futures that are still running
 ///
-/// ## Example:
-/// ```rust
-/// let result = simulate_api_call("users", Duration::from_millis(100), true).await;
-/// match result {
-///     Ok(data) => println!("API call succeeded: {}", data),
-///     Err(error) => println!("API call failed: {}", error),
-/// }
-/// ```
-async fn simulate_api_call(
-    name: &str,
-    delay: Duration,
-    should_succeed: bool,
-) -> Result<String, String> {
-    println!("🌐 Starting API call to '{}'", name);
+/// ## Note:
+/// - Unfinished futures are returned, not cancelled; they keep any progress
+///   and can be awaited again or dropped
+pub async fn drain_until<F: Future>(
+    mut set: FuturesUnordered<F>,
+    deadline: Instant,
+) -> (Vec<F::Output>, FuturesUnordered<F>) {
+    let deadline = tokio::time::Instant::from_std(deadline);
+    let mut done = Vec::new();
+    while let Ok(Some(output)) = tokio::time::timeout_at(deadline, set.next()).await {
+        done.push(output);
+    }
+    (done, set)
+}
 
-    // Simulate network delay
-    sleep(delay).await;
+/// # Function: collect_ordered
+///
+/// Runs futures concurrently and returns their outputs in submission
+/// order, the complement of processing a `FuturesUnordered` in completion
+/// order. `FuturesOrdered` holds back results that finish early until
+/// everything submitted before them is done.
+///
+/// ## Arguments:
+/// - `futures`: The futures to run
+///
+/// ## Returns:
+/// - `Vec<F::Output>`: One output per future, in the order they were given
+pub async fn collect_ordered<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let ordered: FuturesOrdered<F> = futures.into_iter().collect();
+    ordered.collect().await
+}
 
-    if should_succeed {
-        let response = format!("Data from '{}' API (took {:?})", name, delay);
-        println!("✅ API call to '{}' succeeded", name);
-        Ok(response)
-    } else {
-        let error = format!("Failed to call '{}' API", name);
-        println!("❌ API call to '{}' failed", name);
-        Err(error)
+/// # Function: first_n_ok
+///
+/// Runs futures concurrently until `n` of them have succeeded, then drops
+/// (cancels) the rest. Useful for quorum-style requests ("any 3 of these 8
+/// replicas") where waiting for stragglers only adds latency.
+///
+/// ## Arguments:
+/// - `futures`: The fallible futures to run
+/// - `n`: How many successes are needed
+///
+/// ## Returns:
+/// - `(oks, errors)`: Up to `n` successes and every error seen before the
+///   n-th success, both in completion order. If fewer than `n` futures
+///   succeed, all of them run to completion.
+pub async fn first_n_ok<F, T, E>(futures: Vec<F>, n: usize) -> (Vec<T>, Vec<E>)
+where
+    F: Future<Output = Result<T, E>>,
+{
+    let mut oks = Vec::with_capacity(n);
+    let mut errors = Vec::new();
+    if n == 0 {
+        return (oks, errors);
+    }
+
+    let mut pending: FuturesUnordered<F> = futures.into_iter().collect();
+    while let Some(result) = pending.next().await {
+        match result {
+            Ok(value) => {
+                oks.push(value);
+                if oks.len() == n {
+                    break;
+                }
+            }
+            Err(error) => errors.push(error),
+        }
     }
+
+    // Dropping `pending` here cancels whatever is still running
+    (oks, errors)
 }
 
-/// # Function: simulate_database_query
+/// # Struct: TooManyErrors
 ///
-/// Simulates a database query operation.
-/// This demonstrates another type of async operation that we can compose
-/// with other futures using combinators.
+/// Returned by `try_collect_lenient` once a stream produced more failures
+/// than the caller was willing to tolerate.
+///
+/// ## Fields:
+/// - `max_errors`: The number of failures that was tolerated
+/// - `last_error`: The failure that exceeded the limit
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("more than {max_errors} errors (last: {last_error})")]
+pub struct TooManyErrors {
+    pub max_errors: usize,
+    pub last_error: String,
+}
+
+/// # Function: try_collect_lenient
+///
+/// Collects the successful items of a stream of results, skipping up to
+/// `max_errors` failures. This sits between `try_collect` (which gives up
+/// on the first error) and ignoring errors altogether: a few bad records
+/// are fine, a flood of them means something is seriously wrong.
 ///
 /// ## Arguments:
-/// - `table`: The database table being queried
-/// - `delay`: Query execution time
+/// - `stream`: The stream of results to collect
+/// - `max_errors`: How many `Err` items to skip before giving up
 ///
 /// ## Returns:
-/// - A string containing the query result
-async fn simulate_database_query(table: &str, delay: Duration) -> String {
-    println!("🗄️  Executing database query on table '{}'", table);
-    sleep(delay).await;
-    let result = format!("Query result from table '{}' (took {:?})", table, delay);
-    println!("✅ Database query on '{}' completed", table);
-    result
+/// - `Ok(Vec<T>)`: The `Ok` values in stream order, if at most `max_errors`
+///   items failed
+/// - `Err(TooManyErrors)`: As soon as failure number `max_errors + 1`
+///   arrives; the rest of the stream is not consumed
+pub async fn try_collect_lenient<S, T, E>(
+    stream: S,
+    max_errors: usize,
+) -> Result<Vec<T>, TooManyErrors>
+where
+    S: Stream<Item = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut values = Vec::new();
+    let mut errors = 0;
+
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(value) => values.push(value),
+            Err(error) => {
+                errors += 1;
+                if errors > max_errors {
+                    return Err(TooManyErrors {
+                        max_errors,
+                        last_error: error.to_string(),
+                    });
+                }
+                outln!("   Skipping error {}/{}: {}", errors, max_errors, error);
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// # Function: collect_results
+///
+/// Drains a stream of results, splitting it into the values and the
+/// errors. Unlike `try_collect` it never stops early, so one bad record
+/// can't hide the rest of a batch; the errors can be reported afterwards.
+///
+/// ## Arguments:
+/// - `stream`: The stream of results to drain
+///
+/// ## Returns:
+/// - A future resolving to `(oks, errs)`, each in stream order, once the
+///   stream has ended
+pub fn collect_results<S, T, E>(stream: S) -> impl Future<Output = (Vec<T>, Vec<E>)>
+where
+    S: Stream<Item = Result<T, E>>,
+{
+    stream.fold((Vec::new(), Vec::new()), |(mut oks, mut errs), item| {
+        match item {
+            Ok(value) => oks.push(value),
+            Err(error) => errs.push(error),
+        }
+        futures::future::ready((oks, errs))
+    })
+}
+
+/// Turns the input receiver of a pipeline into the receiver of its last stage
+type Connect<In, Out> = Box<dyn FnOnce(mpsc::Receiver<In>) -> mpsc::Receiver<Out> + Send>;
+
+/// # Struct: Pipeline
+///
+/// A chain of async stages, each running as its own task and connected to
+/// the next by a bounded `mpsc` channel. When a downstream stage falls
+/// behind, its channel fills up and upstream stages wait on `send`
+/// (backpressure) instead of buffering without limit.
+///
+/// ## Fields:
+/// - `capacity`: Buffer size of every channel between stages
+/// - `connect`: Spawns the stages built so far and wires them together
+///
+/// ## Example:
+/// ```rust
+/// let output = Pipeline::new(8)
+///     .stage(|n: u32| async move { n * 2 })
+///     .stage(|n| async move { n.to_string() })
+///     .run(futures::stream::iter(1..=3));
+/// ```
+pub struct Pipeline<In, Out> {
+    capacity: usize,
+    connect: Connect<In, Out>,
+}
+
+impl<T: Send + 'static> Pipeline<T, T> {
+    /// # Function: new
+    ///
+    /// Creates an empty pipeline that passes its input through unchanged.
+    ///
+    /// ## Arguments:
+    /// - `capacity`: Buffer size of each channel between stages (at least 1)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            connect: Box::new(|input| input),
+        }
+    }
+}
+
+impl<In: Send + 'static, Out: Send + 'static> Pipeline<In, Out> {
+    /// # Function: stage
+    ///
+    /// Appends a stage that transforms every item with an async function.
+    /// Items are processed one at a time, so each stage preserves order.
+    ///
+    /// ## Arguments:
+    /// - `f`: The async transformation applied to each item
+    ///
+    /// ## Returns:
+    /// - A pipeline whose output is the output of `f`
+    pub fn stage<Next, F, Fut>(self, f: F) -> Pipeline<In, Next>
+    where
+        F: Fn(Out) -> Fut + Send + 'static,
+        Fut: Future<Output = Next> + Send,
+        Next: Send + 'static,
+    {
+        let capacity = self.capacity;
+        let connect = self.connect;
+
+        Pipeline {
+            capacity,
+            connect: Box::new(move |input| {
+                let mut upstream = connect(input);
+                let (tx, rx) = mpsc::channel(capacity);
+
+                tokio::spawn(async move {
+                    while let Some(item) = upstream.recv().await {
+                        // Stop once the consumer has gone away
+                        if tx.send(f(item).await).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                rx
+            }),
+        }
+    }
+
+    /// # Function: run
+    ///
+    /// Starts all stages and feeds them from `input`.
+    ///
+    /// ## Arguments:
+    /// - `input`: The stream of items entering the first stage
+    ///
+    /// ## Returns:
+    /// - A stream of the last stage's outputs; it ends after `input` ends
+    ///   and every item has passed through the pipeline
+    pub fn run<S>(self, input: S) -> impl Stream<Item = Out>
+    where
+        S: Stream<Item = In> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(self.capacity);
+
+        tokio::spawn(async move {
+            let mut input = Box::pin(input);
+            while let Some(item) = input.next().await {
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let output = (self.connect)(rx);
+        futures::stream::unfold(output, |mut output| async move {
+            output.recv().await.map(|item| (item, output))
+        })
+    }
 }
 
 /// # Function: demonstrate_map_combinator
@@ -92,49 +1638,49 @@ async fn simulate_database_query(table: &str, delay: Duration) -> String {
 /// - `map` preserves the error type for Result futures
 /// - Chaining multiple maps creates a transformation pipeline
 async fn demonstrate_map_combinator() {
-    println!("\n=== Map Combinator ===");
+    outln!("\n=== Map Combinator ===");
 
     // Example 1: Simple map transformation
-    println!("1. Basic map transformation:");
+    outln!("1. Basic map transformation:");
     let result = simulate_database_query("users", Duration::from_millis(100))
         .map(|data| {
-            println!("   Transforming data...");
+            outln!("   Transforming data...");
             format!("TRANSFORMED: {}", data.to_uppercase())
         })
         .await;
 
-    println!("   Final result: {}", result);
+    outln!("   Final result: {}", result);
 
     // Example 2: Chaining multiple maps
-    println!("\n2. Chaining multiple maps:");
+    outln!("\n2. Chaining multiple maps:");
     let result = simulate_api_call("products", Duration::from_millis(80), true)
         .map_ok(|data| {
-            println!("   First transformation: adding prefix");
+            outln!("   First transformation: adding prefix");
             format!("PREFIX_{}", data)
         })
         .map_ok(|data| {
-            println!("   Second transformation: adding suffix");
+            outln!("   Second transformation: adding suffix");
             format!("{}_SUFFIX", data)
         })
         .await;
 
     match result {
-        Ok(data) => println!("   Chained result: {}", data),
-        Err(error) => println!("   Error: {}", error),
+        Ok(data) => outln!("   Chained result: {}", data),
+        Err(error) => outln!("   Error: {}", error),
     }
 
     // Example 3: Map with error handling
-    println!("\n3. Map with potential error:");
+    outln!("\n3. Map with potential error:");
     let result = simulate_api_call("invalid", Duration::from_millis(50), false)
         .map_ok(|data| {
-            println!("   This transformation won't run due to error");
+            outln!("   This transformation won't run due to error");
             format!("TRANSFORMED: {}", data)
         })
         .await;
 
     match result {
-        Ok(data) => println!("   Unexpected success: {}", data),
-        Err(error) => println!("   Expected error: {}", error),
+        Ok(data) => outln!("   Unexpected success: {}", data),
+        Err(error) => outln!("   Expected error: {}", error),
     }
 }
 
@@ -149,20 +1695,20 @@ async fn demonstrate_map_combinator() {
 /// - Errors short-circuit the chain
 /// - This enables sequential async workflows
 async fn demonstrate_and_then_combinator() {
-    println!("\n=== And Then Combinator ===");
+    outln!("\n=== And Then Combinator ===");
 
     // Example 1: Sequential API calls where second depends on first
-    println!("1. Sequential dependent operations:");
+    outln!("1. Sequential dependent operations:");
     let start = Instant::now();
 
     let result = simulate_api_call("auth", Duration::from_millis(100), true)
         .and_then(|auth_token| async move {
-            println!("   Using auth token: {}", auth_token);
+            outln!("   Using auth token: {}", auth_token);
             // Second API call that depends on the first
             simulate_api_call("user_data", Duration::from_millis(150), true).await
         })
         .and_then(|user_data| async move {
-            println!("   Processing user data: {}", user_data);
+            outln!("   Processing user data: {}", user_data);
             // Third operation that depends on the second
             simulate_database_query("user_preferences", Duration::from_millis(80)).await;
             Ok(format!("Complete user profile based on: {}", user_data))
@@ -172,28 +1718,28 @@ async fn demonstrate_and_then_combinator() {
     let elapsed = start.elapsed();
     match result {
         Ok(profile) => {
-            println!("   Final profile: {}", profile);
-            println!("   Total time: {:?} (sequential)", elapsed);
+            outln!("   Final profile: {}", profile);
+            outln!("   Total time: {:?} (sequential)", elapsed);
         }
-        Err(error) => println!("   Chain failed: {}", error),
+        Err(error) => outln!("   Chain failed: {}", error),
     }
 
     // Example 2: Error handling in chains
-    println!("\n2. Error handling in and_then chains:");
+    outln!("\n2. Error handling in and_then chains:");
     let result = simulate_api_call("auth", Duration::from_millis(50), true)
         .and_then(|_auth_token| async move {
             // This call will fail
             simulate_api_call("protected_data", Duration::from_millis(100), false).await
         })
         .and_then(|_data| async move {
-            println!("   This won't execute due to previous error");
+            outln!("   This won't execute due to previous error");
             Ok("This won't be reached".to_string())
         })
         .await;
 
     match result {
-        Ok(data) => println!("   Unexpected success: {}", data),
-        Err(error) => println!("   Expected chain failure: {}", error),
+        Ok(data) => outln!("   Unexpected success: {}", data),
+        Err(error) => outln!("   Expected chain failure: {}", error),
     }
 }
 
@@ -208,10 +1754,10 @@ async fn demonstrate_and_then_combinator() {
 /// - Concurrent execution can significantly improve performance
 /// - All futures must complete for join to return
 async fn demonstrate_join_combinators() {
-    println!("\n=== Join Combinators ===");
+    outln!("\n=== Join Combinators ===");
 
     // Example 1: Basic concurrent execution with join!
-    println!("1. Concurrent execution with join!:");
+    outln!("1. Concurrent execution with join!:");
     let start = Instant::now();
 
     let (api_result, db_result, cache_result) = tokio::join!(
@@ -221,16 +1767,16 @@ async fn demonstrate_join_combinators() {
     );
 
     let elapsed = start.elapsed();
-    println!("   API result: {:?}", api_result);
-    println!("   DB result: {}", db_result);
-    println!("   Cache result: {}", cache_result);
-    println!(
+    outln!("   API result: {:?}", api_result);
+    outln!("   DB result: {}", db_result);
+    outln!("   Cache result: {}", cache_result);
+    outln!(
         "   Total time: {:?} (concurrent, ~200ms not 450ms)",
         elapsed
     );
 
     // Example 2: try_join! with error handling
-    println!("\n2. try_join! with error handling:");
+    outln!("\n2. try_join! with error handling:");
     let start = Instant::now();
 
     let result = tokio::try_join!(
@@ -242,17 +1788,17 @@ async fn demonstrate_join_combinators() {
     let elapsed = start.elapsed();
     match result {
         Ok((s1, s2, s3)) => {
-            println!("   All services succeeded:");
-            println!("     Service 1: {}", s1);
-            println!("     Service 2: {}", s2);
-            println!("     Service 3: {}", s3);
-            println!("   Total time: {:?}", elapsed);
+            outln!("   All services succeeded:");
+            outln!("     Service 1: {}", s1);
+            outln!("     Service 2: {}", s2);
+            outln!("     Service 3: {}", s3);
+            outln!("   Total time: {:?}", elapsed);
         }
-        Err(error) => println!("   One service failed: {}", error),
+        Err(error) => outln!("   One service failed: {}", error),
     }
 
     // Example 3: try_join! with failure (short-circuiting)
-    println!("\n3. try_join! with failure (demonstrates short-circuiting):");
+    outln!("\n3. try_join! with failure (demonstrates short-circuiting):");
     let start = Instant::now();
 
     let result = tokio::try_join!(
@@ -263,15 +1809,24 @@ async fn demonstrate_join_combinators() {
 
     let elapsed = start.elapsed();
     match result {
-        Ok(_) => println!("   Unexpected success"),
+        Ok(_) => outln!("   Unexpected success"),
         Err(error) => {
-            println!("   Expected failure: {}", error);
-            println!(
+            outln!("   Expected failure: {}", error);
+            outln!(
                 "   Time: {:?} (note: may complete before slow_service)",
                 elapsed
             );
         }
     }
+
+    // Example 4: Named results instead of a positional tuple
+    outln!("\n4. join_named! for results with names:");
+    let dashboard = join_named!(
+        profile: simulate_api_call("profile", Duration::from_millis(80), true),
+        orders: simulate_database_query("orders", Duration::from_millis(60)),
+    );
+    outln!("   Profile: {:?}", dashboard.profile);
+    outln!("   Orders: {}", dashboard.orders);
 }
 
 /// # Function: demonstrate_select_combinator
@@ -285,64 +1840,87 @@ async fn demonstrate_join_combinators() {
 /// - Useful for implementing timeouts and fallback strategies
 /// - Can handle both success and error cases
 async fn demonstrate_select_combinator() {
-    println!("\n=== Select Combinator ===");
+    outln!("\n=== Select Combinator ===");
 
     // Example 1: Racing multiple API calls (fastest wins)
-    println!("1. Racing API calls (fastest wins):");
+    outln!("1. Racing API calls (fastest wins):");
     let start = Instant::now();
 
     tokio::select! {
         result = simulate_api_call("fast_api", Duration::from_millis(100), true) => {
-            println!("   Fast API won: {:?}", result);
+            outln!("   Fast API won: {:?}", result);
         }
         result = simulate_api_call("slow_api", Duration::from_millis(300), true) => {
-            println!("   Slow API won: {:?}", result);
+            outln!("   Slow API won: {:?}", result);
         }
         result = simulate_api_call("medium_api", Duration::from_millis(200), true) => {
-            println!("   Medium API won: {:?}", result);
+            outln!("   Medium API won: {:?}", result);
         }
     }
 
     let elapsed = start.elapsed();
-    println!("   Race completed in: {:?}", elapsed);
+    outln!("   Race completed in: {:?}", elapsed);
 
     // Example 2: Timeout implementation
-    println!("\n2. Timeout implementation:");
+    outln!("\n2. Timeout implementation:");
     let start = Instant::now();
 
     tokio::select! {
         result = simulate_api_call("slow_operation", Duration::from_millis(300), true) => {
-            println!("   Operation completed: {:?}", result);
+            outln!("   Operation completed: {:?}", result);
         }
         _ = sleep(Duration::from_millis(150)) => {
-            println!("   Operation timed out after 150ms");
+            outln!("   Operation timed out after 150ms");
         }
     }
 
     let elapsed = start.elapsed();
-    println!("   Timeout example completed in: {:?}", elapsed);
+    outln!("   Timeout example completed in: {:?}", elapsed);
 
     // Example 3: Fallback strategy
-    println!("\n3. Fallback strategy:");
+    outln!("\n3. Fallback strategy:");
     let start = Instant::now();
 
     tokio::select! {
         result = simulate_api_call("primary_service", Duration::from_millis(200), false) => {
             match result {
-                Ok(data) => println!("   Primary service succeeded: {}", data),
-                Err(error) => println!("   Primary service failed: {}", error),
+                Ok(data) => outln!("   Primary service succeeded: {}", data),
+                Err(error) => outln!("   Primary service failed: {}", error),
             }
         }
         result = simulate_api_call("backup_service", Duration::from_millis(250), true) => {
             match result {
-                Ok(data) => println!("   Backup service succeeded: {}", data),
-                Err(error) => println!("   Backup service failed: {}", error),
+                Ok(data) => outln!("   Backup service succeeded: {}", data),
+                Err(error) => outln!("   Backup service failed: {}", error),
             }
         }
     }
 
     let elapsed = start.elapsed();
-    println!("   Fallback completed in: {:?}", elapsed);
+    outln!("   Fallback completed in: {:?}", elapsed);
+
+    // Example 4: Deterministic tie-breaking
+    outln!("\n4. Priority select (cache preferred on ties):");
+    let winner = select_priority(vec![
+        (1, Box::pin(async { "network response" })),
+        (10, Box::pin(async { "cached response" })),
+    ])
+    .await;
+    outln!("   Both were ready; winner: {}", winner);
+
+    // Example 5: A select whose result says which branch won
+    outln!("\n5. Named select result:");
+    let outcome = select3(
+        simulate_database_query("replica", Duration::from_millis(120)),
+        simulate_api_call("search", Duration::from_millis(60), true),
+        sleep(Duration::from_millis(200)),
+    )
+    .await;
+    match outcome {
+        Selected::First(rows) => outln!("   Replica won: {}", rows),
+        Selected::Second(response) => outln!("   Search API won: {:?}", response),
+        Selected::Third(()) => outln!("   Timed out"),
+    }
 }
 
 /// # Function: demonstrate_collection_combinators
@@ -356,10 +1934,10 @@ async fn demonstrate_select_combinator() {
 /// - Collections can be built dynamically at runtime
 /// - Results maintain the same order as input futures
 async fn demonstrate_collection_combinators() {
-    println!("\n=== Collection Combinators ===");
+    outln!("\n=== Collection Combinators ===");
 
     // Example 1: join_all with dynamic collection
-    println!("1. join_all with dynamic collection:");
+    outln!("1. join_all with dynamic collection:");
     let start = Instant::now();
 
     // Build a collection of futures dynamically
@@ -374,14 +1952,14 @@ async fn demonstrate_collection_combinators() {
     let results = join_all(futures).await;
     let elapsed = start.elapsed();
 
-    println!("   All queries completed:");
+    outln!("   All queries completed:");
     for (i, result) in results.iter().enumerate() {
-        println!("     Query {}: {}", i + 1, result);
+        outln!("     Query {}: {}", i + 1, result);
     }
-    println!("   Total time: {:?}", elapsed);
+    outln!("   Total time: {:?}", elapsed);
 
     // Example 2: try_join_all with potential failures
-    println!("\n2. try_join_all with potential failures:");
+    outln!("\n2. try_join_all with potential failures:");
     let start = Instant::now();
 
     let api_futures = vec![
@@ -396,17 +1974,17 @@ async fn demonstrate_collection_combinators() {
 
     match result {
         Ok(responses) => {
-            println!("   All API calls succeeded:");
+            outln!("   All API calls succeeded:");
             for (i, response) in responses.iter().enumerate() {
-                println!("     API {}: {}", i + 1, response);
+                outln!("     API {}: {}", i + 1, response);
             }
         }
-        Err(error) => println!("   One API call failed: {}", error),
+        Err(error) => outln!("   One API call failed: {}", error),
     }
-    println!("   Total time: {:?}", elapsed);
+    outln!("   Total time: {:?}", elapsed);
 
     // Example 3: try_join_all with failure
-    println!("\n3. try_join_all with failure (fail-fast behavior):");
+    outln!("\n3. try_join_all with failure (fail-fast behavior):");
     let start = Instant::now();
 
     let mixed_futures = vec![
@@ -431,12 +2009,70 @@ async fn demonstrate_collection_combinators() {
     let elapsed = start.elapsed();
 
     match result {
-        Ok(_) => println!("   Unexpected success"),
+        Ok(_) => outln!("   Unexpected success"),
         Err(error) => {
-            println!("   Expected failure: {}", error);
-            println!("   Failed fast in: {:?} (before slow operation)", elapsed);
+            outln!("   Expected failure: {}", error);
+            outln!("   Failed fast in: {:?} (before slow operation)", elapsed);
         }
     }
+
+    // Example 4: Abandoning a batch when the user cancels
+    outln!("\n4. join_all with an abort signal:");
+    let abort = CancellationToken::new();
+    let canceller = abort.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(250)).await;
+        outln!("   User pressed cancel");
+        canceller.cancel();
+    });
+
+    let downloads = [100, 200, 400, 600]
+        .into_iter()
+        .map(|ms| simulate_database_query("download", Duration::from_millis(ms)))
+        .collect();
+    let results = join_all_abortable(downloads, abort).await;
+    let finished = results.iter().filter(|result| result.is_some()).count();
+    outln!(
+        "   {} of {} downloads finished before the abort",
+        finished,
+        results.len()
+    );
+
+    // Example 5: Tolerating a few failed records
+    outln!("\n5. Collecting results while tolerating some errors:");
+    let records = || {
+        futures::stream::iter(["12", "7", "oops", "30", "n/a", "5"]).map(|raw| {
+            raw.parse::<u32>()
+                .map_err(|_| format!("bad record {:?}", raw))
+        })
+    };
+    match try_collect_lenient(records(), 2).await {
+        Ok(values) => outln!("   Up to 2 errors allowed: {:?}", values),
+        Err(error) => outln!("   Up to 2 errors allowed: {}", error),
+    }
+    match try_collect_lenient(records(), 1).await {
+        Ok(values) => outln!("   Up to 1 error allowed: {:?}", values),
+        Err(error) => outln!("   Up to 1 error allowed: {}", error),
+    }
+    let (values, errors) = collect_results(records()).await;
+    outln!("   Everything: {:?}, with errors {:?}", values, errors);
+
+    // Example 6: Stop once enough replicas have answered
+    outln!("\n6. First 2 successful replicas out of 4:");
+    let start = Instant::now();
+    let replicas = vec![
+        simulate_api_call("replica-a", Duration::from_millis(60), false).boxed(),
+        simulate_api_call("replica-b", Duration::from_millis(90), true).boxed(),
+        simulate_api_call("replica-c", Duration::from_millis(120), true).boxed(),
+        simulate_api_call("replica-d", Duration::from_millis(500), true).boxed(),
+    ];
+    let (oks, errors) = first_n_ok(replicas, 2).await;
+    outln!(
+        "   {} answers and {} errors in {:?} (replica-d was cancelled)",
+        oks.len(),
+        errors.len(),
+        start.elapsed()
+    );
 }
 
 /// # Function: demonstrate_futures_unordered
@@ -451,10 +2087,10 @@ async fn demonstrate_collection_combinators() {
 /// - Useful for streaming results and early processing
 /// - Can handle dynamic addition of new futures
 async fn demonstrate_futures_unordered() {
-    println!("\n=== FuturesUnordered ===");
+    outln!("\n=== FuturesUnordered ===");
 
     // Example 1: Processing results as they complete
-    println!("1. Processing results as they complete:");
+    outln!("1. Processing results as they complete:");
     let start = Instant::now();
 
     let mut unordered = FuturesUnordered::new();
@@ -468,22 +2104,22 @@ async fn demonstrate_futures_unordered() {
         true,
     ));
 
-    println!("   Processing results as they arrive:");
+    outln!("   Processing results as they arrive:");
     let mut count = 0;
     while let Some(result) = unordered.next().await {
         count += 1;
         let elapsed = start.elapsed();
         match result {
-            Ok(data) => println!("     Result {}: {} (at {:?})", count, data, elapsed),
-            Err(error) => println!("     Error {}: {} (at {:?})", count, error, elapsed),
+            Ok(data) => outln!("     Result {}: {} (at {:?})", count, data, elapsed),
+            Err(error) => outln!("     Error {}: {} (at {:?})", count, error, elapsed),
         }
     }
 
     let total_elapsed = start.elapsed();
-    println!("   All results processed in: {:?}", total_elapsed);
+    outln!("   All results processed in: {:?}", total_elapsed);
 
     // Example 2: Dynamic addition of futures
-    println!("\n2. Dynamic addition of futures:");
+    outln!("\n2. Dynamic addition of futures:");
     let start = Instant::now();
 
     let mut unordered = FuturesUnordered::new();
@@ -504,11 +2140,11 @@ async fn demonstrate_futures_unordered() {
     while let Some(result) = unordered.next().await {
         processed += 1;
         let elapsed = start.elapsed();
-        println!("     Processed: {} (at {:?})", result, elapsed);
+        outln!("     Processed: {} (at {:?})", result, elapsed);
 
         // Dynamically add more futures after processing the first result
         if processed == 1 && !added_dynamic {
-            println!("   Adding dynamic futures...");
+            outln!("   Adding dynamic futures...");
             unordered.push(simulate_database_query(
                 "dynamic1",
                 Duration::from_millis(80),
@@ -522,7 +2158,159 @@ async fn demonstrate_futures_unordered() {
     }
 
     let total_elapsed = start.elapsed();
-    println!("   Dynamic processing completed in: {:?}", total_elapsed);
+    outln!("   Dynamic processing completed in: {:?}", total_elapsed);
+
+    // The ordered counterpart: still concurrent, but results come back in
+    // the order the futures were submitted
+    outln!("\n   Collecting in submission order instead:");
+    let start = Instant::now();
+    let ordered = collect_ordered(vec![
+        simulate_database_query("slow", Duration::from_millis(150)),
+        simulate_database_query("fast", Duration::from_millis(50)),
+    ])
+    .await;
+    for result in ordered {
+        outln!("     {}", result);
+    }
+    outln!("   Both ran concurrently: {:?}", start.elapsed());
+
+    // Example 3: Fairly merging event sources
+    outln!("\n3. Fair merging of streams:");
+    let chatty = futures::stream::iter(1..=6).map(|i| format!("chatty-{}", i));
+    let quiet = futures::stream::iter(1..=2).map(|i| format!("quiet-{}", i));
+
+    let merged: Vec<String> = merge_fair(vec![chatty.boxed(), quiet.boxed()])
+        .collect()
+        .await;
+    outln!("   Merged order: {:?}", merged);
+
+    // Example 4: Observing how fast a stream produces items
+    outln!("\n4. Measuring stream throughput:");
+    let ticks = futures::stream::unfold(0, |tick| async move {
+        if tick == 12 {
+            return None;
+        }
+        sleep(Duration::from_millis(25)).await; // ~40 items per second
+        Some((tick, tick + 1))
+    });
+
+    let measured: Vec<(u32, f64)> = measure_throughput(ticks, Duration::from_millis(200))
+        .collect()
+        .await;
+    if let Some((tick, rate)) = measured.last() {
+        outln!("   After item {}: {:.0} items/sec", tick, rate);
+    }
+
+    // Example 5: Stateful processing that stops early
+    outln!("\n5. Running totals until a limit is exceeded:");
+    let orders = futures::stream::iter(vec![40, 25, 30, 15, 50, 10]);
+    let totals: Vec<u32> = scan_async(orders, 0, |total, amount| async move {
+        sleep(Duration::from_millis(10)).await; // e.g. look up a discount
+        let total = total + amount;
+        (total, (total <= 100).then_some(total))
+    })
+    .collect()
+    .await;
+    outln!("   Running totals within budget: {:?}", totals);
+
+    // Example 6: Keep-alive heartbeats during quiet periods
+    outln!("\n6. Heartbeats between slow messages:");
+    let messages = futures::stream::iter(["hello", "world"]).then(|message| async move {
+        sleep(Duration::from_millis(120)).await;
+        message
+    });
+    let frames: Vec<&str> = with_heartbeat(messages, Duration::from_millis(50), "♥")
+        .collect()
+        .await;
+    outln!("   Frames sent: {:?}", frames);
+
+    // Example 7: Rate limits per key rather than for the whole stream
+    outln!("\n7. Throttling per user:");
+    let start = Instant::now();
+    let requests = futures::stream::iter([
+        ("alice", 1),
+        ("alice", 2),
+        ("alice", 3),
+        ("bob", 1),
+        ("alice", 4),
+        ("bob", 2),
+    ]);
+    let mut throttled = std::pin::pin!(throttle_by_key(
+        requests,
+        |(user, _)| *user,
+        2,
+        Duration::from_millis(100),
+    ));
+    while let Some((user, request)) = throttled.next().await {
+        outln!(
+            "   {:>4}ms: {} request {}",
+            start.elapsed().as_millis(),
+            user,
+            request
+        );
+    }
+
+    // Example 8: Concurrent stream processing with a cap
+    outln!("\n8. Mapping a stream with at most 2 queries in flight:");
+    for ordered in [true, false] {
+        let tables = futures::stream::iter([("orders", 120), ("users", 30), ("logs", 60)]);
+        let results: Vec<String> = map_concurrent(tables, 2, ordered, |(table, delay)| {
+            simulate_database_query(table, Duration::from_millis(delay))
+        })
+        .collect()
+        .await;
+        outln!("   ordered = {}: {:?}", ordered, results);
+    }
+
+    // Example 9: Rolling computations over a live feed
+    outln!("\n9. Moving average of the last 3 readings:");
+    let readings = futures::stream::iter([20.0, 22.0, 27.0, 21.0, 19.0]);
+    let averages: Vec<f64> = sliding_windows(readings, 3)
+        .map(|window| window.iter().sum::<f64>() / window.len() as f64)
+        .collect()
+        .await;
+    outln!("   {:?}", averages);
+
+    // Example 10: Forecasting load from request times
+    outln!("\n10. Smoothed request rate (alpha = 0.4):");
+    let forecaster = RateForecaster::new(0.4);
+    for (phase, gap) in [("quiet", 40), ("busy", 10)] {
+        let requests = futures::stream::iter(0..8).then(|_| async move {
+            sleep(Duration::from_millis(gap)).await;
+            Instant::now()
+        });
+        forecaster.track(requests).await;
+        outln!(
+            "   after {} phase: ~{:.0} req/s",
+            phase,
+            forecaster.current()
+        );
+    }
+
+    // Example 11: Consuming a blocking producer without blocking the runtime
+    outln!("\n11. Bridging a blocking iterator (buffer of 4):");
+    let lines = (1..=6).map(|n| {
+        std::thread::sleep(Duration::from_millis(10)); // e.g. a blocking read
+        format!("line {}", n)
+    });
+    let received: Vec<String> = iter_to_stream(lines, 4).collect().await;
+    outln!("   {:?}", received);
+
+    // Example 12: Take what is ready by a deadline, finish the rest later
+    outln!("\n12. Draining whatever finishes within 100ms:");
+    let widgets: FuturesUnordered<_> = [("news", 40), ("weather", 70), ("stocks", 250)]
+        .into_iter()
+        .map(|(widget, ms)| simulate_database_query(widget, Duration::from_millis(ms)))
+        .collect();
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let (ready, still_loading) = drain_until(widgets, deadline).await;
+    outln!(
+        "   Rendered {} widgets now, {} still loading",
+        ready.len(),
+        still_loading.len()
+    );
+    let late: Vec<String> = still_loading.collect().await;
+    outln!("   Filled in later: {:?}", late);
 }
 
 /// # Function: demonstrate_custom_combinator
@@ -536,45 +2324,7 @@ async fn demonstrate_futures_unordered() {
 /// - Combinators compose well with existing async/await code
 /// - They help create domain-specific async abstractions
 async fn demonstrate_custom_combinator() {
-    println!("\n=== Custom Combinators ===");
-
-    /// # Function: with_retry
-    ///
-    /// A custom combinator that retries a future operation up to a specified
-    /// number of times if it fails. This demonstrates how to create reusable
-    /// async patterns.
-    ///
-    /// ## Arguments:
-    /// - `future_fn`: A function that creates the future to retry
-    /// - `max_retries`: Maximum number of retry attempts
-    ///
-    /// ## Returns:
-    /// - The result of the future, or the last error if all retries fail
-    async fn with_retry<F, Fut, T, E>(mut future_fn: F, max_retries: usize) -> Result<T, E>
-    where
-        F: FnMut() -> Fut,
-        Fut: Future<Output = Result<T, E>>,
-        E: std::fmt::Display,
-    {
-        let mut attempts = 0;
-
-        loop {
-            attempts += 1;
-            println!("     Attempt {} of {}", attempts, max_retries + 1);
-
-            match future_fn().await {
-                Ok(result) => return Ok(result),
-                Err(error) => {
-                    if attempts > max_retries {
-                        println!("     All retries exhausted");
-                        return Err(error);
-                    }
-                    println!("     Attempt failed: {}, retrying...", error);
-                    sleep(Duration::from_millis(100)).await; // Brief delay between retries
-                }
-            }
-        }
-    }
+    outln!("\n=== Custom Combinators ===");
 
     /// # Function: with_timeout
     ///
@@ -598,24 +2348,25 @@ async fn demonstrate_custom_combinator() {
     }
 
     // Example 1: Using the retry combinator
-    println!("1. Custom retry combinator:");
-    let start = Instant::now();
+    outln!("1. Custom retry combinator:");
+    let start = tokio::time::Instant::now();
 
     let result = with_retry(
         || simulate_api_call("unreliable_service", Duration::from_millis(50), false),
         3, // Retry up to 3 times
+        start + Duration::from_secs(5),
     )
     .await;
 
     let elapsed = start.elapsed();
     match result {
-        Ok(data) => println!("   Retry succeeded: {}", data),
-        Err(error) => println!("   Retry failed after all attempts: {}", error),
+        Ok(data) => outln!("   Retry succeeded: {}", data),
+        Err(error) => outln!("   Retry failed after all attempts: {}", error),
     }
-    println!("   Total time: {:?}", elapsed);
+    outln!("   Total time: {:?}", elapsed);
 
     // Example 2: Using the timeout combinator
-    println!("\n2. Custom timeout combinator:");
+    outln!("\n2. Custom timeout combinator:");
     let start = Instant::now();
 
     let result = with_timeout(
@@ -624,38 +2375,278 @@ async fn demonstrate_custom_combinator() {
     )
     .await;
 
-    let elapsed = start.elapsed();
-    match result {
-        Ok(api_result) => match api_result {
-            Ok(data) => println!("   Operation completed: {}", data),
-            Err(error) => println!("   Operation failed: {}", error),
-        },
-        Err(timeout_error) => println!("   {}", timeout_error),
-    }
-    println!("   Total time: {:?}", elapsed);
+    let elapsed = start.elapsed();
+    match result {
+        Ok(api_result) => match api_result {
+            Ok(data) => outln!("   Operation completed: {}", data),
+            Err(error) => outln!("   Operation failed: {}", error),
+        },
+        Err(timeout_error) => outln!("   {}", timeout_error),
+    }
+    outln!("   Total time: {:?}", elapsed);
+
+    // Example 3: Combining custom combinators
+    outln!("\n3. Combining custom combinators:");
+    let start = tokio::time::Instant::now();
+
+    // The retry knows the deadline too, so it won't start a backoff that
+    // the timeout would cut short
+    let result = with_timeout(
+        with_retry(
+            || simulate_api_call("flaky_service", Duration::from_millis(80), true),
+            2,
+            start + Duration::from_millis(500),
+        ),
+        Duration::from_millis(500), // Overall timeout
+    )
+    .await;
+
+    let elapsed = start.elapsed();
+    match result {
+        Ok(retry_result) => match retry_result {
+            Ok(data) => outln!("   Combined operation succeeded: {}", data),
+            Err(error) => outln!("   Retry failed: {}", error),
+        },
+        Err(timeout_error) => outln!("   {}", timeout_error),
+    }
+    outln!("   Total time: {:?}", elapsed);
+
+    // Example 4: Observing values without changing them
+    outln!("\n4. Tapping into a pipeline:");
+    let result = tap_err(
+        tap(
+            simulate_api_call("metrics", Duration::from_millis(50), false),
+            |result| outln!("   [tap] metrics call finished, ok = {}", result.is_ok()),
+        ),
+        |error| outln!("   [tap_err] logging error: {}", error),
+    )
+    .await;
+    outln!("   Result passed through unchanged: {:?}", result);
+
+    // Example 5: Making fast operations take a minimum time
+    outln!("\n5. Anti-flicker minimum duration:");
+    let start = Instant::now();
+    let result = at_least(
+        simulate_database_query("settings", Duration::from_millis(20)),
+        Duration::from_millis(150),
+    )
+    .await;
+    outln!(
+        "   {} (spinner shown for {:?}, query took ~20ms)",
+        result,
+        start.elapsed()
+    );
+
+    // Example 6: Flagging slow operations
+    outln!("\n6. Warning about slow operations:");
+    for (table, delay) in [("users", 30), ("audit_log", 250)] {
+        let result = warn_if_slow(
+            simulate_database_query(table, Duration::from_millis(delay)),
+            Duration::from_millis(100),
+            table,
+        )
+        .await;
+        outln!("   {}", result);
+    }
+
+    // Example 7: Degrading gracefully instead of failing
+    outln!("\n7. Falling back to a default after a grace period:");
+    for delay in [50, 300] {
+        let result = or_default_after(
+            simulate_database_query("recommendations", Duration::from_millis(delay)),
+            Duration::from_millis(100),
+            "No recommendations right now".to_string(),
+        )
+        .await;
+        outln!("   {}", result);
+    }
+
+    // Example 8: Keep CPU-bound work off the async worker threads
+    outln!("\n8. Offloading CPU-bound work:");
+    let ticker = async {
+        let mut ticks = 0;
+        let mut interval = tokio::time::interval(Duration::from_millis(20));
+        loop {
+            interval.tick().await;
+            ticks += 1;
+            if ticks == 5 {
+                return ticks;
+            }
+        }
+    };
+    let (sum, ticks) = tokio::join!(run_cpu(|| (1..=20_000_000u64).sum::<u64>()), ticker);
+    outln!(
+        "   Sum: {:?}, timer ticked {} times meanwhile",
+        sum.ok(),
+        ticks
+    );
+
+    #[cfg(feature = "cpu-pool")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .expect("Failed to build thread pool");
+        let sum = run_cpu_on(&pool, || (1..=20_000_000u64).sum::<u64>()).await;
+        outln!("   Sum on a dedicated pool: {:?}", sum.ok());
+    }
+
+    // Example 9: Several awaiters, one computation
+    outln!("\n9. Sharing one query among three awaiters:");
+    let query = shared(simulate_database_query(
+        "settings",
+        Duration::from_millis(100),
+    ));
+    let start = Instant::now();
+    let results = join_all((0..3).map(|_| query.clone())).await;
+    outln!(
+        "   {} awaiters got {:?} in {:?}",
+        results.len(),
+        query.peek(),
+        start.elapsed()
+    );
+
+    // Example 10: Stop waiting without throwing the work away
+    outln!("\n10. Resuming an operation after a timeout:");
+    let report = Box::pin(simulate_database_query(
+        "report",
+        Duration::from_millis(150),
+    ));
+    match timeout_resumable(report, Duration::from_millis(50)).await {
+        Ok(report) => outln!("   Finished in time: {}", report),
+        Err(pending) => {
+            outln!("   Not done after 50ms, showing a spinner and waiting on...");
+            outln!("   {}", pending.await);
+        }
+    }
+}
+
+/// # Function: demonstrate_structured_concurrency
+///
+/// Demonstrates the `scope` helper, which ties the lifetime of spawned
+/// tasks to a single block of code.
+///
+/// ## Key Learning Points:
+/// - `tokio::spawn` detaches tasks; nothing forces them to finish
+/// - A scope waits for all of its children before returning
+/// - A panicking child cancels its siblings instead of leaking them
+async fn demonstrate_structured_concurrency() {
+    outln!("\n=== Structured Concurrency ===");
+
+    outln!("1. Scoped child tasks:");
+    let start = Instant::now();
+
+    let summary = scope(|s| {
+        for (name, delay) in [("orders", 80), ("invoices", 120), ("shipments", 60)] {
+            s.spawn(async move {
+                let _ = simulate_api_call(name, Duration::from_millis(delay), true).await;
+            });
+        }
+        async { "all children spawned" }
+    })
+    .await;
+
+    outln!("   Scope body returned: {}", summary);
+    outln!(
+        "   Scope finished in {:?} (waited for the slowest child)",
+        start.elapsed()
+    );
+
+    outln!("\n2. Measuring peak concurrency:");
+    let tracker = Arc::new(ResourceTracker::default());
+    let (pages, usage) = with_resource_tracking(
+        futures::stream::iter(1..=6)
+            .map(|page| {
+                tracker.track(simulate_database_query(
+                    "pages",
+                    Duration::from_millis(20 * page),
+                ))
+            })
+            .buffer_unordered(3)
+            .collect::<Vec<_>>(),
+        tracker.clone(),
+    )
+    .await;
+    outln!(
+        "   {} pages loaded by {} sub-tasks, at most {} at once, in {:?}",
+        pages.len(),
+        usage.tasks_started,
+        usage.peak_in_flight,
+        usage.elapsed
+    );
+
+    outln!("\n3. Serializing updates per account:");
+    let accounts = KeyedMutex::new();
+    let start = Instant::now();
+    let updates = ["alice", "bob", "alice", "bob"].map(|account| {
+        accounts.with_key(account, async move {
+            sleep(Duration::from_millis(50)).await;
+            account
+        })
+    });
+    let updated = join_all(updates).await;
+    outln!(
+        "   Updated {:?} in {:?} (two per account, accounts in parallel)",
+        updated,
+        start.elapsed()
+    );
 
-    // Example 3: Combining custom combinators
-    println!("\n3. Combining custom combinators:");
+    outln!("\n4. One cap of 2 for API calls and queries together:");
+    let limiter = ConcurrencyLimiter::new(2);
     let start = Instant::now();
+    let (api, orders, users) = tokio::join!(
+        limiter.run(simulate_api_call(
+            "profile",
+            Duration::from_millis(100),
+            true
+        )),
+        limiter.run(simulate_database_query(
+            "orders",
+            Duration::from_millis(100)
+        )),
+        limiter.run(simulate_database_query("users", Duration::from_millis(100))),
+    );
+    outln!(
+        "   Got {}, {} and {} in {:?} (the third waited for a slot)",
+        if api.is_ok() { "profile" } else { "no profile" },
+        orders.len(),
+        users.len(),
+        start.elapsed()
+    );
+}
 
-    let result = with_timeout(
-        with_retry(
-            || simulate_api_call("flaky_service", Duration::from_millis(80), true),
-            2,
-        ),
-        Duration::from_millis(500), // Overall timeout
-    )
-    .await;
+/// # Function: demonstrate_pipeline
+///
+/// Demonstrates a staged pipeline where every stage is its own task.
+///
+/// ## Key Learning Points:
+/// - Stages run concurrently, so different items are in flight at once
+/// - Bounded channels apply backpressure to fast upstream stages
+/// - Order is preserved because each stage handles one item at a time
+async fn demonstrate_pipeline() {
+    outln!("\n=== Staged Pipelines ===");
 
-    let elapsed = start.elapsed();
-    match result {
-        Ok(retry_result) => match retry_result {
-            Ok(data) => println!("   Combined operation succeeded: {}", data),
-            Err(error) => println!("   Retry failed: {}", error),
-        },
-        Err(timeout_error) => println!("   {}", timeout_error),
-    }
-    println!("   Total time: {:?}", elapsed);
+    outln!("1. Fetch → enrich → format over five items:");
+    let start = Instant::now();
+
+    let output = Pipeline::new(2)
+        .stage(|id: u32| async move {
+            sleep(Duration::from_millis(50)).await; // Simulated fetch
+            id * 100
+        })
+        .stage(|score| async move {
+            sleep(Duration::from_millis(50)).await; // Simulated enrichment
+            score + 7
+        })
+        .stage(|score| async move { format!("score={}", score) })
+        .run(futures::stream::iter(1..=5));
+
+    let results: Vec<String> = output.collect().await;
+    outln!("   Results: {:?}", results);
+    outln!(
+        "   Took {:?} (sequential would be ~500ms; stages overlap)",
+        start.elapsed()
+    );
 }
 
 /// # Function: main
@@ -671,11 +2662,19 @@ async fn demonstrate_custom_combinator() {
 /// 5. Collection processing
 /// 6. Stream-like processing with FuturesUnordered
 /// 7. Custom combinator creation
+/// 8. Structured concurrency with scopes
+/// 9. Staged pipelines with backpressure
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔗 Future Combinators Tutorial");
-    println!("==============================");
-    println!("This example demonstrates various ways to compose futures using combinators.");
+    let _flush = output::install_global(BufferedOutput::new(
+        tokio::io::stdout(),
+        8 * 1024,
+        Duration::from_millis(50),
+    ));
+
+    outln!("🔗 Future Combinators Tutorial");
+    outln!("==============================");
+    outln!("This example demonstrates various ways to compose futures using combinators.");
 
     // Basic transformation combinators
     demonstrate_map_combinator().await;
@@ -698,18 +2697,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Custom combinators
     demonstrate_custom_combinator().await;
 
-    println!("\n✅ Combinators Tutorial completed!");
-    println!("Key takeaways:");
-    println!("  - map: Transform future outputs");
-    println!("  - and_then: Chain dependent operations sequentially");
-    println!("  - join!: Run independent operations concurrently");
-    println!("  - try_join!: Concurrent with fail-fast error handling");
-    println!("  - select!: Race futures, first one wins");
-    println!("  - join_all/try_join_all: Handle collections of futures");
-    println!("  - FuturesUnordered: Process results as they complete");
-    println!("  - Custom combinators: Create reusable async patterns");
-
-    println!("\nNext: Try 'cargo run --bin error_handling' to learn about error handling patterns");
+    // Structured concurrency
+    demonstrate_structured_concurrency().await;
+
+    // Staged pipelines
+    demonstrate_pipeline().await;
+
+    outln!("\n✅ Combinators Tutorial completed!");
+    outln!("Key takeaways:");
+    outln!("  - map: Transform future outputs");
+    outln!("  - and_then: Chain dependent operations sequentially");
+    outln!("  - join!: Run independent operations concurrently");
+    outln!("  - try_join!: Concurrent with fail-fast error handling");
+    outln!("  - join_named!: Concurrent, with results in named fields");
+    outln!("  - select!: Race futures, first one wins");
+    outln!("  - select3: Race futures and get back a value naming the winner");
+    outln!("  - join_all/try_join_all: Handle collections of futures");
+    outln!("  - join_all_abortable: Cancel a whole batch from outside");
+    outln!("  - try_collect_lenient: Skip a bounded number of failures");
+    outln!("  - collect_results: Keep every value and every error, never stop early");
+    outln!("  - first_n_ok: Take the first N successes and cancel the rest");
+    outln!("  - FuturesUnordered: Process results as they complete");
+    outln!("  - collect_ordered: Run concurrently, keep submission order");
+    outln!("  - drain_until: Take what finished by a deadline, keep the rest");
+    outln!("  - map_concurrent: Process a stream N items at a time");
+    outln!("  - scan_async: Carry state through a stream and stop early");
+    outln!("  - sliding_windows: See the last N items for every new one");
+    outln!("  - RateForecaster: Smooth a request rate to anticipate load");
+    outln!("  - with_heartbeat: Keep idle streams alive with filler items");
+    outln!("  - iter_to_stream: Feed a blocking iterator into async code");
+    outln!("  - Custom combinators: Create reusable async patterns");
+    outln!("  - with_retry: Pass the deadline so retries never sleep past it");
+    outln!("  - at_least: Give fast operations a minimum duration");
+    outln!("  - warn_if_slow: Flag operations that exceed a time budget");
+    outln!("  - or_default_after: Fall back to a default instead of timing out");
+    outln!("  - timeout_resumable: Stop waiting but keep the work for later");
+    outln!("  - run_cpu: Move CPU-bound work off the async worker threads");
+    outln!("  - shared: Await one computation from many places, run it once");
+    outln!("  - scope: Child tasks never outlive the block that spawned them");
+    outln!("  - ResourceTracker: Measure how many sub-tasks really run at once");
+    outln!("  - KeyedMutex: One operation per key at a time, keys in parallel");
+    outln!("  - ConcurrencyLimiter: One concurrency cap across futures of any type");
+    outln!("  - Pipeline: Stages in separate tasks, linked by bounded channels");
+
+    outln!("\nNext: Try 'cargo run --bin error_handling' to learn about error handling patterns");
 
     Ok(())
 }
@@ -717,7 +2748,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_test;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     /// Test map combinator functionality
     #[tokio::test]
@@ -793,16 +2824,10 @@ mod tests {
     #[tokio::test]
     async fn test_select_racing() {
         let start = Instant::now();
-        let mut fast_won = false;
-
-        tokio::select! {
-            _ = simulate_api_call("fast", Duration::from_millis(30), true) => {
-                fast_won = true;
-            }
-            _ = simulate_api_call("slow", Duration::from_millis(100), true) => {
-                fast_won = false;
-            }
-        }
+        let fast_won = tokio::select! {
+            _ = simulate_api_call("fast", Duration::from_millis(30), true) => true,
+            _ = simulate_api_call("slow", Duration::from_millis(100), true) => false,
+        };
 
         let elapsed = start.elapsed();
 
@@ -812,6 +2837,950 @@ mod tests {
         assert!(elapsed <= Duration::from_millis(60));
     }
 
+    /// Test that a panicking child cancels its siblings and surfaces the panic
+    #[tokio::test]
+    async fn test_scope_cancels_siblings_on_panic() {
+        use std::panic::AssertUnwindSafe;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        struct DropCounter(Arc<AtomicUsize>);
+
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let finished = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let start = Instant::now();
+
+        let result = AssertUnwindSafe(scope(|s| {
+            for _ in 0..2 {
+                let finished = finished.clone();
+                let guard = DropCounter(dropped.clone());
+                s.spawn(async move {
+                    let _guard = guard;
+                    sleep(Duration::from_secs(10)).await;
+                    finished.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            s.spawn(async {
+                sleep(Duration::from_millis(20)).await;
+                panic!("child failed");
+            });
+            async {}
+        }))
+        .catch_unwind()
+        .await;
+
+        // The panic is surfaced to the caller
+        let panic = result.unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"child failed"));
+
+        // The long-running siblings were cancelled, not left running
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+    }
+
+    /// Test that merge_fair interleaves a fast stream with a slow one
+    #[tokio::test]
+    async fn test_merge_fair_interleaves() {
+        let fast = futures::stream::iter(0..20).map(|i| ("fast", i)).boxed();
+        let slow = futures::stream::iter(0..3)
+            .then(|i| async move {
+                // Not ready on the first poll, unlike the fast stream
+                tokio::task::yield_now().await;
+                ("slow", i)
+            })
+            .boxed();
+
+        let merged: Vec<_> = merge_fair(vec![fast, slow]).collect().await;
+
+        // Nothing is lost and each source keeps its own order
+        let fast_items: Vec<_> = merged.iter().filter(|(s, _)| *s == "fast").collect();
+        let slow_items: Vec<_> = merged.iter().filter(|(s, _)| *s == "slow").collect();
+        assert_eq!(fast_items.len(), 20);
+        assert_eq!(slow_items.len(), 3);
+        assert!(slow_items.windows(2).all(|pair| pair[0].1 < pair[1].1));
+
+        // The slow stream was not starved until the fast one finished
+        let last_slow = merged.iter().rposition(|(s, _)| *s == "slow").unwrap();
+        let last_fast = merged.iter().rposition(|(s, _)| *s == "fast").unwrap();
+        assert!(last_slow < last_fast);
+        assert!(last_slow < 10);
+    }
+
+    /// Test that tap observes the output and passes it through unchanged
+    #[tokio::test]
+    async fn test_tap_observes_value() {
+        let mut seen = None;
+
+        let result = tap(
+            simulate_database_query("tapped", Duration::from_millis(10)),
+            |value| seen = Some(value.clone()),
+        )
+        .await;
+
+        assert!(result.contains("tapped"));
+        assert_eq!(seen, Some(result));
+    }
+
+    /// Test that a slow future yields the default and a fast one its value
+    #[tokio::test(start_paused = true)]
+    async fn test_or_default_after() {
+        let held = std::sync::Arc::new(());
+        let guard = held.clone();
+        let slow = async move {
+            let _guard = guard;
+            sleep(Duration::from_secs(5)).await;
+            "real"
+        };
+        let start = tokio::time::Instant::now();
+
+        let value = or_default_after(slow, Duration::from_millis(100), "default").await;
+
+        assert_eq!(value, "default");
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        assert_eq!(std::sync::Arc::strong_count(&held), 1); // The slow future was dropped
+
+        let fast = async {
+            sleep(Duration::from_millis(10)).await;
+            "real"
+        };
+        assert_eq!(
+            or_default_after(fast, Duration::from_millis(100), "default").await,
+            "real"
+        );
+    }
+
+    /// Test that only the operation over the threshold is reported
+    #[tokio::test(start_paused = true)]
+    async fn test_warn_if_slow_reports_only_slow_operations() {
+        let warnings = Mutex::new(Vec::new());
+        let threshold = Duration::from_millis(100);
+
+        for (label, delay) in [("slow", 250), ("fast", 20)] {
+            let value = warn_if_slow_with(
+                async move {
+                    sleep(Duration::from_millis(delay)).await;
+                    delay
+                },
+                threshold,
+                label,
+                |label, elapsed| warnings.lock().unwrap().push((label.to_string(), elapsed)),
+            )
+            .await;
+            assert_eq!(value, delay); // Output passes through either way
+        }
+
+        let warnings = warnings.into_inner().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, "slow");
+        assert!(warnings[0].1 >= Duration::from_millis(250));
+    }
+
+    /// Helper for the map_concurrent tests: sleeps for `delay_ms` while
+    /// tracking how many calls are running at once
+    async fn tracked_sleep(delay_ms: u64, in_flight: &AtomicUsize, peak: &AtomicUsize) -> u64 {
+        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        sleep(Duration::from_millis(delay_ms)).await;
+        in_flight.fetch_sub(1, Ordering::SeqCst);
+        delay_ms
+    }
+
+    /// Test that ordered mode keeps submission order and respects the cap
+    #[tokio::test(start_paused = true)]
+    async fn test_map_concurrent_ordered() {
+        let (in_flight, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+        let results: Vec<u64> = map_concurrent(
+            futures::stream::iter([50, 10, 40, 20, 30]),
+            2,
+            true,
+            |delay| tracked_sleep(delay, &in_flight, &peak),
+        )
+        .collect()
+        .await;
+
+        assert_eq!(results, vec![50, 10, 40, 20, 30]);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    /// Test that unordered mode yields in completion order and respects the cap
+    #[tokio::test(start_paused = true)]
+    async fn test_map_concurrent_unordered() {
+        let (in_flight, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let start = tokio::time::Instant::now();
+
+        let results: Vec<u64> = map_concurrent(
+            futures::stream::iter([50, 10, 40, 20, 30]),
+            2,
+            false,
+            |delay| tracked_sleep(delay, &in_flight, &peak),
+        )
+        .collect()
+        .await;
+
+        // 50 and 10 start together; each finish lets the next item in
+        assert_eq!(results, vec![10, 50, 40, 20, 30]);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        // Two lanes finish the 150ms of work in 80ms, not 150ms
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    /// Test that select3 reports the fastest branch with its value
+    #[tokio::test(start_paused = true)]
+    async fn test_select3_reports_winner() {
+        async fn delayed<T>(ms: u64, value: T) -> T {
+            sleep(Duration::from_millis(ms)).await;
+            value
+        }
+
+        let outcome = select3(delayed(30, 1), delayed(10, "two"), delayed(20, 'c')).await;
+        assert_eq!(outcome, Selected::Second("two"));
+
+        let outcome = select3(delayed(30, 1), delayed(20, "two"), delayed(10, 'c')).await;
+        assert_eq!(outcome, Selected::Third('c'));
+
+        // Ties go to the earliest argument
+        let outcome = select3(delayed(10, 1), delayed(10, "two"), delayed(10, 'c')).await;
+        assert_eq!(outcome, Selected::First(1));
+    }
+
+    /// Test that the snapshot reports the peak number of tracked sub-tasks
+    #[tokio::test(start_paused = true)]
+    async fn test_with_resource_tracking_peak() {
+        let tracker = Arc::new(ResourceTracker::default());
+
+        // Four spawned sub-tasks that all overlap
+        let (sum, usage) = with_resource_tracking(
+            async {
+                let handles: Vec<_> = (1..=4u64)
+                    .map(|n| {
+                        tokio::spawn(tracker.track(async move {
+                            sleep(Duration::from_millis(10 * n)).await;
+                            n
+                        }))
+                    })
+                    .collect();
+                let mut sum = 0;
+                for handle in handles {
+                    sum += handle.await.unwrap();
+                }
+                sum
+            },
+            tracker.clone(),
+        )
+        .await;
+
+        assert_eq!(sum, 10);
+        assert_eq!(usage.peak_in_flight, 4);
+        assert_eq!(usage.tasks_started, 4);
+        assert_eq!(usage.elapsed, Duration::from_millis(40));
+
+        // The same tracker, with a cap of two in flight
+        let (_, usage) = with_resource_tracking(
+            futures::stream::iter(0..6)
+                .map(|_| tracker.track(sleep(Duration::from_millis(10))))
+                .buffer_unordered(2)
+                .collect::<Vec<_>>(),
+            tracker.clone(),
+        )
+        .await;
+
+        assert_eq!(usage.peak_in_flight, 2);
+        assert_eq!(usage.tasks_started, 6);
+    }
+
+    /// Test that same-key operations never overlap while different keys do
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_keyed_mutex_serializes_per_key() {
+        let keyed = Arc::new(KeyedMutex::new());
+        let in_flight: Arc<HashMap<&str, AtomicUsize>> = Arc::new(
+            [("a", AtomicUsize::new(0)), ("b", AtomicUsize::new(0))]
+                .into_iter()
+                .collect(),
+        );
+        let total_in_flight = Arc::new(AtomicUsize::new(0));
+        let max_total = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = ["a", "b"]
+            .iter()
+            .cycle()
+            .take(10)
+            .map(|&key| {
+                let (keyed, in_flight) = (keyed.clone(), in_flight.clone());
+                let (total_in_flight, max_total) = (total_in_flight.clone(), max_total.clone());
+                tokio::spawn(async move {
+                    keyed
+                        .with_key(key, async {
+                            let busy = in_flight[key].fetch_add(1, Ordering::SeqCst);
+                            assert_eq!(busy, 0, "two operations on {:?} overlapped", key);
+                            let total = total_in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_total.fetch_max(total, Ordering::SeqCst);
+
+                            sleep(Duration::from_millis(20)).await;
+
+                            total_in_flight.fetch_sub(1, Ordering::SeqCst);
+                            in_flight[key].fetch_sub(1, Ordering::SeqCst);
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // Keys "a" and "b" did run at the same time
+        assert_eq!(max_total.load(Ordering::SeqCst), 2);
+        assert_eq!(keyed.active_keys(), 0);
+    }
+
+    /// Test that a timed-out future comes back and can still be completed
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_resumable_returns_pending_future() {
+        let start = tokio::time::Instant::now();
+        let slow = Box::pin(async {
+            sleep(Duration::from_millis(100)).await;
+            "finished"
+        });
+
+        let pending = match timeout_resumable(slow, Duration::from_millis(30)).await {
+            Ok(_) => panic!("a 100ms future finished within 30ms"),
+            Err(pending) => pending,
+        };
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+
+        // The sleep kept its deadline: resuming waits only for what is left
+        assert_eq!(pending.await, "finished");
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        let fast = Box::pin(async { 7 });
+        assert_eq!(
+            timeout_resumable(fast, Duration::from_millis(30))
+                .await
+                .ok(),
+            Some(7)
+        );
+    }
+
+    /// Test that a shared computation runs once for all of its awaiters
+    #[tokio::test]
+    async fn test_shared_runs_once() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let expensive = {
+            let runs = runs.clone();
+            shared(async move {
+                sleep(Duration::from_millis(20)).await;
+                runs.fetch_add(1, Ordering::SeqCst) + 42
+            })
+        };
+        assert_eq!(expensive.peek(), None);
+
+        let awaiters: Vec<_> = (0..5).map(|_| tokio::spawn(expensive.clone())).collect();
+        let mut results = Vec::new();
+        for awaiter in awaiters {
+            results.push(awaiter.await.unwrap());
+        }
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(results, vec![42; 5]);
+        assert_eq!(expensive.peek(), Some(42));
+    }
+
+    /// Busy-loops for `duration` without yielding, like a heavy computation
+    fn spin_for(duration: Duration) -> u64 {
+        let start = Instant::now();
+        let mut state = 1u64;
+        while start.elapsed() < duration {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        }
+        state
+    }
+
+    /// Test that offloaded work leaves the runtime free to run a timer
+    #[tokio::test]
+    async fn test_run_cpu_keeps_runtime_responsive() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = {
+            let ticks = ticks.clone();
+            async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(10));
+                loop {
+                    interval.tick().await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        };
+
+        // A current-thread runtime: had the computation run on it, the
+        // ticker could not have run at all until it finished
+        let result = tokio::select! {
+            result = run_cpu(|| spin_for(Duration::from_millis(200))) => result,
+            _ = ticker => unreachable!(),
+        };
+
+        assert!(result.is_ok());
+        assert!(
+            ticks.load(Ordering::SeqCst) >= 5,
+            "only {} ticks",
+            ticks.load(Ordering::SeqCst)
+        );
+        assert!(run_cpu(|| panic!("boom")).await.unwrap_err().is_panic());
+    }
+
+    /// Test that work runs on the given rayon pool and panics are returned
+    #[cfg(feature = "cpu-pool")]
+    #[tokio::test]
+    async fn test_run_cpu_on_pool() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .thread_name(|_| "cpu-pool".to_string())
+            .build()
+            .unwrap();
+
+        let name = run_cpu_on(&pool, || std::thread::current().name().map(String::from)).await;
+        assert_eq!(name.unwrap().as_deref(), Some("cpu-pool"));
+        assert!(run_cpu_on(&pool, || panic!("boom")).await.is_err());
+    }
+
+    /// Test that tap_err sees only errors and leaves the result unchanged
+    #[tokio::test]
+    async fn test_tap_err_observes_error() {
+        let mut seen_error = None;
+        let result = tap_err(
+            simulate_api_call("failing", Duration::from_millis(10), false),
+            |error| seen_error = Some(error.clone()),
+        )
+        .await;
+
+        assert_eq!(result, Err("Failed to call 'failing' API".to_string()));
+        assert_eq!(seen_error, Some("Failed to call 'failing' API".to_string()));
+
+        let mut called = false;
+        let result = tap_err(
+            simulate_api_call("working", Duration::from_millis(10), true),
+            |_| called = true,
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert!(!called);
+    }
+
+    /// Test that the highest-priority branch wins when all are ready
+    #[tokio::test]
+    async fn test_select_priority_breaks_ties() {
+        for _ in 0..50 {
+            let winner = select_priority(vec![
+                (1, Box::pin(async { "low" })),
+                (5, Box::pin(async { "high" })),
+                (3, Box::pin(async { "medium" })),
+            ])
+            .await;
+
+            assert_eq!(winner, "high");
+        }
+
+        // A lower-priority branch still wins if it's the only one ready
+        let winner = select_priority(vec![
+            (
+                9,
+                Box::pin(sleep(Duration::from_millis(100)).map(|_| "slow")),
+            ),
+            (1, Box::pin(async { "fast" })),
+        ])
+        .await;
+        assert_eq!(winner, "fast");
+    }
+
+    /// Test a three-stage pipeline over 100 inputs
+    #[tokio::test]
+    async fn test_pipeline_three_stages() {
+        let output = Pipeline::new(4)
+            .stage(|n: u64| async move { n * 2 })
+            .stage(|n| async move { n + 1 })
+            .stage(|n| async move { n.to_string() })
+            .run(futures::stream::iter(0..100));
+
+        let results: Vec<String> = output.collect().await;
+        let expected: Vec<String> = (0..100u64).map(|n| (n * 2 + 1).to_string()).collect();
+
+        assert_eq!(results, expected);
+    }
+
+    /// A stream that yields `0, 1, 2, ...` after each of the scripted delays
+    fn scripted_stream(delays: Vec<Duration>) -> impl Stream<Item = usize> {
+        futures::stream::unfold(0, move |index| {
+            let delay = delays.get(index).copied();
+            async move {
+                sleep(delay?).await;
+                Some((index, index + 1))
+            }
+        })
+    }
+
+    /// Test that aborting join_all_abortable cancels the unfinished futures
+    #[tokio::test(start_paused = true)]
+    async fn test_join_all_abortable() {
+        let abort = CancellationToken::new();
+        let canceller = abort.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let futures = [10, 40, 100, 150, 200]
+            .into_iter()
+            .map(|ms| async move {
+                sleep(Duration::from_millis(ms)).await;
+                ms
+            })
+            .collect();
+        let start = tokio::time::Instant::now();
+        let results = join_all_abortable(futures, abort).await;
+
+        assert_eq!(results, vec![Some(10), Some(40), None, None, None]);
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+    }
+
+    /// Test that collect_ordered keeps submission order when completion is reversed
+    #[tokio::test(start_paused = true)]
+    async fn test_collect_ordered_keeps_submission_order() {
+        let start = tokio::time::Instant::now();
+        // Submitted first, finishes last
+        let futures = [50, 40, 30, 20, 10]
+            .into_iter()
+            .map(|ms| async move {
+                sleep(Duration::from_millis(ms)).await;
+                ms
+            })
+            .collect();
+
+        let results = collect_ordered(futures).await;
+
+        assert_eq!(results, vec![50, 40, 30, 20, 10]);
+        // Concurrent: as long as the slowest, not the sum
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+    }
+
+    /// Test that first_n_ok returns the first three successes and drops the rest
+    #[tokio::test(start_paused = true)]
+    async fn test_first_n_ok() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let finished = Arc::new(AtomicUsize::new(0));
+        // (delay in ms, succeeds): the third success arrives at 50ms
+        let plan = [
+            (10, false),
+            (20, true),
+            (30, false),
+            (40, true),
+            (50, true),
+            (60, true),
+            (70, false),
+            (80, true),
+        ];
+        let futures = plan
+            .into_iter()
+            .map(|(ms, succeeds)| {
+                let finished = finished.clone();
+                async move {
+                    sleep(Duration::from_millis(ms)).await;
+                    finished.fetch_add(1, Ordering::SeqCst);
+                    if succeeds {
+                        Ok(ms)
+                    } else {
+                        Err(format!("failed after {}ms", ms))
+                    }
+                }
+            })
+            .collect();
+
+        let (oks, errors) = first_n_ok(futures, 3).await;
+
+        assert_eq!(oks, vec![20, 40, 50]);
+        assert_eq!(errors, vec!["failed after 10ms", "failed after 30ms"]);
+
+        // The remaining three futures were dropped, not left running
+        assert_eq!(Arc::strong_count(&finished), 1);
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 5);
+    }
+
+    /// Test try_collect_lenient below and above its error threshold
+    #[tokio::test]
+    async fn test_try_collect_lenient() {
+        let mixed = || {
+            futures::stream::iter(vec![
+                Ok(1),
+                Err("bad 1"),
+                Ok(2),
+                Err("bad 2"),
+                Ok(3),
+                Err("bad 3"),
+                Ok(4),
+            ])
+        };
+
+        assert_eq!(try_collect_lenient(mixed(), 3).await, Ok(vec![1, 2, 3, 4]));
+        assert_eq!(
+            try_collect_lenient(mixed(), 2).await,
+            Err(TooManyErrors {
+                max_errors: 2,
+                last_error: "bad 3".to_string()
+            })
+        );
+        assert_eq!(
+            try_collect_lenient(futures::stream::iter(vec![Err::<u32, _>("bad")]), 0).await,
+            Err(TooManyErrors {
+                max_errors: 0,
+                last_error: "bad".to_string()
+            })
+        );
+    }
+
+    /// Test that collect_results keeps both sides in order and drains the stream
+    #[tokio::test]
+    async fn test_collect_results() {
+        let pulled = AtomicUsize::new(0);
+        let stream = futures::stream::iter(vec![
+            Ok(1),
+            Err("bad 1"),
+            Err("bad 2"),
+            Ok(2),
+            Ok(3),
+            Err("bad 3"),
+        ])
+        .inspect(|_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let (oks, errs) = collect_results(stream).await;
+
+        assert_eq!(oks, vec![1, 2, 3]);
+        assert_eq!(errs, vec!["bad 1", "bad 2", "bad 3"]);
+        assert_eq!(pulled.load(Ordering::SeqCst), 6);
+    }
+
+    /// Test that iter_to_stream keeps order and never runs ahead of a slow
+    /// consumer by more than its buffer
+    #[tokio::test]
+    async fn test_iter_to_stream_backpressure() {
+        const BUFFER: usize = 8;
+        let produced = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&produced);
+        let items = (0..2_000u32).inspect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let mut stream = std::pin::pin!(iter_to_stream(items, BUFFER));
+        let mut received = 0u32;
+        while let Some(item) = stream.next().await {
+            assert_eq!(item, received);
+            received += 1;
+            // The channel holds at most BUFFER items, plus one in hand
+            let ahead = produced.load(Ordering::SeqCst) - received as usize;
+            assert!(ahead <= BUFFER + 1, "producer ran {} items ahead", ahead);
+            if received.is_multiple_of(100) {
+                sleep(Duration::from_millis(2)).await;
+            }
+        }
+        assert_eq!(received, 2_000);
+    }
+
+    /// Test that with_retry gives up when the next backoff would overrun
+    /// the deadline, instead of sleeping through it
+    #[tokio::test(start_paused = true)]
+    async fn test_with_retry_stops_at_deadline() {
+        let attempts = AtomicUsize::new(0);
+        let start = tokio::time::Instant::now();
+        // Attempts at 0ms and 100ms; a third would start after the deadline
+        let deadline = start + RETRY_DELAY + RETRY_DELAY / 2;
+
+        let result: Result<(), &str> = with_retry(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err("always down")
+            },
+            5,
+            deadline,
+        )
+        .await;
+
+        assert_eq!(result, Err("always down"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        // One backoff slept, and the second one skipped
+        assert_eq!(start.elapsed(), RETRY_DELAY);
+    }
+
+    /// Test that drain_until splits a set at the deadline without losing
+    /// the unfinished futures
+    #[tokio::test]
+    async fn test_drain_until() {
+        let set: FuturesUnordered<_> = [10u64, 400, 30, 300]
+            .into_iter()
+            .map(|ms| async move {
+                sleep(Duration::from_millis(ms)).await;
+                ms
+            })
+            .collect();
+
+        let start = Instant::now();
+        let (done, rest) = drain_until(set, start + Duration::from_millis(150)).await;
+        let waited = start.elapsed();
+
+        assert_eq!(done, vec![10, 30]);
+        assert_eq!(rest.len(), 2);
+        assert!(waited >= Duration::from_millis(150) && waited < Duration::from_millis(250));
+
+        let mut late: Vec<u64> = rest.collect().await;
+        late.sort();
+        assert_eq!(late, vec![300, 400]);
+        // The remaining futures kept running; they were not restarted
+        assert!(start.elapsed() < Duration::from_millis(550));
+    }
+
+    /// Test that at_least pads a fast future and preserves its value
+    #[tokio::test(start_paused = true)]
+    async fn test_at_least_pads_fast_future() {
+        let start = tokio::time::Instant::now();
+        let value = at_least(
+            async {
+                sleep(Duration::from_millis(10)).await;
+                "done"
+            },
+            Duration::from_millis(100),
+        )
+        .await;
+
+        assert_eq!(value, "done");
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // A future slower than the minimum isn't delayed further
+        let start = tokio::time::Instant::now();
+        at_least(
+            sleep(Duration::from_millis(150)),
+            Duration::from_millis(100),
+        )
+        .await;
+        assert_eq!(start.elapsed(), Duration::from_millis(150));
+    }
+
+    /// Test that heartbeats fill quiet gaps and stop while data flows
+    #[tokio::test(start_paused = true)]
+    async fn test_with_heartbeat_fills_gaps() {
+        let delays = [10, 130, 10, 10, 10].map(Duration::from_millis).to_vec();
+        let data = scripted_stream(delays).map(Some);
+
+        let items: Vec<Option<usize>> = with_heartbeat(data, Duration::from_millis(50), None)
+            .collect()
+            .await;
+
+        // Heartbeats at 60ms and 110ms, then data every 10ms from 140ms on
+        assert_eq!(
+            items,
+            vec![Some(0), None, None, Some(1), Some(2), Some(3), Some(4)]
+        );
+    }
+
+    /// Test that each key gets its own rate limit and busy keys don't block others
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle_by_key_limits_each_key_independently() {
+        let start = tokio::time::Instant::now();
+        let items = vec![
+            ("a", 1),
+            ("a", 2),
+            ("a", 3),
+            ("a", 4),
+            ("a", 5),
+            ("b", 1),
+            ("b", 2),
+        ];
+
+        let emitted: Vec<((&str, u32), u128)> = throttle_by_key(
+            futures::stream::iter(items),
+            |(key, _)| *key,
+            2,
+            Duration::from_millis(100),
+        )
+        .map(|item| (item, start.elapsed().as_millis()))
+        .collect()
+        .await;
+
+        let of_key = |key: &str| -> Vec<(u32, u128)> {
+            emitted
+                .iter()
+                .filter(|((k, _), _)| *k == key)
+                .map(|((_, n), at)| (*n, *at))
+                .collect()
+        };
+        // Key "a" gets 2 items per 100ms, in order
+        assert_eq!(
+            of_key("a"),
+            vec![(1, 0), (2, 0), (3, 100), (4, 100), (5, 200)]
+        );
+        // Key "b" is within its own limit, so it isn't held up behind "a"
+        assert_eq!(of_key("b"), vec![(1, 0), (2, 0)]);
+    }
+
+    /// Test throughput measurement against a paused, auto-advancing clock
+    #[tokio::test(start_paused = true)]
+    async fn test_measure_throughput() {
+        // One item every 10ms is 100 items per second
+        let stream = scripted_stream(vec![Duration::from_millis(10); 60]);
+        let measured: Vec<(usize, f64)> = measure_throughput(stream, Duration::from_millis(200))
+            .collect()
+            .await;
+
+        assert_eq!(measured.len(), 60);
+
+        // Before the window fills, the rate is still ramping up
+        assert!(measured[4].1 < 50.0);
+
+        // Once a full window has passed, the rate settles at 100/s
+        for (index, rate) in &measured[20..] {
+            assert!((rate - 100.0).abs() < 1e-9, "item {} rate {}", index, rate);
+        }
+    }
+
+    /// Test that scan_async emits running sums and stops past the limit
+    #[tokio::test]
+    async fn test_scan_async_stops_early() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let pulled = AtomicUsize::new(0);
+        let numbers = futures::stream::iter(vec![10, 20, 30, 40, 50, 60, 70]).inspect(|_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let sums: Vec<u32> = scan_async(numbers, 0, |total, n| async move {
+            let total = total + n;
+            (total, (total <= 100).then_some(total))
+        })
+        .collect()
+        .await;
+
+        assert_eq!(sums, vec![10, 30, 60, 100]);
+        // The item that pushed the total past 100 was the last one pulled
+        assert_eq!(pulled.load(Ordering::SeqCst), 5);
+    }
+
+    /// Test that windows slide one item at a time once the buffer is full
+    #[tokio::test]
+    async fn test_sliding_windows() {
+        let windows: Vec<Vec<u32>> = sliding_windows(futures::stream::iter([1, 2, 3, 4]), 2)
+            .collect()
+            .await;
+        assert_eq!(windows, vec![vec![1, 2], vec![2, 3], vec![3, 4]]);
+
+        let too_short: Vec<Vec<u32>> = sliding_windows(futures::stream::iter([1, 2]), 3)
+            .collect()
+            .await;
+        assert!(too_short.is_empty());
+    }
+
+    /// Test that the rate forecast follows a change in request rate
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_forecaster_converges() {
+        // Stamps each item with the (paused, auto-advancing) time it arrives
+        fn arrivals(delays: Vec<Duration>) -> impl Stream<Item = Instant> {
+            scripted_stream(delays).map(|_| tokio::time::Instant::now().into_std())
+        }
+
+        let start = tokio::time::Instant::now().into_std();
+        let forecaster = RateForecaster::new(0.3);
+        assert_eq!(forecaster.current(), 0.0);
+
+        // 10 req/s for two seconds
+        forecaster
+            .track(arrivals(vec![Duration::from_millis(100); 20]))
+            .await;
+        assert!((forecaster.current() - 10.0).abs() < 0.01);
+
+        // Then 50 req/s: the estimate climbs with every request
+        let mut estimates = Vec::new();
+        forecaster
+            .track(
+                arrivals(vec![Duration::from_millis(20); 30])
+                    .inspect(|_| estimates.push(forecaster.current())),
+            )
+            .await;
+        estimates.push(forecaster.current());
+        assert_eq!(estimates.len(), 31);
+        assert!(estimates.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!((forecaster.current() - 50.0).abs() < 0.1);
+
+        // Out-of-order times do not disturb the estimate
+        let previous = forecaster.current();
+        forecaster.record(start);
+        assert_eq!(forecaster.current(), previous);
+    }
+
+    /// Test that join_named! puts each result in the field of its name
+    #[tokio::test]
+    async fn test_join_named() {
+        let start = Instant::now();
+        let joined = join_named!(
+            slow: async {
+                sleep(Duration::from_millis(60)).await;
+                "slow"
+            },
+            fast: async {
+                sleep(Duration::from_millis(30)).await;
+                42
+            },
+        );
+        assert_eq!(joined.slow, "slow");
+        assert_eq!(joined.fast, 42);
+        // Concurrent, not one after the other
+        assert!(start.elapsed() < Duration::from_millis(85));
+
+        let results = join_named!(
+            ok: async { Ok::<u32, String>(1) },
+            failed: async { Err::<u32, String>("down".to_string()) }
+        );
+        assert_eq!(results.ok, Ok(1));
+        assert_eq!(results.failed, Err("down".to_string()));
+        assert!(format!("{:?}", results).contains("failed: Err(\"down\")"));
+    }
+
+    /// Test that futures of different types share one concurrency cap
+    #[tokio::test]
+    async fn test_concurrency_limiter_heterogeneous() {
+        let limiter = ConcurrencyLimiter::new(3);
+        let tracker = Arc::new(ResourceTracker::default());
+
+        let ((api_calls, timers), usage) = with_resource_tracking(
+            async {
+                let api_calls = ["a", "b", "c", "d", "e"].map(|name| {
+                    let call = simulate_api_call(name, Duration::from_millis(40), true);
+                    limiter.run(tracker.track(call))
+                });
+                // A different future type entirely
+                let timers = (0..5).map(|i| {
+                    let timer = tokio::time::sleep(Duration::from_millis(30 + 5 * i));
+                    limiter.run(tracker.track(timer))
+                });
+                tokio::join!(join_all(api_calls), join_all(timers))
+            },
+            tracker.clone(),
+        )
+        .await;
+
+        assert!(api_calls.iter().all(|call| call.is_ok()));
+        assert_eq!(timers.len(), 5);
+        assert_eq!(usage.tasks_started, 10);
+        assert_eq!(usage.peak_in_flight, 3);
+        assert_eq!(limiter.available(), 3);
+    }
+
     /// Test collection combinators
     #[tokio::test]
     async fn test_join_all() {
@@ -8,13 +8,26 @@
 //! 3. Working with Waker for efficient scheduling
 //! 4. Managing shared state between threads
 //! 5. Proper resource cleanup and cancellation safety
+//! 6. Running blocking work without losing its panics
+//!
+//! `DelayFuture` also builds for wasm32, where it runs on browser timers.
+//! The demos in `main` need threads and are native-only.
+
+#![cfg_attr(target_arch = "wasm32", no_main)]
 
+#[cfg(feature = "lock-tracking")]
+use future_trait_tutorial::tracked_mutex::TrackedMutex as Mutex;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+#[cfg(not(feature = "lock-tracking"))]
+use std::sync::Mutex;
 use std::task::{Context, Poll, Waker};
+#[cfg(not(target_arch = "wasm32"))]
 use std::thread;
 use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
 /// # Struct: SharedState
 ///
@@ -110,48 +123,77 @@ impl DelayFuture {
 
     /// # Function: start_timer
     ///
-    /// Starts the background timer thread. This is called from poll()
+    /// Starts the background timer. This is called from poll()
     /// on the first poll to implement lazy execution.
     ///
     /// ## Key Implementation Details:
-    /// - Spawns a new thread to perform the blocking sleep
-    /// - The thread sleeps for the specified duration
-    /// - When the sleep completes, it updates the shared state
+    /// - Hands the shared state to a platform timer (see `spawn_timer`)
+    /// - When the timer fires, it updates the shared state
     /// - If a waker was registered, it calls wake() to notify the executor
     ///
     /// ## Thread Safety:
-    /// - Uses Arc::clone to share ownership of the state with the thread
-    /// - The spawned thread takes ownership of its Arc clone
+    /// - Uses Arc::clone to share ownership of the state with the timer
+    /// - The timer takes ownership of its Arc clone
     /// - Mutex ensures safe concurrent access to the shared state
     fn start_timer(&mut self) {
         if self.started {
-            return; // Already started, don't spawn multiple threads
+            return; // Already started, don't spawn multiple timers
         }
 
         self.started = true;
-        let thread_shared_state = Arc::clone(&self.shared_state);
-        let duration = self.duration;
-
-        // Spawn a background thread to perform the blocking sleep
-        // This keeps the async executor thread free to handle other tasks
-        thread::spawn(move || {
-            // Perform the blocking sleep operation
-            // This is okay because we're in a dedicated thread
-            thread::sleep(duration);
-
-            // Update the shared state to indicate completion
-            let mut state = thread_shared_state.lock().unwrap();
-            state.completed = true;
-
-            // If a waker was registered, wake up the task
-            // This notifies the executor that this future is ready to be polled again
-            if let Some(waker) = state.waker.take() {
-                // Calling wake() schedules the task for re-polling
-                waker.wake();
-            }
+        spawn_timer(self.duration, Arc::clone(&self.shared_state));
+    }
+}
 
-            // The thread ends here, automatically cleaning up resources
-        });
+/// # Function: spawn_timer (native)
+///
+/// Spawns a background thread that sleeps for `duration` and then
+/// completes the shared state.
+///
+/// ## Note:
+/// The timer backend is chosen at compile time. Browsers can't spawn
+/// threads or block, so wasm32 builds use the version below instead.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_timer(duration: Duration, shared_state: Arc<Mutex<SharedState>>) {
+    // Spawn a background thread to perform the blocking sleep
+    // This keeps the async executor thread free to handle other tasks
+    thread::spawn(move || {
+        // Perform the blocking sleep operation
+        // This is okay because we're in a dedicated thread
+        thread::sleep(duration);
+
+        complete_delay(&shared_state);
+
+        // The thread ends here, automatically cleaning up resources
+    });
+}
+
+/// # Function: spawn_timer (wasm32)
+///
+/// Schedules a browser `setTimeout` callback (through `gloo-timers`) that
+/// completes the shared state after `duration`. The JavaScript event loop
+/// runs the callback, so nothing blocks and no thread is needed.
+#[cfg(target_arch = "wasm32")]
+fn spawn_timer(duration: Duration, shared_state: Arc<Mutex<SharedState>>) {
+    // setTimeout takes whole milliseconds; clamp absurdly long delays
+    let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+
+    // forget() keeps the timer alive after the handle goes out of scope;
+    // dropping a gloo Timeout would cancel it
+    gloo_timers::callback::Timeout::new(millis, move || complete_delay(&shared_state)).forget();
+}
+
+/// Marks the delay as completed and wakes the task waiting on it
+fn complete_delay(shared_state: &Mutex<SharedState>) {
+    // Update the shared state to indicate completion
+    let mut state = shared_state.lock().unwrap();
+    state.completed = true;
+
+    // If a waker was registered, wake up the task
+    // This notifies the executor that this future is ready to be polled again
+    if let Some(waker) = state.waker.take() {
+        // Calling wake() schedules the task for re-polling
+        waker.wake();
     }
 }
 
@@ -177,11 +219,16 @@ impl Future for DelayFuture {
     /// standard pattern for custom futures:
     ///
     /// ## Poll Implementation Pattern:
-    /// 1. Check if the work is already complete → return Poll::Ready
-    /// 2. If not complete, ensure background work has started
-    /// 3. Register the current task's waker for later notification
+    /// 1. Ensure the background work has started
+    /// 2. Check if the work is already complete → return Poll::Ready
+    /// 3. If not, register the current task's waker for later notification
     /// 4. Return Poll::Pending to indicate more work is needed
     ///
+    /// Steps 2 and 3 happen under one lock. If the lock were released in
+    /// between, the timer could fire in the gap: it would wake the waker
+    /// stored by an earlier poll (perhaps from another task) and we would
+    /// then store ours and return Pending with nobody left to wake us.
+    ///
     /// ## Arguments:
     /// - `self`: Pin<&mut Self> - ensures the future won't move in memory
     /// - `cx`: &mut Context - provides access to the current task's waker
@@ -195,11 +242,16 @@ impl Future for DelayFuture {
     /// it won't be moved in memory. This is crucial for futures that might
     /// contain self-references in their generated state machines.
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // Step 1: Acquire the lock on our shared state
-        // This ensures thread-safe access to the completion flag and waker
-        let shared_state = self.shared_state.lock().unwrap();
+        // Step 1: Ensure the background timer has started
+        // This implements the "lazy execution" principle - work only starts
+        // when the future is actually polled by an executor. It's done before
+        // taking the lock, which the timer needs in order to complete.
+        self.start_timer();
 
-        // Step 2: Check if the delay has already completed
+        // Step 2: Acquire the lock on our shared state and check if the
+        // delay has already completed. The lock is held until the waker is
+        // registered, so the timer can't complete in between.
+        let mut shared_state = self.shared_state.lock().unwrap();
         if shared_state.completed {
             // The background thread has finished the delay
             // Return the final result and complete the future
@@ -209,14 +261,7 @@ impl Future for DelayFuture {
             ));
         }
 
-        // Step 3: If not completed, ensure the background timer has started
-        // This implements the "lazy execution" principle - work only starts
-        // when the future is actually polled by an executor
-        drop(shared_state); // Release the lock before starting timer
-        self.start_timer();
-        let mut shared_state = self.shared_state.lock().unwrap(); // Re-acquire lock
-
-        // Step 4: Register the current task's waker
+        // Step 3: Register the current task's waker
         // This is crucial for efficient scheduling - it tells the background
         // thread how to notify the executor when the delay completes
 
@@ -231,12 +276,104 @@ impl Future for DelayFuture {
             shared_state.waker = Some(cx.waker().clone());
         }
 
-        // Step 5: Return Pending to indicate the future is not ready yet
+        // Step 4: Return Pending to indicate the future is not ready yet
         // The executor will stop polling this future until wake() is called
         Poll::Pending
     }
 }
 
+/// # Enum: SleepOutcome
+///
+/// How a `sleep_cancellable` call ended, with the time actually spent asleep.
+///
+/// ## Variants:
+/// - `Completed`: The full duration elapsed
+/// - `Cancelled`: The token fired first; holds how long it slept until then
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SleepOutcome {
+    Completed(Duration),
+    Cancelled(Duration),
+}
+
+/// # Function: sleep_cancellable
+///
+/// Sleeps for `dur` unless `cancel` fires first, and reports how long it
+/// really slept. Handy for instrumentation: a shutdown that interrupts a
+/// backoff can log exactly how much of the wait was skipped.
+///
+/// ## Arguments:
+/// - `dur`: How long to sleep
+/// - `cancel`: Token that cuts the sleep short
+///
+/// ## Returns:
+/// - `SleepOutcome::Completed` or `SleepOutcome::Cancelled`, each with the
+///   measured time slept
+///
+/// ## Implementation Note:
+/// - Races a `DelayFuture` against the token with `select!`; on cancellation
+///   the `DelayFuture` is dropped (its timer thread finishes on its own)
+pub async fn sleep_cancellable(dur: Duration, cancel: &CancellationToken) -> SleepOutcome {
+    let start = Instant::now();
+
+    tokio::select! {
+        _ = DelayFuture::new(dur) => SleepOutcome::Completed(start.elapsed()),
+        _ = cancel.cancelled() => SleepOutcome::Cancelled(start.elapsed()),
+    }
+}
+
+/// # Struct: BlockingPanic
+///
+/// Returned by `spawn_blocking_checked` when the blocking closure panicked
+/// (or never ran because the runtime shut down first).
+///
+/// ## Fields:
+/// - `message`: The panic message, if the payload was a string
+#[derive(Error, Debug)]
+#[error("blocking task panicked: {message}")]
+pub struct BlockingPanic {
+    pub message: String,
+}
+
+/// # Function: spawn_blocking_checked
+///
+/// Runs a blocking closure on tokio's blocking thread pool and turns a
+/// panic inside it into an error. `DelayFuture` uses a bare
+/// `thread::spawn`, so a panic on its thread is silently lost and the
+/// future never completes; this wrapper reports it to the caller instead.
+///
+/// ## Arguments:
+/// - `f`: The blocking work to run
+///
+/// ## Returns:
+/// - `Result<T, BlockingPanic>`: The closure's result, or the panic message
+///
+/// ## Drop Safety:
+/// - Dropping the returned future detaches the work: the closure keeps
+///   running to completion on its thread and its result is discarded
+/// - Blocking code can't be interrupted, so keep closures short or have
+///   them check a cancellation flag
+pub async fn spawn_blocking_checked<F, T>(f: F) -> Result<T, BlockingPanic>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => Ok(value),
+        Err(error) if error.is_panic() => {
+            let payload = error.into_panic();
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "<non-string panic>".to_string());
+            Err(BlockingPanic { message })
+        }
+        Err(error) => Err(BlockingPanic {
+            message: error.to_string(),
+        }),
+    }
+}
+
 /// # Function: demonstrate_custom_future_usage
 ///
 /// This function shows various ways to use our custom DelayFuture,
@@ -247,6 +384,7 @@ impl Future for DelayFuture {
 /// - They can be used with combinators like join!
 /// - Multiple instances can run concurrently
 /// - They follow the same cancellation semantics as built-in futures
+#[cfg(not(target_arch = "wasm32"))]
 async fn demonstrate_custom_future_usage() {
     println!("\n=== Using Custom DelayFuture ===");
 
@@ -300,6 +438,7 @@ async fn demonstrate_custom_future_usage() {
 /// - Background threads continue running even if the future is dropped
 /// - Proper cancellation handling requires careful design
 /// - select! can be used to implement timeouts and cancellation
+#[cfg(not(target_arch = "wasm32"))]
 async fn demonstrate_future_cancellation() {
     println!("\n=== Future Cancellation ===");
 
@@ -342,6 +481,22 @@ async fn demonstrate_future_cancellation() {
     // Wait a bit to show the background thread continues
     tokio::time::sleep(Duration::from_millis(100)).await;
     println!("   Note: Background thread continues even after future is dropped");
+
+    // Example 3: Cancelling a sleep from outside and measuring it
+    println!("\n3. Cancellable sleep:");
+    let shutdown = CancellationToken::new();
+    let trigger = shutdown.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        trigger.cancel();
+    });
+
+    match sleep_cancellable(Duration::from_millis(300), &shutdown).await {
+        SleepOutcome::Completed(slept) => println!("   Slept the full {:?}", slept),
+        SleepOutcome::Cancelled(slept) => {
+            println!("   Shutdown interrupted the sleep after {:?}", slept)
+        }
+    }
 }
 
 /// # Function: demonstrate_poll_mechanics
@@ -353,6 +508,7 @@ async fn demonstrate_future_cancellation() {
 /// - Shows the raw polling interface that async/await abstracts away
 /// - Demonstrates how executors interact with futures
 /// - Illustrates the state transitions in future execution
+#[cfg(not(target_arch = "wasm32"))]
 async fn demonstrate_poll_mechanics() {
     println!("\n=== Low-Level Poll Mechanics ===");
 
@@ -387,6 +543,36 @@ async fn demonstrate_poll_mechanics() {
     println!("   Total polls: {}", poll_count);
 }
 
+/// # Function: demonstrate_blocking_work
+///
+/// Demonstrates moving blocking work off the async executor while still
+/// hearing about it when that work panics.
+///
+/// ## Key Learning Points:
+/// - Blocking calls belong on a dedicated thread, not in `poll`
+/// - A panic on another thread must be carried back explicitly
+/// - The runtime keeps running after a blocking task panics
+#[cfg(not(target_arch = "wasm32"))]
+async fn demonstrate_blocking_work() {
+    println!("\n=== Checked Blocking Work ===");
+
+    println!("1. Blocking work that succeeds:");
+    let result = spawn_blocking_checked(|| {
+        thread::sleep(Duration::from_millis(100)); // Stand-in for file or CPU work
+        (1..=1000u64).sum::<u64>()
+    })
+    .await;
+    println!("   Result: {:?}", result);
+
+    println!("\n2. Blocking work that panics:");
+    let result = spawn_blocking_checked(|| -> u64 { panic!("corrupted input file") }).await;
+    match result {
+        Ok(value) => println!("   Unexpected success: {}", value),
+        Err(error) => println!("   Caught: {}", error),
+    }
+    println!("   The runtime is still alive and running this line");
+}
+
 /// # Function: main
 ///
 /// The main function orchestrates all the demonstrations, showing
@@ -397,6 +583,8 @@ async fn demonstrate_poll_mechanics() {
 /// 2. Cancellation and timeout handling
 /// 3. Low-level polling mechanics
 /// 4. Integration with the broader async ecosystem
+/// 5. Checked blocking work on a thread pool
+#[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("🔧 Custom Future Implementation Tutorial");
@@ -412,24 +600,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Demonstrate low-level polling mechanics
     demonstrate_poll_mechanics().await;
 
+    // Run blocking work without losing panics
+    demonstrate_blocking_work().await;
+
     println!("\n✅ Custom Future Tutorial completed!");
     println!("Key takeaways:");
     println!("  - Futures are lazy - work starts only when polled");
     println!("  - poll() returns Ready when complete, Pending when waiting");
     println!("  - Waker enables efficient scheduling without busy-waiting");
     println!("  - Custom futures integrate seamlessly with async/await");
+    println!("  - select! with a CancellationToken makes any wait interruptible");
     println!("  - Proper state management is crucial for thread safety");
+    println!("  - Panics on helper threads must be reported, not silently lost");
 
     println!("\nNext: Try 'cargo run --bin combinators' to learn about future combinators");
 
     Ok(())
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
     use std::time::Instant;
-    use tokio_test;
 
     /// Test basic functionality of our custom DelayFuture
     #[tokio::test]
@@ -511,4 +703,115 @@ mod tests {
         let result = delay_future.await;
         assert!(result.contains("completed successfully"));
     }
+
+    /// Test that a DelayFuture handed between tasks always completes: each
+    /// poll may come from a different task, and the timer may fire at any
+    /// moment, yet the last task to poll must always be woken
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_delay_future_polled_from_alternating_tasks() {
+        /// Polls the delay once on a fresh task and hands it back
+        async fn poll_once_elsewhere(mut delay: DelayFuture) -> (DelayFuture, bool) {
+            tokio::spawn(async move {
+                let ready = std::future::poll_fn(|cx| {
+                    Poll::Ready(Pin::new(&mut delay).poll(cx).is_ready())
+                })
+                .await;
+                (delay, ready)
+            })
+            .await
+            .unwrap()
+        }
+
+        for round in 0..2000u64 {
+            // Delays short enough for the timer to fire between polls
+            let mut delay = DelayFuture::new(Duration::from_micros(round % 60));
+
+            let mut ready = false;
+            for _ in 0..round % 4 {
+                let (returned, now_ready) = poll_once_elsewhere(delay).await;
+                delay = returned;
+                ready |= now_ready;
+                if ready {
+                    break;
+                }
+            }
+            if ready {
+                continue;
+            }
+
+            // A task that didn't poll before waits for the rest; if a wake
+            // went to an earlier task instead, this would hang
+            let finish = tokio::spawn(delay);
+            let result = tokio::time::timeout(Duration::from_secs(2), finish)
+                .await
+                .unwrap_or_else(|_| panic!("wake lost in round {}", round))
+                .unwrap();
+            assert!(result.contains("completed successfully"));
+        }
+    }
+
+    /// Test that cancelling a sleep reports the time actually slept
+    #[tokio::test]
+    async fn test_sleep_cancellable_reports_elapsed() {
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+
+        match sleep_cancellable(Duration::from_millis(200), &cancel).await {
+            SleepOutcome::Cancelled(slept) => {
+                assert!(slept >= Duration::from_millis(50), "slept {:?}", slept);
+                assert!(slept < Duration::from_millis(100), "slept {:?}", slept);
+            }
+            outcome => panic!("expected cancellation, got {:?}", outcome),
+        }
+
+        // Without cancellation the full duration is slept
+        let outcome = sleep_cancellable(Duration::from_millis(30), &CancellationToken::new()).await;
+        assert!(
+            matches!(outcome, SleepOutcome::Completed(slept) if slept >= Duration::from_millis(30))
+        );
+    }
+
+    /// Test that a panicking blocking closure returns an error
+    #[tokio::test]
+    async fn test_spawn_blocking_checked_reports_panic() {
+        let result = spawn_blocking_checked(|| -> u32 { panic!("blocking failure") }).await;
+
+        let error = result.unwrap_err();
+        assert_eq!(error.message, "blocking failure");
+
+        // The runtime survived and can still run blocking work
+        assert_eq!(spawn_blocking_checked(|| 7).await.unwrap(), 7);
+    }
+
+    /// Test that the native thread-based timer completes without any async
+    /// runtime's timer, so the wasm32 backend hasn't displaced it
+    #[test]
+    fn test_native_timer_backend() {
+        let start = Instant::now();
+        let result = futures::executor::block_on(DelayFuture::new(Duration::from_millis(20)));
+
+        assert!(result.contains("completed successfully"));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
+
+/// Tests for the browser timer backend; run with
+/// `wasm-pack test --headless --firefox -- --bin custom_delay`
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Test that a DelayFuture driven by setTimeout completes
+    #[wasm_bindgen_test]
+    async fn test_delay_future_in_browser() {
+        let result = DelayFuture::new(Duration::from_millis(20)).await;
+        assert!(result.contains("completed successfully"));
+    }
 }
@@ -11,9 +11,22 @@
 //! 6. Collecting and handling multiple errors
 //! 7. Best practices for async error handling
 
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
 use anyhow::{Context, Result as AnyhowResult};
+use future_trait_tutorial::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use future_trait_tutorial::rate_limit::{RateLimited, TokenBucket};
+use future_trait_tutorial::resilience::{ResiliencePolicy, RetryPolicy};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::panic::UnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::Semaphore;
 use tokio::time::{sleep, timeout};
 
 /// # Enum: ApiError
@@ -74,6 +87,79 @@ pub enum DatabaseError {
     DatabaseLocked,
 }
 
+/// # Struct: ErrorCounters
+///
+/// Counts how often each `ApiError` variant occurs, to show which failure
+/// modes dominate. The counters are atomics, so one instance can be shared
+/// by every task without a lock.
+///
+/// ## Fields:
+/// - One `AtomicU64` per `ApiError` variant
+#[derive(Debug, Default)]
+pub struct ErrorCounters {
+    network: AtomicU64,
+    authentication: AtomicU64,
+    rate_limit: AtomicU64,
+    validation: AtomicU64,
+    timeout: AtomicU64,
+    service_unavailable: AtomicU64,
+}
+
+/// # Struct: ErrorCountsSnapshot
+///
+/// A point-in-time copy of `ErrorCounters`, one count per `ApiError` variant.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorCountsSnapshot {
+    pub network: u64,
+    pub authentication: u64,
+    pub rate_limit: u64,
+    pub validation: u64,
+    pub timeout: u64,
+    pub service_unavailable: u64,
+}
+
+impl ErrorCounters {
+    /// Creates counters that all start at zero; usable in a `static`.
+    pub const fn new() -> Self {
+        Self {
+            network: AtomicU64::new(0),
+            authentication: AtomicU64::new(0),
+            rate_limit: AtomicU64::new(0),
+            validation: AtomicU64::new(0),
+            timeout: AtomicU64::new(0),
+            service_unavailable: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts one occurrence of `error`'s variant.
+    pub fn record(&self, error: &ApiError) {
+        let counter = match error {
+            ApiError::NetworkError { .. } => &self.network,
+            ApiError::AuthenticationError { .. } => &self.authentication,
+            ApiError::RateLimitError { .. } => &self.rate_limit,
+            ApiError::ValidationError { .. } => &self.validation,
+            ApiError::TimeoutError { .. } => &self.timeout,
+            ApiError::ServiceUnavailable => &self.service_unavailable,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current counts.
+    pub fn snapshot(&self) -> ErrorCountsSnapshot {
+        ErrorCountsSnapshot {
+            network: self.network.load(Ordering::Relaxed),
+            authentication: self.authentication.load(Ordering::Relaxed),
+            rate_limit: self.rate_limit.load(Ordering::Relaxed),
+            validation: self.validation.load(Ordering::Relaxed),
+            timeout: self.timeout.load(Ordering::Relaxed),
+            service_unavailable: self.service_unavailable.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Every error produced by `simulate_api_request`, across all tasks
+static API_ERRORS: ErrorCounters = ErrorCounters::new();
+
 /// # Function: simulate_api_request
 ///
 /// Simulates an API request that can fail in various ways.
@@ -102,6 +188,19 @@ async fn simulate_api_request(
     endpoint: &str,
     should_succeed: bool,
     error_type: &str,
+) -> Result<String, ApiError> {
+    simulate_api_request_counted(endpoint, should_succeed, error_type, &API_ERRORS).await
+}
+
+/// # Function: simulate_api_request_counted
+///
+/// The body of `simulate_api_request`, recording any error into `counters`
+/// instead of the global `API_ERRORS`.
+async fn simulate_api_request_counted(
+    endpoint: &str,
+    should_succeed: bool,
+    error_type: &str,
+    counters: &ErrorCounters,
 ) -> Result<String, ApiError> {
     println!("🌐 Making API request to '{}'", endpoint);
 
@@ -132,6 +231,7 @@ async fn simulate_api_request(
         };
 
         println!("❌ API request to '{}' failed: {}", endpoint, error);
+        counters.record(&error);
         Err(error)
     }
 }
@@ -177,9 +277,841 @@ async fn simulate_database_operation(
             _ => DatabaseError::DatabaseLocked,
         };
 
-        println!("❌ Database operation '{}' failed: {}", operation, error);
-        Err(error)
+        println!("❌ Database operation '{}' failed: {}", operation, error);
+        Err(error)
+    }
+}
+
+/// # Struct: TxGuard
+///
+/// Rolls a transaction back when dropped, unless `commit` was called
+/// first. Because async code can be cancelled at any `.await` (by a
+/// timeout, a `select!`, or an aborted task), cleanup that must happen has
+/// to live in `Drop`: a rollback written after the `.await` would never run.
+///
+/// ## Fields:
+/// - `on_commit`: Run by `commit`
+/// - `on_rollback`: Run on drop while the transaction is still open
+pub struct TxGuard {
+    on_commit: Option<Box<dyn FnOnce() + Send>>,
+    on_rollback: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl TxGuard {
+    /// # Function: new
+    ///
+    /// Opens a transaction with the given commit and rollback actions.
+    pub fn new(
+        on_commit: impl FnOnce() + Send + 'static,
+        on_rollback: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        Self {
+            on_commit: Some(Box::new(on_commit)),
+            on_rollback: Some(Box::new(on_rollback)),
+        }
+    }
+
+    /// Commits the transaction; the rollback action will not run.
+    pub fn commit(mut self) {
+        self.on_rollback = None;
+        if let Some(commit) = self.on_commit.take() {
+            commit();
+        }
+    }
+}
+
+impl Drop for TxGuard {
+    fn drop(&mut self) {
+        if let Some(rollback) = self.on_rollback.take() {
+            rollback();
+        }
+    }
+}
+
+/// # Function: with_transaction
+///
+/// Runs `f` inside a transaction: commits if it returns `Ok`, rolls back
+/// if it returns `Err` or if this future is dropped before finishing.
+///
+/// ## Arguments:
+/// - `tx`: The open transaction; owned by this future, so it is dropped
+///   (rolling back) together with it
+/// - `f`: Creates the transaction's work
+///
+/// ## Returns:
+/// - The result of `f`, after committing or rolling back
+pub async fn with_transaction<F, Fut, T>(tx: TxGuard, f: F) -> Result<T, DatabaseError>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<T, DatabaseError>>,
+{
+    let result = f().await;
+    match result {
+        Ok(_) => tx.commit(),
+        Err(_) => drop(tx), // Rolls back
+    }
+    result
+}
+
+/// # Struct: DbPool
+///
+/// A resilient front for the simulated database. Every operation goes
+/// through a circuit breaker and a semaphore, so a database that keeps
+/// refusing connections is not hammered, and no more than `max_connections`
+/// operations run at once.
+///
+/// ## Fields:
+/// - `breaker`: Opens after repeated `ConnectionFailed` errors
+/// - `connections`: One permit per pooled connection
+/// - `healthy`: Whether the simulated database currently accepts connections
+pub struct DbPool {
+    breaker: CircuitBreaker,
+    connections: Semaphore,
+    healthy: AtomicBool,
+}
+
+impl DbPool {
+    /// # Function: new
+    ///
+    /// Creates a pool for a healthy database.
+    ///
+    /// ## Arguments:
+    /// - `max_connections`: Maximum number of concurrent operations
+    /// - `breaker`: When to open the circuit and how long to keep it open
+    pub fn new(max_connections: usize, breaker: CircuitBreakerConfig) -> Self {
+        Self {
+            breaker: CircuitBreaker::new(breaker),
+            connections: Semaphore::new(max_connections),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    /// Simulates the database going down or coming back up.
+    pub fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::SeqCst);
+    }
+
+    /// # Function: execute
+    ///
+    /// Runs an operation on a pooled connection. While the circuit is open
+    /// the call fails immediately without touching the database.
+    ///
+    /// ## Arguments:
+    /// - `op`: The database operation to perform
+    ///
+    /// ## Returns:
+    /// - `Result<String, DatabaseError>`: The operation's result, or
+    ///   `ConnectionFailed { details: "circuit open" }` when failing fast
+    pub async fn execute(&self, op: &str) -> Result<String, DatabaseError> {
+        if !self.breaker.allow_request() {
+            return Err(DatabaseError::ConnectionFailed {
+                details: "circuit open".to_string(),
+            });
+        }
+
+        // The semaphore is never closed, so acquiring can't fail
+        let _permit = self.connections.acquire().await.unwrap();
+        let healthy = self.healthy.load(Ordering::SeqCst);
+        let result = simulate_database_operation(op, healthy, "connection").await;
+
+        // Only connection problems say anything about the database's health;
+        // a bad query shouldn't trip the breaker
+        match &result {
+            Err(DatabaseError::ConnectionFailed { .. }) => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+}
+
+/// # Function: retry_within
+///
+/// Retries an operation until it succeeds or a total time budget runs out.
+/// Unlike a fixed attempt count, this bounds how long the caller waits
+/// ("keep retrying for up to 5 seconds") regardless of how fast each
+/// attempt fails.
+///
+/// ## Arguments:
+/// - `operation`: A function that creates the future to retry
+/// - `total_budget`: Maximum total time, including attempts and backoffs
+/// - `backoff`: Delay between consecutive attempts
+///
+/// ## Returns:
+/// - The first successful result, or the last error once another backoff
+///   would take the total elapsed time past `total_budget`
+///
+/// ## Note:
+/// - An attempt that is already running is not interrupted; combine with
+///   `timeout` if individual attempts may hang
+async fn retry_within<F, Fut, T, E>(
+    mut operation: F,
+    total_budget: Duration,
+    backoff: Duration,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let start = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        match operation().await {
+            Ok(result) => return Ok(result),
+            Err(error) => {
+                let elapsed = start.elapsed();
+                if elapsed + backoff >= total_budget {
+                    println!(
+                        "     Budget of {:?} exhausted after {} attempts ({:?})",
+                        total_budget, attempt, elapsed
+                    );
+                    return Err(error);
+                }
+
+                println!("     Attempt {} failed: {}, retrying...", attempt, error);
+                sleep(backoff).await;
+            }
+        }
+    }
+}
+
+/// # Function: retry_while
+///
+/// Retries a failing operation for as long as a shared flag allows it, so
+/// an operator (or a shutdown handler) can stop a retry loop from outside
+/// without cancelling the attempt that is running.
+///
+/// ## Arguments:
+/// - `make`: A function that creates the future to retry
+/// - `enabled`: Retries continue while this is `true`
+/// - `backoff`: Delay between consecutive attempts
+///
+/// ## Returns:
+/// - The first successful result, or the last error once `enabled` is
+///   found to be `false`
+///
+/// ## Note:
+/// - The first attempt is always made: the flag controls retries only
+/// - The flag is checked after every failure and again after each backoff,
+///   so turning it off never costs an extra attempt
+async fn retry_while<F, Fut, T, E>(
+    mut make: F,
+    enabled: Arc<AtomicBool>,
+    backoff: Duration,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        let error = match make().await {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+        if !enabled.load(Ordering::SeqCst) {
+            println!("     Retries disabled after {} attempts", attempt);
+            return Err(error);
+        }
+
+        println!("     Attempt {} failed: {}, retrying...", attempt, error);
+        sleep(backoff).await;
+        if !enabled.load(Ordering::SeqCst) {
+            println!("     Retries disabled after {} attempts", attempt);
+            return Err(error);
+        }
+    }
+}
+
+/// # Struct: PollTimeout
+///
+/// Returned by `poll_until` when the condition still did not hold once the
+/// time budget ran out.
+///
+/// ## Fields:
+/// - `polls`: How many times the condition was checked
+/// - `waited`: The budget that was exhausted
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Condition not met after {polls} polls in {waited:?}")]
+pub struct PollTimeout {
+    pub polls: usize,
+    pub waited: Duration,
+}
+
+/// # Function: poll_until
+///
+/// Waits for a condition that can only be observed by asking, such as
+/// "is the job done yet?". Checks are spaced out with exponential backoff,
+/// so a slow job costs few requests while a fast one is noticed quickly.
+///
+/// ## Arguments:
+/// - `check`: Creates the future that fetches the current state
+/// - `is_done`: Whether a fetched state is the one we are waiting for
+/// - `backoff`: Delay after the first check; it doubles after every check
+/// - `max_total`: Maximum total time, including the checks themselves
+///
+/// ## Returns:
+/// - The first state for which `is_done` holds, or `PollTimeout` if the
+///   budget runs out first
+///
+/// ## Note:
+/// - The last delay is shortened so that one final check happens right at
+///   the deadline
+/// - A check that is already running is not interrupted
+pub async fn poll_until<F, Fut, T>(
+    mut check: F,
+    is_done: impl Fn(&T) -> bool,
+    backoff: Duration,
+    max_total: Duration,
+) -> Result<T, PollTimeout>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = T>,
+{
+    let deadline = tokio::time::Instant::now() + max_total;
+    let mut delay = backoff;
+    let mut polls = 0;
+
+    loop {
+        let state = check().await;
+        polls += 1;
+        if is_done(&state) {
+            return Ok(state);
+        }
+
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        if remaining.is_zero() {
+            return Err(PollTimeout {
+                polls,
+                waited: max_total,
+            });
+        }
+        sleep(delay.min(remaining)).await;
+        delay = delay.saturating_mul(2);
+    }
+}
+
+/// # Trait: Retryable
+///
+/// Lets an error type decide whether the operation that produced it is
+/// worth retrying, so retry loops don't need to hardcode which errors are
+/// transient.
+///
+/// ## Methods:
+/// - `should_retry`: `false` for fatal errors that would fail again
+/// - `retry_delay`: How long the error asks the caller to wait, if it knows
+///   (e.g. a rate limit's `retry_after`); `None` uses the caller's default
+pub trait Retryable {
+    fn should_retry(&self) -> bool;
+    fn retry_delay(&self) -> Option<Duration>;
+}
+
+impl Retryable for ApiError {
+    fn should_retry(&self) -> bool {
+        match self {
+            ApiError::NetworkError { .. }
+            | ApiError::RateLimitError { .. }
+            | ApiError::TimeoutError { .. }
+            | ApiError::ServiceUnavailable => true,
+            // Retrying with the same credentials or input fails the same way
+            ApiError::AuthenticationError { .. } | ApiError::ValidationError { .. } => false,
+        }
+    }
+
+    fn retry_delay(&self) -> Option<Duration> {
+        match self {
+            ApiError::RateLimitError { retry_after } => Some(Duration::from_secs(*retry_after)),
+            _ => None,
+        }
+    }
+}
+
+impl Retryable for DatabaseError {
+    fn should_retry(&self) -> bool {
+        match self {
+            DatabaseError::ConnectionFailed { .. }
+            | DatabaseError::TransactionFailed { .. }
+            | DatabaseError::DatabaseLocked => true,
+            // A broken query stays broken
+            DatabaseError::QueryFailed { .. } => false,
+        }
+    }
+
+    fn retry_delay(&self) -> Option<Duration> {
+        match self {
+            // Locks are usually released quickly
+            DatabaseError::DatabaseLocked => Some(Duration::from_millis(20)),
+            _ => None,
+        }
+    }
+}
+
+/// # Function: retry_classified
+///
+/// Retries an operation only while its errors say they are retryable,
+/// waiting as long as each error asks for.
+///
+/// ## Arguments:
+/// - `operation`: A function that creates the future to retry
+/// - `max_attempts`: Upper bound on attempts, including the first
+/// - `default_delay`: Wait used when an error has no `retry_delay`
+///
+/// ## Returns:
+/// - The first success, or the first fatal error, or the last error once
+///   `max_attempts` is reached
+async fn retry_classified<F, Fut, T, E>(
+    mut operation: F,
+    max_attempts: usize,
+    default_delay: Duration,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: Retryable + std::fmt::Display,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match operation().await {
+            Ok(result) => return Ok(result),
+            Err(error) if !error.should_retry() => {
+                println!("     Attempt {} failed fatally: {}", attempt, error);
+                return Err(error);
+            }
+            Err(error) if attempt >= max_attempts => return Err(error),
+            Err(error) => {
+                let delay = error.retry_delay().unwrap_or(default_delay);
+                println!(
+                    "     Attempt {} failed: {}, retrying in {:?}",
+                    attempt, error, delay
+                );
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// # Struct: BudgetExhausted
+///
+/// Returned when a step of a multi-step workflow runs out of time budget.
+///
+/// ## Fields:
+/// - `total`: The budget the whole workflow was given
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Time budget of {total:?} exhausted")]
+pub struct BudgetExhausted {
+    pub total: Duration,
+}
+
+/// # Struct: Budget
+///
+/// A deadline shared by the sequential steps of a workflow. Instead of
+/// giving every step its own fixed timeout (which can add up to far more
+/// than the caller is willing to wait), each step runs against whatever
+/// time is left, so a slow early step leaves less for the later ones and
+/// the workflow as a whole never overruns.
+///
+/// ## Fields:
+/// - `total`: The time the budget was created with
+/// - `deadline`: When the budget runs out
+///
+/// ## Note:
+/// - Uses `tokio::time::Instant`, so tests can drive it with a paused clock
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    total: Duration,
+    deadline: tokio::time::Instant,
+}
+
+impl Budget {
+    /// Starts a budget of `total` from now.
+    pub fn new(total: Duration) -> Self {
+        Self {
+            total,
+            deadline: tokio::time::Instant::now() + total,
+        }
+    }
+
+    /// Returns the time left before the deadline, or zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.deadline
+            .saturating_duration_since(tokio::time::Instant::now())
+    }
+
+    /// # Function: split
+    ///
+    /// Divides the remaining time into `steps` consecutive sub-budgets of
+    /// equal length. Sub-budget `i` ends where slot `i` ends, so a step that
+    /// finishes early hands its unused time to the next one, while a slow
+    /// step is cut off before it eats into the later slots.
+    ///
+    /// ## Arguments:
+    /// - `steps`: Number of sub-budgets; must be greater than zero
+    ///
+    /// ## Returns:
+    /// - `Vec<Budget>`: One budget per step, in order
+    pub fn split(&self, steps: u32) -> Vec<Budget> {
+        assert!(steps > 0, "a budget must be split into at least one step");
+
+        let now = tokio::time::Instant::now();
+        let share = self.remaining() / steps;
+        (1..=steps)
+            .map(|step| Budget {
+                total: self.total,
+                deadline: (now + share * step).min(self.deadline),
+            })
+            .collect()
+    }
+
+    /// # Function: run
+    ///
+    /// Runs one step, cancelling it if it outlives the budget.
+    ///
+    /// ## Arguments:
+    /// - `step`: The future for this step
+    ///
+    /// ## Returns:
+    /// - The step's output, or `BudgetExhausted` if the deadline hit first;
+    ///   with `?` this converts into `anyhow::Error` like any other failure
+    pub async fn run<F: std::future::Future>(&self, step: F) -> Result<F::Output, BudgetExhausted> {
+        timeout(self.remaining(), step)
+            .await
+            .map_err(|_| BudgetExhausted { total: self.total })
+    }
+}
+
+/// # Struct: PanickedTooManyTimes
+///
+/// Returned by `retry_on_panic` when every attempt panicked.
+///
+/// ## Fields:
+/// - `attempts`: How many attempts were made
+/// - `last_message`: The panic message of the final attempt, if it was a string
+#[derive(Error, Debug)]
+#[error("Operation panicked {attempts} times (last panic: {last_message})")]
+pub struct PanickedTooManyTimes {
+    pub attempts: usize,
+    pub last_message: String,
+}
+
+/// # Function: retry_on_panic
+///
+/// Runs a future and, if it panics, runs a freshly created one again.
+/// A panic inside an awaited future normally unwinds through the caller;
+/// `FutureExt::catch_unwind` turns it into an `Err` we can react to.
+///
+/// ## Arguments:
+/// - `make`: Creates a new attempt of the operation
+/// - `max`: How many times to retry after a panic (so up to `max + 1` attempts)
+///
+/// ## Returns:
+/// - The value of the first attempt that didn't panic, or
+///   `PanickedTooManyTimes` once all attempts have panicked
+///
+/// ## Note:
+/// - The future must be `UnwindSafe`: state it shares with the caller could
+///   be left half-updated by the panic. Wrap it in `AssertUnwindSafe` only
+///   if you know that shared state stays consistent.
+/// - The default panic hook still prints each panic to stderr.
+pub async fn retry_on_panic<F, Fut, T>(make: F, max: usize) -> Result<T, PanickedTooManyTimes>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = T> + UnwindSafe,
+{
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        match make().catch_unwind().await {
+            Ok(value) => return Ok(value),
+            Err(payload) => {
+                let last_message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "<non-string panic>".to_string());
+
+                if attempts > max {
+                    return Err(PanickedTooManyTimes {
+                        attempts,
+                        last_message,
+                    });
+                }
+                println!(
+                    "     Attempt {} panicked: {}, retrying...",
+                    attempts, last_message
+                );
+            }
+        }
+    }
+}
+
+/// # Enum: SourceError
+///
+/// Why a single `DataSource` could not provide its data.
+#[derive(Error, Debug)]
+pub enum SourceError {
+    #[error(transparent)]
+    Api(#[from] ApiError),
+
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+/// # Trait: DataSource
+///
+/// One place a `FallbackChain` can get its data from.
+///
+/// ## Note:
+/// `fetch` is the object-safe spelling of `async fn fetch(&self)`: an
+/// `async fn` in a trait can't be called through `dyn DataSource`, so the
+/// future is boxed instead. Implementations just wrap an async block in
+/// `Box::pin`.
+pub trait DataSource<T>: Send + Sync {
+    /// A short name for logs and error reports
+    fn name(&self) -> &str;
+
+    /// Fetches the data from this source
+    fn fetch(&self) -> BoxFuture<'_, Result<T, SourceError>>;
+}
+
+/// # Struct: FallbackFailed
+///
+/// Returned by `FallbackChain::resolve` when every source failed.
+///
+/// ## Fields:
+/// - `failures`: Each source's name and error, in the order they were tried
+#[derive(Error, Debug)]
+#[error("All {} data sources failed: {}", failures.len(), describe_failures(failures))]
+pub struct FallbackFailed {
+    pub failures: Vec<(String, SourceError)>,
+}
+
+fn describe_failures(failures: &[(String, SourceError)]) -> String {
+    failures
+        .iter()
+        .map(|(name, error)| format!("{} ({})", name, error))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// # Struct: FallbackChain
+///
+/// Tries a list of interchangeable data sources in order and returns the
+/// first success. Sources are trait objects, so an API, a database and a
+/// cache can sit in the same chain.
+///
+/// ## Fields:
+/// - `sources`: The sources, most preferred first
+///
+/// ## Example:
+/// ```rust
+/// let chain = FallbackChain::new()
+///     .with_source(ApiSource::new("primary_api", false, "network"))
+///     .with_source(CacheSource { query: "SELECT FROM cache" });
+/// let data = chain.resolve().await?;
+/// ```
+pub struct FallbackChain<T> {
+    sources: Vec<Box<dyn DataSource<T>>>,
+}
+
+impl<T> FallbackChain<T> {
+    /// Creates an empty chain; `resolve` on it fails with no failures listed.
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+        }
+    }
+
+    /// Appends a source, tried after all sources added before it.
+    pub fn with_source(mut self, source: impl DataSource<T> + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// # Function: resolve
+    ///
+    /// Tries each source in order, stopping at the first success.
+    ///
+    /// ## Returns:
+    /// - The first successful value, or `FallbackFailed` listing every
+    ///   source's error if none succeeded
+    pub async fn resolve(&self) -> Result<T, FallbackFailed> {
+        let mut failures = Vec::new();
+
+        for source in &self.sources {
+            match source.fetch().await {
+                Ok(value) => {
+                    println!("   {} succeeded", source.name());
+                    return Ok(value);
+                }
+                Err(error) => {
+                    println!("   {} failed: {}", source.name(), error);
+                    failures.push((source.name().to_string(), error));
+                }
+            }
+        }
+
+        Err(FallbackFailed { failures })
+    }
+}
+
+impl<T> Default for FallbackChain<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Struct: ApiSource
+///
+/// A `DataSource` backed by `simulate_api_request`.
+struct ApiSource {
+    endpoint: &'static str,
+    should_succeed: bool,
+    error_type: &'static str,
+}
+
+impl ApiSource {
+    fn new(endpoint: &'static str, should_succeed: bool, error_type: &'static str) -> Self {
+        Self {
+            endpoint,
+            should_succeed,
+            error_type,
+        }
+    }
+}
+
+impl DataSource<String> for ApiSource {
+    fn name(&self) -> &str {
+        self.endpoint
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<String, SourceError>> {
+        Box::pin(async move {
+            let data =
+                simulate_api_request(self.endpoint, self.should_succeed, self.error_type).await?;
+            Ok(data)
+        })
+    }
+}
+
+/// # Struct: CacheSource
+///
+/// A `DataSource` backed by a (simulated) cache table in the database.
+struct CacheSource {
+    query: &'static str,
+}
+
+impl DataSource<String> for CacheSource {
+    fn name(&self) -> &str {
+        "cache"
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<String, SourceError>> {
+        Box::pin(async move { Ok(simulate_database_operation(self.query, true, "").await?) })
+    }
+}
+
+/// # Struct: PartialResults
+///
+/// The outcome of `join_graceful`: every named operation ends up in
+/// exactly one of the two maps.
+///
+/// ## Fields:
+/// - `successes`: Values of the operations that succeeded, by name
+/// - `failures`: Errors of the operations that failed, by name
+#[derive(Debug)]
+pub struct PartialResults<T, E> {
+    pub successes: HashMap<String, T>,
+    pub failures: HashMap<String, E>,
+}
+
+/// # Struct: MissingRequired
+///
+/// Returned by `PartialResults::require` when critical operations did not
+/// succeed.
+///
+/// ## Fields:
+/// - `missing`: The required names without a success, in the order asked for
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Required sources did not succeed: {}", missing.join(", "))]
+pub struct MissingRequired {
+    pub missing: Vec<String>,
+}
+
+impl<T, E> PartialResults<T, E> {
+    /// # Function: require
+    ///
+    /// Checks that the critical operations succeeded, so a caller can
+    /// degrade gracefully when optional ones fail but still give up when
+    /// an essential one does.
+    ///
+    /// ## Arguments:
+    /// - `names`: Operations that must have succeeded
+    ///
+    /// ## Returns:
+    /// - `Ok(())`, or `MissingRequired` listing each name that failed or was
+    ///   never run
+    pub fn require(&self, names: &[&str]) -> Result<(), MissingRequired> {
+        let missing: Vec<String> = names
+            .iter()
+            .filter(|name| !self.successes.contains_key(**name))
+            .map(|name| name.to_string())
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingRequired { missing })
+        }
+    }
+}
+
+/// # Function: join_graceful
+///
+/// Runs named fallible operations concurrently and waits for all of them,
+/// sorting the results into successes and failures instead of stopping at
+/// the first error like `try_join!`.
+///
+/// ## Arguments:
+/// - `named`: `(name, future)` pairs; names should be unique, since a
+///   later duplicate overwrites an earlier one
+///
+/// ## Returns:
+/// - `PartialResults` with one entry per name
+pub async fn join_graceful<F, T, E>(named: Vec<(&str, F)>) -> PartialResults<T, E>
+where
+    F: std::future::Future<Output = Result<T, E>>,
+{
+    let (names, futures): (Vec<&str>, Vec<F>) = named.into_iter().unzip();
+    let outcomes = futures::future::join_all(futures).await;
+
+    let mut results = PartialResults {
+        successes: HashMap::new(),
+        failures: HashMap::new(),
+    };
+    for (name, outcome) in names.into_iter().zip(outcomes) {
+        match outcome {
+            Ok(value) => {
+                results.successes.insert(name.to_string(), value);
+            }
+            Err(error) => {
+                results.failures.insert(name.to_string(), error);
+            }
+        }
     }
+    results
 }
 
 /// # Function: demonstrate_basic_error_handling
@@ -476,6 +1408,134 @@ async fn demonstrate_timeout_handling() {
     }
     let elapsed = start.elapsed();
     println!("   Total retry time: {:?}", elapsed);
+
+    // Example 4: Retrying within a total time budget
+    println!("\n4. Retry within a time budget:");
+    let start = Instant::now();
+    match retry_within(
+        || simulate_api_request("flaky_service", false, "network"),
+        Duration::from_millis(500),
+        Duration::from_millis(50),
+    )
+    .await
+    {
+        Ok(data) => println!("   Retry succeeded: {}", data),
+        Err(error) => println!("   Gave up with last error: {}", error),
+    }
+    println!("   Total retry time: {:?} (budget 500ms)", start.elapsed());
+
+    // Example 5: One deadline shared by every step of a workflow
+    println!("\n5. Multi-step workflow within a time budget:");
+
+    /// # Function: fetch_user_profile_within
+    ///
+    /// The same three steps as `fetch_user_profile`, each wrapped in
+    /// whatever is left of a single budget.
+    async fn fetch_user_profile_within(budget: Budget) -> AnyhowResult<String> {
+        println!("   Auth: {:?} left", budget.remaining());
+        budget
+            .run(simulate_api_request("auth", true, ""))
+            .await?
+            .context("Authentication step failed")?;
+
+        println!("   User data: {:?} left", budget.remaining());
+        let user_data = budget
+            .run(simulate_api_request("user_data", true, ""))
+            .await?
+            .context("Failed to fetch user data")?;
+
+        println!("   Preferences: {:?} left", budget.remaining());
+        let preferences = budget
+            .run(simulate_database_operation("SELECT preferences", true, ""))
+            .await
+            .context("Preferences step ran out of time")??;
+
+        Ok(format!("{} with {}", user_data, preferences))
+    }
+
+    let start = Instant::now();
+    match fetch_user_profile_within(Budget::new(Duration::from_millis(250))).await {
+        Ok(profile) => println!("   Profile: {}", profile),
+        Err(error) => println!("   Failed: {}", error),
+    }
+    println!("   Total time: {:?} (budget 250ms)", start.elapsed());
+
+    // Example 6: Let the error decide whether to retry
+    println!("\n6. Retrying only retryable errors:");
+    let mut locked = 2;
+    let result = retry_classified(
+        || {
+            locked -= 1;
+            simulate_database_operation("UPDATE stock", locked < 0, "locked")
+        },
+        5,
+        Duration::from_millis(100),
+    )
+    .await;
+    println!("   Locked database: {:?}", result);
+
+    let result = retry_classified(
+        || simulate_api_request("account", false, "auth"),
+        5,
+        Duration::from_millis(100),
+    )
+    .await;
+    println!(
+        "   Bad credentials: {:?}",
+        result.map_err(|e| e.to_string())
+    );
+
+    // Example 7: Poll a background job until it finishes
+    println!("\n7. Polling until a job is done:");
+    let job_started = Instant::now();
+    let job_status = || async move {
+        if job_started.elapsed() >= Duration::from_millis(300) {
+            "done"
+        } else {
+            "running"
+        }
+    };
+    let result = poll_until(
+        job_status,
+        |status| *status == "done",
+        Duration::from_millis(50),
+        Duration::from_secs(2),
+    )
+    .await;
+    println!(
+        "   Job status: {:?} after {:?}",
+        result,
+        job_started.elapsed()
+    );
+
+    let result = poll_until(
+        || async { "running" },
+        |status| *status == "done",
+        Duration::from_millis(50),
+        Duration::from_millis(200),
+    )
+    .await;
+    println!("   Stuck job: {}", result.unwrap_err());
+
+    // Example 8: Let an operator switch retries off
+    println!("\n8. Retrying until retries are switched off:");
+    let retries_enabled = Arc::new(AtomicBool::new(true));
+    let operator = {
+        let retries_enabled = retries_enabled.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(250)).await;
+            println!("     Operator: disabling retries");
+            retries_enabled.store(false, Ordering::SeqCst);
+        })
+    };
+    let result = retry_while(
+        || simulate_api_request("degraded_service", false, "network"),
+        retries_enabled,
+        Duration::from_millis(100),
+    )
+    .await;
+    let _ = operator.await;
+    println!("   Result: {:?}", result.map_err(|e| e.to_string()));
 }
 
 /// # Function: demonstrate_concurrent_error_handling
@@ -516,52 +1576,41 @@ async fn demonstrate_concurrent_error_handling() {
     println!("\n2. Collecting all results (partial success):");
     let start = Instant::now();
 
-    let (result1, result2, result3) = tokio::join!(
-        simulate_api_request("service1", true, ""),
-        simulate_api_request("service2", false, "auth"),
-        simulate_api_request("service3", true, ""),
-    );
+    let results = join_graceful(vec![
+        ("service1", simulate_api_request("service1", true, "")),
+        ("service2", simulate_api_request("service2", false, "auth")),
+        ("service3", simulate_api_request("service3", true, "")),
+    ])
+    .await;
 
     let elapsed = start.elapsed();
 
-    let mut successes = Vec::new();
-    let mut failures = Vec::new();
-
-    match result1 {
-        Ok(data) => successes.push(("service1", data)),
-        Err(error) => failures.push(("service1", error)),
-    }
-
-    match result2 {
-        Ok(data) => successes.push(("service2", data)),
-        Err(error) => failures.push(("service2", error)),
-    }
-
-    match result3 {
-        Ok(data) => successes.push(("service3", data)),
-        Err(error) => failures.push(("service3", error)),
-    }
-
     println!("   Completed in {:?}", elapsed);
-    println!("   Successes: {}", successes.len());
-    for (service, data) in successes {
+    println!("   Successes: {}", results.successes.len());
+    for (service, data) in &results.successes {
         println!("     {}: {}", service, data);
     }
 
-    println!("   Failures: {}", failures.len());
-    for (service, error) in failures {
+    println!("   Failures: {}", results.failures.len());
+    for (service, error) in &results.failures {
         println!("     {}: {}", service, error);
     }
 
+    // service2 is optional, so the page can still be rendered
+    match results.require(&["service1", "service3"]) {
+        Ok(()) => println!("   All critical services answered; degrading gracefully"),
+        Err(error) => println!("   Cannot continue: {}", error),
+    }
+
     // Example 3: Using FuturesUnordered for streaming results
     println!("\n3. Streaming results with error handling:");
     use futures::stream::{FuturesUnordered, StreamExt};
 
-    let mut futures: FuturesUnordered<
-        std::pin::Pin<
-            Box<dyn std::future::Future<Output = (&str, Result<String, ApiError>)> + Send>,
-        >,
-    > = FuturesUnordered::new();
+    type NamedRequest<'a> = std::pin::Pin<
+        Box<dyn std::future::Future<Output = (&'a str, Result<String, ApiError>)> + Send>,
+    >;
+
+    let mut futures: FuturesUnordered<NamedRequest> = FuturesUnordered::new();
     futures.push(Box::pin(async {
         ("api1", simulate_api_request("api1", true, "").await)
     }));
@@ -621,44 +1670,14 @@ async fn demonstrate_error_recovery_strategies() {
     // Example 1: Simple fallback chain
     println!("1. Fallback chain:");
 
-    /// # Function: fetch_data_with_fallback
-    ///
-    /// Attempts to fetch data from multiple sources, falling back
-    /// to alternatives if the primary source fails.
-    async fn fetch_data_with_fallback() -> AnyhowResult<String> {
-        // Try primary source
-        match simulate_api_request("primary_api", false, "network").await {
-            Ok(data) => {
-                println!("   Primary source succeeded");
-                return Ok(format!("Primary: {}", data));
-            }
-            Err(error) => {
-                println!("   Primary source failed: {}", error);
-            }
-        }
-
-        // Try secondary source
-        match simulate_api_request("secondary_api", false, "auth").await {
-            Ok(data) => {
-                println!("   Secondary source succeeded");
-                return Ok(format!("Secondary: {}", data));
-            }
-            Err(error) => {
-                println!("   Secondary source failed: {}", error);
-            }
-        }
-
-        // Try cache as last resort
-        match simulate_database_operation("SELECT FROM cache", true, "").await {
-            Ok(data) => {
-                println!("   Cache fallback succeeded");
-                Ok(format!("Cached: {}", data))
-            }
-            Err(error) => Err(anyhow::Error::new(error).context("All data sources failed")),
-        }
-    }
+    let chain = FallbackChain::new()
+        .with_source(ApiSource::new("primary_api", false, "network"))
+        .with_source(ApiSource::new("secondary_api", false, "auth"))
+        .with_source(CacheSource {
+            query: "SELECT FROM cache",
+        });
 
-    match fetch_data_with_fallback().await {
+    match chain.resolve().await {
         Ok(data) => println!("   Final result: {}", data),
         Err(error) => println!("   Complete failure: {}", error),
     }
@@ -795,6 +1814,172 @@ async fn demonstrate_error_recovery_strategies() {
             sleep(Duration::from_millis(200)).await;
         }
     }
+
+    // Example 4: Surviving a buggy operation that panics
+    println!("\n4. Retrying after a panic:");
+
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let result = retry_on_panic(
+        || {
+            let calls = calls.clone();
+            // tokio's Sleep isn't UnwindSafe; the only shared state here is
+            // an atomic counter, so asserting unwind safety is sound
+            std::panic::AssertUnwindSafe(async move {
+                let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                sleep(Duration::from_millis(20)).await;
+                if call == 1 {
+                    panic!("buggy parser hit an unexpected token");
+                }
+                format!("parsed on call {}", call)
+            })
+        },
+        2,
+    )
+    .await;
+
+    match result {
+        Ok(value) => println!("   Recovered: {}", value),
+        Err(error) => println!("   Gave up: {}", error),
+    }
+
+    // Example 5: A database pool that stops calling a dead database
+    println!("\n5. Database pool behind a circuit breaker:");
+
+    let pool = DbPool::new(
+        4,
+        CircuitBreakerConfig {
+            failure_threshold: 2,
+            reset_timeout: Duration::from_millis(300),
+        },
+    );
+    pool.set_healthy(false);
+
+    // Alerting hangs off the breaker's state changes, not off each call
+    let mut changes = pool.breaker.subscribe();
+    let alerts = tokio::spawn(async move {
+        while let Ok(change) = changes.recv().await {
+            println!("   🚨 Circuit {} -> {}", change.from, change.to);
+            if change.to == CircuitState::Closed {
+                break;
+            }
+        }
+    });
+
+    for i in 1..=4 {
+        let start = Instant::now();
+        match pool.execute("SELECT * FROM orders").await {
+            Ok(rows) => println!("   Call {}: {}", i, rows),
+            Err(error) => println!("   Call {}: {} (after {:?})", i, error, start.elapsed()),
+        }
+    }
+
+    println!("   Database recovers; waiting for the reset timeout...");
+    pool.set_healthy(true);
+    sleep(Duration::from_millis(300)).await;
+    match pool.execute("SELECT * FROM orders").await {
+        Ok(rows) => println!("   Trial call: {}", rows),
+        Err(error) => println!("   Trial call: {}", error),
+    }
+    let _ = alerts.await;
+
+    // Example 6: Rolling back on failure and on cancellation
+    println!("\n6. Transactions that roll back when cancelled:");
+    let transaction = |name: &'static str| {
+        TxGuard::new(
+            move || println!("   💾 {}: committed", name),
+            move || println!("   ↩️  {}: rolled back", name),
+        )
+    };
+
+    let _ = with_transaction(transaction("transfer"), || {
+        simulate_database_operation("UPDATE accounts", true, "")
+    })
+    .await;
+    let _ = with_transaction(transaction("import"), || {
+        simulate_database_operation("INSERT INTO orders", false, "transaction")
+    })
+    .await;
+    // The timeout drops the transaction future before it finishes
+    let slow = with_transaction(transaction("report"), || async {
+        sleep(Duration::from_secs(5)).await;
+        Ok("report")
+    });
+    if timeout(Duration::from_millis(100), slow).await.is_err() {
+        println!("   report: timed out");
+    }
+
+    // Example 7: Protect the database from a burst of queries
+    println!("\n7. Rate-limiting the database pool to 5 queries per second:");
+    let pool = RateLimited::new(
+        DbPool::new(
+            4,
+            CircuitBreakerConfig {
+                failure_threshold: 3,
+                reset_timeout: Duration::from_secs(1),
+            },
+        ),
+        TokenBucket::new(5.0, 2),
+    );
+    let start = Instant::now();
+    for i in 1..=4 {
+        let result = pool.run(|db| db.execute("SELECT * FROM stats")).await;
+        println!(
+            "   Query {} at {:?}: {}",
+            i,
+            start.elapsed(),
+            if result.is_ok() { "ok" } else { "failed" }
+        );
+    }
+
+    // Example 8: Retry, timeout and circuit breaker as one policy
+    println!("\n8. One policy for retries, timeouts and the circuit breaker:");
+    let breaker = Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+        failure_threshold: 2,
+        reset_timeout: Duration::from_secs(1),
+    }));
+    let policy = ResiliencePolicy::new()
+        .circuit_breaker(Arc::clone(&breaker))
+        .timeout(Duration::from_millis(100))
+        .retry(RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(20),
+        });
+    let mut changes = breaker.subscribe();
+    let attempts = AtomicU64::new(0);
+    let result = policy
+        .execute(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                // The first attempt hangs and is cut off by the timeout
+                0 => {
+                    sleep(Duration::from_secs(5)).await;
+                    Ok("too late".to_string())
+                }
+                1 => simulate_api_request("/orders", false, "network").await,
+                _ => simulate_api_request("/orders", true, "").await,
+            }
+        })
+        .await;
+    match result {
+        Ok(data) => println!(
+            "   Succeeded on attempt {}: {}",
+            attempts.load(Ordering::SeqCst),
+            data
+        ),
+        Err(error) => println!("   Failed: {}", error),
+    }
+    for call in 1..=3 {
+        let result = policy
+            .execute(|| simulate_api_request("/orders", false, "network"))
+            .await;
+        match result {
+            Ok(_) => println!("   Call {}: ok", call),
+            Err(error) => println!("   Call {}: {}", call, error),
+        }
+        while let Ok(change) = changes.try_recv() {
+            println!("   🔌 Circuit {} -> {}", change.from, change.to);
+        }
+    }
+    println!("   Breaker is now {}", breaker.state());
 }
 
 /// # Function: main
@@ -807,7 +1992,7 @@ async fn demonstrate_error_recovery_strategies() {
 /// 2. Error propagation with ? operator
 /// 3. Timeout handling and retry logic
 /// 4. Concurrent error handling strategies
-/// 5. Error recovery and resilience patterns
+/// 5. Error recovery and resilience patterns (including panics)
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("🚨 Error Handling in Async Rust Tutorial");
@@ -829,15 +2014,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Error recovery strategies
     demonstrate_error_recovery_strategies().await;
 
+    println!("\n📊 API errors produced during this tutorial:");
+    println!("   {:?}", API_ERRORS.snapshot());
+
     println!("\n✅ Error Handling Tutorial completed!");
     println!("Key takeaways:");
     println!("  - Use custom error types with thiserror for better error handling");
     println!("  - The ? operator makes error propagation clean and readable");
     println!("  - Always handle timeouts in async operations");
+    println!("  - Share one time budget across sequential steps");
+    println!("  - Let errors classify themselves as retryable or fatal");
+    println!("  - Poll with exponential backoff to wait for slow jobs cheaply");
+    println!("  - A shared flag lets operators stop a retry loop from outside");
     println!("  - Consider fail-fast vs. collect-all strategies for concurrent operations");
+    println!("  - join_graceful + require: tolerate optional failures, not critical ones");
     println!("  - Implement fallbacks and graceful degradation for resilience");
     println!("  - Use circuit breakers for failing external services");
+    println!("  - Subscribe to breaker state changes to alert on open circuits");
+    println!("  - Wrap any dependency in RateLimited to cap how often it is called");
+    println!("  - ResiliencePolicy layers breaker, retries and per-attempt timeouts in order");
+    println!("  - catch_unwind lets you retry futures that panic");
+    println!("  - Put cleanup in Drop so it also runs when a future is cancelled");
     println!("  - Add context to errors to make debugging easier");
+    println!("  - Count errors per variant to see which failure modes dominate");
 
     println!("\nNext: Try 'cargo run --bin real_world' to see real-world async patterns");
 
@@ -847,7 +2046,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_test;
 
     /// Test basic error handling
     #[tokio::test]
@@ -864,6 +2062,287 @@ mod tests {
         assert!(matches!(result, Err(ApiError::AuthenticationError { .. })));
     }
 
+    /// Test that each failed request is counted under its own variant
+    #[tokio::test(start_paused = true)]
+    async fn test_error_counters_per_variant() {
+        let counters = ErrorCounters::new();
+        let requests = [
+            ("network", 3),
+            ("auth", 1),
+            ("rate_limit", 2),
+            ("timeout", 4),
+            ("unavailable", 1),
+        ];
+
+        for (error_type, times) in requests {
+            for _ in 0..times {
+                let result =
+                    simulate_api_request_counted("metrics", false, error_type, &counters).await;
+                assert!(result.is_err());
+            }
+        }
+        // Successful requests aren't counted
+        simulate_api_request_counted("metrics", true, "network", &counters)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            counters.snapshot(),
+            ErrorCountsSnapshot {
+                network: 3,
+                authentication: 1,
+                rate_limit: 2,
+                validation: 0,
+                timeout: 4,
+                service_unavailable: 1,
+            }
+        );
+    }
+
+    /// Test that a transaction commits on success and rolls back on error
+    /// or when its future is dropped mid-way
+    #[tokio::test(start_paused = true)]
+    async fn test_with_transaction_commit_and_rollback() {
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let transaction = |name: &'static str| {
+            let (committed, rolled_back) = (log.clone(), log.clone());
+            TxGuard::new(
+                move || committed.lock().unwrap().push(format!("{} commit", name)),
+                move || {
+                    rolled_back
+                        .lock()
+                        .unwrap()
+                        .push(format!("{} rollback", name))
+                },
+            )
+        };
+
+        let ok = with_transaction(transaction("a"), || async { Ok(1) }).await;
+        assert_eq!(ok.unwrap(), 1);
+
+        let failed = with_transaction(transaction("b"), || async {
+            Err::<(), _>(DatabaseError::DatabaseLocked)
+        })
+        .await;
+        assert!(matches!(failed, Err(DatabaseError::DatabaseLocked)));
+
+        let reached_end = Arc::new(AtomicBool::new(false));
+        let slow = with_transaction(transaction("c"), || {
+            let reached_end = reached_end.clone();
+            async move {
+                sleep(Duration::from_secs(1)).await;
+                reached_end.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+        assert!(timeout(Duration::from_millis(100), slow).await.is_err());
+        assert!(!reached_end.load(Ordering::SeqCst));
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["a commit", "b rollback", "c rollback"]
+        );
+    }
+
+    /// Test that join_graceful sorts named results and require checks them
+    #[tokio::test(start_paused = true)]
+    async fn test_join_graceful_partial_results() {
+        let results = join_graceful(vec![
+            ("profile", simulate_api_request("profile", true, "")),
+            (
+                "recommendations",
+                simulate_api_request("recs", false, "timeout"),
+            ),
+            ("ads", simulate_api_request("ads", false, "rate_limit")),
+            ("orders", simulate_api_request("orders", true, "")),
+        ])
+        .await;
+
+        let mut succeeded: Vec<_> = results.successes.keys().cloned().collect();
+        succeeded.sort();
+        assert_eq!(succeeded, vec!["orders", "profile"]);
+        assert!(results.successes["profile"].contains("profile"));
+        assert_eq!(results.failures.len(), 2);
+        assert!(matches!(
+            results.failures["recommendations"],
+            ApiError::TimeoutError { .. }
+        ));
+        assert!(matches!(
+            results.failures["ads"],
+            ApiError::RateLimitError { retry_after: 60 }
+        ));
+
+        assert_eq!(results.require(&["profile", "orders"]), Ok(()));
+        let error = results
+            .require(&["profile", "recommendations", "billing"])
+            .unwrap_err();
+        assert_eq!(error.missing, vec!["recommendations", "billing"]);
+    }
+
+    /// Test that fatal API errors short-circuit and a rate limit's
+    /// retry_after is honored
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_classified_api_errors() {
+        let mut attempts = 0;
+        let result = retry_classified(
+            || {
+                attempts += 1;
+                simulate_api_request("login", false, "auth")
+            },
+            5,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(matches!(result, Err(ApiError::AuthenticationError { .. })));
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let start = tokio::time::Instant::now();
+        let result = retry_classified(
+            || {
+                attempts += 1;
+                simulate_api_request("search", attempts == 3, "rate_limit")
+            },
+            5,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+        // Two 60s waits from retry_after, plus three 100ms requests
+        assert_eq!(start.elapsed(), Duration::from_millis(120_300));
+    }
+
+    /// Test that retries stop once another task turns the flag off
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_while_stops_when_disabled() {
+        let enabled = Arc::new(AtomicBool::new(true));
+        let flipper = {
+            let enabled = enabled.clone();
+            tokio::spawn(async move {
+                // Attempts run at 0ms and 10ms; the flag flips before the third
+                sleep(Duration::from_millis(15)).await;
+                enabled.store(false, Ordering::SeqCst);
+            })
+        };
+
+        let mut attempts = 0;
+        let result: Result<(), String> = retry_while(
+            || {
+                attempts += 1;
+                let attempt = attempts;
+                async move { Err(format!("failure {}", attempt)) }
+            },
+            enabled,
+            Duration::from_millis(10),
+        )
+        .await;
+        flipper.await.unwrap();
+
+        assert_eq!(result, Err("failure 2".to_string()));
+        assert_eq!(attempts, 2);
+    }
+
+    /// Test that polling stops at the first done state, backing off
+    /// exponentially between checks
+    #[tokio::test(start_paused = true)]
+    async fn test_poll_until_done_after_three_pending() {
+        let mut polls = 0;
+        let start = tokio::time::Instant::now();
+        let result = poll_until(
+            || {
+                polls += 1;
+                let status = if polls > 3 { "done" } else { "pending" };
+                async move { status }
+            },
+            |status| *status == "done",
+            Duration::from_millis(10),
+            Duration::from_secs(5),
+        )
+        .await;
+
+        assert_eq!(result, Ok("done"));
+        assert_eq!(polls, 4);
+        // Backoff of 10ms, then 20ms, then 40ms
+        assert_eq!(start.elapsed(), Duration::from_millis(70));
+    }
+
+    /// Test that polling gives up with PollTimeout once the budget runs out
+    #[tokio::test(start_paused = true)]
+    async fn test_poll_until_times_out() {
+        let start = tokio::time::Instant::now();
+        let result = poll_until(
+            || async { "pending" },
+            |status| *status == "done",
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+        )
+        .await;
+
+        // Checks at 0, 10, 30, 70 and, cut short, at 100ms
+        assert_eq!(
+            result,
+            Err(PollTimeout {
+                polls: 5,
+                waited: Duration::from_millis(100),
+            })
+        );
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    /// Test that fatal database errors short-circuit, retryable ones use
+    /// their own delay or the default, and max_attempts is respected
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_classified_database_errors() {
+        let mut attempts = 0;
+        let result = retry_classified(
+            || {
+                attempts += 1;
+                simulate_database_operation("SELEC", false, "query")
+            },
+            5,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(matches!(result, Err(DatabaseError::QueryFailed { .. })));
+        assert_eq!(attempts, 1);
+
+        let start = tokio::time::Instant::now();
+        let mut attempts = 0;
+        let result = retry_classified(
+            || {
+                attempts += 1;
+                simulate_database_operation("UPDATE", attempts == 3, "locked")
+            },
+            5,
+            Duration::from_secs(10),
+        )
+        .await;
+        assert!(result.is_ok());
+        // Three 80ms operations and two 20ms lock delays, not the 10s default
+        assert_eq!(start.elapsed(), Duration::from_millis(280));
+
+        let start = tokio::time::Instant::now();
+        let mut attempts = 0;
+        let result = retry_classified(
+            || {
+                attempts += 1;
+                simulate_database_operation("INSERT", false, "connection")
+            },
+            3,
+            Duration::from_millis(50),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::ConnectionFailed { .. })
+        ));
+        assert_eq!(attempts, 3);
+        assert_eq!(start.elapsed(), Duration::from_millis(3 * 80 + 2 * 50));
+    }
+
     /// Test timeout handling
     #[tokio::test]
     async fn test_timeout_handling() {
@@ -904,6 +2383,151 @@ mod tests {
         assert!(error_message.contains("Test operation failed"));
     }
 
+    /// Test that retry_within respects its time budget
+    #[tokio::test]
+    async fn test_retry_within_budget() {
+        let mut attempts = 0;
+        let start = Instant::now();
+
+        let result: Result<(), String> = retry_within(
+            || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    sleep(Duration::from_millis(40)).await;
+                    Err(format!("attempt {} failed", attempt))
+                }
+            },
+            Duration::from_millis(300),
+            Duration::from_millis(50),
+        )
+        .await;
+
+        let elapsed = start.elapsed();
+
+        // Gave up within the budget (plus scheduling slack)
+        assert!(elapsed <= Duration::from_millis(350));
+        assert!(attempts > 1);
+
+        // The error reported is the one from the final attempt
+        assert_eq!(result.unwrap_err(), format!("attempt {} failed", attempts));
+    }
+
+    /// Test that a slow first step leaves less budget for the last one
+    #[tokio::test(start_paused = true)]
+    async fn test_budget_shared_across_steps() {
+        let budget = Budget::new(Duration::from_millis(100));
+        let start = tokio::time::Instant::now();
+        let mut granted = Vec::new();
+
+        let workflow = async {
+            for step_time in [60, 20, 50] {
+                granted.push(budget.remaining());
+                budget.run(sleep(Duration::from_millis(step_time))).await?;
+            }
+            Ok::<_, BudgetExhausted>(())
+        };
+        let result = workflow.await;
+
+        assert_eq!(
+            result,
+            Err(BudgetExhausted {
+                total: Duration::from_millis(100)
+            })
+        );
+        assert_eq!(
+            granted,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(40),
+                Duration::from_millis(20)
+            ]
+        );
+        assert!(start.elapsed() <= Duration::from_millis(100));
+    }
+
+    /// Test that split sub-budgets end at consecutive equal slots
+    #[tokio::test(start_paused = true)]
+    async fn test_budget_split() {
+        let steps = Budget::new(Duration::from_millis(90)).split(3);
+        let remaining: Vec<Duration> = steps.iter().map(Budget::remaining).collect();
+
+        assert_eq!(
+            remaining,
+            vec![
+                Duration::from_millis(30),
+                Duration::from_millis(60),
+                Duration::from_millis(90)
+            ]
+        );
+    }
+
+    /// Test that a future panicking once is retried and its value returned
+    #[tokio::test]
+    async fn test_retry_on_panic_recovers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let result = retry_on_panic(
+            || {
+                let calls = calls.clone();
+                async move {
+                    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first call fails");
+                    }
+                    42
+                }
+            },
+            3,
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    /// Test that retry_on_panic gives up after max retries
+    #[tokio::test]
+    async fn test_retry_on_panic_gives_up() {
+        let result: Result<(), _> = retry_on_panic(|| async { panic!("always") }, 2).await;
+
+        let error = result.unwrap_err();
+        assert_eq!(error.attempts, 3);
+        assert_eq!(error.last_message, "always");
+    }
+
+    /// Test that repeated connection failures open the pool's breaker
+    #[tokio::test]
+    async fn test_db_pool_circuit_opens_and_fails_fast() {
+        let pool = DbPool::new(
+            2,
+            CircuitBreakerConfig {
+                failure_threshold: 3,
+                reset_timeout: Duration::from_secs(60),
+            },
+        );
+        pool.set_healthy(false);
+
+        for _ in 0..3 {
+            let error = pool.execute("SELECT 1").await.unwrap_err();
+            assert!(
+                matches!(error, DatabaseError::ConnectionFailed { ref details } if details != "circuit open")
+            );
+        }
+        assert!(pool.breaker.is_open());
+
+        // The database is back, but the open circuit still short-circuits
+        pool.set_healthy(true);
+        let start = Instant::now();
+        let error = pool.execute("SELECT 1").await.unwrap_err();
+
+        assert!(
+            matches!(error, DatabaseError::ConnectionFailed { ref details } if details == "circuit open")
+        );
+        assert!(start.elapsed() < Duration::from_millis(20));
+    }
+
     /// Test concurrent error handling
     #[tokio::test]
     async fn test_concurrent_errors() {
@@ -917,4 +2541,63 @@ mod tests {
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), ApiError::NetworkError { .. }));
     }
+
+    /// Test that the chain returns the first success after trying the failing
+    /// sources, and lists every failure when nothing succeeds
+    #[tokio::test]
+    async fn test_fallback_chain() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Arc;
+
+        struct Fixed {
+            name: &'static str,
+            result: Result<u32, &'static str>,
+            calls: Arc<AtomicUsize>,
+        }
+
+        impl DataSource<u32> for Fixed {
+            fn name(&self) -> &str {
+                self.name
+            }
+
+            fn fetch(&self) -> BoxFuture<'_, Result<u32, SourceError>> {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                let result = self
+                    .result
+                    .map_err(|error| SourceError::Other(error.into()));
+                Box::pin(async move { result })
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let source = |name, result| Fixed {
+            name,
+            result,
+            calls: calls.clone(),
+        };
+
+        let chain = FallbackChain::new()
+            .with_source(source("first", Err("down")))
+            .with_source(source("second", Err("timed out")))
+            .with_source(source("third", Ok(42)));
+        assert_eq!(chain.resolve().await.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let error = FallbackChain::new()
+            .with_source(source("first", Err("down")))
+            .with_source(source("second", Err("timed out")))
+            .resolve()
+            .await
+            .unwrap_err();
+        let names: Vec<&str> = error
+            .failures
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+        assert_eq!(
+            error.to_string(),
+            "All 2 data sources failed: first (down), second (timed out)"
+        );
+    }
 }
@@ -0,0 +1,497 @@
+//! # Mini Runtime: Building an Async Runtime from Scratch
+//!
+//! This capstone example builds a complete single-threaded async runtime
+//! without tokio or the futures crate. Everything the other examples take
+//! for granted is spelled out here:
+//!
+//! 1. A reactor that asks the OS (via `mio`) which sockets are ready
+//! 2. I/O futures that register their waker with the reactor
+//! 3. A waker that can interrupt a reactor blocked in the OS
+//! 4. A `block_on` executor that parks until the reactor signals
+//! 5. A real non-blocking `TcpStream` driven end to end
+
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use mio::net::TcpStream as MioTcpStream;
+use mio::{Events, Interest, Poll as MioPoll, Registry, Token};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Token reserved for the reactor's own cross-thread wakeups
+const WAKE_TOKEN: Token = Token(0);
+
+thread_local! {
+    /// The reactor of the `block_on` call running on this thread, if any
+    static CURRENT_REACTOR: RefCell<Option<Rc<Reactor>>> = const { RefCell::new(None) };
+}
+
+/// # Struct: Reactor
+///
+/// Bridges OS readiness notifications and task wakers.
+/// I/O futures register interest in a socket together with the waker of
+/// the task waiting on it; when the OS reports the socket ready, the
+/// reactor wakes that task.
+///
+/// ## Fields:
+/// - `poll`: The mio poll instance (epoll/kqueue/IOCP under the hood)
+/// - `events`: Buffer that `poll` fills with ready events
+/// - `wakers`: Waker of the task waiting on each registered socket
+/// - `next_token`: Next unused token for a new registration
+struct Reactor {
+    poll: RefCell<MioPoll>,
+    events: RefCell<Events>,
+    wakers: RefCell<HashMap<Token, Waker>>,
+    next_token: Cell<usize>,
+}
+
+impl Reactor {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            poll: RefCell::new(MioPoll::new()?),
+            events: RefCell::new(Events::with_capacity(64)),
+            wakers: RefCell::new(HashMap::new()),
+            next_token: Cell::new(WAKE_TOKEN.0 + 1),
+        })
+    }
+
+    /// Returns the reactor driving the current `block_on` call.
+    ///
+    /// ## Panics:
+    /// - If called outside of `block_on`, just like tokio's I/O types
+    ///   panic outside of a tokio runtime
+    fn current() -> Rc<Reactor> {
+        CURRENT_REACTOR.with(|current| {
+            current
+                .borrow()
+                .clone()
+                .expect("mini_runtime I/O must be used inside block_on")
+        })
+    }
+
+    fn registry(&self) -> Registry {
+        self.poll
+            .borrow()
+            .registry()
+            .try_clone()
+            .expect("Failed to clone mio registry")
+    }
+
+    /// Registers a socket and returns the token its events will carry.
+    fn register(&self, source: &mut MioTcpStream, interest: Interest) -> io::Result<Token> {
+        let token = Token(self.next_token.get());
+        self.next_token.set(token.0 + 1);
+        self.poll
+            .borrow()
+            .registry()
+            .register(source, token, interest)?;
+        Ok(token)
+    }
+
+    fn deregister(&self, source: &mut MioTcpStream, token: Token) {
+        self.wakers.borrow_mut().remove(&token);
+        let _ = self.poll.borrow().registry().deregister(source);
+    }
+
+    /// Remembers which task to wake when `token` becomes ready.
+    fn set_waker(&self, token: Token, waker: &Waker) {
+        self.wakers.borrow_mut().insert(token, waker.clone());
+    }
+
+    /// Blocks until at least one event arrives, then wakes the tasks
+    /// waiting on the sockets that became ready.
+    fn wait(&self) -> io::Result<()> {
+        let mut events = self.events.borrow_mut();
+        loop {
+            match self.poll.borrow_mut().poll(&mut events, None) {
+                Ok(()) => break,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+
+        for event in events.iter() {
+            if event.token() == WAKE_TOKEN {
+                continue; // The task waker already flagged the task
+            }
+            // Take the waker out so it is only used once; the future
+            // registers a fresh one the next time it returns Pending
+            let waker = self.wakers.borrow_mut().remove(&event.token());
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// # Struct: TaskWaker
+///
+/// The waker handed to the future by `block_on`.
+///
+/// ## Fields:
+/// - `woken`: Set when the task should be polled again
+/// - `interrupt`: Unblocks the reactor if it is waiting in the OS, so a
+///   wake from another thread isn't lost while `block_on` is parked
+struct TaskWaker {
+    woken: AtomicBool,
+    interrupt: mio::Waker,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.woken.swap(true, Ordering::SeqCst) {
+            let _ = self.interrupt.wake();
+        }
+    }
+}
+
+/// Restores the previous thread-local reactor when `block_on` returns,
+/// even if the future panics
+struct ReactorGuard(Option<Rc<Reactor>>);
+
+impl Drop for ReactorGuard {
+    fn drop(&mut self) {
+        CURRENT_REACTOR.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// # Function: block_on
+///
+/// Runs a future to completion on the current thread.
+///
+/// ## Arguments:
+/// - `future`: The future to drive
+///
+/// ## Returns:
+/// - The future's output
+///
+/// ## How it works:
+/// 1. Poll the future whenever its waker has been triggered
+/// 2. If it is still pending, park in `Reactor::wait` until the OS reports
+///    readiness (or another thread calls the waker)
+/// 3. The reactor wakes the task, and the loop polls it again
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let reactor = Rc::new(Reactor::new().expect("Failed to create reactor"));
+    let previous = CURRENT_REACTOR.with(|current| current.replace(Some(reactor.clone())));
+    let _guard = ReactorGuard(previous);
+
+    let task_waker = Arc::new(TaskWaker {
+        woken: AtomicBool::new(true), // Poll once right away
+        interrupt: mio::Waker::new(&reactor.registry(), WAKE_TOKEN)
+            .expect("Failed to create reactor waker"),
+    });
+    let waker = Waker::from(task_waker.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        if task_waker.woken.swap(false, Ordering::SeqCst) {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            continue; // The future may have woken itself while being polled
+        }
+
+        reactor.wait().expect("Reactor failed to poll for events");
+    }
+}
+
+/// # Struct: TcpStream
+///
+/// A non-blocking TCP stream driven by the mini runtime's reactor.
+///
+/// ## Fields:
+/// - `inner`: The underlying non-blocking mio socket
+/// - `token`: Identifies this socket's events in the reactor
+/// - `reactor`: The reactor the socket is registered with
+pub struct TcpStream {
+    inner: MioTcpStream,
+    token: Token,
+    reactor: Rc<Reactor>,
+}
+
+impl TcpStream {
+    /// # Function: connect
+    ///
+    /// Opens a TCP connection without blocking the thread.
+    ///
+    /// ## Arguments:
+    /// - `addr`: The address to connect to
+    ///
+    /// ## Returns:
+    /// - `io::Result<TcpStream>`: The connected stream or the connect error
+    ///
+    /// ## Non-blocking connect:
+    /// The OS starts the handshake immediately and reports the socket as
+    /// writable once it finishes. Until then, polling finds the socket not
+    /// yet connected, registers the waker and returns `Pending`.
+    pub async fn connect(addr: SocketAddr) -> io::Result<TcpStream> {
+        let reactor = Reactor::current();
+        let mut inner = MioTcpStream::connect(addr)?;
+        let token = reactor.register(&mut inner, Interest::READABLE | Interest::WRITABLE)?;
+        let stream = TcpStream {
+            inner,
+            token,
+            reactor,
+        };
+
+        std::future::poll_fn(|cx| {
+            if let Some(error) = stream.inner.take_error()? {
+                return Poll::Ready(Err(error));
+            }
+            match stream.inner.peer_addr() {
+                Ok(_) => Poll::Ready(Ok(())),
+                Err(error)
+                    if error.kind() == io::ErrorKind::NotConnected
+                        || error.kind() == io::ErrorKind::WouldBlock =>
+                {
+                    stream.reactor.set_waker(stream.token, cx.waker());
+                    Poll::Pending
+                }
+                Err(error) => Poll::Ready(Err(error)),
+            }
+        })
+        .await?;
+
+        Ok(stream)
+    }
+
+    /// # Function: read
+    ///
+    /// Reads available bytes into `buf`, waiting for the socket to become
+    /// readable if no data has arrived yet.
+    ///
+    /// ## Arguments:
+    /// - `buf`: Destination buffer
+    ///
+    /// ## Returns:
+    /// - `io::Result<usize>`: Number of bytes read (0 means the peer closed)
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        std::future::poll_fn(|cx| loop {
+            // Always try the operation first: mio readiness is edge-triggered,
+            // so waiting without trying could miss data that is already there
+            match self.inner.read(buf) {
+                Ok(n) => return Poll::Ready(Ok(n)),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    self.reactor.set_waker(self.token, cx.waker());
+                    return Poll::Pending;
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Poll::Ready(Err(error)),
+            }
+        })
+        .await
+    }
+
+    /// # Function: read_to_end
+    ///
+    /// Reads until the peer closes the connection.
+    ///
+    /// ## Returns:
+    /// - `io::Result<Vec<u8>>`: Everything the peer sent
+    pub async fn read_to_end(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut chunk = [0u8; 1024];
+        loop {
+            match self.read(&mut chunk).await? {
+                0 => return Ok(data),
+                n => data.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        self.reactor.deregister(&mut self.inner, self.token);
+    }
+}
+
+/// # Function: spawn_greeting_server
+///
+/// Starts a plain blocking `std` TCP server on a background thread that
+/// sends `greeting` to each client and closes the connection.
+///
+/// ## Returns:
+/// - The address the server listens on
+fn spawn_greeting_server(greeting: &'static str) -> io::Result<SocketAddr> {
+    use std::io::Write;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    std::thread::spawn(move || {
+        for mut connection in listener.incoming().flatten() {
+            // Pause so the client genuinely has to wait for readability
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let _ = connection.write_all(greeting.as_bytes());
+        }
+    });
+
+    Ok(addr)
+}
+
+/// # Function: demonstrate_block_on
+///
+/// Demonstrates the executor half of the runtime on futures that need no I/O.
+///
+/// ## Key Learning Points:
+/// - `block_on` is just a loop around `poll`
+/// - A future that wakes itself is polled again without touching the OS
+fn demonstrate_block_on() {
+    println!("\n=== block_on Without I/O ===");
+
+    println!("1. A ready future:");
+    let sum = block_on(async { 40 + 2 });
+    println!("   async {{ 40 + 2 }} = {}", sum);
+
+    println!("\n2. A future that yields three times:");
+    let mut remaining = 3;
+    let polls = block_on(std::future::poll_fn(|cx| {
+        if remaining == 0 {
+            return Poll::Ready(4);
+        }
+        remaining -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }));
+    println!("   Completed after {} polls", polls);
+}
+
+/// # Function: demonstrate_tcp_connect
+///
+/// Demonstrates the reactor half: a real TCP connection and read driven by
+/// OS readiness events.
+///
+/// ## Key Learning Points:
+/// - Non-blocking connect completes when the socket becomes writable
+/// - Reads register the waker and park until the socket is readable
+/// - The thread sleeps in the OS instead of spinning while it waits
+fn demonstrate_tcp_connect() {
+    println!("\n=== Driving a TcpStream ===");
+
+    let addr = match spawn_greeting_server("hello from a real socket") {
+        Ok(addr) => addr,
+        Err(error) => {
+            println!("   Could not start local server: {}", error);
+            return;
+        }
+    };
+
+    println!("1. Connecting to {} and reading the greeting:", addr);
+    let start = std::time::Instant::now();
+    let result = block_on(async {
+        let mut stream = TcpStream::connect(addr).await?;
+        println!("   Connected after {:?}", start.elapsed());
+        stream.read_to_end().await
+    });
+
+    match result {
+        Ok(data) => println!(
+            "   Received {:?} after {:?}",
+            String::from_utf8_lossy(&data),
+            start.elapsed()
+        ),
+        Err(error) => println!("   Connection failed: {}", error),
+    }
+}
+
+/// # Function: main
+///
+/// Runs the mini runtime demonstrations. Note that there is no
+/// `#[tokio::main]` here: `block_on` is our runtime.
+///
+/// ## Learning Progression:
+/// 1. The executor loop on its own
+/// 2. Reactor and waker integration with real sockets
+fn main() {
+    println!("🛠️  Mini Runtime Tutorial");
+    println!("========================");
+    println!("This example builds a single-threaded async runtime from scratch.");
+
+    demonstrate_block_on();
+
+    demonstrate_tcp_connect();
+
+    println!("\n✅ Mini Runtime Tutorial completed!");
+    println!("Key takeaways:");
+    println!("  - An executor is a loop that polls futures when they are woken");
+    println!("  - A reactor turns OS readiness events into waker calls");
+    println!("  - I/O futures try the operation first and register the waker on WouldBlock");
+    println!("  - Wakers must be able to interrupt a reactor blocked in the OS");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that block_on returns the output of a ready future
+    #[test]
+    fn test_block_on_ready() {
+        assert_eq!(block_on(async { "done" }), "done");
+    }
+
+    /// Test that a wake from another thread unblocks a parked block_on
+    #[test]
+    fn test_block_on_cross_thread_wake() {
+        let (tx, rx) = std::sync::mpsc::channel::<Waker>();
+        let done = Arc::new(AtomicBool::new(false));
+        let done_by_thread = done.clone();
+
+        std::thread::spawn(move || {
+            let waker = rx.recv().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            done_by_thread.store(true, Ordering::SeqCst);
+            waker.wake();
+        });
+
+        let mut sent = false;
+        block_on(std::future::poll_fn(|cx| {
+            if done.load(Ordering::SeqCst) {
+                return Poll::Ready(());
+            }
+            if !sent {
+                tx.send(cx.waker().clone()).unwrap();
+                sent = true;
+            }
+            Poll::Pending
+        }));
+    }
+
+    /// Test connecting to a local listener and reading a byte end to end
+    #[test]
+    fn test_tcp_connect_and_read_byte() {
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut connection, _) = listener.accept().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            connection.write_all(b"!").unwrap();
+        });
+
+        let byte = block_on(async {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let mut buf = [0u8; 1];
+            let n = stream.read(&mut buf).await.unwrap();
+            assert_eq!(n, 1);
+            buf[0]
+        });
+
+        assert_eq!(byte, b'!');
+    }
+}
@@ -11,14 +11,112 @@
 //! 6. Background task processing
 //! 7. Real-world error handling and resilience
 
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
 use anyhow::{Context, Result as AnyhowResult};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use future_trait_tutorial::cache::TtlCache;
+use future_trait_tutorial::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, StateChange};
+use future_trait_tutorial::ring_buffer::AsyncRingBuffer;
+use future_trait_tutorial::seed::{self, TestRng};
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::ready;
+use futures::stream::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, Response};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::task::Poll;
 use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::broadcast;
 use tokio::time::{sleep, timeout};
 
+/// # Enum: ApiError
+///
+/// Typed errors produced by the ApiClient itself (as opposed to transport
+/// or parsing errors, which are reported through anyhow with context).
+/// Callers can recover the variant with `error.downcast_ref::<ApiError>()`.
+#[derive(Error, Debug)]
+enum ApiError {
+    #[error("Service temporarily unavailable")]
+    ServiceUnavailable,
+
+    #[error("{kind} timeout after {duration:?}")]
+    TimeoutError {
+        duration: Duration,
+        kind: TimeoutKind,
+    },
+
+    #[error("Invalid response data: {0}")]
+    ValidationError(#[from] ValidationError),
+}
+
+/// # Enum: TimeoutKind
+///
+/// Which phase of a request ran out of time. Each points at a different
+/// problem: a connect timeout at an unreachable host, a read timeout at a
+/// server that stalls mid-response, an overall timeout at a slow whole.
+///
+/// ## Variants:
+/// - `Connect`: Establishing the TCP/TLS connection (`connect_timeout`)
+/// - `Read`: Receiving the response body (`read_timeout`)
+/// - `Overall`: The request as a whole (`timeout`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeoutKind {
+    Connect,
+    Read,
+    Overall,
+}
+
+impl std::fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            TimeoutKind::Connect => "Connect",
+            TimeoutKind::Read => "Read",
+            TimeoutKind::Overall => "Overall",
+        };
+        f.write_str(phase)
+    }
+}
+
+/// # Struct: ValidationError
+///
+/// Describes why a successfully parsed value is semantically invalid.
+///
+/// ## Fields:
+/// - `field`: The offending field
+/// - `message`: What is wrong with it
+#[derive(Error, Debug, PartialEq)]
+#[error("{field} {message}")]
+struct ValidationError {
+    field: String,
+    message: String,
+}
+
+impl ValidationError {
+    fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// # Trait: Validate
+///
+/// Semantic checks that run after deserialization. `serde_json::from_str`
+/// only guarantees the shape of the data; this trait catches values that
+/// are well-formed but make no sense (empty names, malformed emails, ...).
+trait Validate {
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
 /// # Struct: User
 ///
 /// Represents a user from a REST API.
@@ -41,6 +139,18 @@ struct User {
     posts_count: u32,
 }
 
+impl Validate for User {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.trim().is_empty() {
+            return Err(ValidationError::new("name", "must not be empty"));
+        }
+        if !self.email.contains('@') {
+            return Err(ValidationError::new("email", "must contain '@'"));
+        }
+        Ok(())
+    }
+}
+
 /// # Struct: Post
 ///
 /// Represents a blog post from a REST API.
@@ -102,6 +212,596 @@ struct UserProfile {
     fetch_time: Duration,
 }
 
+/// # Struct: ApiClientConfig
+///
+/// Tuning knobs for the underlying reqwest HTTP client.
+/// This demonstrates how to expose transport settings without forcing every
+/// caller to know about them: `Default` provides sensible values.
+///
+/// ## Fields:
+/// - `timeout`: Overall timeout for a single request
+/// - `connect_timeout`: Timeout for establishing the TCP/TLS connection
+/// - `read_timeout`: Timeout for receiving the body once headers arrived
+/// - `user_agent`: Value sent in the User-Agent header
+/// - `pool_max_idle_per_host`: Maximum idle pooled connections kept per host
+/// - `http2_prior_knowledge`: Speak HTTP/2 immediately instead of HTTP/1.1
+/// - `cache_capacity`: Maximum number of responses kept in the cache
+/// - `min_request_interval`: Minimum time between two requests (rate limit)
+/// - `default_cache_ttl`: How long `get` serves a response from the cache
+#[derive(Debug, Clone)]
+struct ApiClientConfig {
+    timeout: Duration,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    user_agent: String,
+    pool_max_idle_per_host: usize,
+    http2_prior_knowledge: bool,
+    cache_capacity: usize,
+    min_request_interval: Duration,
+    default_cache_ttl: Duration,
+}
+
+impl Default for ApiClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(10),
+            user_agent: "Future-Tutorial/1.0".to_string(),
+            pool_max_idle_per_host: usize::MAX, // reqwest's default: no limit
+            http2_prior_knowledge: false,
+            cache_capacity: 256,
+            min_request_interval: Duration::from_millis(100), // 10 requests per second max
+            default_cache_ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+/// # Struct: AppConfig
+///
+/// File-based settings for the example clients, so base URLs, timeouts and
+/// rate limits don't have to be hardcoded.
+///
+/// ## Fields:
+/// - `base_url`: Base URL for all API requests
+/// - `timeout_ms`: Overall timeout for a single request
+/// - `rate_per_sec`: Maximum number of requests per second
+/// - `cache_ttl_ms`: How long responses are served from the cache
+///
+/// ## Example:
+/// ```toml
+/// base_url = "https://jsonplaceholder.typicode.com"
+/// timeout_ms = 5000
+/// rate_per_sec = 10
+/// cache_ttl_ms = 60000
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+struct AppConfig {
+    base_url: String,
+    timeout_ms: u64,
+    rate_per_sec: u32,
+    cache_ttl_ms: u64,
+}
+
+impl AppConfig {
+    /// # Function: from_toml_str
+    ///
+    /// Parses a configuration from TOML text.
+    ///
+    /// ## Arguments:
+    /// - `input`: TOML source with the fields as top-level keys
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<AppConfig>`: The configuration, or what was wrong with it
+    fn from_toml_str(input: &str) -> AnyhowResult<Self> {
+        toml::from_str(input).context("Invalid configuration")
+    }
+}
+
+/// # Struct: ApiResponse
+///
+/// A successful response with its HTTP status and headers kept alongside
+/// the body, for callers that need more than the body (e.g. 201 vs 200).
+///
+/// ## Fields:
+/// - `status`: The HTTP status code (always 2xx; other statuses are errors)
+/// - `headers`: Response headers with lowercase names; if a header is
+///   repeated, the last value wins
+/// - `body`: The response body, raw or deserialized
+#[derive(Debug, Clone, PartialEq)]
+struct ApiResponse<T> {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: T,
+}
+
+/// # Struct: ClientMetrics
+///
+/// Request and cache statistics for an `ApiClient`, exportable in the
+/// Prometheus text format. Everything is an atomic counter, so recording
+/// from many concurrent requests never takes a lock or blocks a task.
+///
+/// ## Fields:
+/// - `requests_ok` / `requests_failed`: HTTP requests by outcome
+/// - `cache_hits` / `cache_misses`: Lookups answered from / not in the cache
+/// - `timeouts`: Failed requests that ran out of time (any `TimeoutKind`)
+/// - `latency_buckets`: Request counts per exponential latency bucket
+///   (not cumulative; the export makes them cumulative)
+/// - `latency_sum_micros`: Total request latency, for the histogram `_sum`
+#[derive(Debug, Default)]
+struct ClientMetrics {
+    requests_ok: AtomicU64,
+    requests_failed: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    timeouts: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS],
+    latency_sum_micros: AtomicU64,
+}
+
+/// Number of finite latency buckets: upper bounds 1ms, 2ms, 4ms ... ~8.2s
+const LATENCY_BUCKETS: usize = 14;
+
+impl ClientMetrics {
+    /// Upper bound of latency bucket `index`, in seconds
+    fn bucket_bound(index: usize) -> f64 {
+        0.001 * (1u64 << index) as f64
+    }
+
+    fn record_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_request(&self, latency: Duration, succeeded: bool) {
+        let counter = if succeeded {
+            &self.requests_ok
+        } else {
+            &self.requests_failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        // Slower than the last bound: only counted in the +Inf bucket
+        let seconds = latency.as_secs_f64();
+        if let Some(bucket) = (0..LATENCY_BUCKETS).find(|&i| seconds <= Self::bucket_bound(i)) {
+            self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// # Function: to_prometheus
+    ///
+    /// Renders the counters and the latency histogram in the Prometheus
+    /// text exposition format.
+    fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let ok = load(&self.requests_ok);
+        let failed = load(&self.requests_failed);
+        let mut out = String::new();
+
+        // Writing to a String cannot fail
+        let _ = writeln!(
+            out,
+            "# HELP api_client_requests_total HTTP requests sent, by outcome."
+        );
+        let _ = writeln!(out, "# TYPE api_client_requests_total counter");
+        let _ = writeln!(
+            out,
+            "api_client_requests_total{{outcome=\"success\"}} {}",
+            ok
+        );
+        let _ = writeln!(
+            out,
+            "api_client_requests_total{{outcome=\"error\"}} {}",
+            failed
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP api_client_cache_hits_total Requests answered from the cache."
+        );
+        let _ = writeln!(out, "# TYPE api_client_cache_hits_total counter");
+        let _ = writeln!(
+            out,
+            "api_client_cache_hits_total {}",
+            load(&self.cache_hits)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP api_client_cache_misses_total Requests not found in the cache."
+        );
+        let _ = writeln!(out, "# TYPE api_client_cache_misses_total counter");
+        let _ = writeln!(
+            out,
+            "api_client_cache_misses_total {}",
+            load(&self.cache_misses)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP api_client_timeouts_total HTTP requests that timed out."
+        );
+        let _ = writeln!(out, "# TYPE api_client_timeouts_total counter");
+        let _ = writeln!(out, "api_client_timeouts_total {}", load(&self.timeouts));
+
+        let _ = writeln!(
+            out,
+            "# HELP api_client_request_duration_seconds HTTP request latency."
+        );
+        let _ = writeln!(out, "# TYPE api_client_request_duration_seconds histogram");
+        let mut cumulative = 0;
+        for (index, bucket) in self.latency_buckets.iter().enumerate() {
+            cumulative += load(bucket);
+            let _ = writeln!(
+                out,
+                "api_client_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                Self::bucket_bound(index),
+                cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "api_client_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            ok + failed
+        );
+        let _ = writeln!(
+            out,
+            "api_client_request_duration_seconds_sum {}",
+            load(&self.latency_sum_micros) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(
+            out,
+            "api_client_request_duration_seconds_count {}",
+            ok + failed
+        );
+
+        out
+    }
+}
+
+/// # Struct: LatencySnapshot
+///
+/// Point-in-time statistics computed by a `LatencyAggregator`.
+///
+/// ## Fields:
+/// - `count`: Number of latencies observed
+/// - `min` / `max` / `mean`: Exact over all observed latencies
+/// - `p99`: Approximate 99th percentile (within one histogram bucket, ~9%)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p99: Duration,
+}
+
+/// Running totals behind a `LatencyAggregator`
+#[derive(Debug, Default)]
+struct LatencyStats {
+    count: u64,
+    min: Duration,
+    max: Duration,
+    total_nanos: u128,
+    /// Sparse log-scale histogram: bucket index -> count
+    buckets: BTreeMap<u32, u64>,
+}
+
+/// Histogram buckets per doubling of latency; each bucket is ~9% wide
+const LATENCY_SUB_BUCKETS: f64 = 8.0;
+
+impl LatencyStats {
+    /// Bucket holding `latency`; bucket `i` covers up to `2^(i/8)` microseconds
+    fn bucket_index(latency: Duration) -> u32 {
+        let micros = latency.as_secs_f64() * 1_000_000.0;
+        if micros <= 1.0 {
+            0
+        } else {
+            (micros.log2() * LATENCY_SUB_BUCKETS).ceil() as u32
+        }
+    }
+
+    fn bucket_bound(index: u32) -> Duration {
+        Duration::from_secs_f64(2f64.powf(index as f64 / LATENCY_SUB_BUCKETS) / 1_000_000.0)
+    }
+}
+
+/// # Struct: LatencyAggregator
+///
+/// Computes running statistics over a stream of latencies without storing
+/// the individual samples: min, max and mean are exact, and the p99 comes
+/// from a log-scale histogram. Clones share the same statistics, so one
+/// clone can consume a stream in a background task while another takes
+/// snapshots.
+///
+/// ## Example:
+/// ```rust
+/// let latencies = LatencyAggregator::default();
+/// tokio::spawn({
+///     let latencies = latencies.clone();
+///     let stream = client.latency_stream();
+///     async move { latencies.consume(stream).await }
+/// });
+/// // ... make requests ...
+/// println!("p99: {:?}", latencies.snapshot().p99);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LatencyAggregator {
+    stats: Arc<Mutex<LatencyStats>>,
+}
+
+impl LatencyAggregator {
+    /// Adds a single latency to the statistics.
+    pub fn record(&self, latency: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        if stats.count == 0 || latency < stats.min {
+            stats.min = latency;
+        }
+        stats.max = stats.max.max(latency);
+        stats.count += 1;
+        stats.total_nanos += latency.as_nanos();
+        *stats
+            .buckets
+            .entry(LatencyStats::bucket_index(latency))
+            .or_default() += 1;
+    }
+
+    /// # Function: consume
+    ///
+    /// Records every latency from a stream until the stream ends.
+    ///
+    /// ## Arguments:
+    /// - `latencies`: The latencies to aggregate, e.g. `ApiClient::latency_stream`
+    pub async fn consume<S>(&self, latencies: S)
+    where
+        S: Stream<Item = Duration>,
+    {
+        let mut latencies = std::pin::pin!(latencies);
+        while let Some(latency) = latencies.next().await {
+            self.record(latency);
+        }
+    }
+
+    /// # Function: snapshot
+    ///
+    /// Returns the statistics over everything recorded so far.
+    ///
+    /// ## Returns:
+    /// - `LatencySnapshot`: All zeros if nothing has been recorded yet
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let stats = self.stats.lock().unwrap();
+        if stats.count == 0 {
+            return LatencySnapshot::default();
+        }
+
+        // Upper bound of the bucket containing the 99th percentile sample
+        let rank = (stats.count * 99).div_ceil(100);
+        let mut seen = 0;
+        let p99_bucket = stats
+            .buckets
+            .iter()
+            .find(|(_, &count)| {
+                seen += count;
+                seen >= rank
+            })
+            .map(|(&index, _)| index)
+            .unwrap_or_default();
+
+        LatencySnapshot {
+            count: stats.count,
+            min: stats.min,
+            max: stats.max,
+            mean: Duration::from_nanos((stats.total_nanos / stats.count as u128) as u64),
+            // The bucket bound may overshoot the slowest actual sample
+            p99: LatencyStats::bucket_bound(p99_bucket).clamp(stats.min, stats.max),
+        }
+    }
+}
+
+/// # Struct: KeepAliveHandle
+///
+/// Owns a background keep-alive task started by
+/// `ApiClient::start_keepalive`. Dropping the handle aborts the task.
+///
+/// ## Fields:
+/// - `task`: The pinging task
+/// - `pings`: Number of pings sent so far, successful or not
+struct KeepAliveHandle {
+    task: tokio::task::JoinHandle<()>,
+    pings: Arc<AtomicU64>,
+}
+
+impl KeepAliveHandle {
+    /// Returns how many pings have been sent so far.
+    fn pings(&self) -> u64 {
+        self.pings.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for KeepAliveHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// # Struct: RefreshGuard
+///
+/// Marks a cache key as being refreshed. At most one guard exists per key,
+/// so concurrent stale reads start a single refresh between them: the
+/// first reader gets the guard, the others see it taken and skip theirs.
+/// The key is released on drop, even if the refresh fails, panics or is
+/// aborted.
+///
+/// ## Fields:
+/// - `refreshing`: Keys with a live guard, shared by all clones of a client
+/// - `key`: The key this guard holds
+struct RefreshGuard {
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl RefreshGuard {
+    /// Takes the refresh slot for `key`, or returns `None` if a refresh of
+    /// that key is already running.
+    fn try_acquire(refreshing: &Arc<Mutex<HashSet<String>>>, key: &str) -> Option<Self> {
+        let acquired = refreshing.lock().unwrap().insert(key.to_string());
+        acquired.then(|| Self {
+            refreshing: refreshing.clone(),
+            key: key.to_string(),
+        })
+    }
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        self.refreshing.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// # Enum: ArrayStep
+///
+/// What `JsonArrayParser::next_item` found in the bytes received so far.
+enum ArrayStep<T> {
+    /// A complete element
+    Item(T),
+    /// The next element is not complete yet
+    NeedMore,
+    /// The closing `]` was reached
+    End,
+}
+
+/// # Struct: JsonArrayParser
+///
+/// Splits a JSON array arriving in chunks into its elements. Only the bytes
+/// of the element being parsed are buffered, so memory use depends on the
+/// largest element rather than on the whole array.
+///
+/// ## Fields:
+/// - `buffer`: Received bytes not consumed yet
+/// - `opened`: Whether the opening `[` has been consumed
+/// - `closed`: Whether the closing `]` has been consumed
+/// - `expect_comma`: Whether a `,` must come before the next element
+/// - `eof`: Whether the body has ended, so no more bytes will arrive
+#[derive(Debug, Default)]
+struct JsonArrayParser {
+    buffer: Vec<u8>,
+    opened: bool,
+    closed: bool,
+    expect_comma: bool,
+    eof: bool,
+}
+
+impl JsonArrayParser {
+    /// Appends a chunk of the body.
+    fn extend(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Marks the end of the body.
+    fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    /// Drops leading whitespace, then reports whether anything is left.
+    fn skip_whitespace(&mut self) -> AnyhowResult<bool> {
+        let whitespace = self
+            .buffer
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        self.buffer.drain(..whitespace);
+
+        if self.buffer.is_empty() && self.eof {
+            anyhow::bail!("JSON array ended early");
+        }
+        Ok(!self.buffer.is_empty())
+    }
+
+    /// # Function: next_item
+    ///
+    /// Parses the next element out of the buffered bytes, using
+    /// `serde_json`'s streaming deserializer for the element itself.
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<ArrayStep<T>>`: The element, a request for more
+    ///   bytes, the end of the array, or an error for malformed JSON
+    fn next_item<T: DeserializeOwned>(&mut self) -> AnyhowResult<ArrayStep<T>> {
+        if self.closed {
+            return Ok(ArrayStep::End);
+        }
+        if !self.skip_whitespace()? {
+            return Ok(ArrayStep::NeedMore);
+        }
+
+        if !self.opened {
+            anyhow::ensure!(self.buffer[0] == b'[', "Response body is not a JSON array");
+            self.buffer.remove(0);
+            self.opened = true;
+            if !self.skip_whitespace()? {
+                return Ok(ArrayStep::NeedMore);
+            }
+        }
+
+        if self.buffer[0] == b']' {
+            self.buffer.remove(0);
+            self.closed = true;
+            return Ok(ArrayStep::End);
+        }
+
+        if self.expect_comma {
+            anyhow::ensure!(
+                self.buffer[0] == b',',
+                "Expected ',' between array elements"
+            );
+            self.buffer.remove(0);
+            self.expect_comma = false;
+            if !self.skip_whitespace()? {
+                return Ok(ArrayStep::NeedMore);
+            }
+        }
+
+        let mut elements = serde_json::Deserializer::from_slice(&self.buffer).into_iter::<T>();
+        match elements.next() {
+            Some(Ok(item)) => {
+                let consumed = elements.byte_offset();
+                // A number at the very end of the buffer may continue in the
+                // next chunk ("12" of "123"), so wait for more bytes first
+                if consumed == self.buffer.len() && !self.eof {
+                    return Ok(ArrayStep::NeedMore);
+                }
+                self.buffer.drain(..consumed);
+                self.expect_comma = true;
+                Ok(ArrayStep::Item(item))
+            }
+            Some(Err(error)) if error.is_eof() && !self.eof => Ok(ArrayStep::NeedMore),
+            Some(Err(error)) => Err(error).context("Failed to parse JSON array element"),
+            None => Ok(ArrayStep::NeedMore),
+        }
+    }
+}
+
+/// # Struct: Timeouts
+///
+/// The limits an `ApiClient` applies to each phase of a request, kept so
+/// that timeout errors can report which one was exceeded.
+#[derive(Debug, Clone, Copy)]
+struct Timeouts {
+    connect: Duration,
+    read: Duration,
+    overall: Duration,
+}
+
 /// # Struct: ApiClient
 ///
 /// A wrapper around reqwest::Client that provides higher-level API operations.
@@ -117,14 +817,44 @@ struct UserProfile {
 /// ## Fields:
 /// - `client`: The underlying HTTP client
 /// - `base_url`: Base URL for all API requests
-/// - `cache`: Simple in-memory cache for responses
+/// - `cache`: LRU cache of response bodies and when they were fetched
 /// - `rate_limiter`: Tracks request timing for rate limiting
+/// - `refreshing`: Cache keys with a background refresh in flight
+/// - `rate_limit_jitter`: Fraction (0.0-1.0) by which rate-limit sleeps vary
+/// - `jitter`: Random source for the rate-limit jitter, plus the factors
+///   it chose
+/// - `circuit_breaker`: Optional breaker guarding every real request
+/// - `min_request_interval`: Minimum time between two requests
+/// - `default_cache_ttl`: Cache duration used by `get`
+/// - `timeouts`: Connect, read and overall limits, to classify timeouts
+/// - `default_headers`: Headers added to every request unless overridden
+/// - `max_response_bytes`: Optional limit on the size of a response body
+/// - `metrics`: Request, cache and latency statistics
+/// - `latency_observers`: Subscribers to individual request latencies
 #[derive(Clone)]
-struct ApiClient {
+pub struct ApiClient {
     client: Client,
     base_url: String,
-    cache: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    cache: Arc<TtlCache<String, (String, Instant)>>,
     rate_limiter: Arc<Mutex<Instant>>,
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    rate_limit_jitter: f64,
+    jitter: Arc<Mutex<Jitter>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    min_request_interval: Duration,
+    default_cache_ttl: Duration,
+    timeouts: Timeouts,
+    default_headers: HeaderMap,
+    max_response_bytes: Option<usize>,
+    metrics: Arc<ClientMetrics>,
+    latency_observers: Arc<Mutex<Vec<UnboundedSender<Duration>>>>,
+}
+
+/// The random state behind the rate-limit jitter of an `ApiClient`
+#[derive(Debug)]
+struct Jitter {
+    rng: TestRng,
+    factors: Vec<f64>,
 }
 
 impl ApiClient {
@@ -144,759 +874,3254 @@ impl ApiClient {
     /// let users = client.get_users().await?;
     /// ```
     fn new(base_url: &str) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .user_agent("Future-Tutorial/1.0")
-            .build()
-            .expect("Failed to create HTTP client");
+        Self::with_config(base_url, ApiClientConfig::default())
+    }
+
+    /// # Function: with_config
+    ///
+    /// Creates a new ApiClient with custom transport settings.
+    ///
+    /// ## Arguments:
+    /// - `base_url`: The base URL for all API requests
+    /// - `config`: Timeouts, pooling and protocol settings for the HTTP client
+    ///
+    /// ## Returns:
+    /// - A new ApiClient instance ready for use
+    ///
+    /// ## Example:
+    /// ```rust
+    /// let config = ApiClientConfig {
+    ///     connect_timeout: Duration::from_secs(2),
+    ///     ..ApiClientConfig::default()
+    /// };
+    /// let client = ApiClient::with_config("https://jsonplaceholder.typicode.com", config);
+    /// ```
+    fn with_config(base_url: &str, config: ApiClientConfig) -> Self {
+        let mut builder = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .user_agent(config.user_agent)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host);
+
+        if config.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        let client = builder.build().expect("Failed to create HTTP client");
 
         Self {
             client,
             base_url: base_url.to_string(),
-            cache: Arc::new(Mutex::new(HashMap::new())),
+            cache: Arc::new(TtlCache::new(
+                config.cache_capacity,
+                config.default_cache_ttl,
+            )),
             rate_limiter: Arc::new(Mutex::new(Instant::now())),
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+            rate_limit_jitter: 0.0,
+            // Derived from the run seed, but different for every client
+            jitter: Arc::new(Mutex::new(Jitter {
+                rng: TestRng::from_run_seed(),
+                factors: Vec::new(),
+            })),
+            circuit_breaker: None,
+            min_request_interval: config.min_request_interval,
+            default_cache_ttl: config.default_cache_ttl,
+            timeouts: Timeouts {
+                connect: config.connect_timeout,
+                read: config.read_timeout,
+                overall: config.timeout,
+            },
+            default_headers: HeaderMap::new(),
+            max_response_bytes: None,
+            metrics: Arc::new(ClientMetrics::default()),
+            latency_observers: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
-    /// # Function: get_with_cache
+    /// # Function: from_config
     ///
-    /// Makes a GET request with caching support.
-    /// This demonstrates how to implement caching in async operations.
+    /// Creates a client from file-based application settings.
     ///
     /// ## Arguments:
-    /// - `endpoint`: The API endpoint to call (relative to base_url)
-    /// - `cache_duration`: How long to cache the response
+    /// - `config`: Parsed application configuration
     ///
     /// ## Returns:
-    /// - `AnyhowResult<String>`: The response body or an error
+    /// - A client using the configured base URL, timeout, rate and cache TTL
     ///
-    /// ## Caching Strategy:
-    /// - Checks cache first before making HTTP request
-    /// - Stores successful responses in memory cache
-    /// - Respects cache expiration times
-    /// - Falls back to fresh request if cache miss or expired
-    async fn get_with_cache(
-        &self,
-        endpoint: &str,
-        cache_duration: Duration,
-    ) -> AnyhowResult<String> {
-        let cache_key = format!("{}/{}", self.base_url, endpoint);
-
-        // Check cache first
-        {
-            let cache = self.cache.lock().unwrap();
-            if let Some((cached_response, cached_at)) = cache.get(&cache_key) {
-                if cached_at.elapsed() < cache_duration {
-                    println!("📦 Cache hit for {}", endpoint);
-                    return Ok(cached_response.clone());
-                }
-            }
-        }
+    /// ## Example:
+    /// ```rust
+    /// let config = AppConfig::from_toml_str(&std::fs::read_to_string("client.toml")?)?;
+    /// let client = ApiClient::from_config(&config);
+    /// ```
+    fn from_config(config: &AppConfig) -> Self {
+        let client_config = ApiClientConfig {
+            timeout: Duration::from_millis(config.timeout_ms),
+            min_request_interval: Duration::from_secs(1) / config.rate_per_sec.max(1),
+            default_cache_ttl: Duration::from_millis(config.cache_ttl_ms),
+            ..ApiClientConfig::default()
+        };
+        Self::with_config(&config.base_url, client_config)
+    }
 
-        // Rate limiting: ensure minimum time between requests
-        {
-            let mut last_request = self.rate_limiter.lock().unwrap();
-            let time_since_last = last_request.elapsed();
-            let min_interval = Duration::from_millis(100); // 10 requests per second max
+    /// # Function: with_circuit_breaker
+    ///
+    /// Routes every real HTTP request through a circuit breaker. Once the
+    /// server has failed `failure_threshold` times in a row, requests fail
+    /// fast with `ApiError::ServiceUnavailable` instead of reaching it.
+    /// Cache hits never touch the breaker.
+    ///
+    /// ## Arguments:
+    /// - `config`: Failure threshold and reset timeout for the breaker
+    ///
+    /// ## Returns:
+    /// - The client with the circuit breaker enabled
+    fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(Arc::new(CircuitBreaker::new(config)));
+        self
+    }
 
-            if time_since_last < min_interval {
-                let sleep_time = min_interval - time_since_last;
-                println!("⏱️  Rate limiting: waiting {:?}", sleep_time);
-                drop(last_request); // Release lock before sleeping
-                sleep(sleep_time).await;
-                *self.rate_limiter.lock().unwrap() = Instant::now();
-            } else {
-                *last_request = Instant::now();
-            }
-        }
+    /// Returns a receiver for the circuit breaker's state changes, or
+    /// `None` if the client has no circuit breaker.
+    fn subscribe_circuit(&self) -> Option<broadcast::Receiver<StateChange>> {
+        self.circuit_breaker
+            .as_ref()
+            .map(|breaker| breaker.subscribe())
+    }
 
-        // Make the HTTP request
-        println!("🌐 Making HTTP GET request to {}", endpoint);
-        let url = format!("{}/{}", self.base_url, endpoint);
+    /// # Function: with_default_headers
+    ///
+    /// Adds headers to every request the client sends (API keys, tracing
+    /// IDs, a custom `User-Agent`, ...). A header given to a single request
+    /// through `request_with_headers` replaces a default of the same name.
+    ///
+    /// ## Arguments:
+    /// - `headers`: The default headers, replacing any set before
+    ///
+    /// ## Returns:
+    /// - The client with the default headers set
+    fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers = headers;
+        self
+    }
 
-        let response = timeout(Duration::from_secs(10), self.client.get(&url).send())
-            .await
-            .context("Request timed out")?
-            .context("Failed to send HTTP request")?;
+    /// # Function: with_max_response_bytes
+    ///
+    /// Limits how large a response body may be. The body is read chunk by
+    /// chunk and the request fails as soon as the limit is passed, so a
+    /// hostile or broken server can't make the client buffer gigabytes.
+    ///
+    /// ## Arguments:
+    /// - `max`: Largest accepted body, in bytes
+    ///
+    /// ## Returns:
+    /// - The client with the limit set; an oversized body fails with
+    ///   `ApiError::ValidationError` for the field `body`
+    fn with_max_response_bytes(mut self, max: usize) -> Self {
+        self.max_response_bytes = Some(max);
+        self
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "HTTP request failed with status: {}",
-                response.status()
-            ));
-        }
+    /// Returns the default headers with `overrides` applied on top.
+    fn headers_for(&self, overrides: &HeaderMap) -> HeaderMap {
+        let mut headers = self.default_headers.clone();
+        // Extending with a `HeaderMap` replaces existing values by name
+        headers.extend(overrides.clone());
+        headers
+    }
 
-        let body = response
-            .text()
-            .await
-            .context("Failed to read response body")?;
+    /// # Function: with_rate_limit_jitter
+    ///
+    /// Randomizes each rate-limit sleep by up to `±fraction` of its length.
+    /// Without jitter, many clients started together wake up at the same
+    /// instants and hit the server in synchronized bursts (thundering herd).
+    ///
+    /// ## Arguments:
+    /// - `fraction`: Maximum relative deviation, clamped to `0.0..=1.0`
+    ///
+    /// ## Returns:
+    /// - The client with jitter enabled
+    fn with_rate_limit_jitter(mut self, fraction: f64) -> Self {
+        self.rate_limit_jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
 
-        // Cache the successful response
-        {
-            let mut cache = self.cache.lock().unwrap();
-            cache.insert(cache_key, (body.clone(), Instant::now()));
-        }
+    /// # Function: with_jitter_rng
+    ///
+    /// Draws the rate-limit jitter from `rng` instead of a generator from
+    /// `TestRng::from_run_seed()`, so the same seed gives the same sequence
+    /// of jitter factors.
+    ///
+    /// ## Arguments:
+    /// - `rng`: Source of the jitter factors
+    ///
+    /// ## Returns:
+    /// - The client with a reproducible jitter sequence. Clones share the
+    ///   generator.
+    pub fn with_jitter_rng(mut self, rng: TestRng) -> Self {
+        self.jitter = Arc::new(Mutex::new(Jitter {
+            rng,
+            factors: Vec::new(),
+        }));
+        self
+    }
 
-        Ok(body)
+    /// Returns the jitter factors applied to rate-limit sleeps so far.
+    pub fn chosen_jitter(&self) -> Vec<f64> {
+        self.jitter.lock().unwrap().factors.clone()
     }
 
-    /// # Function: get_users
+    /// # Function: get
     ///
-    /// Fetches all users from the API.
-    /// Demonstrates JSON deserialization and error handling.
+    /// Makes a cached GET request using the client's default cache TTL.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
     ///
     /// ## Returns:
-    /// - `AnyhowResult<Vec<User>>`: List of users or an error
+    /// - `AnyhowResult<String>`: The response body or an error
+    async fn get(&self, endpoint: &str) -> AnyhowResult<String> {
+        self.get_with_cache(endpoint, self.default_cache_ttl).await
+    }
+
+    /// # Function: export_metrics
     ///
-    /// ## Error Handling:
-    /// - Network errors are propagated with context
-    /// - JSON parsing errors include the problematic data
-    /// - Timeouts are handled gracefully
-    async fn get_users(&self) -> AnyhowResult<Vec<User>> {
-        let body = self
-            .get_with_cache("users", Duration::from_secs(300)) // Cache for 5 minutes
-            .await
-            .context("Failed to fetch users")?;
+    /// Exports request counts, cache hit/miss counters and the request
+    /// latency histogram as Prometheus exposition text, ready to be served
+    /// from a `/metrics` endpoint.
+    ///
+    /// ## Returns:
+    /// - `String`: The metrics in Prometheus text format
+    fn export_metrics(&self) -> String {
+        self.metrics.to_prometheus()
+    }
 
-        let users: Vec<User> = serde_json::from_str(&body).context("Failed to parse users JSON")?;
+    /// # Function: latency_stream
+    ///
+    /// Subscribes to the latency of every HTTP request this client (or any
+    /// clone of it) sends from now on. Cache hits are not included.
+    ///
+    /// ## Returns:
+    /// - A stream of request durations that ends once the client is dropped
+    fn latency_stream(&self) -> impl Stream<Item = Duration> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.latency_observers.lock().unwrap().push(sender);
+        receiver
+    }
 
-        println!("✅ Fetched {} users", users.len());
-        Ok(users)
+    /// # Function: start_keepalive
+    ///
+    /// Sends a HEAD request to `endpoint` every `interval` in a background
+    /// task, so pooled connections stay open through quiet periods instead
+    /// of being closed as idle. Pings bypass the cache, rate limiter,
+    /// circuit breaker and metrics; failures are logged and ignored.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: A cheap endpoint to ping (relative to base_url)
+    /// - `interval`: Time between pings; the first is sent immediately
+    ///
+    /// ## Returns:
+    /// - `KeepAliveHandle`: Pinging stops when it is dropped
+    fn start_keepalive(&self, endpoint: &str, interval: Duration) -> KeepAliveHandle {
+        let client = self.client.clone();
+        let url = format!("{}/{}", self.base_url, endpoint);
+        let headers = self.default_headers.clone();
+        let pings = Arc::new(AtomicU64::new(0));
+        let sent = pings.clone();
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                sent.fetch_add(1, Ordering::Relaxed);
+                match timeout(interval, client.head(&url).headers(headers.clone()).send()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(error)) => println!("💓 Keep-alive ping to {} failed: {}", url, error),
+                    Err(_) => println!("💓 Keep-alive ping to {} timed out", url),
+                }
+            }
+        });
+
+        KeepAliveHandle { task, pings }
     }
 
-    /// # Function: get_user_posts
+    /// # Function: get_with_cache
     ///
-    /// Fetches all posts for a specific user.
-    /// Shows how to make parameterized API calls.
+    /// Makes a GET request with caching support.
+    /// This demonstrates how to implement caching in async operations.
     ///
     /// ## Arguments:
-    /// - `user_id`: The ID of the user whose posts to fetch
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    /// - `cache_duration`: How long to cache the response
     ///
     /// ## Returns:
-    /// - `AnyhowResult<Vec<Post>>`: List of posts or an error
-    async fn get_user_posts(&self, user_id: u32) -> AnyhowResult<Vec<Post>> {
-        let endpoint = format!("users/{}/posts", user_id);
-        let body = self
-            .get_with_cache(&endpoint, Duration::from_secs(60)) // Cache for 1 minute
-            .await
-            .context(format!("Failed to fetch posts for user {}", user_id))?;
+    /// - `AnyhowResult<String>`: The response body or an error
+    ///
+    /// ## Caching Strategy:
+    /// - Checks cache first before making HTTP request
+    /// - Stores successful responses in memory cache
+    /// - Respects cache expiration times
+    /// - Falls back to fresh request if cache miss or expired
+    async fn get_with_cache(
+        &self,
+        endpoint: &str,
+        cache_duration: Duration,
+    ) -> AnyhowResult<String> {
+        let cache_key = format!("{}/{}", self.base_url, endpoint);
 
-        let posts: Vec<Post> = serde_json::from_str(&body).context("Failed to parse posts JSON")?;
+        // Check cache first
+        if let Some((cached_response, cached_at)) = self.cache.get(&cache_key) {
+            if cached_at.elapsed() < cache_duration {
+                println!("📦 Cache hit for {}", endpoint);
+                self.metrics.record_cache(true);
+                return Ok(cached_response);
+            }
+        }
 
-        println!("✅ Fetched {} posts for user {}", posts.len(), user_id);
-        Ok(posts)
+        self.metrics.record_cache(false);
+        self.fetch_and_cache(endpoint, cache_duration).await
     }
 
-    /// # Function: get_post_comments
+    /// # Function: get_swr
     ///
-    /// Fetches all comments for a specific post.
-    /// Demonstrates nested API calls and data relationships.
+    /// Makes a GET request using the stale-while-revalidate strategy.
+    /// This demonstrates how to trade freshness for latency by serving
+    /// cached data immediately and refreshing it in a background task.
     ///
     /// ## Arguments:
-    /// - `post_id`: The ID of the post whose comments to fetch
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    /// - `fresh_for`: How long a cached response is served without revalidation
+    /// - `stale_for`: How long a cached response may be served at all
     ///
     /// ## Returns:
-    /// - `AnyhowResult<Vec<Comment>>`: List of comments or an error
-    async fn get_post_comments(&self, post_id: u32) -> AnyhowResult<Vec<Comment>> {
-        let endpoint = format!("posts/{}/comments", post_id);
-        let body = self
-            .get_with_cache(&endpoint, Duration::from_secs(30)) // Cache for 30 seconds
-            .await
-            .context(format!("Failed to fetch comments for post {}", post_id))?;
+    /// - `AnyhowResult<String>`: The response body or an error
+    ///
+    /// ## Caching Strategy:
+    /// - Younger than `fresh_for`: return the cached response
+    /// - Younger than `stale_for`: return the cached response immediately and
+    ///   spawn a background refresh (at most one per endpoint at a time)
+    /// - Older than `stale_for` or missing: fetch synchronously
+    async fn get_swr(
+        &self,
+        endpoint: &str,
+        fresh_for: Duration,
+        stale_for: Duration,
+    ) -> AnyhowResult<String> {
+        let cache_key = format!("{}/{}", self.base_url, endpoint);
 
-        let comments: Vec<Comment> =
-            serde_json::from_str(&body).context("Failed to parse comments JSON")?;
+        let stale_response = match self.cache.get(&cache_key) {
+            Some((cached_response, cached_at)) if cached_at.elapsed() < fresh_for => {
+                println!("📦 Fresh cache hit for {}", endpoint);
+                self.metrics.record_cache(true);
+                return Ok(cached_response);
+            }
+            Some((cached_response, cached_at)) if cached_at.elapsed() < stale_for => {
+                Some(cached_response)
+            }
+            _ => None,
+        };
 
-        println!(
-            "✅ Fetched {} comments for post {}",
-            comments.len(),
-            post_id
-        );
-        Ok(comments)
+        // A stale response still answers the request from the cache
+        self.metrics.record_cache(stale_response.is_some());
+        let Some(stale_response) = stale_response else {
+            return self.fetch_and_cache(endpoint, stale_for).await;
+        };
+
+        // Only start a refresh if one isn't already running for this key
+        if let Some(guard) = RefreshGuard::try_acquire(&self.refreshing, &cache_key) {
+            println!("♻️  Serving stale {} while revalidating", endpoint);
+            let client = self.clone();
+            let endpoint = endpoint.to_string();
+            tokio::spawn(async move {
+                // Held until the refresh is over, however it ends
+                let _guard = guard;
+                if let Err(error) = client.fetch_and_cache(&endpoint, stale_for).await {
+                    println!("⚠️  Background refresh of {} failed: {}", endpoint, error);
+                }
+            });
+        } else {
+            println!("♻️  Serving stale {} (refresh already in flight)", endpoint);
+        }
+
+        Ok(stale_response)
     }
-}
 
-/// # Function: demonstrate_basic_http_operations
-///
-/// Demonstrates basic HTTP operations with async/await.
-/// Shows how to make simple API calls and handle responses.
-///
-/// ## Key Learning Points:
-/// - Making HTTP requests with reqwest
-/// - JSON deserialization with serde
-/// - Error handling for network operations
-/// - Working with structured data in async contexts
-async fn demonstrate_basic_http_operations() {
-    println!("\n=== Basic HTTP Operations ===");
+    /// # Function: fetch_and_cache
+    ///
+    /// Performs a rate-limited GET request and stores the body in the cache.
+    /// Shared by all caching strategies once they decide a fetch is needed.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    /// - `ttl`: How long the cache keeps the response
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<String>`: The response body or an error
+    ///   (`ApiError::ServiceUnavailable` if the circuit breaker is open)
+    async fn fetch_and_cache(&self, endpoint: &str, ttl: Duration) -> AnyhowResult<String> {
+        let cache_key = format!("{}/{}", self.base_url, endpoint);
+        let body = self.request(endpoint).await?.body;
 
-    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+        // Cache the successful response
+        self.cache
+            .insert_with_ttl(cache_key, (body.clone(), Instant::now()), ttl);
 
-    // Example 1: Fetch all users
-    println!("1. Fetching all users:");
-    match client.get_users().await {
-        Ok(users) => {
-            println!("   Successfully fetched {} users", users.len());
-            if let Some(first_user) = users.first() {
-                println!("   First user: {} ({})", first_user.name, first_user.email);
+        Ok(body)
+    }
+
+    /// # Function: request
+    ///
+    /// Sends a GET request through the circuit breaker and rate limiter,
+    /// recording its latency. Never consults the cache.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<ApiResponse<String>>`: The raw response or an error
+    ///   (`ApiError::ServiceUnavailable` if the circuit breaker is open)
+    async fn request(&self, endpoint: &str) -> AnyhowResult<ApiResponse<String>> {
+        self.request_with_headers(endpoint, HeaderMap::new()).await
+    }
+
+    /// # Function: request_with_headers
+    ///
+    /// Like `request`, with extra headers for this request only. They take
+    /// precedence over default headers of the same name.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    /// - `headers`: Headers to add to, or replace in, the defaults
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<ApiResponse<String>>`: The raw response or an error
+    async fn request_with_headers(
+        &self,
+        endpoint: &str,
+        headers: HeaderMap,
+    ) -> AnyhowResult<ApiResponse<String>> {
+        if let Some(breaker) = &self.circuit_breaker {
+            if !breaker.allow_request() {
+                println!("🚫 Circuit open, not requesting {}", endpoint);
+                return Err(ApiError::ServiceUnavailable.into());
             }
         }
-        Err(error) => {
-            println!("   Failed to fetch users: {}", error);
-        }
-    }
 
-    // Example 2: Fetch posts for a specific user
-    println!("\n2. Fetching posts for user 1:");
-    match client.get_user_posts(1).await {
-        Ok(posts) => {
-            println!("   Successfully fetched {} posts", posts.len());
-            if let Some(first_post) = posts.first() {
-                println!("   First post: \"{}\"", first_post.title);
-                println!(
-                    "   Body preview: {}...",
-                    first_post.body.chars().take(50).collect::<String>()
-                );
+        self.wait_for_rate_limit().await;
+
+        let started = Instant::now();
+        let result = self.send_get(endpoint, &headers).await;
+        let latency = started.elapsed();
+        self.metrics.record_request(latency, result.is_ok());
+        if let Err(error) = &result {
+            if let Some(ApiError::TimeoutError { .. }) = error.downcast_ref::<ApiError>() {
+                self.metrics.record_timeout();
             }
         }
-        Err(error) => {
-            println!("   Failed to fetch posts: {}", error);
+        // Forget subscribers whose stream has been dropped
+        self.latency_observers
+            .lock()
+            .unwrap()
+            .retain(|observer| observer.unbounded_send(latency).is_ok());
+        if let Some(breaker) = &self.circuit_breaker {
+            match &result {
+                Ok(_) => breaker.record_success(),
+                Err(_) => breaker.record_failure(),
+            }
         }
+        result
     }
 
-    // Example 3: Fetch comments for a specific post
-    println!("\n3. Fetching comments for post 1:");
-    match client.get_post_comments(1).await {
-        Ok(comments) => {
-            println!("   Successfully fetched {} comments", comments.len());
-            if let Some(first_comment) = comments.first() {
-                println!(
-                    "   First comment by: {} ({})",
-                    first_comment.name, first_comment.email
-                );
+    /// # Function: send_get
+    ///
+    /// Sends a single GET request and returns the successful response.
+    /// Non-2xx statuses are reported as errors.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    /// - `headers`: Per-request headers, applied over the defaults
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<ApiResponse<String>>`: Status, headers and body, or an error
+    ///   (`ApiError::TimeoutError` naming the phase that timed out)
+    ///
+    /// ## Note:
+    /// - On a timeout the in-flight reqwest future is dropped, which closes
+    ///   its connection instead of leaving it waiting in the pool
+    async fn send_get(
+        &self,
+        endpoint: &str,
+        headers: &HeaderMap,
+    ) -> AnyhowResult<ApiResponse<String>> {
+        let overall = self.timeouts.overall;
+        match timeout(overall, self.send_get_phases(endpoint, headers)).await {
+            Ok(result) => result,
+            Err(_) => Err(ApiError::TimeoutError {
+                duration: overall,
+                kind: TimeoutKind::Overall,
             }
-        }
-        Err(error) => {
-            println!("   Failed to fetch comments: {}", error);
+            .into()),
         }
     }
-}
 
-/// # Function: demonstrate_concurrent_api_calls
-///
-/// Demonstrates how to make multiple API calls concurrently.
+    /// The body of `send_get`, without the overall timeout
+    async fn send_get_phases(
+        &self,
+        endpoint: &str,
+        headers: &HeaderMap,
+    ) -> AnyhowResult<ApiResponse<String>> {
+        // Make the HTTP request
+        println!("🌐 Making HTTP GET request to {}", endpoint);
+        let url = format!("{}/{}", self.base_url, endpoint);
+
+        let response = self
+            .client
+            .get(&url)
+            .headers(self.headers_for(headers))
+            .send()
+            .await
+            .map_err(|error| self.classify_transport_error(error, "Failed to send HTTP request"))?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "HTTP request failed with status: {}",
+                response.status()
+            ));
+        }
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.as_str().to_string(), value)
+            })
+            .collect();
+        let body = match timeout(self.timeouts.read, self.read_body(response)).await {
+            Ok(body) => body?,
+            Err(_) => {
+                return Err(ApiError::TimeoutError {
+                    duration: self.timeouts.read,
+                    kind: TimeoutKind::Read,
+                }
+                .into())
+            }
+        };
+
+        Ok(ApiResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// # Function: read_body
+    ///
+    /// Reads a response body as text, enforcing `max_response_bytes`.
+    ///
+    /// ## Arguments:
+    /// - `response`: The response whose body to read
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<String>`: The body, or an error if reading failed or
+    ///   the body is too large
+    ///
+    /// ## Note:
+    /// - A `Content-Length` over the limit fails before any of the body is
+    ///   read; otherwise the chunks are counted as they arrive
+    async fn read_body(&self, mut response: Response) -> AnyhowResult<String> {
+        let read_error =
+            |error| self.classify_transport_error(error, "Failed to read response body");
+        let Some(max) = self.max_response_bytes else {
+            return response.text().await.map_err(read_error);
+        };
+        let too_large = || ApiError::ValidationError(ValidationError::new("body", "too large"));
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > max as u64)
+        {
+            return Err(too_large().into());
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(read_error)? {
+            if body.len() + chunk.len() > max {
+                // Dropping the response closes the connection mid-body
+                return Err(too_large().into());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// # Function: classify_transport_error
+    ///
+    /// Wraps a reqwest error in context: reqwest's own timeouts become an
+    /// `ApiError::TimeoutError` of the right kind, while the original error
+    /// stays in the chain as the cause. Other errors get `context`.
+    fn classify_transport_error(
+        &self,
+        error: reqwest::Error,
+        context: &'static str,
+    ) -> anyhow::Error {
+        let timeout_kind = match (error.is_timeout(), error.is_connect()) {
+            (true, true) => Some((TimeoutKind::Connect, self.timeouts.connect)),
+            (true, false) => Some((TimeoutKind::Overall, self.timeouts.overall)),
+            (false, _) => None,
+        };
+
+        match timeout_kind {
+            Some((kind, duration)) => {
+                anyhow::Error::new(error).context(ApiError::TimeoutError { duration, kind })
+            }
+            None => anyhow::Error::new(error).context(context),
+        }
+    }
+
+    /// # Function: wait_for_rate_limit
+    ///
+    /// Waits until the minimum interval since the previous request has passed.
+    /// The sleep is optionally jittered (see `with_rate_limit_jitter`).
+    ///
+    /// ## Key Implementation Details:
+    /// - The mutex guard is dropped before sleeping, never held across `.await`
+    /// - Jitter scales the computed sleep by a factor in `1 ± jitter`
+    async fn wait_for_rate_limit(&self) {
+        // Rate limiting: ensure minimum time between requests
+        let sleep_time = {
+            let mut last_request = self.rate_limiter.lock().unwrap();
+            let time_since_last = last_request.elapsed();
+            let min_interval = self.min_request_interval;
+
+            if time_since_last < min_interval {
+                Some(min_interval - time_since_last)
+            } else {
+                *last_request = Instant::now();
+                None
+            }
+        }; // Lock is released here, before any sleeping
+
+        if let Some(mut sleep_time) = sleep_time {
+            if self.rate_limit_jitter > 0.0 {
+                let factor = {
+                    let mut jitter = self.jitter.lock().unwrap();
+                    // Uniform in [-1.0, 1.0)
+                    let offset = jitter.rng.next_f64() * 2.0 - 1.0;
+                    let factor = 1.0 + offset * self.rate_limit_jitter;
+                    jitter.factors.push(factor);
+                    factor
+                };
+                sleep_time = sleep_time.mul_f64(factor);
+            }
+
+            println!("⏱️  Rate limiting: waiting {:?}", sleep_time);
+            sleep(sleep_time).await;
+            *self.rate_limiter.lock().unwrap() = Instant::now();
+        }
+    }
+
+    /// # Function: get_json_validated
+    ///
+    /// Fetches an endpoint, deserializes the JSON body and validates the
+    /// result before handing it to the caller.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    /// - `cache_duration`: How long the raw response may be served from cache
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<T>`: The validated value, or an error
+    ///   (`ApiError::ValidationError` if the data fails validation)
+    async fn get_json_validated<T: DeserializeOwned + Validate>(
+        &self,
+        endpoint: &str,
+        cache_duration: Duration,
+    ) -> AnyhowResult<T> {
+        let body = self.get_with_cache(endpoint, cache_duration).await?;
+        let value: T = serde_json::from_str(&body)
+            .with_context(|| format!("Failed to parse JSON from {}", endpoint))?;
+
+        value.validate().map_err(ApiError::from)?;
+        Ok(value)
+    }
+
+    /// # Function: get_full
+    ///
+    /// Fetches and deserializes an endpoint, keeping the HTTP status and
+    /// headers. Always sends a request: the cache only stores bodies, so it
+    /// is bypassed (and not updated) here.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<ApiResponse<T>>`: The parsed body with its status and headers
+    async fn get_full<T: DeserializeOwned>(&self, endpoint: &str) -> AnyhowResult<ApiResponse<T>> {
+        let response = self.request(endpoint).await?;
+        let body = serde_json::from_str(&response.body)
+            .with_context(|| format!("Failed to parse JSON from {}", endpoint))?;
+
+        Ok(ApiResponse {
+            status: response.status,
+            headers: response.headers,
+            body,
+        })
+    }
+
+    /// # Function: get_many
+    ///
+    /// Fetches and deserializes many endpoints concurrently, with at most
+    /// `concurrency` requests in flight. Every request goes through `get`,
+    /// so caching and rate limiting apply as usual.
+    ///
+    /// ## Arguments:
+    /// - `endpoints`: The API endpoints to call (relative to base_url)
+    /// - `concurrency`: Maximum number of simultaneous requests (at least 1)
+    ///
+    /// ## Returns:
+    /// - `Vec<AnyhowResult<T>>`: One result per endpoint, in input order;
+    ///   a failing endpoint does not affect the others
+    async fn get_many<T: DeserializeOwned>(
+        &self,
+        endpoints: Vec<String>,
+        concurrency: usize,
+    ) -> Vec<AnyhowResult<T>> {
+        futures::stream::iter(endpoints)
+            .map(|endpoint| async move {
+                let body = self.get(&endpoint).await?;
+                serde_json::from_str(&body)
+                    .with_context(|| format!("Failed to parse JSON from {}", endpoint))
+            })
+            // `buffered` (unlike `buffer_unordered`) yields in input order
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// # Function: get_json_array_stream
+    ///
+    /// Fetches an endpoint returning a JSON array and yields its elements as
+    /// they arrive, instead of buffering the whole body like `get_users`.
+    /// Memory use stays bounded however large the array is.
+    ///
+    /// ## Arguments:
+    /// - `endpoint`: The API endpoint to call (relative to base_url)
+    ///
+    /// ## Returns:
+    /// - A stream of parsed elements. It ends after the first error
+    ///   (network, timeout or malformed JSON), which is yielded as its
+    ///   last item.
+    ///
+    /// ## Note:
+    /// - The request is rate limited, but bypasses the cache, circuit
+    ///   breaker and metrics, which all deal in whole bodies
+    /// - Each chunk must arrive within the read timeout
+    fn get_json_array_stream<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+    ) -> impl Stream<Item = AnyhowResult<T>> + '_ {
+        let endpoint = endpoint.to_string();
+        let state = Some((None, JsonArrayParser::default()));
+
+        futures::stream::unfold(state, move |state| {
+            let endpoint = endpoint.clone();
+            async move {
+                let (mut response, mut parser) = state?;
+                match self
+                    .next_array_item(&endpoint, &mut response, &mut parser)
+                    .await
+                {
+                    Ok(Some(item)) => Some((Ok(item), Some((response, parser)))),
+                    Ok(None) => None,
+                    Err(error) => Some((Err(error), None)),
+                }
+            }
+        })
+    }
+
+    /// Reads until `parser` has the next element of `endpoint`'s array,
+    /// sending the request first if `response` is still `None`.
+    async fn next_array_item<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        response: &mut Option<reqwest::Response>,
+        parser: &mut JsonArrayParser,
+    ) -> AnyhowResult<Option<T>> {
+        let response = match response {
+            Some(response) => response,
+            None => response.insert(self.open_stream(endpoint).await?),
+        };
+
+        loop {
+            match parser.next_item()? {
+                ArrayStep::Item(item) => return Ok(Some(item)),
+                ArrayStep::End => return Ok(None),
+                ArrayStep::NeedMore => {}
+            }
+
+            match timeout(self.timeouts.read, response.chunk()).await {
+                Ok(Ok(Some(chunk))) => parser.extend(&chunk),
+                Ok(Ok(None)) => parser.finish(),
+                Ok(Err(error)) => {
+                    return Err(self.classify_transport_error(error, "Failed to read response body"))
+                }
+                Err(_) => {
+                    return Err(ApiError::TimeoutError {
+                        duration: self.timeouts.read,
+                        kind: TimeoutKind::Read,
+                    }
+                    .into())
+                }
+            }
+        }
+    }
+
+    /// Sends a GET request and returns the response with its body unread.
+    async fn open_stream(&self, endpoint: &str) -> AnyhowResult<reqwest::Response> {
+        self.wait_for_rate_limit().await;
+
+        println!("🌐 Streaming HTTP GET request to {}", endpoint);
+        let url = format!("{}/{}", self.base_url, endpoint);
+        let response = self
+            .client
+            .get(&url)
+            .headers(self.default_headers.clone())
+            .send()
+            .await
+            .map_err(|error| self.classify_transport_error(error, "Failed to send HTTP request"))?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "HTTP request failed with status: {}",
+                response.status()
+            ));
+        }
+        Ok(response)
+    }
+
+    /// # Function: get_users
+    ///
+    /// Fetches all users from the API.
+    /// Demonstrates JSON deserialization and error handling.
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<Vec<User>>`: List of users or an error
+    ///
+    /// ## Error Handling:
+    /// - Network errors are propagated with context
+    /// - JSON parsing errors include the problematic data
+    /// - Timeouts are handled gracefully
+    async fn get_users(&self) -> AnyhowResult<Vec<User>> {
+        let body = self
+            .get_with_cache("users", Duration::from_secs(300)) // Cache for 5 minutes
+            .await
+            .context("Failed to fetch users")?;
+
+        let users: Vec<User> = serde_json::from_str(&body).context("Failed to parse users JSON")?;
+
+        println!("✅ Fetched {} users", users.len());
+        Ok(users)
+    }
+
+    /// # Function: get_user_posts
+    ///
+    /// Fetches all posts for a specific user.
+    /// Shows how to make parameterized API calls.
+    ///
+    /// ## Arguments:
+    /// - `user_id`: The ID of the user whose posts to fetch
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<Vec<Post>>`: List of posts or an error
+    async fn get_user_posts(&self, user_id: u32) -> AnyhowResult<Vec<Post>> {
+        let endpoint = format!("users/{}/posts", user_id);
+        let body = self
+            .get_with_cache(&endpoint, Duration::from_secs(60)) // Cache for 1 minute
+            .await
+            .context(format!("Failed to fetch posts for user {}", user_id))?;
+
+        let posts: Vec<Post> = serde_json::from_str(&body).context("Failed to parse posts JSON")?;
+
+        println!("✅ Fetched {} posts for user {}", posts.len(), user_id);
+        Ok(posts)
+    }
+
+    /// # Function: get_post_comments
+    ///
+    /// Fetches all comments for a specific post.
+    /// Demonstrates nested API calls and data relationships.
+    ///
+    /// ## Arguments:
+    /// - `post_id`: The ID of the post whose comments to fetch
+    ///
+    /// ## Returns:
+    /// - `AnyhowResult<Vec<Comment>>`: List of comments or an error
+    async fn get_post_comments(&self, post_id: u32) -> AnyhowResult<Vec<Comment>> {
+        let endpoint = format!("posts/{}/comments", post_id);
+        let body = self
+            .get_with_cache(&endpoint, Duration::from_secs(30)) // Cache for 30 seconds
+            .await
+            .context(format!("Failed to fetch comments for post {}", post_id))?;
+
+        let comments: Vec<Comment> =
+            serde_json::from_str(&body).context("Failed to parse comments JSON")?;
+
+        println!(
+            "✅ Fetched {} comments for post {}",
+            comments.len(),
+            post_id
+        );
+        Ok(comments)
+    }
+}
+
+/// A memoized call: every caller awaiting the same key polls one shared future
+pub type CachedFuture<V> = Shared<BoxFuture<'static, V>>;
+
+/// # Function: retry_round_robin
+///
+/// Fetches and deserializes JSON, moving on to the next endpoint after
+/// each failure instead of retrying the same one. When the endpoints are
+/// mirrors of each other, one bad host then costs a single attempt rather
+/// than the whole retry budget. Requests bypass the cache.
+///
+/// ## Arguments:
+/// - `endpoints`: Equivalent endpoints (relative to the client's base_url),
+///   tried in order and wrapping around
+/// - `client`: The client used for every attempt
+/// - `max_attempts`: Total attempts across all endpoints
+///
+/// ## Returns:
+/// - `AnyhowResult<T>`: The first successful result, or the last error
+///
+/// ## Retry Strategy:
+/// - The next endpoint is tried immediately, since it is a different host
+/// - After every full round the wait doubles, starting at 100ms, so a
+///   failure of all mirrors at once isn't hammered
+async fn retry_round_robin<T: DeserializeOwned>(
+    endpoints: Vec<String>,
+    client: &ApiClient,
+    max_attempts: usize,
+) -> AnyhowResult<T> {
+    if endpoints.is_empty() {
+        anyhow::bail!("No endpoints to try");
+    }
+
+    let mut last_error = anyhow::anyhow!("No attempts made (max_attempts is 0)");
+    for attempt in 0..max_attempts {
+        let round = attempt / endpoints.len();
+        if attempt > 0 && attempt % endpoints.len() == 0 {
+            sleep(Duration::from_millis(100) * 2u32.pow(round as u32 - 1)).await;
+        }
+
+        let endpoint = &endpoints[attempt % endpoints.len()];
+        match client.get_full(endpoint).await {
+            Ok(response) => return Ok(response.body),
+            Err(error) => {
+                println!(
+                    "🔁 Attempt {} via {} failed: {:#}",
+                    attempt + 1,
+                    endpoint,
+                    error
+                );
+                last_error = error;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// # Function: cached
+///
+/// Wraps an async function in a read-through cache. This is the
+/// function-level analog of `ApiClient::get_with_cache`: any async
+/// computation keyed by `K` can be memoized the same way.
+///
+/// ## Arguments:
+/// - `ttl`: How long a result is reused, measured from when its computation started
+/// - `f`: The async function to memoize
+///
+/// ## Returns:
+/// - A function with the same shape as `f` whose results are cached per key
+///
+/// ## Caching Strategy:
+/// - **Single-flight**: concurrent calls for a key that is still being
+///   computed await the same in-flight future instead of starting another
+/// - Results (including errors, if `V` is a `Result`) are reused until `ttl` passes
+/// - Expired entries are dropped whenever a new computation is stored, so
+///   keys that are never asked for again don't pile up
+/// - The computation runs when first awaited, like any other future
+pub fn cached<K, V, F, Fut>(ttl: Duration, f: F) -> impl Fn(K) -> CachedFuture<V>
+where
+    K: Eq + std::hash::Hash + Clone,
+    V: Clone + Send + Sync + 'static,
+    F: Fn(K) -> Fut,
+    Fut: std::future::Future<Output = V> + Send + 'static,
+{
+    let entries: Mutex<HashMap<K, (CachedFuture<V>, Instant)>> = Mutex::new(HashMap::new());
+
+    move |key: K| {
+        let mut entries = entries.lock().unwrap();
+
+        if let Some((future, started_at)) = entries.get(&key) {
+            if started_at.elapsed() < ttl {
+                return future.clone();
+            }
+        }
+
+        entries.retain(|_, (_, started_at)| started_at.elapsed() < ttl);
+        let future = f(key.clone()).boxed().shared();
+        entries.insert(key, (future.clone(), Instant::now()));
+        future
+    }
+}
+
+/// # Struct: ReconnectingStream
+///
+/// Keeps a long-lived stream (a feed, a websocket, a server-sent events
+/// connection) flowing across dropped connections. Whenever the current
+/// connection yields an error or ends, it waits a backoff delay, asks
+/// `make` for a fresh connection and carries on yielding items, so the
+/// consumer sees one continuous stream.
+///
+/// ## Fields:
+/// - `make`: Opens a new underlying connection
+/// - `backoff`: Delay before the first reconnect; doubled for each
+///   consecutive reconnect that produces no items (up to 32x)
+/// - `current`: The connection currently being read
+/// - `reconnect_delay`: Pending backoff timer while reconnecting
+/// - `failed_attempts`: Reconnects since the last successfully received item
+/// - `reconnect_count`: Total number of reconnects so far
+///
+/// ## Note:
+/// - The stream never ends on its own; bound it with `take` or drop it
+/// - Errors are logged and trigger a reconnect rather than being yielded
+struct ReconnectingStream<F, S> {
+    make: F,
+    backoff: Duration,
+    current: Pin<Box<S>>,
+    reconnect_delay: Option<Pin<Box<tokio::time::Sleep>>>,
+    failed_attempts: u32,
+    reconnect_count: usize,
+}
+
+impl<F, S, T, E> ReconnectingStream<F, S>
+where
+    F: FnMut() -> S,
+    S: Stream<Item = Result<T, E>>,
+{
+    /// # Function: new
+    ///
+    /// Opens the first connection right away.
+    ///
+    /// ## Arguments:
+    /// - `make`: Factory for fresh connections
+    /// - `backoff`: Base delay between a dropped connection and the next one
+    fn new(mut make: F, backoff: Duration) -> Self {
+        let current = Box::pin(make());
+        Self {
+            make,
+            backoff,
+            current,
+            reconnect_delay: None,
+            failed_attempts: 0,
+            reconnect_count: 0,
+        }
+    }
+
+    /// Returns how many times the stream has reconnected.
+    fn reconnect_count(&self) -> usize {
+        self.reconnect_count
+    }
+}
+
+impl<F, S, T, E> Stream for ReconnectingStream<F, S>
+where
+    F: FnMut() -> S + Unpin,
+    S: Stream<Item = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<T>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(delay) = this.reconnect_delay.as_mut() {
+                ready!(delay.as_mut().poll(cx));
+                this.reconnect_delay = None;
+                this.current = Box::pin((this.make)());
+                this.reconnect_count += 1;
+            }
+
+            match ready!(this.current.as_mut().poll_next(cx)) {
+                Some(Ok(item)) => {
+                    this.failed_attempts = 0;
+                    return Poll::Ready(Some(item));
+                }
+                Some(Err(error)) => println!("🔄 Stream error: {}; reconnecting", error),
+                None => println!("🔄 Stream ended; reconnecting"),
+            }
+
+            let delay = this.backoff * 2u32.pow(this.failed_attempts.min(5));
+            this.failed_attempts += 1;
+            this.reconnect_delay = Some(Box::pin(sleep(delay)));
+        }
+    }
+}
+
+/// # Struct: WorkQueue
+///
+/// A fixed pool of worker tasks fed through a bounded queue, for background
+/// jobs such as sending emails or resizing images. Producers slow down
+/// when the queue is full instead of piling up unbounded work.
+///
+/// ## Fields:
+/// - `jobs`: Sending half of the queue; dropping it tells workers to stop
+/// - `workers`: The worker tasks, awaited by `drain`
+///
+/// ## Note:
+/// - Workers share one receiver behind an async mutex. A worker only holds
+///   the lock while waiting for the next job, never while handling it, so
+///   jobs are still processed in parallel.
+struct WorkQueue<T> {
+    jobs: tokio::sync::mpsc::Sender<T>,
+    workers: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> WorkQueue<T> {
+    /// # Function: new
+    ///
+    /// Starts `workers` tasks that run `handler` on each submitted job.
+    ///
+    /// ## Arguments:
+    /// - `capacity`: Jobs that can wait in the queue before `submit` blocks
+    /// - `workers`: Number of jobs handled at the same time
+    /// - `handler`: Processes a single job
+    ///
+    /// ## Panics:
+    /// - If `capacity` or `workers` is zero
+    fn new<H, Fut>(capacity: usize, workers: usize, handler: H) -> Self
+    where
+        H: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        assert!(workers > 0, "a work queue needs at least one worker");
+        let (jobs, receiver) = tokio::sync::mpsc::channel(capacity);
+        let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+        let handler = Arc::new(handler);
+
+        let workers = (0..workers)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    loop {
+                        // The guard is a temporary: it is released as soon
+                        // as a job (or the end of the queue) is received
+                        let job = receiver.lock().await.recv().await;
+                        match job {
+                            Some(job) => handler(job).await,
+                            None => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self { jobs, workers }
+    }
+
+    /// # Function: submit
+    ///
+    /// Queues a job, waiting for room if the queue is full.
+    ///
+    /// ## Panics:
+    /// - If every worker has stopped because its handler panicked
+    async fn submit(&self, job: T) {
+        if self.jobs.send(job).await.is_err() {
+            panic!("every work queue worker has stopped");
+        }
+    }
+
+    /// # Function: drain
+    ///
+    /// Stops accepting jobs and waits until every queued and in-flight job
+    /// has been handled. Consuming the queue makes a `submit` after the
+    /// drain impossible.
+    ///
+    /// ## Panics:
+    /// - Re-raises the panic of a handler that panicked
+    async fn drain(self) {
+        drop(self.jobs);
+        for worker in self.workers {
+            if let Err(error) = worker.await {
+                if error.is_panic() {
+                    std::panic::resume_unwind(error.into_panic());
+                }
+            }
+        }
+    }
+}
+
+/// # Function: demonstrate_basic_http_operations
+///
+/// Demonstrates basic HTTP operations with async/await.
+/// Shows how to make simple API calls and handle responses.
+///
+/// ## Key Learning Points:
+/// - Making HTTP requests with reqwest
+/// - JSON deserialization with serde
+/// - Error handling for network operations
+/// - Working with structured data in async contexts
+async fn demonstrate_basic_http_operations() {
+    println!("\n=== Basic HTTP Operations ===");
+
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+
+    // Example 1: Fetch all users
+    println!("1. Fetching all users:");
+    match client.get_users().await {
+        Ok(users) => {
+            println!("   Successfully fetched {} users", users.len());
+            if let Some(first_user) = users.first() {
+                println!("   First user: {} ({})", first_user.name, first_user.email);
+            }
+        }
+        Err(error) => {
+            println!("   Failed to fetch users: {}", error);
+        }
+    }
+
+    // Example 2: Fetch posts for a specific user
+    println!("\n2. Fetching posts for user 1:");
+    match client.get_user_posts(1).await {
+        Ok(posts) => {
+            println!("   Successfully fetched {} posts", posts.len());
+            if let Some(first_post) = posts.first() {
+                println!("   First post: \"{}\"", first_post.title);
+                println!(
+                    "   Body preview: {}...",
+                    first_post.body.chars().take(50).collect::<String>()
+                );
+            }
+        }
+        Err(error) => {
+            println!("   Failed to fetch posts: {}", error);
+        }
+    }
+
+    // Example 3: Fetch comments for a specific post
+    println!("\n3. Fetching comments for post 1:");
+    match client.get_post_comments(1).await {
+        Ok(comments) => {
+            println!("   Successfully fetched {} comments", comments.len());
+            if let Some(first_comment) = comments.first() {
+                println!(
+                    "   First comment by: {} ({})",
+                    first_comment.name, first_comment.email
+                );
+            }
+        }
+        Err(error) => {
+            println!("   Failed to fetch comments: {}", error);
+        }
+    }
+
+    // Example 4: Deserialize and validate a single user
+    println!("\n4. Fetching a validated user:");
+    match client
+        .get_json_validated::<User>("users/1", Duration::from_secs(300))
+        .await
+    {
+        Ok(user) => println!("   Valid user: {} ({})", user.name, user.email),
+        Err(error) => println!("   Failed to fetch a valid user: {}", error),
+    }
+
+    // Example 5: Keep the status and headers alongside the parsed body
+    println!("\n5. Fetching a user with status and headers:");
+    match client.get_full::<User>("users/2").await {
+        Ok(response) => {
+            println!("   Status: {}", response.status);
+            if let Some(content_type) = response.headers.get("content-type") {
+                println!("   Content-Type: {}", content_type);
+            }
+            println!("   User: {}", response.body.name);
+        }
+        Err(error) => println!("   Failed to fetch user: {}", error),
+    }
+
+    // Example 6: Parse a large array element by element as it downloads
+    println!("\n6. Streaming all comments:");
+    let comments = client.get_json_array_stream::<Comment>("comments");
+    futures::pin_mut!(comments);
+    let mut count = 0;
+    while let Some(comment) = comments.next().await {
+        match comment {
+            Ok(_) => count += 1,
+            Err(error) => {
+                println!("   Stream failed after {} comments: {:#}", count, error);
+                break;
+            }
+        }
+    }
+    println!("   Streamed {} comments", count);
+
+    // Example 7: Headers on every request, overridable per request
+    println!("\n7. Default headers with a per-request override:");
+    let mut defaults = HeaderMap::new();
+    defaults.insert("x-client-name", HeaderValue::from_static("future-tutorial"));
+    defaults.insert(
+        reqwest::header::ACCEPT,
+        HeaderValue::from_static("application/json"),
+    );
+    let client = client.clone().with_default_headers(defaults);
+    let mut overrides = HeaderMap::new();
+    overrides.insert(reqwest::header::ACCEPT, HeaderValue::from_static("*/*"));
+    match client.request_with_headers("users/3", overrides).await {
+        Ok(response) => println!("   Status: {}", response.status),
+        Err(error) => println!("   Request failed: {}", error),
+    }
+
+    // Example 8: Refuse bodies larger than expected
+    println!("\n8. Capping the response size at 1 KiB:");
+    let capped = client.clone().with_max_response_bytes(1024);
+    for endpoint in ["users/1", "photos"] {
+        match capped.request(endpoint).await {
+            Ok(response) => println!("   {}: {} bytes", endpoint, response.body.len()),
+            Err(error) => println!("   {}: {}", endpoint, error),
+        }
+    }
+}
+
+/// # Function: demonstrate_concurrent_api_calls
+///
+/// Demonstrates how to make multiple API calls concurrently.
 /// Shows the performance benefits of async programming for I/O-bound operations.
 ///
-/// ## Key Learning Points:
-/// - Using tokio::join! for concurrent operations
-/// - Measuring performance improvements
-/// - Handling partial failures in concurrent operations
-/// - Aggregating data from multiple sources
-async fn demonstrate_concurrent_api_calls() {
-    println!("\n=== Concurrent API Calls ===");
+/// ## Key Learning Points:
+/// - Using tokio::join! for concurrent operations
+/// - Measuring performance improvements
+/// - Handling partial failures in concurrent operations
+/// - Aggregating data from multiple sources
+async fn demonstrate_concurrent_api_calls() {
+    println!("\n=== Concurrent API Calls ===");
+
+    // Jitter keeps many clients started together from waking in lockstep
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com").with_rate_limit_jitter(0.2);
+
+    // Example 1: Sequential vs Concurrent comparison
+    println!("1. Performance comparison - Sequential vs Concurrent:");
+
+    // Sequential approach
+    println!("   Sequential approach:");
+    let sequential_start = Instant::now();
+
+    let _users = client.get_users().await.unwrap_or_default();
+    let _posts_1 = client.get_user_posts(1).await.unwrap_or_default();
+    let _posts_2 = client.get_user_posts(2).await.unwrap_or_default();
+    let _posts_3 = client.get_user_posts(3).await.unwrap_or_default();
+
+    let sequential_time = sequential_start.elapsed();
+    println!("     Sequential time: {:?}", sequential_time);
+
+    // Concurrent approach
+    println!("   Concurrent approach:");
+    let concurrent_start = Instant::now();
+
+    let users_future = client.get_users();
+    let posts_1_future = client.get_user_posts(1);
+    let posts_2_future = client.get_user_posts(2);
+    let posts_3_future = client.get_user_posts(3);
+
+    let (users_result, posts_1_result, posts_2_result, posts_3_result) =
+        tokio::join!(users_future, posts_1_future, posts_2_future, posts_3_future);
+
+    let concurrent_time = concurrent_start.elapsed();
+    println!("     Concurrent time: {:?}", concurrent_time);
+
+    // Calculate speedup
+    let speedup = sequential_time.as_millis() as f64 / concurrent_time.as_millis() as f64;
+    println!("     Speedup: {:.2}x faster", speedup);
+
+    // Handle results
+    let users = users_result.unwrap_or_default();
+    let posts_1 = posts_1_result.unwrap_or_default();
+    let posts_2 = posts_2_result.unwrap_or_default();
+    let posts_3 = posts_3_result.unwrap_or_default();
+
+    println!(
+        "     Results: {} users, {} + {} + {} posts",
+        users.len(),
+        posts_1.len(),
+        posts_2.len(),
+        posts_3.len()
+    );
+
+    // Example 2: Fetching data for multiple users concurrently
+    println!("\n2. Fetching posts for multiple users concurrently:");
+    let start = Instant::now();
+
+    // One endpoint per user, fetched at most three at a time
+    let user_ids = [1, 2, 3, 4, 5];
+    let endpoints = user_ids
+        .iter()
+        .map(|user_id| format!("users/{}/posts", user_id))
+        .collect();
+
+    let results: Vec<AnyhowResult<Vec<Post>>> = client.get_many(endpoints, 3).await;
+    let elapsed = start.elapsed();
+
+    // Process results
+    let mut total_posts = 0;
+    let mut successful_users = 0;
+
+    for (i, result) in results.iter().enumerate() {
+        match result {
+            Ok(posts) => {
+                total_posts += posts.len();
+                successful_users += 1;
+                println!("     User {}: {} posts", user_ids[i], posts.len());
+            }
+            Err(error) => {
+                println!("     User {}: Error - {}", user_ids[i], error);
+            }
+        }
+    }
+
+    println!(
+        "     Summary: {} total posts from {} users in {:?}",
+        total_posts, successful_users, elapsed
+    );
+
+    // Example 3: Background jobs on a fixed number of workers
+    println!("\n3. Sending notifications through a work queue:");
+    let start = Instant::now();
+    let queue = WorkQueue::new(4, 3, |user_id: u32| async move {
+        // Simulate sending a notification email
+        sleep(Duration::from_millis(50)).await;
+        println!("     Notified user {}", user_id);
+    });
+    for user_id in 1..=6 {
+        queue.submit(user_id).await;
+    }
+    queue.drain().await;
+    println!("     All notifications sent in {:?}", start.elapsed());
+}
+
+/// # Function: build_user_profile
+///
+/// Builds a complete user profile by fetching data from multiple endpoints.
+/// This demonstrates a real-world async workflow with multiple dependencies.
+///
+/// ## Workflow:
+/// 1. Fetch user basic information
+/// 2. Fetch all posts by the user
+/// 3. For each post, fetch its comments (concurrently)
+/// 4. Aggregate all data into a UserProfile
+///
+/// ## Arguments:
+/// - `client`: The API client to use for requests
+/// - `user_id`: The ID of the user to build a profile for
+///
+/// ## Returns:
+/// - `AnyhowResult<UserProfile>`: Complete user profile or error
+async fn build_user_profile(client: &ApiClient, user_id: u32) -> AnyhowResult<UserProfile> {
+    let start_time = Instant::now();
+
+    println!("   Building profile for user {}...", user_id);
+
+    // Step 1: Get user basic info and their posts concurrently
+    let (user_result, posts_result) = tokio::join!(
+        async {
+            let users = client.get_users().await?;
+            users
+                .into_iter()
+                .find(|u| u.id == user_id)
+                .ok_or_else(|| anyhow::anyhow!("User {} not found", user_id))
+        },
+        client.get_user_posts(user_id)
+    );
+
+    let user = user_result.context("Failed to fetch user info")?;
+    let posts = posts_result.context("Failed to fetch user posts")?;
+
+    // Step 2: Fetch comments for all posts concurrently
+    let comment_futures: Vec<_> = posts
+        .iter()
+        .map(|post| client.get_post_comments(post.id))
+        .collect();
+
+    let comment_results = futures::future::join_all(comment_futures).await;
+
+    // Step 3: Count total comments (ignoring failed requests)
+    let total_comments: u32 = comment_results
+        .into_iter()
+        .filter_map(|result| result.ok())
+        .map(|comments| comments.len() as u32)
+        .sum();
+
+    let fetch_time = start_time.elapsed();
+
+    Ok(UserProfile {
+        user,
+        posts,
+        total_comments,
+        fetch_time,
+    })
+}
+
+/// # Function: profile_stream
+///
+/// Builds profiles for many users with at most `concurrency` in progress at
+/// once, yielding each profile as soon as it is done. A slow user no longer
+/// holds back the ones behind it, as it does when collecting with `join_all`.
+///
+/// ## Arguments:
+/// - `client`: The API client to use for requests
+/// - `user_ids`: The users to build profiles for
+/// - `concurrency`: Maximum number of profiles being built at once (at least 1)
+///
+/// ## Returns:
+/// - A stream with one result per user, in completion order; a failing
+///   user does not affect the others
+fn profile_stream(
+    client: &ApiClient,
+    user_ids: Vec<u32>,
+    concurrency: usize,
+) -> impl Stream<Item = AnyhowResult<UserProfile>> + '_ {
+    futures::stream::iter(user_ids)
+        .map(move |user_id| build_user_profile(client, user_id))
+        // Unlike `get_many`, hand results out in the order they finish
+        .buffer_unordered(concurrency.max(1))
+}
+
+/// # Function: demonstrate_user_profile_aggregation
+///
+/// Demonstrates a complex real-world scenario: building a complete user profile
+/// by aggregating data from multiple API endpoints.
+///
+/// ## Key Learning Points:
+/// - Orchestrating multiple dependent async operations
+/// - Building complex data structures from API responses
+/// - Error handling in multi-step operations
+/// - Performance optimization with concurrent requests
+async fn demonstrate_user_profile_aggregation() {
+    println!("\n=== User Profile Aggregation ===");
+
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+
+    // Example 1: Build profile for a single user
+    println!("1. Building profile for user 1:");
+    match build_user_profile(&client, 1).await {
+        Ok(profile) => {
+            println!("   ✅ Profile completed in {:?}", profile.fetch_time);
+            println!("   User: {} ({})", profile.user.name, profile.user.email);
+            println!("   Posts: {}", profile.posts.len());
+            println!("   Total comments: {}", profile.total_comments);
+
+            if let Some(most_commented_post) = profile.posts.first() {
+                println!("   Sample post: \"{}\"", most_commented_post.title);
+            }
+        }
+        Err(error) => {
+            println!("   ❌ Failed to build profile: {}", error);
+        }
+    }
+
+    // Example 2: Build profiles for multiple users concurrently
+    println!("\n2. Building profiles for multiple users:");
+    let start = Instant::now();
+
+    let user_ids = [1, 2, 3];
+    let profile_futures: Vec<_> = user_ids
+        .iter()
+        .map(|&user_id| build_user_profile(&client, user_id))
+        .collect();
+
+    let profile_results = futures::future::join_all(profile_futures).await;
+    let total_time = start.elapsed();
+
+    let mut successful_profiles = 0;
+    let mut total_posts = 0;
+    let mut total_comments = 0;
+
+    for (i, result) in profile_results.iter().enumerate() {
+        match result {
+            Ok(profile) => {
+                successful_profiles += 1;
+                total_posts += profile.posts.len();
+                total_comments += profile.total_comments;
+                println!(
+                    "   User {}: {} posts, {} comments (built in {:?})",
+                    user_ids[i],
+                    profile.posts.len(),
+                    profile.total_comments,
+                    profile.fetch_time
+                );
+            }
+            Err(error) => {
+                println!("   User {}: Failed - {}", user_ids[i], error);
+            }
+        }
+    }
+
+    println!(
+        "   Summary: {} profiles built in {:?}",
+        successful_profiles, total_time
+    );
+    println!(
+        "   Totals: {} posts, {} comments",
+        total_posts, total_comments
+    );
+
+    // Example 3: Stream profiles as they finish
+    println!("\n3. Streaming profiles as they finish (2 at a time):");
+    let start = Instant::now();
+    let mut profiles = std::pin::pin!(profile_stream(&client, vec![4, 5, 6, 7], 2));
+    while let Some(result) = profiles.next().await {
+        match result {
+            Ok(profile) => println!(
+                "   {:?}: user {} ready ({} posts)",
+                start.elapsed(),
+                profile.user.id,
+                profile.posts.len()
+            ),
+            Err(error) => println!("   {:?}: failed - {}", start.elapsed(), error),
+        }
+    }
+}
+
+/// # Function: demonstrate_caching_and_performance
+///
+/// Demonstrates caching strategies and their performance impact.
+/// Shows how caching can dramatically improve async application performance.
+///
+/// ## Key Learning Points:
+/// - Implementing in-memory caching for HTTP responses
+/// - Measuring cache hit rates and performance improvements
+/// - Cache invalidation strategies
+/// - Trade-offs between memory usage and performance
+async fn demonstrate_caching_and_performance() {
+    println!("\n=== Caching and Performance ===");
+
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+
+    // Aggregate request latencies in the background as they happen
+    let latencies = LatencyAggregator::default();
+    tokio::spawn({
+        let latencies = latencies.clone();
+        let stream = client.latency_stream();
+        async move { latencies.consume(stream).await }
+    });
+
+    // Keep the last few latencies around for a "recent requests" view
+    let recent = Arc::new(AsyncRingBuffer::new(3));
+    tokio::spawn({
+        let recent = recent.clone();
+        let mut stream = client.latency_stream();
+        async move {
+            while let Some(latency) = stream.next().await {
+                recent.push(latency).await;
+            }
+        }
+    });
+
+    // Example 1: Demonstrate cache performance
+    println!("1. Cache performance demonstration:");
+
+    // First request (cache miss)
+    println!("   First request (cache miss):");
+    let start = Instant::now();
+    let _users1 = client.get_users().await.unwrap_or_default();
+    let first_request_time = start.elapsed();
+    println!("     Time: {:?}", first_request_time);
+
+    // Second request (cache hit)
+    println!("   Second request (cache hit):");
+    let start = Instant::now();
+    let _users2 = client.get_users().await.unwrap_or_default();
+    let second_request_time = start.elapsed();
+    println!("     Time: {:?}", second_request_time);
+
+    // Calculate speedup
+    let speedup = first_request_time.as_millis() as f64 / second_request_time.as_millis() as f64;
+    println!("     Cache speedup: {:.2}x faster", speedup);
+
+    // Example 2: Multiple requests showing cache effectiveness
+    println!("\n2. Multiple requests with caching:");
+    let start = Instant::now();
+
+    // Make multiple requests for the same data
+    let (users1, users2, posts1_1, posts1_2, posts2_1, posts2_2) = tokio::join!(
+        client.get_users(),
+        client.get_users(),
+        client.get_user_posts(1),
+        client.get_user_posts(1),
+        client.get_user_posts(2),
+        client.get_user_posts(2),
+    );
+
+    // Count successful requests for demonstration
+    let user_requests_ok = [&users1, &users2].iter().filter(|r| r.is_ok()).count();
+    let post_requests_ok = [&posts1_1, &posts1_2, &posts2_1, &posts2_2]
+        .iter()
+        .filter(|r| r.is_ok())
+        .count();
+    let total_successful = user_requests_ok + post_requests_ok;
+
+    println!("     {} out of 6 requests succeeded", total_successful);
+    let total_time = start.elapsed();
+
+    println!("     6 requests completed in {:?}", total_time);
+    println!("     (Notice how subsequent requests are much faster due to caching)");
+
+    println!("\n   Metrics so far (Prometheus format):");
+    for line in client
+        .export_metrics()
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.contains("_bucket"))
+    {
+        println!("     {}", line);
+    }
+
+    let stats = latencies.snapshot();
+    println!(
+        "\n   Request latency: {} requests, min {:?}, mean {:?}, max {:?}, p99 ~{:?}",
+        stats.count, stats.min, stats.mean, stats.max, stats.p99
+    );
+    println!(
+        "   Most recent {} of {} latencies: {:?}",
+        recent.capacity(),
+        recent.pushed(),
+        recent.snapshot()
+    );
+
+    // Example 3: Stale-while-revalidate for low-latency reads
+    println!("\n3. Stale-while-revalidate:");
+    let fresh_for = Duration::from_millis(200);
+    let stale_for = Duration::from_secs(60);
+
+    let _ = client.get_swr("todos/1", fresh_for, stale_for).await;
+    sleep(Duration::from_millis(250)).await; // Let the cached entry go stale
+
+    let start = Instant::now();
+    match client.get_swr("todos/1", fresh_for, stale_for).await {
+        Ok(_) => println!("     Stale response served in {:?}", start.elapsed()),
+        Err(error) => println!("     Failed to fetch todo: {}", error),
+    }
+    println!("     (A background task is refreshing the entry for the next caller)");
+
+    // Example 4: Memoizing any async function
+    println!("\n4. Memoized async function:");
+    let title_of = cached(Duration::from_secs(60), move |id: u32| {
+        let client = client.clone();
+        async move {
+            let body = client
+                .get_with_cache(&format!("posts/{}", id), Duration::ZERO)
+                .await
+                .map_err(|error| error.to_string())?;
+            serde_json::from_str::<Post>(&body)
+                .map(|post| post.title)
+                .map_err(|error| error.to_string())
+        }
+    });
+
+    // Both callers share a single request, and the third reuses its result
+    let start = Instant::now();
+    let (first, second) = tokio::join!(title_of(3), title_of(3));
+    let third = title_of(3).await;
+    println!(
+        "     3 lookups in {:?}, all equal: {}",
+        start.elapsed(),
+        first == second && second == third
+    );
+    if let Ok(title) = first {
+        println!("     Title: {}", title);
+    }
+
+    // Example 5: Settings loaded from configuration instead of hardcoded
+    println!("\n5. Client built from configuration:");
+    let config_text = r#"
+        # Loaded from a file in a real application
+        base_url = "https://jsonplaceholder.typicode.com"
+        timeout_ms = 5_000
+        rate_per_sec = 5
+        cache_ttl_ms = 30_000
+    "#;
+
+    match AppConfig::from_toml_str(config_text) {
+        Ok(config) => {
+            println!("     Loaded: {:?}", config);
+            let configured_client = ApiClient::from_config(&config);
+            let start = Instant::now();
+            let _ = configured_client.get("albums/1").await;
+            let _ = configured_client.get("albums/1").await;
+            println!("     Two reads of albums/1 in {:?}", start.elapsed());
+        }
+        Err(error) => println!("     Invalid configuration: {:#}", error),
+    }
+
+    // Example 6: Keeping connections warm between bursts of requests
+    println!("\n6. Keep-alive pinger:");
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+    let keepalive = client.start_keepalive("posts/1", Duration::from_millis(200));
+    sleep(Duration::from_millis(500)).await;
+    println!("     {} pings sent while idle", keepalive.pings());
+    drop(keepalive); // Aborts the pinging task
+    let start = Instant::now();
+    let _ = client.get("posts/1").await;
+    println!(
+        "     Next request on a warm connection: {:?}",
+        start.elapsed()
+    );
+}
+
+/// # Function: demonstrate_error_resilience
+///
+/// Demonstrates error handling and resilience patterns in real-world scenarios.
+/// Shows how to build robust async applications that handle failures gracefully.
+///
+/// ## Key Learning Points:
+/// - Handling network failures and timeouts
+/// - Implementing retry logic with exponential backoff
+/// - Graceful degradation when services are unavailable
+/// - Circuit breaker patterns for failing services
+async fn demonstrate_error_resilience() {
+    println!("\n=== Error Resilience ===");
+
+    // Example 1: Handling invalid endpoints gracefully
+    println!("1. Handling invalid endpoints:");
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+
+    match client
+        .get_with_cache("invalid-endpoint", Duration::from_secs(60))
+        .await
+    {
+        Ok(_) => println!("   Unexpected success"),
+        Err(error) => {
+            println!("   Expected error handled gracefully: {}", error);
+            // In a real app, you might log this error and use fallback data
+        }
+    }
+
+    // Example 2: Timeout handling
+    println!("\n2. Timeout handling:");
+    let slow_client = ApiClient::new("https://httpbin.org");
+
+    match timeout(
+        Duration::from_millis(100), // Very short timeout
+        slow_client.get_with_cache("delay/5", Duration::from_secs(1)), // 5 second delay
+    )
+    .await
+    {
+        Ok(_) => println!("   Unexpected completion"),
+        Err(_) => {
+            println!("   Request timed out as expected");
+            println!("   → Application can continue with cached data or default values");
+        }
+    }
+
+    // Example 3: Failing fast on connection problems
+    println!("\n3. Short connect timeout:");
+    let impatient_client = ApiClient::with_config(
+        "https://jsonplaceholder.typicode.com",
+        ApiClientConfig {
+            connect_timeout: Duration::from_millis(500),
+            pool_max_idle_per_host: 4,
+            ..ApiClientConfig::default()
+        },
+    );
+
+    match impatient_client.get_users().await {
+        Ok(users) => println!("   Connected quickly, fetched {} users", users.len()),
+        Err(error) => println!("   Gave up connecting: {}", error),
+    }
+
+    // Example 4: Circuit breaker around a failing endpoint
+    println!("\n4. Circuit breaker:");
+    let guarded_client =
+        ApiClient::new("https://httpbin.org").with_circuit_breaker(CircuitBreakerConfig {
+            failure_threshold: 2,
+            reset_timeout: Duration::from_secs(30),
+        });
+    let mut changes = guarded_client
+        .subscribe_circuit()
+        .expect("the client has a circuit breaker");
+
+    for attempt in 1..=4 {
+        match guarded_client
+            .get_with_cache("status/500", Duration::from_secs(60))
+            .await
+        {
+            Ok(_) => println!("   Attempt {}: unexpected success", attempt),
+            Err(error) => match error.downcast_ref::<ApiError>() {
+                Some(ApiError::ServiceUnavailable) => {
+                    println!("   Attempt {}: rejected by open circuit", attempt)
+                }
+                _ => println!("   Attempt {}: request failed: {}", attempt, error),
+            },
+        }
+        while let Ok(change) = changes.try_recv() {
+            println!("   🔌 Circuit {} -> {}", change.from, change.to);
+        }
+    }
+
+    // Example 5: Partial failure handling
+    println!("\n5. Partial failure handling:");
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+
+    // Try to fetch data for multiple users, some of which might fail
+    let user_ids = [1, 2, 999, 3]; // 999 doesn't exist
+    let futures: Vec<_> = user_ids
+        .iter()
+        .map(|&id| {
+            let client_clone = client.clone();
+            async move { (id, client_clone.get_user_posts(id).await) }
+        })
+        .collect();
+
+    let results = futures::future::join_all(futures).await;
+
+    let mut successful = 0;
+    let mut failed = 0;
+
+    for (user_id, result) in results {
+        match result {
+            Ok(posts) => {
+                successful += 1;
+                println!("   User {}: {} posts", user_id, posts.len());
+            }
+            Err(_) => {
+                failed += 1;
+                println!("   User {}: Failed (using default data)", user_id);
+            }
+        }
+    }
+
+    println!("   Summary: {} successful, {} failed", successful, failed);
+    println!("   → Application continues to work despite partial failures");
+
+    // Example 6: A live feed that survives dropped connections
+    println!("\n6. Reconnecting stream:");
+    let mut next_price = 100;
+    let mut feed = ReconnectingStream::new(
+        move || {
+            // Each simulated connection delivers three ticks, then drops
+            let start = next_price;
+            next_price += 3;
+            futures::stream::iter(start..start + 3)
+                .map(Ok)
+                .chain(futures::stream::once(async {
+                    Err("connection reset by peer")
+                }))
+        },
+        Duration::from_millis(50),
+    );
+
+    let prices: Vec<u32> = (&mut feed).take(7).collect().await;
+    println!("   Received ticks: {:?}", prices);
+    println!("   Reconnects: {}", feed.reconnect_count());
+
+    // Example 7: Spreading retries over mirrors of the same resource
+    println!("\n7. Round-robin retries over mirrors:");
+    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+    let mirrors = vec!["missing-mirror/users/1".to_string(), "users/1".to_string()];
+    match retry_round_robin::<User>(mirrors, &client, 4).await {
+        Ok(user) => println!("   Got {} from the second mirror", user.name),
+        Err(error) => println!("   All attempts failed: {:#}", error),
+    }
+}
+
+/// # Function: main
+///
+/// The main function orchestrates all real-world async pattern demonstrations.
+///
+/// ## Learning Progression:
+/// 1. Basic HTTP operations with structured data
+/// 2. Concurrent API calls for performance
+/// 3. Complex data aggregation workflows
+/// 4. Caching strategies and performance optimization
+/// 5. Error handling and resilience patterns
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("🌍 Real-World Async Patterns Tutorial");
+    println!("====================================");
+    println!("This example demonstrates practical async patterns using real HTTP APIs.");
+    println!(
+        "🎲 Seed {} (set {} to repeat this run's jitter)",
+        seed::run_seed(),
+        seed::SEED_ENV_VAR
+    );
+
+    // Basic HTTP operations
+    demonstrate_basic_http_operations().await;
+
+    // Concurrent API calls
+    demonstrate_concurrent_api_calls().await;
+
+    // Complex data aggregation
+    demonstrate_user_profile_aggregation().await;
+
+    // Caching and performance
+    demonstrate_caching_and_performance().await;
+
+    // Error resilience
+    demonstrate_error_resilience().await;
+
+    println!("\n✅ Real-World Patterns Tutorial completed!");
+    println!("Key takeaways:");
+    println!("  - HTTP clients integrate seamlessly with async/await");
+    println!("  - Concurrent API calls provide significant performance benefits");
+    println!("  - Complex workflows can be built by composing simple async operations");
+    println!("  - Caching dramatically improves performance for repeated requests");
+    println!("  - Robust error handling is essential for production applications");
+    println!("  - Rate limiting prevents overwhelming external services");
+    println!("  - A bounded work queue drains every accepted job before shutting down");
+    println!("  - Keep-alive pings stop when their handle is dropped");
+    println!("  - Long-lived streams should reconnect with backoff instead of ending");
+    println!("  - Rotate retries across mirrors so one bad host can't use them all");
+    println!("  - Structured data with serde makes JSON handling ergonomic");
+    println!("  - Large JSON arrays can be parsed element by element as they stream in");
+
+    println!("\nNext: Try 'cargo run --bin advanced_patterns' for advanced async patterns");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test basic API client functionality
+    #[tokio::test]
+    async fn test_api_client_creation() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
+
+        // Test that we can create the client without errors
+        assert_eq!(client.base_url, server.url());
+    }
+
+    /// Test JSON deserialization
+    #[test]
+    fn test_user_deserialization() {
+        let json = r#"
+        {
+            "id": 1,
+            "name": "Test User",
+            "email": "test@example.com"
+        }
+        "#;
+
+        let user: User = serde_json::from_str(json).unwrap();
+        assert_eq!(user.id, 1);
+        assert_eq!(user.name, "Test User");
+        assert_eq!(user.email, "test@example.com");
+        assert_eq!(user.posts_count, 0); // Default value
+    }
+
+    /// Test post deserialization with renamed fields
+    #[test]
+    fn test_post_deserialization() {
+        let json = r#"
+        {
+            "id": 1,
+            "userId": 123,
+            "title": "Test Post",
+            "body": "This is a test post"
+        }
+        "#;
+
+        let post: Post = serde_json::from_str(json).unwrap();
+        assert_eq!(post.id, 1);
+        assert_eq!(post.user_id, 123);
+        assert_eq!(post.title, "Test Post");
+        assert_eq!(post.body, "This is a test post");
+    }
+
+    /// Test user profile serialization
+    #[test]
+    fn test_user_profile_serialization() {
+        let user = User {
+            id: 1,
+            name: "Test User".to_string(),
+            email: "test@example.com".to_string(),
+            posts_count: 5,
+        };
+
+        let posts = vec![Post {
+            id: 1,
+            user_id: 1,
+            title: "Test Post".to_string(),
+            body: "Test body".to_string(),
+            created_at: None,
+        }];
+
+        let profile = UserProfile {
+            user,
+            posts,
+            total_comments: 10,
+            fetch_time: Duration::from_millis(500),
+        };
+
+        let json = serde_json::to_string(&profile).unwrap();
+        assert!(json.contains("Test User"));
+        assert!(json.contains("test@example.com"));
+        assert!(json.contains("Test Post"));
+    }
+
+    /// Canned JSONPlaceholder-style data served by `MockServer::jsonplaceholder`
+    const MOCK_USERS: &str = r#"[
+        {"id": 1, "name": "Leanne Graham", "email": "sincere@april.biz"},
+        {"id": 2, "name": "Ervin Howell", "email": "shanna@melissa.tv"}
+    ]"#;
+    const MOCK_POSTS: &str = r#"[
+        {"id": 1, "userId": 1, "title": "First post", "body": "Hello"},
+        {"id": 2, "userId": 1, "title": "Second post", "body": "Again"}
+    ]"#;
+    const MOCK_COMMENTS: &str = r#"[
+        {"id": 1, "postId": 1, "name": "Nice", "email": "fan@example.com", "body": "Great post"}
+    ]"#;
+
+    /// # Struct: MockServer
+    ///
+    /// A minimal local HTTP server so tests never depend on the live
+    /// internet. Each request is answered by a responder function that sees
+    /// the request path and how many requests came before it.
+    ///
+    /// ## Fields:
+    /// - `base_url`: URL to point an `ApiClient` at
+    /// - `hits`: Number of requests served so far
+    struct MockServer {
+        base_url: String,
+        hits: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl MockServer {
+        /// Starts a server answering with `respond(path, request_index)`.
+        async fn start<R>(respond: R) -> Self
+        where
+            R: Fn(&str, usize) -> (u16, String) + Send + Sync + 'static,
+        {
+            Self::start_with_headers(move |path, served| {
+                let (status, body) = respond(path, served);
+                (status, Vec::new(), body)
+            })
+            .await
+        }
+
+        /// Like `start`, but the responder also returns extra headers.
+        async fn start_with_headers<R>(respond: R) -> Self
+        where
+            R: Fn(&str, usize) -> (u16, Vec<(&'static str, String)>, String)
+                + Send
+                + Sync
+                + 'static,
+        {
+            use tokio::io::AsyncWriteExt;
+
+            let served = std::sync::atomic::AtomicUsize::new(0);
+            Self::start_raw(move |request, mut socket| {
+                let (status, headers, body) = respond(
+                    request_path(&request),
+                    served.fetch_add(1, Ordering::SeqCst),
+                );
+                let response = http_response(status, &headers, &body);
+                async move {
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            })
+            .await
+        }
+
+        /// Starts a server that handles connections concurrently, answering
+        /// each with `respond(path)` after the delay it returns.
+        async fn start_delayed<R>(respond: R) -> Self
+        where
+            R: Fn(&str) -> (Duration, u16, String) + Send + Sync + 'static,
+        {
+            use tokio::io::AsyncWriteExt;
+
+            Self::start_raw(move |request, mut socket| {
+                let (delay, status, body) = respond(request_path(&request));
+                let response = http_response(status, &[], &body);
+                async move {
+                    sleep(delay).await;
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            })
+            .await
+        }
+
+        /// Starts a server that hands each connection, with the request
+        /// read from it, to `handle`, which writes whatever response it
+        /// likes: a stalled or endless body, the request echoed back, or
+        /// nothing at all. The futures `handle` returns run concurrently.
+        async fn start_raw<H, F>(handle: H) -> Self
+        where
+            H: Fn(String, tokio::net::TcpStream) -> F + Send + 'static,
+            F: Future<Output = ()> + Send + 'static,
+        {
+            use tokio::io::AsyncReadExt;
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let server_hits = hits.clone();
+
+            tokio::spawn(async move {
+                while let Ok((mut socket, _)) = listener.accept().await {
+                    let mut buffer = [0u8; 4096];
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buffer[..read]).into_owned();
+
+                    server_hits.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(handle(request, socket));
+                }
+            });
+
+            Self {
+                base_url: format!("http://{}", address),
+                hits,
+            }
+        }
+
+        /// Answers each request with the next `(status, body)` from
+        /// `responses`, repeating the last one, whatever the path.
+        async fn sequence(responses: Vec<(u16, &'static str)>) -> Self {
+            Self::start(move |_, served| {
+                let (status, body) = responses[served.min(responses.len() - 1)];
+                (status, body.to_string())
+            })
+            .await
+        }
+
+        /// Serves canned users, posts and comments like the real API, a 500
+        /// under `/broken` and a 404 for everything else.
+        async fn jsonplaceholder() -> Self {
+            Self::start(|path, _| match path {
+                "/users" => (200, MOCK_USERS.to_string()),
+                "/users/1/posts" => (200, MOCK_POSTS.to_string()),
+                "/posts/1/comments" => (200, MOCK_COMMENTS.to_string()),
+                path if path.starts_with("/broken") => (500, "internal error".to_string()),
+                _ => (404, "{}".to_string()),
+            })
+            .await
+        }
+
+        fn url(&self) -> &str {
+            &self.base_url
+        }
+
+        fn hits(&self) -> usize {
+            self.hits.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    /// The path from a request line such as "GET /users/1/posts HTTP/1.1"
+    fn request_path(request: &str) -> &str {
+        request.split_whitespace().nth(1).unwrap_or("/")
+    }
+
+    /// A complete response that closes the connection after the body
+    fn http_response(status: u16, headers: &[(&str, String)], body: &str) -> String {
+        let headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\r\n", name, value))
+            .collect();
+        format!(
+            "HTTP/1.1 {} Mock\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    /// Test that a large array is yielded element by element while it is
+    /// still downloading
+    #[tokio::test]
+    async fn test_json_array_stream_is_incremental() {
+        use tokio::io::AsyncWriteExt;
+
+        const USERS: u32 = 10_000;
+        let release = Arc::new(tokio::sync::Notify::new());
+        let released = release.clone();
+
+        let server = MockServer::start_raw(move |_, mut socket| {
+            let released = released.clone();
+            async move {
+                // No Content-Length: the body ends when the connection closes
+                let head = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n[";
+                socket.write_all(head.as_bytes()).await.unwrap();
+                let user = |id: u32| {
+                    let separator = if id == 1 { "" } else { ",\n" };
+                    format!(
+                        r#"{}{{"id": {}, "name": "User {}", "email": "user{}@example.com"}}"#,
+                        separator, id, id, id
+                    )
+                };
+                for id in 1..=USERS / 2 {
+                    socket.write_all(user(id).as_bytes()).await.unwrap();
+                }
+                // Hold back the second half until the client has seen
+                // elements from the first
+                released.notified().await;
+                for id in USERS / 2 + 1..=USERS {
+                    socket.write_all(user(id).as_bytes()).await.unwrap();
+                }
+                socket.write_all(b"]").await.unwrap();
+            }
+        })
+        .await;
+
+        let client = ApiClient::new(server.url());
+        let users = client.get_json_array_stream::<User>("users");
+        futures::pin_mut!(users);
 
-    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+        let first = users.next().await.unwrap().unwrap();
+        assert_eq!(first.id, 1);
+        release.notify_one();
 
-    // Example 1: Sequential vs Concurrent comparison
-    println!("1. Performance comparison - Sequential vs Concurrent:");
+        let mut ids = vec![first.id];
+        while let Some(user) = users.next().await {
+            ids.push(user.unwrap().id);
+        }
+        assert_eq!(ids, (1..=USERS).collect::<Vec<_>>());
+    }
 
-    // Sequential approach
-    println!("   Sequential approach:");
-    let sequential_start = Instant::now();
+    /// Test that malformed JSON and non-array bodies end the stream with an
+    /// error
+    #[tokio::test]
+    async fn test_json_array_stream_rejects_malformed_json() {
+        let server = MockServer::start(|path, _| match path {
+            "/object" => (200, r#"{"id": 1}"#.to_string()),
+            _ => (
+                200,
+                r#"[{"id": 1, "name": "A", "email": "a@b.c"} {"id": 2}]"#.to_string(),
+            ),
+        })
+        .await;
+        let client = ApiClient::new(server.url());
 
-    let _users = client.get_users().await.unwrap_or_default();
-    let _posts_1 = client.get_user_posts(1).await.unwrap_or_default();
-    let _posts_2 = client.get_user_posts(2).await.unwrap_or_default();
-    let _posts_3 = client.get_user_posts(3).await.unwrap_or_default();
+        let results: Vec<AnyhowResult<User>> =
+            client.get_json_array_stream("broken").collect().await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().id, 1);
+        assert!(results[1].is_err());
 
-    let sequential_time = sequential_start.elapsed();
-    println!("     Sequential time: {:?}", sequential_time);
+        let results: Vec<AnyhowResult<User>> =
+            client.get_json_array_stream("object").collect().await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
 
-    // Concurrent approach
-    println!("   Concurrent approach:");
-    let concurrent_start = Instant::now();
+    /// Test that an oversized body is rejected as soon as the cap is passed,
+    /// without waiting for the rest of it
+    #[tokio::test]
+    async fn test_max_response_bytes() {
+        use tokio::io::AsyncWriteExt;
 
-    let users_future = client.get_users();
-    let posts_1_future = client.get_user_posts(1);
-    let posts_2_future = client.get_user_posts(2);
-    let posts_3_future = client.get_user_posts(3);
+        // "small" gets a short body; anything else gets an endless chunked one
+        let server = MockServer::start_raw(|request, mut socket| async move {
+            if request_path(&request) == "/small" {
+                let _ = socket
+                    .write_all(http_response(200, &[], "small").as_bytes())
+                    .await;
+                return;
+            }
+            let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+            let _ = socket.write_all(head.as_bytes()).await;
+            let chunk = format!("400\r\n{}\r\n", "x".repeat(1024));
+            while socket.write_all(chunk.as_bytes()).await.is_ok() {
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
 
-    let (users_result, posts_1_result, posts_2_result, posts_3_result) =
-        tokio::join!(users_future, posts_1_future, posts_2_future, posts_3_future);
+        let client = ApiClient::new(server.url()).with_max_response_bytes(3 * 1024);
+        assert_eq!(client.request("small").await.unwrap().body, "small");
 
-    let concurrent_time = concurrent_start.elapsed();
-    println!("     Concurrent time: {:?}", concurrent_time);
+        let started = Instant::now();
+        let error = client.request("huge").await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        match error.downcast_ref::<ApiError>() {
+            Some(ApiError::ValidationError(validation)) => {
+                assert_eq!(*validation, ValidationError::new("body", "too large"));
+            }
+            other => panic!("expected a size-limit error, got {:?}", other),
+        }
+    }
 
-    // Calculate speedup
-    let speedup = sequential_time.as_millis() as f64 / concurrent_time.as_millis() as f64;
-    println!("     Speedup: {:.2}x faster", speedup);
+    /// Test that default headers reach the server and a per-request header
+    /// replaces the default of the same name
+    #[tokio::test]
+    async fn test_default_headers_and_overrides() {
+        use tokio::io::AsyncWriteExt;
 
-    // Handle results
-    let users = users_result.unwrap_or_default();
-    let posts_1 = posts_1_result.unwrap_or_default();
-    let posts_2 = posts_2_result.unwrap_or_default();
-    let posts_3 = posts_3_result.unwrap_or_default();
+        // Answers every request with the request's own headers as the body
+        let server = MockServer::start_raw(|request, mut socket| async move {
+            let response = http_response(200, &[], &request);
+            let _ = socket.write_all(response.as_bytes()).await;
+        })
+        .await;
 
-    println!(
-        "     Results: {} users, {} + {} + {} posts",
-        users.len(),
-        posts_1.len(),
-        posts_2.len(),
-        posts_3.len()
-    );
+        fn header<'a>(echoed: &'a str, name: &str) -> Vec<&'a str> {
+            echoed
+                .lines()
+                .filter_map(|line| line.split_once(": "))
+                .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+                .collect()
+        }
 
-    // Example 2: Fetching data for multiple users concurrently
-    println!("\n2. Fetching posts for multiple users concurrently:");
-    let start = Instant::now();
+        let mut defaults = HeaderMap::new();
+        defaults.insert("x-api-key", HeaderValue::from_static("secret"));
+        defaults.insert("x-trace", HeaderValue::from_static("default"));
+        defaults.insert(
+            reqwest::header::USER_AGENT,
+            HeaderValue::from_static("custom-agent/2.0"),
+        );
+        let client = ApiClient::new(server.url()).with_default_headers(defaults);
 
-    // Create futures for multiple users
-    let user_ids = vec![1, 2, 3, 4, 5];
-    let mut post_futures = Vec::new();
+        let echoed = client.request("plain").await.unwrap().body;
+        assert_eq!(header(&echoed, "x-api-key"), vec!["secret"]);
+        assert_eq!(header(&echoed, "x-trace"), vec!["default"]);
+        assert_eq!(header(&echoed, "user-agent"), vec!["custom-agent/2.0"]);
 
-    for user_id in &user_ids {
-        post_futures.push(client.get_user_posts(*user_id));
+        let mut overrides = HeaderMap::new();
+        overrides.insert("x-trace", HeaderValue::from_static("per-request"));
+        let echoed = client
+            .request_with_headers("override", overrides)
+            .await
+            .unwrap()
+            .body;
+        assert_eq!(header(&echoed, "x-api-key"), vec!["secret"]);
+        assert_eq!(header(&echoed, "x-trace"), vec!["per-request"]);
     }
 
-    // Wait for all futures to complete
-    let results = futures::future::join_all(post_futures).await;
-    let elapsed = start.elapsed();
+    /// Test that every submitted job is handled exactly once before drain
+    /// returns, by no more workers at a time than configured
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_work_queue_drains_all_jobs() {
+        use std::sync::atomic::AtomicUsize;
 
-    // Process results
-    let mut total_posts = 0;
-    let mut successful_users = 0;
+        let handled: Arc<Vec<AtomicUsize>> =
+            Arc::new((0..50).map(|_| AtomicUsize::new(0)).collect());
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
 
-    for (i, result) in results.iter().enumerate() {
-        match result {
-            Ok(posts) => {
-                total_posts += posts.len();
-                successful_users += 1;
-                println!("     User {}: {} posts", user_ids[i], posts.len());
-            }
-            Err(error) => {
-                println!("     User {}: Error - {}", user_ids[i], error);
+        let queue = {
+            let (handled, in_flight, peak) = (handled.clone(), in_flight.clone(), peak.clone());
+            WorkQueue::new(8, 4, move |job: usize| {
+                let (handled, in_flight, peak) = (handled.clone(), in_flight.clone(), peak.clone());
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    sleep(Duration::from_millis(5)).await;
+                    handled[job].fetch_add(1, Ordering::SeqCst);
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        for job in 0..50 {
+            queue.submit(job).await;
+        }
+        queue.drain().await;
+
+        for (job, count) in handled.iter().enumerate() {
+            assert_eq!(count.load(Ordering::SeqCst), 1, "job {}", job);
+        }
+        assert!(peak.load(Ordering::SeqCst) <= 4);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+    }
+
+    /// Test that a timed-out request is counted, its connection is closed,
+    /// and the client keeps working afterwards
+    #[tokio::test]
+    async fn test_timeout_is_counted_and_cancelled() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Never answers "/slow"; reports when the client gives up on it
+        let hung_up = Arc::new(tokio::sync::Notify::new());
+        let client_hung_up = hung_up.clone();
+        let server = MockServer::start_raw(move |request, mut socket| {
+            let hung_up = hung_up.clone();
+            async move {
+                if request_path(&request) == "/slow" {
+                    // Read returns 0 once the client closes the connection
+                    let _ = socket.read(&mut [0u8; 1024]).await;
+                    hung_up.notify_one();
+                } else {
+                    let response = http_response(200, &[], "ok");
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
             }
+        })
+        .await;
+
+        let config = ApiClientConfig {
+            timeout: Duration::from_millis(100),
+            min_request_interval: Duration::ZERO,
+            ..ApiClientConfig::default()
+        };
+        let client = ApiClient::with_config(server.url(), config);
+
+        let error = client
+            .get_with_cache("slow", Duration::from_secs(60))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ApiError>(),
+            Some(ApiError::TimeoutError { .. })
+        ));
+        assert_eq!(client.metrics.timeouts.load(Ordering::Relaxed), 1);
+        timeout(Duration::from_secs(2), client_hung_up.notified())
+            .await
+            .expect("the timed-out request's connection was left open");
+
+        let body = timeout(
+            Duration::from_secs(2),
+            client.get_with_cache("fast", Duration::from_secs(60)),
+        )
+        .await
+        .expect("the client is stuck after a timeout");
+        assert_eq!(body.unwrap(), "ok");
+        assert!(client
+            .export_metrics()
+            .contains("api_client_timeouts_total 1\n"));
+    }
+
+    /// Test that keep-alive pings are sent while the handle lives and stop
+    /// once it is dropped
+    #[tokio::test]
+    async fn test_keepalive_stops_when_dropped() {
+        let server = MockServer::start(|_, _| (200, String::new())).await;
+        let client = ApiClient::new(server.url());
+
+        let keepalive = client.start_keepalive("health", Duration::from_millis(20));
+        sleep(Duration::from_millis(150)).await;
+        assert!(server.hits() >= 3, "only {} pings", server.hits());
+        // A ping may be counted but not yet have reached the server
+        let pings = keepalive.pings() as usize;
+        assert!(pings == server.hits() || pings == server.hits() + 1);
+
+        drop(keepalive);
+        sleep(Duration::from_millis(30)).await; // Let an in-flight ping land
+        let hits_after_drop = server.hits();
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.hits(), hits_after_drop);
+    }
+
+    /// Test that a retry moves on to the next endpoint after a failure
+    #[tokio::test]
+    async fn test_retry_round_robin_switches_endpoint() {
+        let server = MockServer::start(|path, _| match path {
+            "/primary/users" => (500, "internal error".to_string()),
+            _ => (200, MOCK_USERS.to_string()),
+        })
+        .await;
+        let client = ApiClient::new(server.url());
+
+        let endpoints = vec!["primary/users".to_string(), "secondary/users".to_string()];
+        let users: Vec<User> = retry_round_robin(endpoints, &client, 5).await.unwrap();
+
+        assert_eq!(users.len(), 2);
+        // One failed attempt on the primary, then success on the secondary
+        assert_eq!(server.hits(), 2);
+
+        let endpoints = vec!["primary/users".to_string()];
+        let result: AnyhowResult<Vec<User>> = retry_round_robin(endpoints, &client, 2).await;
+        assert!(result.unwrap_err().to_string().contains("500"));
+        assert_eq!(server.hits(), 4);
+    }
+
+    /// Test that metrics are exported in Prometheus text format
+    #[tokio::test]
+    async fn test_export_metrics() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
+
+        client.get_users().await.unwrap();
+        client.get_users().await.unwrap(); // served from the cache
+        client.get_user_posts(999).await.unwrap_err();
+
+        let text = client.export_metrics();
+
+        for metric in [
+            "api_client_requests_total counter",
+            "api_client_cache_hits_total counter",
+            "api_client_cache_misses_total counter",
+            "api_client_timeouts_total counter",
+            "api_client_request_duration_seconds histogram",
+        ] {
+            assert!(text.contains(&format!("# TYPE {}", metric)), "{}", text);
         }
+        assert!(text.contains("api_client_requests_total{outcome=\"success\"} 1\n"));
+        assert!(text.contains("api_client_requests_total{outcome=\"error\"} 1\n"));
+        assert!(text.contains("api_client_cache_hits_total 1\n"));
+        assert!(text.contains("api_client_cache_misses_total 2\n"));
+        assert!(text.contains("api_client_timeouts_total 0\n"));
+        assert!(text.contains("api_client_request_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("api_client_request_duration_seconds_count 2\n"));
     }
 
-    println!(
-        "     Summary: {} total posts from {} users in {:?}",
-        total_posts, successful_users, elapsed
-    );
-}
+    /// Test that the aggregator's statistics match a known latency sequence
+    #[tokio::test]
+    async fn test_latency_aggregator_statistics() {
+        let aggregator = LatencyAggregator::default();
+        assert_eq!(aggregator.snapshot(), LatencySnapshot::default());
 
-/// # Function: demonstrate_user_profile_aggregation
-///
-/// Demonstrates a complex real-world scenario: building a complete user profile
-/// by aggregating data from multiple API endpoints.
-///
-/// ## Key Learning Points:
-/// - Orchestrating multiple dependent async operations
-/// - Building complex data structures from API responses
-/// - Error handling in multi-step operations
-/// - Performance optimization with concurrent requests
-async fn demonstrate_user_profile_aggregation() {
-    println!("\n=== User Profile Aggregation ===");
+        // 1ms, 2ms, ... 100ms in shuffled order: p99 is 99ms
+        let latencies = (0..100u64).map(|i| Duration::from_millis((i * 37) % 100 + 1));
+        aggregator.consume(futures::stream::iter(latencies)).await;
 
-    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+        let stats = aggregator.snapshot();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.mean, Duration::from_micros(50_500));
 
-    /// # Function: build_user_profile
-    ///
-    /// Builds a complete user profile by fetching data from multiple endpoints.
-    /// This demonstrates a real-world async workflow with multiple dependencies.
-    ///
-    /// ## Workflow:
-    /// 1. Fetch user basic information
-    /// 2. Fetch all posts by the user
-    /// 3. For each post, fetch its comments (concurrently)
-    /// 4. Aggregate all data into a UserProfile
-    ///
-    /// ## Arguments:
-    /// - `client`: The API client to use for requests
-    /// - `user_id`: The ID of the user to build a profile for
-    ///
-    /// ## Returns:
-    /// - `AnyhowResult<UserProfile>`: Complete user profile or error
-    async fn build_user_profile(client: &ApiClient, user_id: u32) -> AnyhowResult<UserProfile> {
-        let start_time = Instant::now();
-
-        println!("   Building profile for user {}...", user_id);
-
-        // Step 1: Get user basic info and their posts concurrently
-        let (user_result, posts_result) = tokio::join!(
-            async {
-                let users = client.get_users().await?;
-                users
-                    .into_iter()
-                    .find(|u| u.id == user_id)
-                    .ok_or_else(|| anyhow::anyhow!("User {} not found", user_id))
-            },
-            client.get_user_posts(user_id)
+        // Within one bucket (2^(1/8) ≈ 9%) of the exact value
+        let p99 = stats.p99.as_secs_f64();
+        let exact = 0.099;
+        assert!(
+            p99 >= exact && p99 <= exact * 2f64.powf(1.0 / 8.0),
+            "{:?}",
+            stats.p99
         );
+    }
 
-        let user = user_result.context("Failed to fetch user info")?;
-        let posts = posts_result.context("Failed to fetch user posts")?;
+    /// Test that latency_stream observes the durations of real requests
+    #[tokio::test]
+    async fn test_latency_stream_observes_requests() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
+        let aggregator = LatencyAggregator::default();
+        let stream = client.latency_stream();
 
-        // Step 2: Fetch comments for all posts concurrently
-        let comment_futures: Vec<_> = posts
-            .iter()
-            .map(|post| client.get_post_comments(post.id))
-            .collect();
+        client.get_users().await.unwrap();
+        client.get_users().await.unwrap(); // cache hit: not a request
+        client.get_user_posts(1).await.unwrap();
+        drop(client); // ends the stream
 
-        let comment_results = futures::future::join_all(comment_futures).await;
+        aggregator.consume(stream).await;
+        let stats = aggregator.snapshot();
+        assert_eq!(stats.count, 2);
+        assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+    }
 
-        // Step 3: Count total comments (ignoring failed requests)
-        let total_comments: u32 = comment_results
-            .into_iter()
-            .filter_map(|result| result.ok())
-            .map(|comments| comments.len() as u32)
-            .sum();
+    /// Test that get_full keeps the status and headers with the parsed body
+    #[tokio::test]
+    async fn test_get_full_preserves_status_and_headers() {
+        let server = MockServer::start_with_headers(|_, _| {
+            let headers = vec![("X-Request-Id", "abc-123".to_string())];
+            (200, headers, MOCK_USERS.to_string())
+        })
+        .await;
+        let client = ApiClient::new(server.url());
 
-        let fetch_time = start_time.elapsed();
+        let response: ApiResponse<Vec<User>> = client.get_full("users").await.unwrap();
 
-        Ok(UserProfile {
-            user,
-            posts,
-            total_comments,
-            fetch_time,
-        })
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.headers.get("x-request-id").map(String::as_str),
+            Some("abc-123")
+        );
+        assert_eq!(response.body.len(), 2);
+        assert_eq!(response.body[0].name, "Leanne Graham");
+
+        // The cache is bypassed: a second call is a second request
+        let _: ApiResponse<Vec<User>> = client.get_full("users").await.unwrap();
+        assert_eq!(server.hits(), 2);
     }
 
-    // Example 1: Build profile for a single user
-    println!("1. Building profile for user 1:");
-    match build_user_profile(&client, 1).await {
-        Ok(profile) => {
-            println!("   ✅ Profile completed in {:?}", profile.fetch_time);
-            println!("   User: {} ({})", profile.user.name, profile.user.email);
-            println!("   Posts: {}", profile.posts.len());
-            println!("   Total comments: {}", profile.total_comments);
+    /// Test that get_many returns one result per endpoint in input order
+    #[tokio::test]
+    async fn test_get_many_preserves_order() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
+        let endpoints = [
+            "users",
+            "users/1/posts",
+            "users/999/posts",
+            "posts/1/comments",
+            "users",
+        ]
+        .map(String::from)
+        .to_vec();
 
-            if let Some(most_commented_post) = profile.posts.first() {
-                println!("   Sample post: \"{}\"", most_commented_post.title);
-            }
-        }
-        Err(error) => {
-            println!("   ❌ Failed to build profile: {}", error);
-        }
+        let results: Vec<AnyhowResult<Vec<serde_json::Value>>> =
+            client.get_many(endpoints, 2).await;
+
+        let lengths: Vec<Option<usize>> = results
+            .iter()
+            .map(|result| result.as_ref().ok().map(Vec::len))
+            .collect();
+        assert_eq!(lengths, vec![Some(2), Some(2), None, Some(1), Some(2)]);
+        assert!(format!("{:#}", results[2].as_ref().unwrap_err()).contains("404"));
     }
 
-    // Example 2: Build profiles for multiple users concurrently
-    println!("\n2. Building profiles for multiple users:");
-    let start = Instant::now();
+    /// Test that profiles stream out in the order they finish, not the
+    /// order they were requested, and that every user is accounted for
+    #[tokio::test]
+    async fn test_profile_stream_yields_in_completion_order() {
+        const USERS: &str = r#"[
+            {"id": 1, "name": "Slow", "email": "slow@example.com"},
+            {"id": 2, "name": "Fast", "email": "fast@example.com"},
+            {"id": 3, "name": "Medium", "email": "medium@example.com"}
+        ]"#;
+        let server = MockServer::start_delayed(|path| {
+            let millis = match path {
+                "/users/1/posts" => 300,
+                "/users/2/posts" => 0,
+                "/users/3/posts" => 150,
+                _ => 0,
+            };
+            let body = match path {
+                "/users" => USERS,
+                path if path.ends_with("/posts") => MOCK_POSTS,
+                _ => MOCK_COMMENTS,
+            };
+            (Duration::from_millis(millis), 200, body.to_string())
+        })
+        .await;
+        let config = ApiClientConfig {
+            min_request_interval: Duration::ZERO,
+            ..ApiClientConfig::default()
+        };
+        let client = ApiClient::with_config(server.url(), config);
 
-    let user_ids = vec![1, 2, 3];
-    let profile_futures: Vec<_> = user_ids
-        .iter()
-        .map(|&user_id| build_user_profile(&client, user_id))
-        .collect();
+        let results: Vec<AnyhowResult<UserProfile>> =
+            profile_stream(&client, vec![1, 2, 3, 4], 4).collect().await;
 
-    let profile_results = futures::future::join_all(profile_futures).await;
-    let total_time = start.elapsed();
+        // User 4 doesn't exist and fails at once; the rest by latency
+        let order: Vec<Option<u32>> = results
+            .iter()
+            .map(|result| result.as_ref().ok().map(|profile| profile.user.id))
+            .collect();
+        assert_eq!(order, vec![None, Some(2), Some(3), Some(1)]);
+        assert!(format!("{:#}", results[0].as_ref().unwrap_err()).contains("User 4 not found"));
 
-    let mut successful_profiles = 0;
-    let mut total_posts = 0;
-    let mut total_comments = 0;
+        // All started together, so completion order is fetch-time order
+        let fetch_times: Vec<Duration> = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .map(|profile| profile.fetch_time)
+            .collect();
+        assert!(fetch_times.windows(2).all(|pair| pair[0] <= pair[1]));
 
-    for (i, result) in profile_results.iter().enumerate() {
-        match result {
-            Ok(profile) => {
-                successful_profiles += 1;
-                total_posts += profile.posts.len();
-                total_comments += profile.total_comments;
-                println!(
-                    "   User {}: {} posts, {} comments (built in {:?})",
-                    user_ids[i],
-                    profile.posts.len(),
-                    profile.total_comments,
-                    profile.fetch_time
-                );
-            }
-            Err(error) => {
-                println!("   User {}: Failed - {}", user_ids[i], error);
-            }
-        }
+        // With one at a time, completion order is request order
+        let serial: Vec<u32> = profile_stream(&client, vec![1, 2, 3], 1)
+            .map(|result| result.unwrap().user.id)
+            .collect()
+            .await;
+        assert_eq!(serial, vec![1, 2, 3]);
     }
 
-    println!(
-        "   Summary: {} profiles built in {:?}",
-        successful_profiles, total_time
-    );
-    println!(
-        "   Totals: {} posts, {} comments",
-        total_posts, total_comments
-    );
-}
+    /// Test fetching users, posts and comments from the mock API
+    #[tokio::test]
+    async fn test_mock_api_success() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
 
-/// # Function: demonstrate_caching_and_performance
-///
-/// Demonstrates caching strategies and their performance impact.
-/// Shows how caching can dramatically improve async application performance.
-///
-/// ## Key Learning Points:
-/// - Implementing in-memory caching for HTTP responses
-/// - Measuring cache hit rates and performance improvements
-/// - Cache invalidation strategies
-/// - Trade-offs between memory usage and performance
-async fn demonstrate_caching_and_performance() {
-    println!("\n=== Caching and Performance ===");
+        let users = client.get_users().await.unwrap();
+        let posts = client.get_user_posts(users[0].id).await.unwrap();
+        let comments = client.get_post_comments(posts[0].id).await.unwrap();
 
-    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[1].name, "Ervin Howell");
+        assert_eq!(posts.len(), 2);
+        assert!(posts.iter().all(|post| post.user_id == 1));
+        assert_eq!(comments[0].body, "Great post");
+        assert_eq!(server.hits(), 3);
+    }
 
-    // Example 1: Demonstrate cache performance
-    println!("1. Cache performance demonstration:");
+    /// Test that a missing resource surfaces as a 404 error
+    #[tokio::test]
+    async fn test_mock_api_not_found() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
 
-    // First request (cache miss)
-    println!("   First request (cache miss):");
-    let start = Instant::now();
-    let _users1 = client.get_users().await.unwrap_or_default();
-    let first_request_time = start.elapsed();
-    println!("     Time: {:?}", first_request_time);
+        let error = client.get_user_posts(999).await.unwrap_err();
 
-    // Second request (cache hit)
-    println!("   Second request (cache hit):");
-    let start = Instant::now();
-    let _users2 = client.get_users().await.unwrap_or_default();
-    let second_request_time = start.elapsed();
-    println!("     Time: {:?}", second_request_time);
+        assert!(error
+            .to_string()
+            .contains("Failed to fetch posts for user 999"));
+        assert!(format!("{:#}", error).contains("404"));
+    }
 
-    // Calculate speedup
-    let speedup = first_request_time.as_millis() as f64 / second_request_time.as_millis() as f64;
-    println!("     Cache speedup: {:.2}x faster", speedup);
+    /// Test that a server failure surfaces as a 500 error and isn't cached
+    #[tokio::test]
+    async fn test_mock_api_server_error() {
+        let server = MockServer::jsonplaceholder().await;
+        let client = ApiClient::new(server.url());
+
+        for _ in 0..2 {
+            let error = client
+                .get_with_cache("broken", Duration::from_secs(60))
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("500"));
+        }
+        assert_eq!(server.hits(), 2);
+    }
+
+    /// Test that stale entries are served instantly while being refreshed
+    #[tokio::test]
+    async fn test_get_swr_serves_stale_then_refreshed() {
+        let server = MockServer::sequence(vec![(200, "v1"), (200, "v2")]).await;
+        let client = ApiClient::new(server.url());
+        let fresh_for = Duration::from_millis(50);
+        let stale_for = Duration::from_secs(5);
+
+        // Cache miss: fetched synchronously
+        let first = client.get_swr("item", fresh_for, stale_for).await.unwrap();
+        assert_eq!(first, "v1");
+
+        // Within the stale window: old value returned without waiting
+        sleep(Duration::from_millis(80)).await;
+        let start = Instant::now();
+        let stale = client.get_swr("item", fresh_for, stale_for).await.unwrap();
+        assert_eq!(stale, "v1");
+        assert!(start.elapsed() < Duration::from_millis(20));
+
+        // The background refresh has replaced the entry
+        sleep(Duration::from_millis(200)).await;
+        let refreshed = client.get_swr("item", fresh_for, stale_for).await.unwrap();
+        assert_eq!(refreshed, "v2");
+    }
 
-    // Example 2: Multiple requests showing cache effectiveness
-    println!("\n2. Multiple requests with caching:");
-    let start = Instant::now();
+    /// Test that concurrent stale reads of one key start a single refresh
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_get_swr_refreshes_once_per_key() {
+        let server = MockServer::sequence(vec![(200, "v1"), (200, "v2")]).await;
+        let client = ApiClient::new(server.url());
+        let fresh_for = Duration::from_millis(50);
+        let stale_for = Duration::from_secs(5);
 
-    // Make multiple requests for the same data
-    let (users1, users2, posts1_1, posts1_2, posts2_1, posts2_2) = tokio::join!(
-        client.get_users(),
-        client.get_users(),
-        client.get_user_posts(1),
-        client.get_user_posts(1),
-        client.get_user_posts(2),
-        client.get_user_posts(2),
-    );
+        client.get_swr("item", fresh_for, stale_for).await.unwrap();
+        sleep(Duration::from_millis(80)).await; // Now stale
 
-    // Count successful requests for demonstration
-    let user_requests_ok = [&users1, &users2].iter().filter(|r| r.is_ok()).count();
-    let post_requests_ok = [&posts1_1, &posts1_2, &posts2_1, &posts2_2]
-        .iter()
-        .filter(|r| r.is_ok())
-        .count();
-    let total_successful = user_requests_ok + post_requests_ok;
+        let readers: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.get_swr("item", fresh_for, stale_for).await })
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.await.unwrap().unwrap(), "v1");
+        }
 
-    println!("     {} out of 6 requests succeeded", total_successful);
-    let total_time = start.elapsed();
+        // Let the refresh finish: the initial fetch plus exactly one refresh
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(server.hits(), 2);
+        assert!(client.refreshing.lock().unwrap().is_empty());
+        let refreshed = client.get_swr("item", fresh_for, stale_for).await.unwrap();
+        assert_eq!(refreshed, "v2");
+    }
 
-    println!("     6 requests completed in {:?}", total_time);
-    println!("     (Notice how subsequent requests are much faster due to caching)");
-}
+    /// Test that the refresh slot is released when the guard is dropped
+    #[test]
+    fn test_refresh_guard_releases_key() {
+        let refreshing = Arc::new(Mutex::new(HashSet::new()));
 
-/// # Function: demonstrate_error_resilience
-///
-/// Demonstrates error handling and resilience patterns in real-world scenarios.
-/// Shows how to build robust async applications that handle failures gracefully.
-///
-/// ## Key Learning Points:
-/// - Handling network failures and timeouts
-/// - Implementing retry logic with exponential backoff
-/// - Graceful degradation when services are unavailable
-/// - Circuit breaker patterns for failing services
-async fn demonstrate_error_resilience() {
-    println!("\n=== Error Resilience ===");
+        let guard = RefreshGuard::try_acquire(&refreshing, "users").unwrap();
+        assert!(RefreshGuard::try_acquire(&refreshing, "users").is_none());
+        assert!(RefreshGuard::try_acquire(&refreshing, "posts").is_some());
 
-    // Example 1: Handling invalid endpoints gracefully
-    println!("1. Handling invalid endpoints:");
-    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+        drop(guard);
+        assert!(RefreshGuard::try_acquire(&refreshing, "users").is_some());
+    }
 
-    match client
-        .get_with_cache("invalid-endpoint", Duration::from_secs(60))
-        .await
-    {
-        Ok(_) => println!("   Unexpected success"),
-        Err(error) => {
-            println!("   Expected error handled gracefully: {}", error);
-            // In a real app, you might log this error and use fallback data
+    /// Test that jittered rate-limit sleeps don't wake every client together
+    #[tokio::test]
+    async fn test_rate_limit_jitter_spreads_clients() {
+        async fn wake_times(clients: Vec<ApiClient>, last_request: Instant) -> Vec<Instant> {
+            for client in &clients {
+                *client.rate_limiter.lock().unwrap() = last_request;
+            }
+            let waits = clients.iter().map(|client| async move {
+                client.wait_for_rate_limit().await;
+                Instant::now()
+            });
+            futures::future::join_all(waits).await
+        }
+
+        fn spread(times: &[Instant]) -> Duration {
+            let first = times.iter().min().unwrap();
+            let last = times.iter().max().unwrap();
+            *last - *first
         }
+
+        let aligned: Vec<_> = (0..12)
+            .map(|_| ApiClient::new("http://localhost"))
+            .collect();
+        let jittered: Vec<_> = (0..12)
+            .map(|seed| {
+                ApiClient::new("http://localhost")
+                    .with_rate_limit_jitter(0.5)
+                    .with_jitter_rng(TestRng::new(seed))
+            })
+            .collect();
+
+        // Every client shares the same last-request time, so each one owes
+        // the same ~100ms rate-limit sleep
+        let now = Instant::now();
+        let (aligned_times, jittered_times) =
+            tokio::join!(wake_times(aligned, now), wake_times(jittered, now));
+
+        assert!(spread(&aligned_times) < Duration::from_millis(15));
+        assert!(spread(&jittered_times) > Duration::from_millis(40));
     }
 
-    // Example 2: Timeout handling
-    println!("\n2. Timeout handling:");
-    let slow_client = ApiClient::new("https://httpbin.org");
+    /// Test that a client run is identical for the same seed: result,
+    /// attempt counts and the jitter chosen for every rate-limit sleep
+    #[tokio::test]
+    async fn test_same_seed_same_client_run() {
+        async fn run(seed: u64) -> (usize, u64, u64, usize, Vec<f64>) {
+            let server = MockServer::jsonplaceholder().await;
+            // Long enough that every attempt, however slow, owes a sleep
+            let config = ApiClientConfig {
+                min_request_interval: Duration::from_millis(200),
+                ..ApiClientConfig::default()
+            };
+            let client = ApiClient::with_config(server.url(), config)
+                .with_rate_limit_jitter(0.5)
+                .with_jitter_rng(TestRng::new(seed));
 
-    match timeout(
-        Duration::from_millis(100), // Very short timeout
-        slow_client.get_with_cache("delay/5", Duration::from_secs(1)), // 5 second delay
-    )
-    .await
-    {
-        Ok(_) => println!("   Unexpected completion"),
-        Err(_) => {
-            println!("   Request timed out as expected");
-            println!("   → Application can continue with cached data or default values");
+            let endpoints = vec![
+                "broken/a".to_string(),
+                "broken/b".to_string(),
+                "users".to_string(),
+            ];
+            let users: Vec<User> = retry_round_robin(endpoints, &client, 5).await.unwrap();
+            (
+                users.len(),
+                client.metrics.requests_ok.load(Ordering::Relaxed),
+                client.metrics.requests_failed.load(Ordering::Relaxed),
+                server.hits(),
+                client.chosen_jitter(),
+            )
         }
+
+        let first = run(2024).await;
+        let second = run(2024).await;
+        assert_eq!(first, second);
+
+        let (users, ok, failed, hits, jitter) = &first;
+        assert_eq!(*users, 2);
+        assert_eq!((*ok, *failed, *hits), (1, 2, 3));
+        assert_eq!(jitter.len(), 3);
+        assert!(jitter.iter().all(|factor| (0.5..1.5).contains(factor)));
+
+        // Sanity check: the seed does matter
+        assert_ne!(run(7).await.4, first.4);
     }
 
-    // Example 3: Partial failure handling
-    println!("\n3. Partial failure handling:");
-    let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+    /// Test that a short connect timeout surfaces promptly as a connect error
+    #[tokio::test]
+    async fn test_connect_timeout_surfaces_promptly() {
+        // A listener whose accept backlog is full silently drops new SYNs,
+        // behaving like an unroutable address but without needing a network
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = socket.listen(1).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut backlog = Vec::new();
+        while let Ok(Ok(stream)) = timeout(
+            Duration::from_millis(100),
+            tokio::net::TcpStream::connect(address),
+        )
+        .await
+        {
+            backlog.push(stream);
+        }
 
-    // Try to fetch data for multiple users, some of which might fail
-    let user_ids = vec![1, 2, 999, 3]; // 999 doesn't exist
-    let futures: Vec<_> = user_ids
-        .iter()
-        .map(|&id| {
-            let client_clone = client.clone();
-            async move { (id, client_clone.get_user_posts(id).await) }
-        })
-        .collect();
+        let client = ApiClient::with_config(
+            &format!("http://{}", address),
+            ApiClientConfig {
+                connect_timeout: Duration::from_millis(100),
+                ..ApiClientConfig::default()
+            },
+        );
 
-    let results = futures::future::join_all(futures).await;
+        let start = Instant::now();
+        let error = client
+            .get_with_cache("users", Duration::from_secs(60))
+            .await
+            .unwrap_err();
 
-    let mut successful = 0;
-    let mut failed = 0;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(error.chain().any(|cause| cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|error| error.is_connect())));
+    }
 
-    for (user_id, result) in results {
-        match result {
-            Ok(posts) => {
-                successful += 1;
-                println!("   User {}: {} posts", user_id, posts.len());
-            }
-            Err(_) => {
-                failed += 1;
-                println!("   User {}: Failed (using default data)", user_id);
+    /// Test that each phase's timeout is reported with its own kind
+    #[tokio::test]
+    async fn test_timeout_kinds() {
+        use tokio::io::AsyncWriteExt;
+
+        fn timeout_kind(error: &anyhow::Error) -> Option<TimeoutKind> {
+            match error.downcast_ref::<ApiError>() {
+                Some(ApiError::TimeoutError { kind, .. }) => Some(*kind),
+                _ => None,
             }
         }
-    }
 
-    println!("   Summary: {} successful, {} failed", successful, failed);
-    println!("   → Application continues to work despite partial failures");
-}
+        /// Serves every connection with `head` right away and a body after
+        /// `body_delay`
+        async fn stalling_server(head: &'static str, body_delay: Duration) -> MockServer {
+            MockServer::start_raw(move |_, mut socket| async move {
+                let _ = socket.write_all(head.as_bytes()).await;
+                sleep(body_delay).await;
+                let _ = socket.write_all(b"0123456789").await;
+            })
+            .await
+        }
 
-/// # Function: main
-///
-/// The main function orchestrates all real-world async pattern demonstrations.
-///
-/// ## Learning Progression:
-/// 1. Basic HTTP operations with structured data
-/// 2. Concurrent API calls for performance
-/// 3. Complex data aggregation workflows
-/// 4. Caching strategies and performance optimization
-/// 5. Error handling and resilience patterns
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🌍 Real-World Async Patterns Tutorial");
-    println!("====================================");
-    println!("This example demonstrates practical async patterns using real HTTP APIs.");
+        let config = ApiClientConfig {
+            timeout: Duration::from_millis(500),
+            connect_timeout: Duration::from_millis(100),
+            read_timeout: Duration::from_millis(100),
+            ..ApiClientConfig::default()
+        };
 
-    // Basic HTTP operations
-    demonstrate_basic_http_operations().await;
+        // Connect: a listener with a full backlog never completes handshakes
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = socket.listen(1).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut backlog = Vec::new();
+        while let Ok(Ok(stream)) = timeout(
+            Duration::from_millis(100),
+            tokio::net::TcpStream::connect(address),
+        )
+        .await
+        {
+            backlog.push(stream);
+        }
+        let client = ApiClient::with_config(&format!("http://{}", address), config.clone());
+        let error = client.request("slow").await.unwrap_err();
+        assert_eq!(
+            timeout_kind(&error),
+            Some(TimeoutKind::Connect),
+            "{:#}",
+            error
+        );
 
-    // Concurrent API calls
-    demonstrate_concurrent_api_calls().await;
+        // Read: headers arrive at once, the body stalls
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n";
+        let server = stalling_server(head, Duration::from_secs(5)).await;
+        let client = ApiClient::with_config(server.url(), config.clone());
+        let error = client.request("slow").await.unwrap_err();
+        assert_eq!(timeout_kind(&error), Some(TimeoutKind::Read), "{:#}", error);
+        assert_eq!(error.to_string(), "Read timeout after 100ms");
 
-    // Complex data aggregation
-    demonstrate_user_profile_aggregation().await;
+        // Overall: the server never even sends headers
+        let server = stalling_server("", Duration::from_secs(5)).await;
+        let client = ApiClient::with_config(server.url(), config);
+        let start = Instant::now();
+        let error = client.request("slow").await.unwrap_err();
+        assert_eq!(
+            timeout_kind(&error),
+            Some(TimeoutKind::Overall),
+            "{:#}",
+            error
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
 
-    // Caching and performance
-    demonstrate_caching_and_performance().await;
+    /// Test that repeated server errors open the breaker and later calls fail fast
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_on_repeated_errors() {
+        let server = MockServer::sequence(vec![(500, "boom")]).await;
+        let client = ApiClient::new(server.url()).with_circuit_breaker(CircuitBreakerConfig {
+            failure_threshold: 3,
+            reset_timeout: Duration::from_secs(60),
+        });
 
-    // Error resilience
-    demonstrate_error_resilience().await;
+        for _ in 0..3 {
+            let error = client
+                .get_with_cache("flaky", Duration::from_secs(60))
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("500"));
+        }
+        assert_eq!(server.hits(), 3);
 
-    println!("\n✅ Real-World Patterns Tutorial completed!");
-    println!("Key takeaways:");
-    println!("  - HTTP clients integrate seamlessly with async/await");
-    println!("  - Concurrent API calls provide significant performance benefits");
-    println!("  - Complex workflows can be built by composing simple async operations");
-    println!("  - Caching dramatically improves performance for repeated requests");
-    println!("  - Robust error handling is essential for production applications");
-    println!("  - Rate limiting prevents overwhelming external services");
-    println!("  - Structured data with serde makes JSON handling ergonomic");
+        // The breaker is now open: no request reaches the server
+        let start = Instant::now();
+        let error = client
+            .get_with_cache("flaky", Duration::from_secs(60))
+            .await
+            .unwrap_err();
 
-    println!("\nNext: Try 'cargo run --bin advanced_patterns' for advanced async patterns");
+        assert!(start.elapsed() < Duration::from_millis(10));
+        assert!(matches!(
+            error.downcast_ref::<ApiError>(),
+            Some(ApiError::ServiceUnavailable)
+        ));
+        assert_eq!(server.hits(), 3);
+    }
 
-    Ok(())
-}
+    /// Test that a well-formed, sensible user passes validation
+    #[tokio::test]
+    async fn test_get_json_validated_accepts_valid_user() {
+        let server = MockServer::sequence(vec![(
+            200,
+            r#"{"id": 1, "name": "Jane Doe", "email": "jane@example.com"}"#,
+        )])
+        .await;
+        let client = ApiClient::new(server.url());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tokio_test;
+        let user: User = client
+            .get_json_validated("users/1", Duration::from_secs(60))
+            .await
+            .unwrap();
 
-    /// Test basic API client functionality
+        assert_eq!(user.name, "Jane Doe");
+    }
+
+    /// Test that a structurally valid but nonsensical user is rejected
     #[tokio::test]
-    async fn test_api_client_creation() {
-        let client = ApiClient::new("https://jsonplaceholder.typicode.com");
+    async fn test_get_json_validated_rejects_invalid_user() {
+        let server = MockServer::sequence(vec![(
+            200,
+            r#"{"id": 1, "name": "Jane Doe", "email": "not-an-email"}"#,
+        )])
+        .await;
+        let client = ApiClient::new(server.url());
 
-        // Test that we can create the client without errors
-        assert_eq!(client.base_url, "https://jsonplaceholder.typicode.com");
-    }
+        let error = client
+            .get_json_validated::<User>("users/1", Duration::from_secs(60))
+            .await
+            .unwrap_err();
 
-    /// Test JSON deserialization
-    #[test]
-    fn test_user_deserialization() {
-        let json = r#"
-        {
-            "id": 1,
-            "name": "Test User",
-            "email": "test@example.com"
+        match error.downcast_ref::<ApiError>() {
+            Some(ApiError::ValidationError(validation)) => {
+                assert_eq!(validation.field, "email");
+            }
+            other => panic!("expected a validation error, got {:?}", other),
         }
-        "#;
+    }
 
-        let user: User = serde_json::from_str(json).unwrap();
-        assert_eq!(user.id, 1);
-        assert_eq!(user.name, "Test User");
-        assert_eq!(user.email, "test@example.com");
-        assert_eq!(user.posts_count, 0); // Default value
+    /// Test that cached reuses results within the TTL and recomputes after
+    #[tokio::test]
+    async fn test_cached_reuses_until_expiry() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = calls.clone();
+        let lookup = cached(Duration::from_millis(100), move |key: &'static str| {
+            let calls = counted_calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(10)).await;
+                key.len()
+            }
+        });
+
+        // Two concurrent calls and a quick follow-up share one computation
+        let (a, b) = tokio::join!(lookup("key"), lookup("key"));
+        assert_eq!((a, b, lookup("key").await), (3, 3, 3));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(lookup("key").await, 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
     }
 
-    /// Test post deserialization with renamed fields
-    #[test]
-    fn test_post_deserialization() {
-        let json = r#"
-        {
-            "id": 1,
-            "userId": 123,
-            "title": "Test Post",
-            "body": "This is a test post"
-        }
-        "#;
+    /// Test that expired results are released once another key is computed
+    #[tokio::test]
+    async fn test_cached_prunes_expired_entries() {
+        let lookup = cached(Duration::from_millis(50), |key: u32| async move {
+            Arc::new(key)
+        });
 
-        let post: Post = serde_json::from_str(json).unwrap();
-        assert_eq!(post.id, 1);
-        assert_eq!(post.user_id, 123);
-        assert_eq!(post.title, "Test Post");
-        assert_eq!(post.body, "This is a test post");
+        let first = lookup(1).await;
+        assert_eq!(Arc::strong_count(&first), 2); // Ours and the cache's
+
+        sleep(Duration::from_millis(60)).await;
+        lookup(2).await;
+        assert_eq!(Arc::strong_count(&first), 1);
     }
 
-    /// Test user profile serialization
+    /// Test parsing a TOML configuration into a working client
+    #[tokio::test]
+    async fn test_app_config_from_toml() {
+        let server = MockServer::sequence(vec![(200, "v1"), (200, "v2")]).await;
+        let base_url = server.url().to_string();
+        let toml = format!(
+            r#"
+            # Test client settings
+            base_url = "{}"
+            timeout_ms = 2_000
+            rate_per_sec = 50   # at most one request every 20ms
+            cache_ttl_ms = 100
+            "#,
+            base_url
+        );
+
+        let config = AppConfig::from_toml_str(&toml).unwrap();
+        assert_eq!(
+            config,
+            AppConfig {
+                base_url: base_url.clone(),
+                timeout_ms: 2000,
+                rate_per_sec: 50,
+                cache_ttl_ms: 100,
+            }
+        );
+
+        let client = ApiClient::from_config(&config);
+        assert_eq!(client.base_url, base_url);
+        assert_eq!(client.min_request_interval, Duration::from_millis(20));
+
+        // Within the configured TTL the cached body is served
+        assert_eq!(client.get("data").await.unwrap(), "v1");
+        assert_eq!(client.get("data").await.unwrap(), "v1");
+        assert_eq!(server.hits(), 1);
+
+        // After it, the client fetches again
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(client.get("data").await.unwrap(), "v2");
+    }
+
+    /// Test that malformed or incomplete configuration is rejected
     #[test]
-    fn test_user_profile_serialization() {
-        let user = User {
-            id: 1,
-            name: "Test User".to_string(),
-            email: "test@example.com".to_string(),
-            posts_count: 5,
-        };
+    fn test_app_config_rejects_invalid_toml() {
+        assert!(AppConfig::from_toml_str("base_url = \"http://x\"").is_err());
+        assert!(AppConfig::from_toml_str("[client]\nbase_url = \"http://x\"").is_err());
+        assert!(AppConfig::from_toml_str("timeout_ms = soon").is_err());
+    }
 
-        let posts = vec![Post {
-            id: 1,
-            user_id: 1,
-            title: "Test Post".to_string(),
-            body: "Test body".to_string(),
-            created_at: None,
-        }];
+    /// Test that a dropped connection is replaced without a gap in the items
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnecting_stream_continues_after_failure() {
+        let mut connections = 0;
+        let mut stream = ReconnectingStream::new(
+            move || {
+                connections += 1;
+                if connections == 1 {
+                    futures::stream::iter(vec![Ok(1), Ok(2), Err("connection reset")]).boxed()
+                } else {
+                    futures::stream::iter((3..).map(Ok)).boxed()
+                }
+            },
+            Duration::from_millis(100),
+        );
 
-        let profile = UserProfile {
-            user,
-            posts,
-            total_comments: 10,
-            fetch_time: Duration::from_millis(500),
-        };
+        let items: Vec<u32> = (&mut stream).take(6).collect().await;
 
-        let json = serde_json::to_string(&profile).unwrap();
-        assert!(json.contains("Test User"));
-        assert!(json.contains("test@example.com"));
-        assert!(json.contains("Test Post"));
+        assert_eq!(items, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(stream.reconnect_count(), 1);
     }
 
     /// Test concurrent operations timing
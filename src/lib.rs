@@ -0,0 +1,37 @@
+//! # Future Trait Tutorial - Shared Building Blocks
+//!
+//! The examples in `src/examples/` are standalone binaries. Pieces that are
+//! useful to more than one example, or worth reusing outside the tutorial,
+//! live in this library crate instead.
+//!
+//! ## Modules:
+//! - `cache`: A thread-safe LRU cache with per-entry time-to-live
+//! - `circuit_breaker`: Fail-fast protection for a repeatedly failing dependency
+//! - `output`: Batched, line-buffered output for chatty examples
+//! - `rate_limit`: A token bucket, and a wrapper that rate-limits any value
+//! - `resilience`: Retry, per-attempt timeout and circuit breaker as one policy
+//! - `ring_buffer`: A bounded history of recent items for concurrent producers
+//! - `seed`: Seedable randomness, so a single seed reproduces a whole run
+//! - `state_machine`: A macro that generates the poll loop of a state-machine
+//!   future
+//! - `sched`: A seeded executor for reproducing async race conditions
+//!   (feature `test-sched`)
+//! - `tracked_mutex`: A mutex that detects lock-ordering cycles in debug builds
+//!
+//! ## Note:
+//! - The library and the `custom_delay` example also build for
+//!   `wasm32-unknown-unknown`; the other examples need threads and sockets
+//!   and are native-only
+
+pub mod cache;
+pub mod circuit_breaker;
+pub mod output;
+pub mod rate_limit;
+pub mod resilience;
+pub mod ring_buffer;
+pub mod seed;
+pub mod state_machine;
+pub mod tracked_mutex;
+
+#[cfg(any(test, feature = "test-sched"))]
+pub mod sched;
@@ -0,0 +1,302 @@
+//! # Buffered Output
+//!
+//! `println!` locks stdout and issues a write for every line, which adds up
+//! when an example logs from many concurrent tasks. `BufferedOutput` sends
+//! lines to a background task that collects them and writes them out in
+//! batches: when the buffer is full, when the flush interval elapses, or
+//! when asked to.
+//!
+//! ## Usage:
+//! - Build one around any `AsyncWrite` (usually `tokio::io::stdout()`)
+//! - `install_global` it at the start of `main` and log with [`outln!`].
+//!   Keep the returned guard alive until `main` returns; dropping it
+//!   flushes, so no lines are lost even when `main` returns early
+//! - Without an installed global, [`outln!`] falls back to `println!`, so
+//!   tests and code running outside `main` still print normally
+//!
+//! ## Async Awareness:
+//! `write_line` never blocks or awaits: it only queues the line on an
+//! unbounded channel. It can be called from synchronous code such as
+//! `Future::poll` implementations or plain threads.
+
+use std::io;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::MissedTickBehavior;
+
+/// Messages from `BufferedOutput` handles to the writer task
+enum Message {
+    Line(String),
+    Flush(oneshot::Sender<()>),
+}
+
+/// # Struct: BufferedOutput
+///
+/// A line-oriented writer that batches lines in a background task.
+///
+/// ## Fields:
+/// - `lines`: Queue feeding the writer task
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::output::BufferedOutput;
+/// use std::time::Duration;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let out = BufferedOutput::new(tokio::io::stdout(), 8 * 1024, Duration::from_millis(50));
+/// out.write_line("first");
+/// out.write_line("second");
+/// out.flush().await; // Both lines are written in a single batch
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct BufferedOutput {
+    lines: mpsc::UnboundedSender<Message>,
+}
+
+impl BufferedOutput {
+    /// # Function: new
+    ///
+    /// Spawns the writer task on the current tokio runtime.
+    ///
+    /// ## Arguments:
+    /// - `writer`: Where the lines end up
+    /// - `capacity`: Buffered bytes that trigger a write
+    /// - `flush_interval`: Longest time a line waits in the buffer
+    ///
+    /// ## Panics:
+    /// - If called outside a tokio runtime
+    pub fn new<W>(writer: W, capacity: usize, flush_interval: Duration) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (lines, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            if let Err(error) = run_writer(writer, receiver, capacity, flush_interval).await {
+                eprintln!("Buffered output failed: {}", error);
+            }
+        });
+        Self { lines }
+    }
+
+    /// Queues a line; a newline is appended when it is written. Falls back
+    /// to `println!` if the writer task has stopped.
+    pub fn write_line(&self, line: impl Into<String>) {
+        if let Err(mpsc::error::SendError(Message::Line(line))) =
+            self.lines.send(Message::Line(line.into()))
+        {
+            println!("{}", line);
+        }
+    }
+
+    /// Writes out everything queued so far and waits until it is done.
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.lines.send(Message::Flush(done)).is_ok() {
+            let _ = written.await;
+        }
+    }
+}
+
+/// The writer task: collects lines and writes them out in batches
+async fn run_writer<W>(
+    mut writer: W,
+    mut lines: mpsc::UnboundedReceiver<Message>,
+    capacity: usize,
+    flush_interval: Duration,
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = Vec::with_capacity(capacity);
+    let mut ticker = tokio::time::interval(flush_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            message = lines.recv() => match message {
+                Some(Message::Line(line)) => {
+                    buffer.extend_from_slice(line.as_bytes());
+                    buffer.push(b'\n');
+                    if buffer.len() >= capacity {
+                        write_out(&mut writer, &mut buffer).await?;
+                    }
+                }
+                Some(Message::Flush(done)) => {
+                    write_out(&mut writer, &mut buffer).await?;
+                    let _ = done.send(());
+                }
+                // Every handle is gone; nothing more can arrive
+                None => return write_out(&mut writer, &mut buffer).await,
+            },
+            _ = ticker.tick() => write_out(&mut writer, &mut buffer).await?,
+        }
+    }
+}
+
+/// Writes and clears the buffer, if there is anything in it
+async fn write_out<W: AsyncWrite + Unpin>(writer: &mut W, buffer: &mut Vec<u8>) -> io::Result<()> {
+    if !buffer.is_empty() {
+        writer.write_all(buffer).await?;
+        writer.flush().await?;
+        buffer.clear();
+    }
+    Ok(())
+}
+
+static GLOBAL: OnceLock<BufferedOutput> = OnceLock::new();
+
+/// Makes `output` the destination of [`outln!`] for the rest of the
+/// program. Only the first call has an effect.
+///
+/// ## Returns:
+/// - `FlushGuard`: Flushes the global output when dropped
+pub fn install_global(output: BufferedOutput) -> FlushGuard {
+    let _ = GLOBAL.set(output);
+    FlushGuard { _private: () }
+}
+
+/// Writes a line to the global output, or prints it directly if none is
+/// installed. Used by [`outln!`].
+pub fn print_line(line: String) {
+    match GLOBAL.get() {
+        Some(output) => output.write_line(line),
+        None => println!("{}", line),
+    }
+}
+
+/// Flushes the global output, if one is installed.
+pub async fn flush_global() {
+    if let Some(output) = GLOBAL.get() {
+        output.flush().await;
+    }
+}
+
+/// # Struct: FlushGuard
+///
+/// Flushes the global output when dropped, however the scope holding it
+/// is left: by returning normally, through `?`, or by unwinding.
+///
+/// ## Note:
+/// - Dropping the guard blocks until the lines are written. The writer
+///   task has to make progress meanwhile, so this only works on a
+///   multi-threaded runtime; on any other the guard does nothing and
+///   `flush_global().await` has to be called instead
+#[must_use = "the global output is flushed when the guard is dropped"]
+#[derive(Debug)]
+pub struct FlushGuard {
+    _private: (),
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        // wasm32 has no multi-threaded runtime, so there is nothing to do
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            if runtime.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread {
+                tokio::task::block_in_place(|| futures::executor::block_on(flush_global()));
+            }
+        }
+    }
+}
+
+/// # Macro: outln
+///
+/// Like `println!`, but goes through the global `BufferedOutput` when one
+/// is installed.
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::output::print_line(String::new())
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print_line(format!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    /// An in-memory sink that records every write call separately
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl AsyncWrite for RecordingSink {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.writes.lock().unwrap().push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Test that many lines arrive complete and in order, in a few batches
+    #[tokio::test]
+    async fn test_lines_are_written_in_batches() {
+        let sink = RecordingSink::default();
+        let out = BufferedOutput::new(sink.clone(), 256, Duration::from_secs(60));
+
+        let lines: Vec<String> = (0..200).map(|i| format!("line {:03}", i)).collect();
+        for line in &lines {
+            out.write_line(line.as_str());
+        }
+        out.flush().await;
+
+        let writes = sink.writes.lock().unwrap();
+        // 200 lines of 9 bytes: a write per ~256 bytes, not one per line
+        assert!(
+            writes.len() > 1 && writes.len() <= 10,
+            "{} writes",
+            writes.len()
+        );
+
+        let written = String::from_utf8(writes.concat()).unwrap();
+        assert_eq!(written, lines.join("\n") + "\n");
+    }
+
+    /// Test that the interval flushes lines nobody flushed explicitly
+    #[tokio::test(start_paused = true)]
+    async fn test_interval_flush() {
+        let sink = RecordingSink::default();
+        let out = BufferedOutput::new(sink.clone(), 1024, Duration::from_millis(50));
+
+        out.write_line("waiting");
+        tokio::time::sleep(Duration::from_millis(120)).await;
+
+        assert_eq!(sink.writes.lock().unwrap().concat(), b"waiting\n");
+    }
+
+    /// Test that dropping the guard writes out what is still buffered
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_guard_flushes_global() {
+        let sink = RecordingSink::default();
+        let guard = install_global(BufferedOutput::new(
+            sink.clone(),
+            1024,
+            Duration::from_secs(60),
+        ));
+
+        crate::outln!("last words");
+        drop(guard);
+
+        assert_eq!(sink.writes.lock().unwrap().concat(), b"last words\n");
+    }
+}
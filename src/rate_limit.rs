@@ -0,0 +1,221 @@
+//! # Rate Limiting
+//!
+//! A token bucket and a wrapper that puts any value behind one. The
+//! `real_world` example's API client paces its own requests; `RateLimited`
+//! does the same for anything else, such as a database pool or an agent's
+//! LLM client, without that type knowing about rate limits.
+//!
+//! ## Async Awareness:
+//! Waiting for a token is a `tokio::time::sleep`, so a throttled caller
+//! never blocks its worker thread. Time is read from `tokio::time::Instant`,
+//! which lets tests run the limiter on a paused clock.
+
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+/// # Struct: TokenBucket
+///
+/// Hands out tokens at a steady rate. Unused tokens accumulate up to
+/// `burst`, so a caller that was idle may briefly go faster than `rate`.
+///
+/// ## Fields:
+/// - `rate`: Tokens added per second
+/// - `burst`: Most tokens that can be saved up
+/// - `state`: Tokens currently available and when they were last topped up
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::rate_limit::TokenBucket;
+///
+/// let bucket = TokenBucket::new(10.0, 2);
+/// assert!(bucket.try_acquire());
+/// assert!(bucket.try_acquire());
+/// assert!(!bucket.try_acquire()); // burst used up; the next token takes 100ms
+/// ```
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// # Function: new
+    ///
+    /// Creates a full bucket.
+    ///
+    /// ## Arguments:
+    /// - `rate`: Tokens per second; must be positive
+    /// - `burst`: Bucket size; at least 1
+    ///
+    /// ## Panics:
+    /// - If `rate` is not positive or `burst` is zero
+    pub fn new(rate: f64, burst: u32) -> Self {
+        assert!(rate > 0.0, "token rate must be positive, got {}", rate);
+        assert!(burst > 0, "token bucket must hold at least one token");
+
+        Self {
+            rate,
+            burst: f64::from(burst),
+            state: Mutex::new(BucketState {
+                tokens: f64::from(burst),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token if one is available, without waiting.
+    pub fn try_acquire(&self) -> bool {
+        self.take_or_wait().is_none()
+    }
+
+    /// # Function: acquire
+    ///
+    /// Takes a token, sleeping until one is available.
+    ///
+    /// ## Note:
+    /// - Waiters are not queued: when several wait at once, whichever wakes
+    ///   first after a refill gets the token
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.take_or_wait() {
+            sleep(wait).await;
+        }
+    }
+
+    /// Tops up the bucket, then takes a token (`None`) or returns how long
+    /// until the next one
+    fn take_or_wait(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let earned = now.duration_since(state.refilled_at).as_secs_f64() * self.rate;
+        state.tokens = (state.tokens + earned).min(self.burst);
+        state.refilled_at = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / self.rate))
+        }
+    }
+}
+
+/// # Struct: RateLimited
+///
+/// Wraps a value so that every operation on it first takes a token from a
+/// `TokenBucket`.
+///
+/// ## Fields:
+/// - `inner`: The wrapped value
+/// - `limiter`: The bucket every operation draws from
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::rate_limit::{RateLimited, TokenBucket};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let numbers = RateLimited::new(vec![1, 2, 3], TokenBucket::new(100.0, 1));
+/// let total = numbers.run(|numbers| async move { numbers.iter().sum::<i32>() }).await;
+/// assert_eq!(total, 6);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct RateLimited<T> {
+    inner: T,
+    limiter: TokenBucket,
+}
+
+impl<T> RateLimited<T> {
+    /// # Function: new
+    ///
+    /// Puts `inner` behind `limiter`.
+    pub fn new(inner: T, limiter: TokenBucket) -> Self {
+        Self { inner, limiter }
+    }
+
+    /// # Function: run
+    ///
+    /// Waits for a token, then runs `f` on the wrapped value.
+    ///
+    /// ## Arguments:
+    /// - `f`: Starts the operation; may borrow the wrapped value
+    ///
+    /// ## Returns:
+    /// - Whatever the operation resolves to
+    pub async fn run<'a, F, R>(&'a self, f: impl FnOnce(&'a T) -> F) -> R
+    where
+        F: Future<Output = R>,
+    {
+        self.limiter.acquire().await;
+        f(&self.inner).await
+    }
+
+    /// Returns the wrapped value, bypassing the limiter.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Test that calls through a 2/sec limiter are spaced 500ms apart
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_spacing() {
+        let counter = RateLimited::new(AtomicU32::new(0), TokenBucket::new(2.0, 1));
+        let start = Instant::now();
+        let calls = Mutex::new(Vec::new());
+
+        let call = || {
+            counter.run(|count| async {
+                calls.lock().unwrap().push(start.elapsed());
+                count.fetch_add(1, Ordering::SeqCst)
+            })
+        };
+        futures::future::join_all((0..5).map(|_| call())).await;
+
+        assert_eq!(counter.inner().load(Ordering::SeqCst), 5);
+        let mut calls = calls.into_inner().unwrap();
+        calls.sort();
+        assert_eq!(calls[0], Duration::ZERO);
+        for pair in calls.windows(2) {
+            assert!(
+                pair[1] - pair[0] >= Duration::from_millis(499),
+                "calls at {:?}",
+                calls
+            );
+        }
+        // Not slower than needed either
+        assert!(
+            calls[4] < Duration::from_millis(2100),
+            "calls at {:?}",
+            calls
+        );
+    }
+
+    /// Test that an idle bucket saves up to `burst` tokens and no more
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_is_capped() {
+        let bucket = TokenBucket::new(10.0, 3);
+        for _ in 0..3 {
+            assert!(bucket.try_acquire());
+        }
+        assert!(!bucket.try_acquire());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        for _ in 0..3 {
+            assert!(bucket.try_acquire());
+        }
+        assert!(!bucket.try_acquire());
+    }
+}
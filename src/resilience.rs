@@ -0,0 +1,307 @@
+//! # Combined Resilience Policies
+//!
+//! Retries, timeouts and a circuit breaker each solve part of the problem
+//! of calling an unreliable dependency, but nesting them by hand is easy to
+//! get subtly wrong: a timeout around the whole retry loop cuts retries
+//! short, and a breaker checked per attempt turns one slow call into
+//! several recorded failures. `ResiliencePolicy` fixes the order once.
+//!
+//! ## Layering (outermost first):
+//! 1. **Circuit breaker**: checked once per call; the call as a whole,
+//!    retries included, counts as one success or failure
+//! 2. **Retry**: repeats failed or timed-out attempts with doubling backoff
+//! 3. **Timeout**: bounds each attempt separately
+//!
+//! ## Async Awareness:
+//! Timeouts and backoff use `tokio::time`, so tests can run a policy on a
+//! paused clock. A timed-out attempt is dropped, which cancels it.
+
+use crate::circuit_breaker::CircuitBreaker;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::{sleep, timeout};
+
+/// # Struct: RetryPolicy
+///
+/// How often, and how patiently, `ResiliencePolicy` retries.
+///
+/// ## Fields:
+/// - `max_attempts`: Attempts in total, including the first
+/// - `backoff`: Delay before the first retry; it doubles after each one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff: Duration,
+}
+
+/// # Enum: ResilienceError
+///
+/// Why a call through a `ResiliencePolicy` failed.
+///
+/// ## Variants:
+/// - `CircuitOpen`: The breaker rejected the call; the operation never ran
+/// - `TimedOut`: The last attempt ran out of time
+/// - `Failed`: The last attempt failed with the operation's own error
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ResilienceError<E> {
+    #[error("circuit breaker is open")]
+    CircuitOpen,
+
+    #[error("attempt {attempts} timed out after {timeout:?}")]
+    TimedOut { attempts: u32, timeout: Duration },
+
+    #[error("failed after {attempts} attempts: {error}")]
+    Failed { attempts: u32, error: E },
+}
+
+/// # Struct: ResiliencePolicy
+///
+/// A retry policy, a per-attempt timeout and a circuit breaker, applied
+/// together by `execute`. Every layer is optional; a policy with none of
+/// them just runs the operation once.
+///
+/// ## Fields:
+/// - `retry`: Retry settings; `None` means a single attempt
+/// - `timeout`: Time limit for each attempt
+/// - `breaker`: Breaker guarding the whole call, shared with whoever else
+///   calls the same dependency
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::resilience::{ResiliencePolicy, RetryPolicy};
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// use std::time::Duration;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let policy = ResiliencePolicy::new()
+///     .retry(RetryPolicy { max_attempts: 3, backoff: Duration::from_millis(1) })
+///     .timeout(Duration::from_secs(1));
+///
+/// let calls = AtomicU32::new(0);
+/// let result = policy
+///     .execute(|| async {
+///         match calls.fetch_add(1, Ordering::SeqCst) {
+///             0 => Err("flaky"),
+///             _ => Ok("done"),
+///         }
+///     })
+///     .await;
+/// assert_eq!(result, Ok("done"));
+/// assert_eq!(calls.load(Ordering::SeqCst), 2);
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ResiliencePolicy {
+    retry: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl ResiliencePolicy {
+    /// # Function: new
+    ///
+    /// Creates a policy with no layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Function: retry
+    ///
+    /// Retries failed and timed-out attempts according to `policy`.
+    ///
+    /// ## Panics:
+    /// - If `policy.max_attempts` is zero
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        assert!(
+            policy.max_attempts > 0,
+            "a retry policy needs at least one attempt"
+        );
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Bounds each attempt to `limit`.
+    pub fn timeout(mut self, limit: Duration) -> Self {
+        self.timeout = Some(limit);
+        self
+    }
+
+    /// Guards calls with `breaker`.
+    pub fn circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// # Function: execute
+    ///
+    /// Runs an operation through every configured layer.
+    ///
+    /// ## Arguments:
+    /// - `make`: Creates the future for one attempt
+    ///
+    /// ## Returns:
+    /// - The first successful result, or a `ResilienceError` describing the
+    ///   last attempt (or the breaker's rejection)
+    pub async fn execute<F, Fut, T, E>(&self, mut make: F) -> Result<T, ResilienceError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(breaker) = &self.breaker {
+            if !breaker.allow_request() {
+                return Err(ResilienceError::CircuitOpen);
+            }
+        }
+
+        let result = self.retry_attempts(&mut make).await;
+
+        if let Some(breaker) = &self.breaker {
+            match &result {
+                Ok(_) => breaker.record_success(),
+                Err(_) => breaker.record_failure(),
+            }
+        }
+        result
+    }
+
+    /// The retry loop, with each attempt bounded by the timeout
+    async fn retry_attempts<F, Fut, T, E>(&self, make: &mut F) -> Result<T, ResilienceError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let max_attempts = self.retry.map_or(1, |retry| retry.max_attempts);
+        let mut backoff = self.retry.map_or(Duration::ZERO, |retry| retry.backoff);
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            let outcome = match self.timeout {
+                Some(limit) => match timeout(limit, make()).await {
+                    Ok(outcome) => {
+                        outcome.map_err(|error| ResilienceError::Failed { attempts, error })
+                    }
+                    Err(_) => Err(ResilienceError::TimedOut {
+                        attempts,
+                        timeout: limit,
+                    }),
+                },
+                None => make()
+                    .await
+                    .map_err(|error| ResilienceError::Failed { attempts, error }),
+            };
+
+            match outcome {
+                Ok(value) => return Ok(value),
+                Err(error) if attempts >= max_attempts => return Err(error),
+                Err(_) => {
+                    sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit_breaker::CircuitBreakerConfig;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Instant;
+
+    /// Test a flaky operation through all three layers: attempts that hang
+    /// are cut off by the timeout and retried, the breaker sees one outcome
+    /// per call, and once it opens the operation is no longer run
+    #[tokio::test(start_paused = true)]
+    async fn test_layers_combine() {
+        let breaker = Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            reset_timeout: Duration::from_secs(60),
+        }));
+        let policy = ResiliencePolicy::new()
+            .circuit_breaker(Arc::clone(&breaker))
+            .timeout(Duration::from_millis(100))
+            .retry(RetryPolicy {
+                max_attempts: 3,
+                backoff: Duration::from_millis(10),
+            });
+        let calls = AtomicU32::new(0);
+
+        // Hangs, then fails, then succeeds: 100ms timeout + 10ms + 20ms backoff
+        let start = Instant::now();
+        let flaky = policy
+            .execute(|| async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => {
+                        sleep(Duration::from_secs(10)).await;
+                        Ok(0)
+                    }
+                    1 => Err("connection reset"),
+                    n => Ok(n),
+                }
+            })
+            .await;
+        assert_eq!(flaky, Ok(2));
+        assert_eq!(start.elapsed(), Duration::from_millis(130));
+        assert!(!breaker.is_open());
+
+        // Every attempt hangs: the last timeout is reported, and the whole
+        // call is one failure for the breaker
+        calls.store(0, Ordering::SeqCst);
+        let hanging = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            sleep(Duration::from_secs(10)).await;
+            Ok::<_, &str>(())
+        };
+        assert_eq!(
+            policy.execute(hanging).await,
+            Err(ResilienceError::TimedOut {
+                attempts: 3,
+                timeout: Duration::from_millis(100)
+            })
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(!breaker.is_open());
+
+        // The second failed call opens the breaker, which then rejects
+        // calls without running them
+        assert_eq!(
+            policy.execute(|| async { Err::<(), _>("down") }).await,
+            Err(ResilienceError::Failed {
+                attempts: 3,
+                error: "down"
+            })
+        );
+        assert!(breaker.is_open());
+        calls.store(0, Ordering::SeqCst);
+        assert_eq!(
+            policy.execute(hanging).await,
+            Err(ResilienceError::CircuitOpen)
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    /// Test that a policy without layers runs the operation exactly once
+    #[tokio::test]
+    async fn test_no_layers_runs_once() {
+        let calls = AtomicU32::new(0);
+        let result = ResiliencePolicy::new()
+            .execute(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>("nope")
+            })
+            .await;
+
+        assert_eq!(
+            result,
+            Err(ResilienceError::Failed {
+                attempts: 1,
+                error: "nope"
+            })
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
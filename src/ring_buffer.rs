@@ -0,0 +1,153 @@
+//! # Async Ring Buffer
+//!
+//! A fixed-size history of the most recent items, such as the last few
+//! events shown on a dashboard. Once full, every push overwrites the
+//! oldest item, so memory use stays bounded no matter how long producers
+//! keep running.
+//!
+//! ## Async Awareness:
+//! Any number of tasks can push concurrently through a shared reference.
+//! The internal `std::sync::Mutex` is only held to move a single item in or
+//! to copy the contents out, never across an `.await`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// # Struct: AsyncRingBuffer
+///
+/// Keeps the `capacity` most recently pushed items, oldest first.
+///
+/// ## Fields:
+/// - `capacity`: Maximum number of items kept
+/// - `inner`: The items and the next sequence number, guarded by a mutex
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::ring_buffer::AsyncRingBuffer;
+///
+/// # futures::executor::block_on(async {
+/// let recent = AsyncRingBuffer::new(2);
+/// recent.push("a").await;
+/// recent.push("b").await;
+/// recent.push("c").await; // overwrites "a"
+///
+/// assert_eq!(recent.snapshot(), vec!["b", "c"]);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AsyncRingBuffer<T> {
+    capacity: usize,
+    inner: Mutex<Ring<T>>,
+}
+
+#[derive(Debug)]
+struct Ring<T> {
+    items: VecDeque<T>,
+    next_sequence: u64,
+}
+
+impl<T> AsyncRingBuffer<T> {
+    /// # Function: new
+    ///
+    /// Creates an empty buffer.
+    ///
+    /// ## Panics:
+    /// - If `capacity` is zero, since nothing could ever be kept
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "ring buffer capacity must be at least 1");
+        Self {
+            capacity,
+            inner: Mutex::new(Ring {
+                items: VecDeque::with_capacity(capacity),
+                next_sequence: 0,
+            }),
+        }
+    }
+
+    /// # Function: push
+    ///
+    /// Appends an item, dropping the oldest one if the buffer is full.
+    ///
+    /// ## Returns:
+    /// - The item's sequence number: pushes are numbered from 0 in the
+    ///   order they took effect, across all producers
+    pub async fn push(&self, item: T) -> u64 {
+        let mut ring = self.inner.lock().unwrap();
+        if ring.items.len() == self.capacity {
+            ring.items.pop_front();
+        }
+        ring.items.push_back(item);
+
+        let sequence = ring.next_sequence;
+        ring.next_sequence += 1;
+        sequence
+    }
+
+    /// Returns the maximum number of items kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns how many items have been pushed in total, including
+    /// overwritten ones.
+    pub fn pushed(&self) -> u64 {
+        self.inner.lock().unwrap().next_sequence
+    }
+}
+
+impl<T: Clone> AsyncRingBuffer<T> {
+    /// Returns a copy of the kept items, oldest first.
+    pub fn snapshot(&self) -> Vec<T> {
+        self.inner.lock().unwrap().items.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Test that concurrent pushes keep exactly the most recent items
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_pushes_keep_most_recent() {
+        let buffer = Arc::new(AsyncRingBuffer::new(3));
+
+        let producers: Vec<_> = (0..5)
+            .map(|producer| {
+                let buffer = buffer.clone();
+                tokio::spawn(async move {
+                    let item = format!("event from producer {}", producer);
+                    (buffer.push(item.clone()).await, item)
+                })
+            })
+            .collect();
+
+        let mut pushed = Vec::new();
+        for producer in producers {
+            pushed.push(producer.await.unwrap());
+        }
+
+        // Whatever order the pushes landed in, the survivors are the three
+        // with the highest sequence numbers, oldest first
+        pushed.sort();
+        let sequences: Vec<u64> = pushed.iter().map(|(sequence, _)| *sequence).collect();
+        assert_eq!(sequences, vec![0, 1, 2, 3, 4]);
+        let expected: Vec<String> = pushed[2..].iter().map(|(_, item)| item.clone()).collect();
+
+        assert_eq!(buffer.snapshot(), expected);
+        assert_eq!(buffer.pushed(), 5);
+    }
+
+    /// Test that a buffer below capacity keeps everything in push order
+    #[tokio::test]
+    async fn test_partial_fill() {
+        let buffer = AsyncRingBuffer::new(4);
+        assert!(buffer.snapshot().is_empty());
+
+        buffer.push(1).await;
+        buffer.push(2).await;
+
+        assert_eq!(buffer.snapshot(), vec![1, 2]);
+        assert_eq!(buffer.capacity(), 4);
+    }
+}
@@ -0,0 +1,141 @@
+//! # Reproducible Randomness
+//!
+//! Mock services, retry jitter and scheduling all make random choices.
+//! They draw them from `TestRng`, so a whole run is determined by a single
+//! seed: set `TUTORIAL_SEED` to replay a run that misbehaved.
+//!
+//! ## Usage:
+//! - Components that take randomness accept a `TestRng` in a constructor or
+//!   builder method, so tests can pass a fixed seed
+//! - Components that create their own use `TestRng::from_run_seed`, which
+//!   hands out independent generators derived from `run_seed()`
+//! - Examples print `run_seed()` at startup so a run can be repeated
+//!
+//! ## Note:
+//! Generators from `from_run_seed` are handed out in creation order, so a
+//! run is only reproducible if components are created in the same order.
+//! That holds for the sequential setup in the examples' `main`, not for
+//! tests running in parallel; tests should use `TestRng::new` instead.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable that fixes the seed of a run
+pub const SEED_ENV_VAR: &str = "TUTORIAL_SEED";
+
+/// # Struct: TestRng
+///
+/// A small, seedable pseudo-random generator (splitmix64). Not suitable for
+/// cryptography; good enough to pick delays, failures and interleavings.
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::seed::TestRng;
+///
+/// let mut a = TestRng::new(42);
+/// let mut b = TestRng::new(42);
+/// assert_eq!(a.next_u64(), b.next_u64());
+///
+/// let roll = a.below(6) + 1;
+/// assert!((1..=6).contains(&roll));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestRng {
+    state: u64,
+}
+
+impl TestRng {
+    /// Creates a generator whose sequence is determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// # Function: from_run_seed
+    ///
+    /// Creates a generator derived from the run's seed. Every call returns
+    /// a different generator, so components don't share a sequence.
+    pub fn from_run_seed() -> Self {
+        static STREAMS: OnceLock<Mutex<TestRng>> = OnceLock::new();
+        STREAMS
+            .get_or_init(|| Mutex::new(TestRng::new(run_seed())))
+            .lock()
+            .unwrap()
+            .fork()
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed value in `[0.0, 1.0)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a value in `0..n`, with negligible bias for small `n`.
+    ///
+    /// ## Panics:
+    /// - If `n` is zero
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "below(0) has no values to choose from");
+        self.next_u64() % n
+    }
+
+    /// Splits off an independent generator, for handing to a component.
+    pub fn fork(&mut self) -> TestRng {
+        TestRng::new(self.next_u64())
+    }
+}
+
+/// # Function: run_seed
+///
+/// Returns the seed of this run: `TUTORIAL_SEED` if it is set to a number,
+/// otherwise one taken from the clock. It is chosen once and then fixed.
+pub fn run_seed() -> u64 {
+    static SEED: OnceLock<u64> = OnceLock::new();
+    *SEED.get_or_init(|| {
+        std::env::var(SEED_ENV_VAR)
+            .ok()
+            .and_then(|seed| seed.trim().parse().ok())
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_nanos() as u64)
+                    .unwrap_or_default()
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that forks are reproducible but differ from each other
+    #[test]
+    fn test_forks_are_reproducible_and_independent() {
+        let mut parent = TestRng::new(7);
+        let mut first = parent.fork();
+        let mut second = parent.fork();
+
+        let mut again = TestRng::new(7);
+        let mut first_again = again.fork();
+
+        let draws: Vec<u64> = (0..4).map(|_| first.next_u64()).collect();
+        assert_eq!(
+            draws,
+            (0..4).map(|_| first_again.next_u64()).collect::<Vec<_>>()
+        );
+        assert_ne!(draws, (0..4).map(|_| second.next_u64()).collect::<Vec<_>>());
+
+        for _ in 0..1000 {
+            let unit = first.next_f64();
+            assert!((0.0..1.0).contains(&unit));
+            assert!(first.below(3) < 3);
+        }
+    }
+}
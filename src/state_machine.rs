@@ -0,0 +1,329 @@
+//! # Poll-Driven State Machines
+//!
+//! A hand-written `Future` that is a state machine tends to repeat the same
+//! plumbing: take the state out of `self`, match on it, put the next state
+//! back, loop until something is `Pending`, and yield now and then so one
+//! future can't monopolize its thread. Forgetting to put a state back is an
+//! easy bug to write and a hard one to spot.
+//!
+//! [`state_machine!`](crate::state_machine!) generates that loop. Each
+//! transition only says what comes next, as a [`Step`].
+//!
+//! ## Async Awareness:
+//! The generated `poll` parks the machine in a "poisoned" state while a
+//! transition runs. If a transition panics, the machine stays poisoned
+//! instead of looking like it should start over.
+
+/// # Enum: Step
+///
+/// What a transition of a [`state_machine!`](crate::state_machine!) decided.
+///
+/// ## Variants:
+/// - `Next`: Move to this state and keep going in the same `poll`
+/// - `Pending`: Store this state and return `Poll::Pending`; whatever the
+///   transition waited on must have registered the waker
+/// - `Ready`: Store this state and complete with the output
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step<S, T> {
+    Next(S),
+    Pending(S),
+    Ready(S, T),
+}
+
+/// # Macro: state_machine
+///
+/// Implements `Future` for a type that keeps its state in an enum field.
+///
+/// ## Syntax:
+/// ```text
+/// state_machine! {
+///     impl Future for Type {
+///         type Output = Output;
+///         state = field, poisoned = Enum::Poisoned, budget = 16;
+///         on_transition = method;            // optional
+///         poll(this, cx) {
+///             Enum::A { .. } => { /* returns a Step */ }
+///             ...
+///         }
+///     }
+/// }
+/// ```
+///
+/// ## Parts:
+/// - `state`: The field holding the state enum
+/// - `poisoned`: The state stored while a transition runs
+/// - `budget`: Transitions per `poll` before yielding to the executor
+/// - `on_transition`: A `&mut self` method called after every `Step::Next`
+/// - `poll(this, cx)`: Names for `&mut Self` and the task `Context` inside
+///   the arms. Each arm matches the state, taken by value, and evaluates to
+///   a `Step`. Guards are not supported.
+///
+/// Doc comments and other attributes may be put on the `impl`, the
+/// `type Output` and the `poll`; they are carried over to the generated code.
+///
+/// ## Note:
+/// - The type must be `Unpin`
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::state_machine::Step;
+/// use std::task::Poll;
+///
+/// enum Count { Running(u32), Done, Poisoned }
+/// struct Countdown { state: Count }
+///
+/// future_trait_tutorial::state_machine! {
+///     impl Future for Countdown {
+///         type Output = &'static str;
+///         state = state, poisoned = Count::Poisoned, budget = 8;
+///         poll(this, cx) {
+///             Count::Running(0) => { Step::Next(Count::Done) }
+///             Count::Running(n) => { Step::Next(Count::Running(n - 1)) }
+///             Count::Done => { Step::Ready(Count::Done, "liftoff") }
+///             Count::Poisoned => { panic!("polled after a panic") }
+///         }
+///     }
+/// }
+///
+/// let countdown = Countdown { state: Count::Running(3) };
+/// assert_eq!(futures::executor::block_on(countdown), "liftoff");
+/// ```
+#[macro_export]
+macro_rules! state_machine {
+    (
+        $(#[$impl_attr:meta])*
+        impl Future for $machine:ty {
+            $(#[$output_attr:meta])*
+            type Output = $output:ty;
+            state = $field:ident, poisoned = $poisoned:expr, budget = $budget:expr;
+            $(on_transition = $hook:ident;)?
+            $(#[$poll_attr:meta])*
+            poll($this:ident, $cx:ident) {
+                $($state:pat => $transition:block)+
+            }
+        }
+    ) => {
+        $(#[$impl_attr])*
+        impl ::std::future::Future for $machine {
+            $(#[$output_attr])*
+            type Output = $output;
+
+            $(#[$poll_attr])*
+            fn poll(
+                mut self: ::std::pin::Pin<&mut Self>,
+                $cx: &mut ::std::task::Context<'_>,
+            ) -> ::std::task::Poll<Self::Output> {
+                let $this = &mut *self;
+                let mut transitions = 0;
+                loop {
+                    if transitions == $budget {
+                        // Budget spent: ask to be polled again and let
+                        // other tasks run
+                        $cx.waker().wake_by_ref();
+                        return ::std::task::Poll::Pending;
+                    }
+                    transitions += 1;
+
+                    // If a transition panics, the machine stays poisoned
+                    let step = match ::std::mem::replace(&mut $this.$field, $poisoned) {
+                        $($state => $transition)+
+                    };
+                    match step {
+                        $crate::state_machine::Step::Next(next) => {
+                            $this.$field = next;
+                            $($this.$hook();)?
+                        }
+                        $crate::state_machine::Step::Pending(state) => {
+                            $this.$field = state;
+                            return ::std::task::Poll::Pending;
+                        }
+                        $crate::state_machine::Step::Ready(state, output) => {
+                            $this.$field = state;
+                            return ::std::task::Poll::Ready(output);
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// A machine that adds up numbers, waiting for a "tick" between them.
+    /// `Explode` is a state whose transition panics.
+    #[derive(Debug, Clone, PartialEq)]
+    enum Summing {
+        Adding { left: Vec<u32>, total: u32 },
+        Waiting { left: Vec<u32>, total: u32 },
+        Done(u32),
+        Explode,
+        Poisoned,
+    }
+
+    /// The hand-written version
+    struct HandWritten {
+        state: Summing,
+        transitions: usize,
+    }
+
+    impl Future for HandWritten {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            let mut budget = 0;
+            loop {
+                if budget == 4 {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                budget += 1;
+
+                match std::mem::replace(&mut self.state, Summing::Poisoned) {
+                    Summing::Adding { mut left, total } => {
+                        let total = total + left.pop().unwrap_or(0);
+                        self.state = if left.is_empty() {
+                            Summing::Done(total)
+                        } else if total % 2 == 0 {
+                            Summing::Waiting { left, total }
+                        } else {
+                            Summing::Adding { left, total }
+                        };
+                        self.transitions += 1;
+                    }
+                    Summing::Waiting { left, total } => {
+                        // Wait for one wake-up before adding the next number
+                        self.state = Summing::Adding { left, total };
+                        self.transitions += 1;
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                    Summing::Done(total) => {
+                        self.state = Summing::Done(total);
+                        return Poll::Ready(total);
+                    }
+                    Summing::Explode => panic!("exploded"),
+                    Summing::Poisoned => panic!("poisoned"),
+                }
+            }
+        }
+    }
+
+    /// The same machine, generated
+    struct Generated {
+        state: Summing,
+        transitions: usize,
+    }
+
+    impl Generated {
+        fn count_transition(&mut self) {
+            self.transitions += 1;
+        }
+    }
+
+    crate::state_machine! {
+        impl Future for Generated {
+            type Output = u32;
+            state = state, poisoned = Summing::Poisoned, budget = 4;
+            on_transition = count_transition;
+            poll(this, cx) {
+                Summing::Adding { mut left, total } => {
+                    let total = total + left.pop().unwrap_or(0);
+                    Step::Next(if left.is_empty() {
+                        Summing::Done(total)
+                    } else if total % 2 == 0 {
+                        Summing::Waiting { left, total }
+                    } else {
+                        Summing::Adding { left, total }
+                    })
+                }
+                Summing::Waiting { left, total } => {
+                    this.transitions += 1;
+                    cx.waker().wake_by_ref();
+                    Step::Pending(Summing::Adding { left, total })
+                }
+                Summing::Done(total) => { Step::Ready(Summing::Done(total), total) }
+                Summing::Explode => { panic!("exploded") }
+                Summing::Poisoned => { panic!("poisoned") }
+            }
+        }
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Polls to completion, recording the state and the number of
+    /// transitions so far after every poll
+    fn trace<F: Future<Output = u32> + Unpin>(
+        mut future: F,
+        inspect: impl Fn(&F) -> (Summing, usize),
+    ) -> (u32, Vec<(Summing, usize)>, usize) {
+        let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut states = Vec::new();
+        loop {
+            let poll = Pin::new(&mut future).poll(&mut cx);
+            states.push(inspect(&future));
+            if let Poll::Ready(total) = poll {
+                return (total, states, wakes.0.load(Ordering::SeqCst));
+            }
+        }
+    }
+
+    /// Test that the generated machine behaves exactly like the hand-written
+    /// one: same output, same state after every poll, same wake-ups and
+    /// transition counts
+    #[test]
+    fn test_generated_matches_hand_written() {
+        for numbers in [vec![], vec![1, 2, 3], (1..=40).collect(), vec![2; 25]] {
+            let start = Summing::Adding {
+                left: numbers.clone(),
+                total: 0,
+            };
+            let hand = HandWritten {
+                state: start.clone(),
+                transitions: 0,
+            };
+            let generated = Generated {
+                state: start,
+                transitions: 0,
+            };
+
+            let expected = trace(hand, |f| (f.state.clone(), f.transitions));
+            let actual = trace(generated, |f| (f.state.clone(), f.transitions));
+            assert_eq!(actual, expected, "for {:?}", numbers);
+            assert_eq!(expected.0, numbers.iter().sum::<u32>());
+            let (_, transitions) = expected.1.last().unwrap();
+            assert!(*transitions >= numbers.len().max(1));
+        }
+    }
+
+    /// Test that a panicking transition leaves the machine poisoned
+    #[test]
+    fn test_panic_poisons() {
+        let mut machine = Generated {
+            state: Summing::Explode,
+            transitions: 0,
+        };
+
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            futures::executor::block_on(&mut machine)
+        }));
+
+        assert!(outcome.is_err());
+        assert_eq!(machine.state, Summing::Poisoned);
+    }
+}
@@ -0,0 +1,259 @@
+//! # Lock-Order Tracking Mutex
+//!
+//! Two threads that take the same pair of locks in opposite orders can
+//! deadlock, but only when their timing lines up, so the bug may hide for a
+//! long time. `TrackedMutex` catches the *possibility* instead: every time a
+//! lock is acquired while others are held, it records "held before" edges in
+//! a global lock-order graph, and panics as soon as an acquisition would
+//! close a cycle in that graph, even if this particular run didn't deadlock.
+//!
+//! ## Usage:
+//! - A drop-in replacement for `std::sync::Mutex` (`new`, `lock`)
+//! - The examples switch to it with the `lock-tracking` feature:
+//!   `cargo run --bin custom_delay --features lock-tracking`
+//! - Tracking only happens in debug builds; in release builds it is a plain
+//!   `std::sync::Mutex`
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LockResult, Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// Source of unique lock ids; ids are never reused
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Ids of the tracked locks the current thread holds, in acquisition order
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Lock-order graph: an edge `a -> b` means `b` was acquired while `a` was held
+fn order_graph() -> &'static Mutex<HashMap<usize, HashSet<usize>>> {
+    static GRAPH: OnceLock<Mutex<HashMap<usize, HashSet<usize>>>> = OnceLock::new();
+    GRAPH.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Returns whether `to` is reachable from `from` in the lock-order graph
+fn reachable(graph: &HashMap<usize, HashSet<usize>>, from: usize, to: usize) -> bool {
+    let mut stack = vec![from];
+    let mut seen = HashSet::new();
+
+    while let Some(node) = stack.pop() {
+        if node == to {
+            return true;
+        }
+        if seen.insert(node) {
+            stack.extend(graph.get(&node).into_iter().flatten());
+        }
+    }
+    false
+}
+
+/// # Struct: TrackedMutex
+///
+/// A `std::sync::Mutex` that checks lock ordering in debug builds.
+///
+/// ## Fields:
+/// - `id`: Identifies this lock in the global lock-order graph
+/// - `inner`: The actual mutex
+///
+/// ## Panics:
+/// - `lock` panics with a "lock-order cycle" message when acquiring this
+///   lock while holding another would contradict an order seen before
+///
+/// ## Example:
+/// ```rust
+/// use future_trait_tutorial::tracked_mutex::TrackedMutex;
+///
+/// let counter = TrackedMutex::new(0);
+/// *counter.lock().unwrap() += 1;
+/// assert_eq!(*counter.lock().unwrap(), 1);
+/// ```
+pub struct TrackedMutex<T> {
+    id: usize,
+    inner: Mutex<T>,
+}
+
+impl<T> TrackedMutex<T> {
+    /// Creates a new, unlocked mutex.
+    pub fn new(value: T) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            inner: Mutex::new(value),
+        }
+    }
+
+    /// # Function: lock
+    ///
+    /// Checks the lock order (debug builds only), then blocks until the
+    /// lock is acquired.
+    ///
+    /// ## Returns:
+    /// - The guard, wrapped in a `LockResult` exactly like `Mutex::lock`
+    pub fn lock(&self) -> LockResult<TrackedMutexGuard<'_, T>> {
+        if cfg!(debug_assertions) {
+            self.check_order();
+        }
+
+        let tracked = |guard| TrackedMutexGuard { id: self.id, guard };
+        let result = match self.inner.lock() {
+            Ok(guard) => Ok(tracked(guard)),
+            Err(poisoned) => Err(PoisonError::new(tracked(poisoned.into_inner()))),
+        };
+
+        if cfg!(debug_assertions) {
+            HELD.with(|held| held.borrow_mut().push(self.id));
+        }
+        result
+    }
+
+    /// Records an edge from every held lock to this one, or panics if one of
+    /// those edges would close a cycle
+    fn check_order(&self) {
+        let held = HELD.with(|held| held.borrow().clone());
+        if held.is_empty() {
+            return;
+        }
+
+        let mut graph = order_graph().lock().unwrap_or_else(PoisonError::into_inner);
+        for &before in &held {
+            if before == self.id {
+                continue; // Re-locking is a plain deadlock, not an ordering issue
+            }
+            if reachable(&graph, self.id, before) {
+                drop(graph);
+                panic!(
+                    "lock-order cycle: acquiring lock #{} while holding lock #{}, \
+                     but #{} has previously been acquired while #{} was held",
+                    self.id, before, before, self.id
+                );
+            }
+            graph.entry(before).or_default().insert(self.id);
+        }
+    }
+}
+
+/// Removes a lock that no longer exists from the lock-order graph
+fn forget_lock(id: usize) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let mut graph = order_graph().lock().unwrap_or_else(PoisonError::into_inner);
+    graph.remove(&id);
+    for successors in graph.values_mut() {
+        successors.remove(&id);
+    }
+}
+
+impl<T> Drop for TrackedMutex<T> {
+    fn drop(&mut self) {
+        forget_lock(self.id);
+    }
+}
+
+impl<T: Default> Default for TrackedMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TrackedMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrackedMutex")
+            .field("id", &self.id)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+/// # Struct: TrackedMutexGuard
+///
+/// The guard returned by `TrackedMutex::lock`. Dropping it releases the
+/// lock and removes it from the thread's set of held locks.
+pub struct TrackedMutexGuard<'a, T> {
+    id: usize,
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T> Deref for TrackedMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TrackedMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for TrackedMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) {
+            HELD.with(|held| {
+                let mut held = held.borrow_mut();
+                // Guards may be dropped in any order
+                if let Some(position) = held.iter().rposition(|&id| id == self.id) {
+                    held.remove(position);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Test that opposite lock orders on two threads are reported as a cycle
+    #[test]
+    fn test_opposite_order_is_detected() {
+        let a = Arc::new(TrackedMutex::new("a"));
+        let b = Arc::new(TrackedMutex::new("b"));
+
+        // Thread 1 establishes the order a -> b
+        let (a1, b1) = (a.clone(), b.clone());
+        thread::spawn(move || {
+            let _a = a1.lock().unwrap();
+            let _b = b1.lock().unwrap();
+        })
+        .join()
+        .unwrap();
+
+        // Thread 2 takes b -> a; it doesn't deadlock this time, but could
+        let (a2, b2) = (a.clone(), b.clone());
+        let error = thread::spawn(move || {
+            let _b = b2.lock().unwrap();
+            let _a = a2.lock().unwrap();
+        })
+        .join()
+        .unwrap_err();
+
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains("lock-order cycle"), "{}", message);
+    }
+
+    /// Test that a consistent order and lone locks are accepted
+    #[test]
+    fn test_consistent_order_is_accepted() {
+        let a = TrackedMutex::new(1);
+        let b = TrackedMutex::new(2);
+
+        for _ in 0..3 {
+            let a_guard = a.lock().unwrap();
+            let b_guard = b.lock().unwrap();
+            // Releasing in either order is fine
+            drop(a_guard);
+            drop(b_guard);
+        }
+        *b.lock().unwrap() += *a.lock().unwrap();
+
+        assert_eq!(*b.lock().unwrap(), 3);
+    }
+}